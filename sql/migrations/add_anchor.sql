@@ -0,0 +1,7 @@
+-- Store the section anchor of each document (e.g. method.route), so citations can
+-- deep-link to the exact item on a page
+
+-- Existing rows hold whole pages and stay NULL; re-populate a crate to split its
+-- pages into sections
+ALTER TABLE doc_embeddings
+ADD COLUMN IF NOT EXISTS anchor TEXT;
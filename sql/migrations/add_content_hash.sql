@@ -0,0 +1,7 @@
+-- Store the sha256 of each document's content, so incremental population
+-- (populate_db --incremental) can skip pages that haven't changed
+
+-- Existing rows can stay NULL: they are hashed from their content when compared,
+-- and get a stored hash the next time they are written
+ALTER TABLE doc_embeddings
+ADD COLUMN IF NOT EXISTS content_hash TEXT;
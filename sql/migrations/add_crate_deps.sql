@@ -0,0 +1,9 @@
+-- Store each crate's direct dependencies, so query_rust_docs with follow_deps can widen
+-- a weak single-crate search to the dependencies that are also indexed
+
+-- Existing crates have no dependencies recorded until they are populated again
+CREATE TABLE IF NOT EXISTS crate_deps (
+    crate_name VARCHAR(255) NOT NULL,
+    dependency VARCHAR(255) NOT NULL,
+    PRIMARY KEY (crate_name, dependency)
+);
@@ -0,0 +1,6 @@
+-- Record which embedding model each crate was populated with, so the server can
+-- refuse to search crates whose vectors came from a different model than its own
+
+-- Existing crates stay NULL (only their dimension is checked) until they are populated again
+ALTER TABLE crates
+ADD COLUMN IF NOT EXISTS embedding_model TEXT;
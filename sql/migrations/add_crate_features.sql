@@ -0,0 +1,6 @@
+-- Record which features each crate's docs were loaded with
+-- (populate_db --features / proxy-config.json "features")
+
+-- Existing crates stay NULL (docs.rs defaults) until they are populated again
+ALTER TABLE crates
+ADD COLUMN IF NOT EXISTS features TEXT;
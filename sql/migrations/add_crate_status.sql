@@ -0,0 +1,6 @@
+-- Record whether each crate is being populated, so servers don't answer from the first
+-- batches of a crate while populate_db / populate_all are still storing it
+
+-- Existing crates were fully populated, so they start out ready
+ALTER TABLE crates
+ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'ready';
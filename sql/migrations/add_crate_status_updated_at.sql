@@ -0,0 +1,7 @@
+-- Record when each crate's status was last set. populate_db / populate_all refresh it while
+-- they run, so a populating status left behind by a killed run goes stale and counts as
+-- failed instead of blocking the crate forever
+
+-- Existing rows have no time, so a populating status already stored counts as failed
+ALTER TABLE crates
+ADD COLUMN IF NOT EXISTS status_updated_at TIMESTAMP;
@@ -0,0 +1,15 @@
+-- Record the kind of item each document covers (struct, trait, fn, enum, macro, mod
+-- or type), so searches can be limited with kind
+
+-- Existing rows start out NULL; fill them in from doc_path with
+--   cargo run --bin populate_db -- --backfill-doc-kinds
+ALTER TABLE doc_embeddings
+ADD COLUMN IF NOT EXISTS doc_kind TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_doc_kind ON doc_embeddings(crate_name, doc_kind);
+
+-- Documents per kind; NULL are pages without a kind or rows not backfilled yet
+SELECT crate_name, doc_kind, COUNT(*) AS docs
+FROM doc_embeddings
+GROUP BY crate_name, doc_kind
+ORDER BY crate_name, doc_kind;
@@ -0,0 +1,14 @@
+-- Flag documents that are mostly code, so usage questions can prefer them
+
+-- Existing rows default to false; re-run populate_db --force (or populate_all)
+-- for a crate to compute the flag for its documents
+ALTER TABLE doc_embeddings
+ADD COLUMN IF NOT EXISTS has_code_example BOOLEAN NOT NULL DEFAULT false;
+
+-- Show how many documents are flagged per crate
+SELECT crate_name,
+       COUNT(*) FILTER (WHERE has_code_example) AS example_docs,
+       COUNT(*) AS total_docs
+FROM doc_embeddings
+GROUP BY crate_name
+ORDER BY crate_name;
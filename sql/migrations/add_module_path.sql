@@ -0,0 +1,17 @@
+-- Record the Rust module each document belongs to (e.g. tokio::sync), so searches
+-- can be scoped with module_prefix
+
+-- Existing rows start out NULL; fill them in from doc_path with
+--   cargo run --bin populate_db -- --backfill-module-paths
+ALTER TABLE doc_embeddings
+ADD COLUMN IF NOT EXISTS module_path TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_module_path ON doc_embeddings(crate_name, module_path);
+
+-- Show how many documents still need a module path per crate
+SELECT crate_name,
+       COUNT(*) FILTER (WHERE module_path IS NULL) AS missing_module_path,
+       COUNT(*) AS total_docs
+FROM doc_embeddings
+GROUP BY crate_name
+ORDER BY crate_name;
@@ -8,6 +8,8 @@ WHERE extname = 'vector';
 
 -- Create HNSW index (better performance for high dimensions)
 -- HNSW doesn't have the 2000 dimension limit that IVFFlat has
+-- The operator class must match the server's --distance / MCPDOCS_DISTANCE:
+--   cosine -> vector_cosine_ops, l2 -> vector_l2_ops, inner-product -> vector_ip_ops
 CREATE INDEX idx_doc_embeddings_vector_hnsw
 ON doc_embeddings
 USING hnsw (embedding vector_cosine_ops);
@@ -7,7 +7,11 @@ CREATE TABLE IF NOT EXISTS crates (
     version VARCHAR(50),
     last_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     total_docs INTEGER DEFAULT 0,
-    total_tokens INTEGER DEFAULT 0
+    total_tokens INTEGER DEFAULT 0,
+    features TEXT, -- Comma-separated features the docs were loaded with, NULL for the defaults
+    embedding_model TEXT, -- Model the crate's documents were embedded with, NULL if populated before it was recorded
+    status VARCHAR(20) NOT NULL DEFAULT 'ready', -- populating, ready or failed (database::CrateStatus); only ready crates are searched
+    status_updated_at TIMESTAMP -- When status was last set (UTC); a populating status left stale by a killed run counts as failed
 );
 
 -- Table to store document embeddings
@@ -19,13 +23,27 @@ CREATE TABLE IF NOT EXISTS doc_embeddings (
     content TEXT NOT NULL,
     embedding vector(3072), -- OpenAI text-embedding-3-large dimension
     token_count INTEGER,
+    has_code_example BOOLEAN NOT NULL DEFAULT false, -- Mostly code (examples::is_code_example), boosted for usage questions
+    module_path TEXT, -- Rust module of the page (doc_loader::module_path), e.g. tokio::sync
+    content_hash TEXT, -- sha256 of content (dedupe::content_hash), compared by incremental population
+    anchor TEXT, -- Section of the page this row holds (doc_loader::doc_anchor), e.g. method.route; NULL for top-level docs
+    doc_kind TEXT, -- Kind of item the page documents (doc_loader::doc_kind): struct, trait, fn, enum, macro, mod or type
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     UNIQUE(crate_name, doc_path)
 );
 
+-- Direct (normal) dependencies of each crate, from crates.io, used to widen weak searches
+CREATE TABLE IF NOT EXISTS crate_deps (
+    crate_name VARCHAR(255) NOT NULL,
+    dependency VARCHAR(255) NOT NULL,
+    PRIMARY KEY (crate_name, dependency)
+);
+
 -- Indexes for performance
 CREATE INDEX IF NOT EXISTS idx_doc_embeddings_crate_name ON doc_embeddings(crate_name);
 CREATE INDEX IF NOT EXISTS idx_doc_embeddings_crate_id ON doc_embeddings(crate_id);
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_module_path ON doc_embeddings(crate_name, module_path);
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_doc_kind ON doc_embeddings(crate_name, doc_kind);
 
 -- Note: pgvector indexes (IVFFlat and HNSW) have a 2000 dimension limit
 -- For 3072 dimensions, we skip the index. Queries will still work but be slower.
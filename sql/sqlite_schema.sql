@@ -0,0 +1,46 @@
+-- Schema for the SQLite (sqlite-vec) backend
+-- Applied automatically when connecting with a sqlite:// MCPDOCS_DATABASE_URL
+
+-- Table to store crate information
+CREATE TABLE IF NOT EXISTS crates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT UNIQUE NOT NULL,
+    version TEXT,
+    last_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    total_docs INTEGER DEFAULT 0,
+    total_tokens INTEGER DEFAULT 0,
+    features TEXT, -- Comma-separated features the docs were loaded with, NULL for the defaults
+    embedding_model TEXT, -- Model the crate's documents were embedded with, NULL if populated before it was recorded
+    status TEXT NOT NULL DEFAULT 'ready', -- populating, ready or failed (database::CrateStatus); only ready crates are searched
+    status_updated_at TIMESTAMP -- When status was last set (UTC); a populating status left stale by a killed run counts as failed
+);
+
+-- Table to store document embeddings
+-- Embeddings are raw little-endian float32 blobs, compared with vec_distance_cosine()
+CREATE TABLE IF NOT EXISTS doc_embeddings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    crate_id INTEGER REFERENCES crates(id) ON DELETE CASCADE,
+    crate_name TEXT NOT NULL, -- Denormalized for faster queries
+    doc_path TEXT NOT NULL,
+    content TEXT NOT NULL,
+    embedding BLOB NOT NULL,
+    token_count INTEGER,
+    has_code_example BOOLEAN NOT NULL DEFAULT 0, -- examples::is_code_example at insert time
+    module_path TEXT, -- Rust module of the page (doc_loader::module_path), e.g. tokio::sync
+    content_hash TEXT, -- sha256 of content (dedupe::content_hash), compared by incremental population
+    anchor TEXT, -- Section of the page this row holds (doc_loader::doc_anchor), e.g. method.route; NULL for top-level docs
+    doc_kind TEXT, -- Kind of item the page documents (doc_loader::doc_kind): struct, trait, fn, enum, macro, mod or type
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(crate_name, doc_path)
+);
+
+-- Direct (normal) dependencies of each crate, from crates.io, used to widen weak searches
+CREATE TABLE IF NOT EXISTS crate_deps (
+    crate_name TEXT NOT NULL,
+    dependency TEXT NOT NULL,
+    PRIMARY KEY (crate_name, dependency)
+);
+
+-- Indexes for performance
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_crate_name ON doc_embeddings(crate_name);
+CREATE INDEX IF NOT EXISTS idx_doc_embeddings_crate_id ON doc_embeddings(crate_id);
@@ -1,7 +1,10 @@
 use rustdocs_mcp_server::{
-    database::Database,
+    database,
+    doc_loader,
     error::ServerError,
+    verbosity::VerbosityArgs,
 };
+use tracing::{debug, info, warn};
 use scraper::{Html, Selector};
 use clap::Parser;
 use serde::{Deserialize, Serialize};
@@ -28,14 +31,36 @@ struct Cli {
     #[arg(long, default_value_t = true)]
     enabled: bool,
 
+    /// Pages populate_all should crawl for this crate (default: populate_all's --max-pages)
+    #[arg(long)]
+    max_pages: Option<usize>,
+
+    /// Link depth populate_all should crawl this crate to, instead of a page count
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Self-hosted rustdoc site to crawl instead of docs.rs (e.g. https://docs.mycorp.internal/mycrate/)
+    #[arg(long)]
+    base_url: Option<String>,
+
     /// Force update if crate already exists
     #[arg(short, long)]
     force: bool,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct ProxyConfig {
     rustdocs_binary_path: String,
+    // Read by populate_all; kept here so rewriting the file preserves them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_dimensions: Option<usize>,
     crates: Vec<CrateConfig>,
 }
 
@@ -46,20 +71,33 @@ struct CrateConfig {
     enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     expected_docs: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_pages: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
 }
 
-async fn scan_crate_docs_count(crate_name: &str, max_pages: usize) -> Result<usize, ServerError> {
-    println!("🔍 Scanning docs.rs to estimate document count for: {}", crate_name);
+async fn scan_crate_docs_count(start_url: &reqwest::Url, crate_name: &str, max_pages: usize) -> Result<usize, ServerError> {
+    info!("🔍 Scanning {} to estimate document count for: {}", start_url, crate_name);
     
-    let base_url = format!("https://docs.rs/{}/latest/{}/", crate_name, crate_name);
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .map_err(|e| ServerError::Network(e.to_string()))?;
+    let client = doc_loader::crawler_client()?;
 
     let mut visited = HashSet::new();
     let mut to_visit = VecDeque::new();
-    to_visit.push_back(base_url.clone());
+    to_visit.push_back(start_url.to_string());
+
+    // Enumerate items the way population does, so the estimate counts the same pages
+    // instead of whatever link-following happens to reach
+    let items = doc_loader::fetch_all_items(&client, start_url).await;
+    if !items.is_empty() {
+        info!("📑 Queued {} items from {}", items.len(), doc_loader::ALL_ITEMS_PAGE);
+    }
+    if let Ok(all_items_url) = start_url.join(doc_loader::ALL_ITEMS_PAGE) {
+        visited.insert(all_items_url.to_string());
+    }
+    to_visit.extend(items.into_iter().map(|item| item.to_string()));
     
     let mut doc_pages_found = 0;
     let mut processed = 0;
@@ -92,13 +130,13 @@ async fn scan_crate_docs_count(crate_name: &str, max_pages: usize) -> Result<usi
         processed += 1;
 
         if processed % 50 == 0 {
-            println!("📊 Scanned {}/{} pages, found {} docs", processed, max_pages, doc_pages_found);
+            debug!("📊 Scanned {}/{} pages, found {} docs", processed, max_pages, doc_pages_found);
         }
 
         let html_content = match fetch_with_retry(&client, &url, 3).await {
             Ok(content) => content,
             Err(e) => {
-                eprintln!("Failed to fetch {} after retries: {}", url, e);
+                warn!("Failed to fetch {} after retries: {}", url, e);
                 continue;
             }
         };
@@ -142,7 +180,7 @@ async fn scan_crate_docs_count(crate_name: &str, max_pages: usize) -> Result<usi
                     }
                     
                     let full_url = if href.starts_with('/') {
-                        format!("https://docs.rs{}", href)
+                        format!("{}{}", start_url.origin().ascii_serialization(), href)
                     } else if href.starts_with("http") {
                         href.to_string()
                     } else if href.starts_with("../") || href.starts_with("./") {
@@ -155,7 +193,7 @@ async fn scan_crate_docs_count(crate_name: &str, max_pages: usize) -> Result<usi
                         } else {
                             // Remove filename and keep directory
                             let mut parts: Vec<&str> = url.split('/').collect();
-                            if parts.last().map_or(false, |p| p.contains('.')) {
+                            if parts.last().is_some_and(|p| p.contains('.')) {
                                 parts.pop(); // Remove filename
                             }
                             format!("{}/", parts.join("/"))
@@ -164,7 +202,9 @@ async fn scan_crate_docs_count(crate_name: &str, max_pages: usize) -> Result<usi
                     };
 
                     // Only follow links within the same crate's documentation, and skip fragments
-                    if full_url.contains(&format!("docs.rs/{}/", crate_name)) && 
+                    let in_scope = reqwest::Url::parse(&full_url)
+                        .is_ok_and(|url| doc_loader::in_crawl_scope(start_url, &url));
+                    if in_scope && 
                        !full_url.contains('#') &&
                        !visited.contains(&full_url) &&
                        to_visit.len() < max_pages * 2 { // Prevent queue explosion
@@ -190,8 +230,9 @@ async fn fetch_with_retry(client: &reqwest::Client, url: &str, retries: usize) -
                     return response.text().await
                         .map_err(|e| ServerError::Network(e.to_string()));
                 } else if response.status().as_u16() == 429 {
-                    // Rate limited, wait and retry
-                    let wait_time = Duration::from_secs(2_u64.pow(attempt as u32));
+                    // Rate limited, wait (at least as long as Retry-After asks) and retry
+                    let backoff = Duration::from_secs(2_u64.pow(attempt as u32));
+                    let wait_time = doc_loader::rate_limit_wait(response.headers(), backoff);
                     tokio::time::sleep(wait_time).await;
                     continue;
                 } else {
@@ -212,22 +253,23 @@ async fn fetch_with_retry(client: &reqwest::Client, url: &str, retries: usize) -
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
     let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
 
-    // Check if crate exists on docs.rs first
-    let test_url = format!("https://docs.rs/{}/latest/{}/", cli.crate_name, cli.crate_name);
-    let client = reqwest::Client::new();
-    let response = client.head(&test_url).send().await
+    // Check if crate exists on docs.rs (or the self-hosted site) first
+    let start_url = doc_loader::crawl_start_url(&cli.crate_name, cli.base_url.as_deref())?;
+    let client = doc_loader::crawler_client()?;
+    let response = client.head(start_url.clone()).send().await
         .map_err(|e| ServerError::Network(e.to_string()))?;
 
     if !response.status().is_success() {
         return Err(ServerError::Config(format!(
-            "Crate '{}' not found on docs.rs (HTTP {}). Please verify the crate name.",
-            cli.crate_name, response.status()
+            "Crate '{}' not found at {} (HTTP {}). Please verify the crate name.",
+            cli.crate_name, start_url, response.status()
         )));
     }
 
     // Scan for expected document count
-    let expected_docs = scan_crate_docs_count(&cli.crate_name, cli.max_scan_pages).await?;
+    let expected_docs = scan_crate_docs_count(&start_url, &cli.crate_name, cli.max_scan_pages).await?;
 
     // Load existing proxy config
     let config_path = "proxy-config.json";
@@ -239,6 +281,9 @@ async fn main() -> Result<(), ServerError> {
     } else {
         ProxyConfig {
             rustdocs_binary_path: "rustdocs_mcp_server".to_string(),
+            embedding_provider: None,
+            embedding_model: None,
+            embedding_dimensions: None,
             crates: Vec::new(),
         }
     };
@@ -252,17 +297,29 @@ async fn main() -> Result<(), ServerError> {
             )));
         }
         
-        println!("📝 Updating existing crate '{}'", cli.crate_name);
+        info!("📝 Updating existing crate '{}'", cli.crate_name);
         existing.features = cli.features;
         existing.enabled = cli.enabled;
         existing.expected_docs = Some(expected_docs);
+        if cli.max_pages.is_some() {
+            existing.max_pages = cli.max_pages;
+        }
+        if cli.max_depth.is_some() {
+            existing.max_depth = cli.max_depth;
+        }
+        if cli.base_url.is_some() {
+            existing.base_url = cli.base_url;
+        }
     } else {
-        println!("➕ Adding new crate '{}'", cli.crate_name);
+        info!("➕ Adding new crate '{}'", cli.crate_name);
         config.crates.push(CrateConfig {
             name: cli.crate_name.clone(),
             features: cli.features,
             enabled: cli.enabled,
             expected_docs: Some(expected_docs),
+            max_pages: cli.max_pages,
+            max_depth: cli.max_depth,
+            base_url: cli.base_url,
         });
     }
 
@@ -280,7 +337,7 @@ async fn main() -> Result<(), ServerError> {
     println!("📊 Expected documents: {}", expected_docs);
     
     // Optional: Show current database stats for this crate
-    if let Ok(db) = Database::new().await {
+    if let Ok(db) = database::connect().await {
         if let Ok(current_count) = db.count_crate_documents(&cli.crate_name).await {
             if current_count > 0 {
                 println!("📚 Current documents in database: {}", current_count);
@@ -1,16 +1,39 @@
 use rustdocs_mcp_server::{
-    database::Database,
-    doc_loader,
+    database,
+    doc_loader::{self, CrawlLimit, PageLimits},
     error::ServerError,
+    verbosity::VerbosityArgs,
 };
-use std::env;
+use clap::Parser;
+use futures::stream::{self, StreamExt};
+use tracing::info;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Detect the version of crates stored without one and repair doc/token totals", long_about = None)]
+struct Cli {
+    /// Crates looked up on docs.rs at once (each backs off on its own when rate limited)
+    #[arg(long, env = "BACKFILL_CONCURRENCY", default_value_t = 4)]
+    concurrency: usize,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+/// What happened to one crate
+enum Outcome {
+    Updated,
+    NoVersion,
+    Failed,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
     dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
 
     // Initialize database
-    let db = Database::new().await?;
+    let db = database::connect().await?;
 
     // Get all crates without version
     let crates = db.get_crate_stats().await?;
@@ -21,47 +44,68 @@ async fn main() -> Result<(), ServerError> {
 
     println!("Found {} crates without version information", crates_without_version.len());
 
-    let mut updated = 0;
-    let mut failed = 0;
+    let client = doc_loader::crawler_client()?;
+    let total = crates_without_version.len();
+    let outcomes: Vec<Outcome> = stream::iter(crates_without_version.iter().enumerate())
+        .map(|(i, crate_stat)| {
+            let db = &db;
+            let client = &client;
+            async move {
+                info!("[{}/{}] Processing: {}", i + 1, total, crate_stat.name);
 
-    for (i, crate_stat) in crates_without_version.iter().enumerate() {
-        println!("\n[{}/{}] Processing: {}", i + 1, crates_without_version.len(), crate_stat.name);
+                // Load just the first page to extract version
+                let load_result = doc_loader::load_documents_from_docs_rs_with_limits(
+                    client,
+                    &crate_stat.name,
+                    "*",
+                    None,
+                    CrawlLimit::new(Some(1), None),
+                    &PageLimits::default(),
+                    None,
+                    None,
+                    None,
+                    false,
+                )
+                .await;
+                match load_result {
+                    Ok(load_result) => {
+                        let Some(version) = load_result.version else {
+                            println!("  ⚠️  {}: no version detected", crate_stat.name);
+                            return Outcome::NoVersion;
+                        };
 
-        // Load just the first page to extract version
-        match doc_loader::load_documents_from_docs_rs(&crate_stat.name, "*", None, Some(1)).await {
-            Ok(load_result) => {
-                if let Some(version) = load_result.version {
-                    println!("  ✅ Detected version: {}", version);
-
-                    // Update the crate with version
-                    match db.upsert_crate(&crate_stat.name, Some(&version)).await {
-                        Ok(_) => {
-                            println!("  ✅ Updated database");
-                            updated += 1;
-                        }
-                        Err(e) => {
-                            println!("  ❌ Failed to update database: {}", e);
-                            failed += 1;
+                        // Update the crate with version
+                        match db.upsert_crate(&crate_stat.name, Some(&version)).await {
+                            Ok(_) => {
+                                println!("  ✅ {}: updated to version {}", crate_stat.name, version);
+                                Outcome::Updated
+                            }
+                            Err(e) => {
+                                println!("  ❌ {}: failed to update database: {}", crate_stat.name, e);
+                                Outcome::Failed
+                            }
                         }
                     }
-                } else {
-                    println!("  ⚠️  No version detected");
+                    Err(e) => {
+                        println!("  ❌ {}: failed to load: {}", crate_stat.name, e);
+                        Outcome::Failed
+                    }
                 }
             }
-            Err(e) => {
-                println!("  ❌ Failed to load: {}", e);
-                failed += 1;
-            }
-        }
-
-        // Small delay to be respectful to docs.rs
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-    }
+        })
+        .buffer_unordered(cli.concurrency.max(1))
+        .collect()
+        .await;
 
+    let count = |wanted: fn(&Outcome) -> bool| outcomes.iter().filter(|outcome| wanted(outcome)).count();
     println!("\n📊 Summary:");
-    println!("  ✅ Updated: {} crates", updated);
-    println!("  ❌ Failed: {} crates", failed);
-    println!("  ⚠️  No version: {} crates", crates_without_version.len() - updated - failed);
+    println!("  ✅ Updated: {} crates", count(|o| matches!(o, Outcome::Updated)));
+    println!("  ❌ Failed: {} crates", count(|o| matches!(o, Outcome::Failed)));
+    println!("  ⚠️  No version: {} crates", count(|o| matches!(o, Outcome::NoVersion)));
+
+    // Doc and token totals can go stale when pages are deleted outside a population
+    let repaired = db.repair_crate_stats().await?;
+    println!("  🔧 Repaired doc/token totals: {} crates", repaired);
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,316 @@
+use rustdocs_mcp_server::{
+    client::{self, SearchHit},
+    database::{self, DistanceMetric, VectorStore},
+    embeddings::{EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
+    error::ServerError,
+    verbosity::VerbosityArgs,
+};
+use clap::Parser;
+use futures::stream::{self, StreamExt};
+use ndarray::Array1;
+use serde::Serialize;
+use std::{fs, time::Instant};
+use tracing::{info, warn};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Benchmark embedding, vector search, answer generation and end-to-end query latency", long_about = None)]
+struct Cli {
+    /// The crate to run the queries against
+    #[arg(short, long)]
+    crate_name: String,
+
+    /// Queries to run (repeat the flag for several queries)
+    #[arg(short, long = "query")]
+    queries: Vec<String>,
+
+    /// File with one query per line (blank lines are ignored)
+    #[arg(long)]
+    queries_file: Option<String>,
+
+    /// Number of times each query is executed
+    #[arg(short, long, default_value_t = 10)]
+    iterations: usize,
+
+    /// Number of queries executed concurrently to simulate load
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+
+    /// Number of documents requested from search_similar_docs
+    #[arg(long, default_value_t = 3)]
+    limit: i32,
+
+    /// Embedding provider to use (openai or voyage)
+    #[arg(long, default_value = "openai", env = "EMBEDDING_PROVIDER")]
+    embedding_provider: String,
+
+    /// Embedding model to use
+    #[arg(long, env = "EMBEDDING_MODEL")]
+    embedding_model: Option<String>,
+
+    /// Chat model that answers each query from the documents found
+    #[arg(long, env = "LLM_MODEL", default_value = client::DEFAULT_LLM_MODEL)]
+    llm_model: String,
+
+    /// Write the JSON report to this path in addition to the printed summary
+    #[arg(long)]
+    json_output: Option<String>,
+
+    /// Distance metric for vector search (must match the database's vector index)
+    #[arg(long, value_enum, default_value_t = DistanceMetric::Cosine, env = "MCPDOCS_DISTANCE")]
+    distance: DistanceMetric,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+/// Latency samples (in milliseconds) for a single query run
+struct Sample {
+    embedding_ms: f64,
+    search_ms: f64,
+    generation_ms: f64,
+    end_to_end_ms: f64,
+    results: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct LatencySummary {
+    count: usize,
+    min_ms: f64,
+    mean_ms: f64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    crate_name: String,
+    embedding_model: String,
+    llm_model: String,
+    queries: usize,
+    iterations: usize,
+    concurrency: usize,
+    limit: i32,
+    failures: usize,
+    empty_results: usize,
+    wall_time_ms: f64,
+    throughput_qps: f64,
+    embedding: LatencySummary,
+    search: LatencySummary,
+    generation: LatencySummary,
+    end_to_end: LatencySummary,
+}
+
+/// Nearest-rank percentile over an already sorted slice
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn summarize(mut values: Vec<f64>) -> LatencySummary {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let count = values.len();
+    let mean_ms = if count == 0 { 0.0 } else { values.iter().sum::<f64>() / count as f64 };
+    LatencySummary {
+        count,
+        min_ms: values.first().copied().unwrap_or(0.0),
+        mean_ms,
+        p50_ms: percentile(&values, 50.0),
+        p95_ms: percentile(&values, 95.0),
+        p99_ms: percentile(&values, 99.0),
+        max_ms: values.last().copied().unwrap_or(0.0),
+    }
+}
+
+/// Answer a single question the way `query_rust_docs` does (embed, search, then ask the
+/// chat model with the documents found), timing each phase
+async fn run_query(
+    db: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+    question: &str,
+    limit: i32,
+    llm_model: &str,
+) -> Result<Sample, ServerError> {
+    let provider = EMBEDDING_CLIENT
+        .get()
+        .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+
+    let start = Instant::now();
+    let (embeddings, _tokens) = provider.generate_embeddings(&[question.to_string()]).await?;
+    let embedding_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let question_embedding = embeddings
+        .into_iter()
+        .next()
+        .ok_or_else(|| ServerError::Internal("No embedding generated".to_string()))?;
+    let question_vector = Array1::from(question_embedding);
+
+    let search_start = Instant::now();
+    let results = db.search_similar_docs(crate_name, &question_vector, limit).await?;
+    let search_ms = search_start.elapsed().as_secs_f64() * 1000.0;
+    let result_count = results.len();
+
+    let generation_start = Instant::now();
+    let hits = results.into_iter().map(|result| SearchHit::new(crate_name, result)).collect();
+    client::answer_from_hits(llm_model, crate_name, question, hits, None, None).await?;
+    let generation_ms = generation_start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(Sample {
+        embedding_ms,
+        search_ms,
+        generation_ms,
+        end_to_end_ms: start.elapsed().as_secs_f64() * 1000.0,
+        results: result_count,
+    })
+}
+
+fn print_summary(name: &str, summary: &LatencySummary) {
+    println!(
+        "{:<12} {:>8} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+        name,
+        summary.count,
+        summary.min_ms,
+        summary.mean_ms,
+        summary.p50_ms,
+        summary.p95_ms,
+        summary.p99_ms,
+        summary.max_ms
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<(), ServerError> {
+    dotenvy::dotenv().ok();
+
+    let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
+
+    let mut queries = cli.queries.clone();
+    if let Some(path) = &cli.queries_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ServerError::Config(format!("Failed to read {}: {}", path, e)))?;
+        queries.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string),
+        );
+    }
+    if queries.is_empty() {
+        return Err(ServerError::Config(
+            "No queries given. Use --query (repeatable) or --queries-file".to_string(),
+        ));
+    }
+    if cli.iterations == 0 || cli.concurrency == 0 {
+        return Err(ServerError::Config(
+            "--iterations and --concurrency must be at least 1".to_string(),
+        ));
+    }
+
+    info!("🔌 Connecting to database...");
+    let db = database::connect_with_metric(cli.distance).await?;
+
+    if !db.has_embeddings(&cli.crate_name).await? {
+        return Err(ServerError::Config(format!(
+            "No embeddings found for crate '{}'. Populate it first with populate_db",
+            cli.crate_name
+        )));
+    }
+
+    let provider_name = cli.embedding_provider.to_lowercase();
+    let embedding_config = EmbeddingConfig::from_provider(&provider_name, cli.embedding_model.clone())?;
+
+    let provider = initialize_embedding_provider(embedding_config);
+    database::check_embedding_dimensions(db.as_ref(), provider.as_ref()).await?;
+    let embedding_model = provider.get_model_name().to_string();
+    if EMBEDDING_CLIENT.set(provider).is_err() {
+        return Err(ServerError::Internal("Failed to set embedding provider".to_string()));
+    }
+
+    let total_runs = queries.len() * cli.iterations;
+    info!(
+        "⏱️  Running {} queries x {} iterations against '{}' (concurrency {})...",
+        queries.len(), cli.iterations, cli.crate_name, cli.concurrency
+    );
+
+    let runs: Vec<&str> = (0..cli.iterations)
+        .flat_map(|_| queries.iter().map(String::as_str))
+        .collect();
+
+    let wall_start = Instant::now();
+    let results = stream::iter(runs)
+        .map(|question| run_query(db.as_ref(), &cli.crate_name, question, cli.limit, &cli.llm_model))
+        .buffer_unordered(cli.concurrency)
+        .collect::<Vec<Result<Sample, ServerError>>>()
+        .await;
+    let wall_time_ms = wall_start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut samples = Vec::with_capacity(total_runs);
+    let mut failures = 0;
+    for result in results {
+        match result {
+            Ok(sample) => samples.push(sample),
+            Err(e) => {
+                warn!("❌ Query failed: {}", e);
+                failures += 1;
+            }
+        }
+    }
+
+    let empty_results = samples.iter().filter(|s| s.results == 0).count();
+    let report = BenchReport {
+        crate_name: cli.crate_name.clone(),
+        embedding_model,
+        llm_model: cli.llm_model.clone(),
+        queries: queries.len(),
+        iterations: cli.iterations,
+        concurrency: cli.concurrency,
+        limit: cli.limit,
+        failures,
+        empty_results,
+        wall_time_ms,
+        throughput_qps: samples.len() as f64 / (wall_time_ms / 1000.0).max(f64::EPSILON),
+        embedding: summarize(samples.iter().map(|s| s.embedding_ms).collect()),
+        search: summarize(samples.iter().map(|s| s.search_ms).collect()),
+        generation: summarize(samples.iter().map(|s| s.generation_ms).collect()),
+        end_to_end: summarize(samples.iter().map(|s| s.end_to_end_ms).collect()),
+    };
+
+    println!(
+        "\n📊 Benchmark results for '{}' ({}, {})",
+        report.crate_name, report.embedding_model, report.llm_model
+    );
+    println!(
+        "  {} runs, {} failed, {} with no results, {:.2}s wall time, {:.2} queries/s",
+        total_runs, report.failures, report.empty_results, report.wall_time_ms / 1000.0, report.throughput_qps
+    );
+    println!(
+        "\n{:<12} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "Phase", "Count", "Min ms", "Mean ms", "p50 ms", "p95 ms", "p99 ms", "Max ms"
+    );
+    println!("{:-<88}", "");
+    print_summary("embedding", &report.embedding);
+    print_summary("search", &report.search);
+    print_summary("generation", &report.generation);
+    print_summary("end-to-end", &report.end_to_end);
+
+    let json = serde_json::to_string_pretty(&report)?;
+    if let Some(path) = &cli.json_output {
+        fs::write(path, &json)
+            .map_err(|e| ServerError::Config(format!("Failed to write {}: {}", path, e)))?;
+        info!("💾 JSON report written to {}", path);
+    } else {
+        println!("\n{}", json);
+    }
+
+    if failures > 0 {
+        return Err(ServerError::Internal(format!("{} of {} queries failed", failures, total_runs)));
+    }
+
+    Ok(())
+}
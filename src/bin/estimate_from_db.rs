@@ -1,5 +1,5 @@
 use rustdocs_mcp_server::{
-    database::Database,
+    database,
     error::ServerError,
 };
 use clap::Parser;
@@ -18,6 +18,13 @@ struct Cli {
 #[derive(Debug, Deserialize, Serialize)]
 struct ProxyConfig {
     rustdocs_binary_path: String,
+    // Read by populate_all; kept here so rewriting the file preserves them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_dimensions: Option<usize>,
     crates: Vec<CrateConfig>,
 }
 
@@ -28,6 +35,12 @@ struct CrateConfig {
     enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     expected_docs: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_pages: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
 }
 
 #[tokio::main]
@@ -35,7 +48,7 @@ async fn main() -> Result<(), ServerError> {
     let cli = Cli::parse();
     
     // Connect to database
-    let db = Database::new().await?;
+    let db = database::connect().await?;
     
     // Load existing proxy config
     let config_path = "proxy-config.json";
@@ -70,7 +83,7 @@ async fn main() -> Result<(), ServerError> {
                 None => true,
                 Some(existing) => {
                     let ratio = existing as f32 / current_docs as f32;
-                    ratio < 0.8 || ratio > 3.0 // Update if more than 20% under or 3x over
+                    !(0.8..=3.0).contains(&ratio) // Update if more than 20% under or 3x over
                 }
             };
             
@@ -0,0 +1,23 @@
+use rustdocs_mcp_server::database;
+
+/// Exit 0 when the database answers and serves at least one ready crate, 1 otherwise,
+/// printing a one-line status. Cheap enough for Docker `HEALTHCHECK` and Kubernetes exec probes.
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+
+    let result = async {
+        let store = database::connect().await?;
+        let ready = database::check_health(store.as_ref()).await?;
+        Ok::<_, rustdocs_mcp_server::error::ServerError>((store.backend(), ready))
+    }
+    .await;
+
+    match result {
+        Ok((backend, ready)) => println!("✅ healthy: {} ready crate(s) in {:?}", ready, backend),
+        Err(e) => {
+            println!("❌ unhealthy: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
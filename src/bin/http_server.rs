@@ -1,34 +1,65 @@
 use rustdocs_mcp_server::{
-    database::Database,
+    crate_filter::CrateFilter,
+    database::{self, DistanceMetric, DocFilter, VectorStore},
+    doc_loader,
     embeddings::{EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
     error::ServerError,
+    query_limit::{QueryLimiter, DEFAULT_QUEUE_TIMEOUT},
+    rate_limit::{self, RateLimiter},
+    pagination::{self, DEFAULT_PAGE_SIZE},
+    refresh,
+    resources::{self, RESOURCE_PAGE_SIZE},
+    runtime_info::RuntimeInfo,
+    search_cache::{SearchCache, SearchKey},
+    self_test,
+    verbosity::VerbosityArgs,
+    server::{
+        elapsed_ms, normalize_doc_kind, normalize_module_prefix, require_crates_with_embeddings, require_non_empty, resolve_target_crates,
+        truncate_snippet, validate_snippet_chars,
+    },
+};
+use axum::{
+    extract::{
+        rejection::JsonRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, DefaultBodyLimit, Query, State,
+    },
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{get, post},
+    Router,
 };
-use async_openai::{Client as OpenAIClient, config::OpenAIConfig};
 use clap::Parser;
+use futures::{future::join_all, SinkExt, Stream, StreamExt};
 use rmcp::{
     ServerHandler, tool,
-    transport::sse_server::{SseServer, SseServerConfig},
     service::{ServiceExt, RequestContext, RoleServer},
     model::{
-        CallToolResult, Content, 
+        CallToolResult, Content, ClientJsonRpcMessage, ErrorCode,
         ListResourcesResult, ListPromptsResult, 
         ListResourceTemplatesResult, ReadResourceResult, GetPromptResult,
         PaginatedRequestParam, ReadResourceRequestParam, GetPromptRequestParam,
-        ProtocolVersion, ServerCapabilities, ServerInfo, Implementation,
-        Resource, RawResource, ResourceContents, AnnotateAble,
+        ProtocolVersion, ServerCapabilities, ServerInfo, Implementation, ServerJsonRpcMessage,
+        Prompt, Resource, RawResource, ResourceTemplate, AnnotateAble,
     },
     Error as McpError,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use schemars::JsonSchema;
 use ndarray::Array1;
-use std::{env, sync::Arc, net::SocketAddr};
-use tokio_util::sync::CancellationToken;
-use tracing::{info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::{collections::HashMap, env, sync::Arc, net::SocketAddr, time::{Duration, Instant}};
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::PollSender;
+use tower_http::{limit::RequestBodyLimitLayer, timeout::TimeoutLayer};
+use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Rust documentation MCP server with HTTP SSE transport", long_about = None)]
+#[command(author, version, about = "Rust documentation MCP server over HTTP (SSE and WebSocket transports)", long_about = None)]
 struct Cli {
     /// Port to listen on
     #[arg(short, long, default_value = "3000", env = "PORT")]
@@ -53,24 +84,176 @@ struct Cli {
     /// Embedding model to use
     #[arg(long, env = "EMBEDDING_MODEL")]
     embedding_model: Option<String>,
+
+    /// Distance metric for vector search (must match the database's vector index)
+    #[arg(long, value_enum, default_value_t = DistanceMetric::Cosine, env = "MCPDOCS_DISTANCE")]
+    distance: DistanceMetric,
+
+    /// Maximum queries per minute for each MCP session or /query client (unlimited if not set)
+    #[arg(long, env = "MCPDOCS_RATE_LIMIT_PER_MINUTE")]
+    rate_limit_per_minute: Option<u32>,
+
+    /// Maximum queries executing at once across all clients (unlimited if not set)
+    #[arg(long, env = "MAX_CONCURRENT_QUERIES")]
+    max_concurrent_queries: Option<usize>,
+
+    /// Seconds a query waits for a free slot before failing with "server busy"
+    #[arg(long, default_value_t = DEFAULT_QUEUE_TIMEOUT.as_secs(), env = "MCPDOCS_QUERY_QUEUE_TIMEOUT_SECS")]
+    query_queue_timeout_secs: u64,
+
+    /// Enable the refresh_crate tool, which re-scrapes docs.rs and updates the database
+    #[arg(long, env = "MCPDOCS_ALLOW_REFRESH")]
+    allow_refresh: bool,
+
+    /// Embed a test question and search every served crate before serving; exit non-zero on failure
+    #[arg(long, env = "MCPDOCS_SELF_TEST")]
+    self_test: bool,
+
+    /// Only serve these crates, even if the database has more (repeatable or comma-separated)
+    #[arg(long = "allow-crate", env = "MCPDOCS_ALLOW_CRATES", value_delimiter = ',')]
+    allow_crates: Vec<String>,
+
+    /// Never serve these crates (repeatable or comma-separated); wins over --allow-crate
+    #[arg(long = "deny-crate", env = "MCPDOCS_DENY_CRATES", value_delimiter = ',')]
+    deny_crates: Vec<String>,
+
+    /// MCP transports to serve (repeatable or comma-separated): sse (`/sse` + `/message`), ws (`/ws`)
+    #[arg(long = "transport", value_enum, default_value = "sse", env = "MCPDOCS_TRANSPORT", value_delimiter = ',')]
+    transports: Vec<Transport>,
+
+    /// Largest request body accepted, in bytes; bigger requests get 413
+    #[arg(long, default_value_t = DEFAULT_MAX_BODY_BYTES, env = "MCPDOCS_MAX_BODY_BYTES")]
+    max_body_bytes: usize,
+
+    /// Seconds an HTTP request or MCP query may run before failing with 504 / `timeout` (0 disables)
+    #[arg(long, default_value_t = DEFAULT_REQUEST_TIMEOUT.as_secs(), env = "MCPDOCS_REQUEST_TIMEOUT_SECS")]
+    request_timeout_secs: u64,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+/// Default for `--max-body-bytes`; MCP messages and /query bodies are a few KiB
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default for `--request-timeout-secs`
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How MCP clients connect; every transport serves the same `McpHandler`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// Server-Sent Events for server messages, POST for client messages
+    Sse,
+    /// One WebSocket carrying JSON-RPC text frames both ways
+    Ws,
+}
+
+impl Transport {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Sse => "SSE",
+            Self::Ws => "WebSocket",
+        }
+    }
+}
+
+/// How startup logs name the served transports, e.g. `SSE + WebSocket`
+fn transports_label(transports: &[Transport]) -> String {
+    let mut labels: Vec<&str> = Vec::new();
+    for transport in transports {
+        if !labels.contains(&transport.label()) {
+            labels.push(transport.label());
+        }
+    }
+    labels.join(" + ")
 }
 
 #[derive(Clone)]
 struct McpHandler {
-    database: Database,
+    database: Arc<dyn VectorStore + Send + Sync>,
     available_crates: Arc<Vec<String>>,
     startup_message: String,
+    rate_limiter: Arc<RateLimiter>,
+    /// Shared by every session and /query
+    query_limiter: Arc<QueryLimiter>,
+    /// Rate limit bucket for this handler's session
+    client: Arc<str>,
+    allow_refresh: bool,
+    /// Retrieval results shared by every session
+    search_cache: Arc<SearchCache>,
+    /// Operator scope; `available_crates` is already filtered by it
+    crate_filter: Arc<CrateFilter>,
+    /// How long one `query_rust_docs` search may run (`None` is unlimited)
+    request_timeout: Option<Duration>,
 }
 
 impl McpHandler {
-    fn new(database: Database, available_crates: Vec<String>, startup_message: String) -> Self {
+    fn new(database: Arc<dyn VectorStore + Send + Sync>, available_crates: Vec<String>, startup_message: String) -> Self {
         Self {
             database,
             available_crates: Arc::new(available_crates),
             startup_message,
+            rate_limiter: Arc::new(RateLimiter::unlimited()),
+            query_limiter: Arc::new(QueryLimiter::unlimited()),
+            client: Arc::from("default"),
+            allow_refresh: false,
+            search_cache: Arc::new(SearchCache::from_env()),
+            crate_filter: Arc::new(CrateFilter::default()),
+            request_timeout: None,
+        }
+    }
+
+    /// Refuse crates outside `filter`, including for `refresh_crate`
+    fn with_crate_filter(mut self, filter: CrateFilter) -> Self {
+        self.available_crates = Arc::new(filter.partition(self.available_crates.to_vec()).0);
+        self.crate_filter = Arc::new(filter);
+        self
+    }
+
+    /// Enable the `refresh_crate` tool, which re-scrapes docs.rs and writes to the database
+    fn with_refresh(mut self, allow_refresh: bool) -> Self {
+        self.allow_refresh = allow_refresh;
+        self
+    }
+
+    /// Limit each client to `per_minute` queries (`None` keeps the default of unlimited)
+    fn with_rate_limit(mut self, per_minute: Option<u32>) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(per_minute));
+        self
+    }
+
+    /// Run at most `max_concurrent` queries at once, queueing the rest for up to `queue_timeout`
+    fn with_query_limit(mut self, max_concurrent: Option<usize>, queue_timeout: Duration) -> Self {
+        self.query_limiter = Arc::new(QueryLimiter::new(max_concurrent, queue_timeout));
+        self
+    }
+
+    /// Fail `query_rust_docs` searches that run longer than `timeout` (`None` keeps the default of unlimited)
+    fn with_request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// A handler for one SSE session, sharing the rate limiter but with its own bucket
+    fn for_session(&self, session_id: &SessionId) -> Self {
+        Self {
+            client: Arc::from(format!("session:{}", session_id)),
+            ..self.clone()
         }
     }
     
+    /// Effective configuration, reported by `server_info` and `GET /info`
+    async fn runtime_info(&self) -> Result<RuntimeInfo, ServerError> {
+        RuntimeInfo::collect(
+            "rustdocs-mcp-server-http",
+            self.database.as_ref(),
+            &self.available_crates,
+            self.allow_refresh,
+            self.rate_limiter.per_minute(),
+        )
+        .await
+    }
+
     fn _create_resource_text(&self, uri: &str, name: &str) -> Resource {
         RawResource::new(uri, name.to_string()).no_annotation()
     }
@@ -79,17 +262,48 @@ impl McpHandler {
 #[derive(Deserialize, Serialize, JsonSchema)]
 struct QueryRustDocsArgs {
     /// The crate to search in (e.g., "axum", "tokio", "serde")
-    crate_name: String,
+    #[serde(default)]
+    crate_name: Option<String>,
+    /// Several crates to search at once, as an alternative to crate_name (e.g., ["axum", "tokio"])
+    #[serde(default)]
+    crate_names: Option<Vec<String>>,
     /// The specific question about the crate's API or usage.
     question: String,
+    /// Only search items in this module and its submodules (e.g., "tokio::sync")
+    #[serde(default)]
+    module_prefix: Option<String>,
+    /// Only search items of this kind: "struct", "trait", "fn", "enum", "macro", "mod" or "type"
+    #[serde(default)]
+    kind: Option<String>,
+    /// Cut each snippet to about this many characters (at least 50), ending with its doc path for follow-up
+    #[serde(default)]
+    snippet_chars: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+struct RefreshCrateArgs {
+    /// The crate to re-scrape from docs.rs (must already be in the database)
+    crate_name: String,
+}
+
+/// A search result tagged with the crate it came from
+#[derive(Debug)]
+struct CrateResult {
+    crate_name: String,
+    doc_path: String,
+    content: String,
+    similarity: f32,
+    token_count: Option<usize>,
 }
 
 // Implement ServerHandler trait with correct signatures
+#[tool(tool_box)]
 impl ServerHandler for McpHandler {
     fn get_info(&self) -> ServerInfo {
+        // No logging capability: this server logs through tracing and sends no log notifications
         let capabilities = ServerCapabilities::builder()
+            .enable_resources()
             .enable_tools()
-            .enable_logging()
             .build();
 
         ServerInfo {
@@ -105,32 +319,31 @@ impl ServerHandler for McpHandler {
 
     async fn list_resources(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListResourcesResult, McpError> {
-        Ok(ListResourcesResult {
-            resources: vec![],
-            next_cursor: None,
-        })
+        let cursor = pagination::request_cursor(request);
+        resources::list_doc_resources(self.database.as_ref(), &self.available_crates, cursor.as_deref(), RESOURCE_PAGE_SIZE)
+            .await
     }
 
     async fn read_resource(
         &self,
-        _request: ReadResourceRequestParam,
+        request: ReadResourceRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ReadResourceResult, McpError> {
-        Err(McpError::invalid_request("No resources available".to_string(), None))
+        resources::read_doc_resource(self.database.as_ref(), &self.available_crates, &request.uri).await
     }
 
     async fn list_prompts(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListPromptsResult, McpError> {
-        Ok(ListPromptsResult {
-            prompts: vec![],
-            next_cursor: None,
-        })
+        let cursor = pagination::request_cursor(request);
+        let prompts: Vec<Prompt> = Vec::new(); // No prompts defined yet
+        let (prompts, next_cursor) = pagination::paginate(prompts, cursor.as_deref(), DEFAULT_PAGE_SIZE)?;
+        Ok(ListPromptsResult { prompts, next_cursor })
     }
 
     async fn get_prompt(
@@ -146,117 +359,598 @@ impl ServerHandler for McpHandler {
 
     async fn list_resource_templates(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListResourceTemplatesResult, McpError> {
-        Ok(ListResourceTemplatesResult {
-            resource_templates: vec![],
-            next_cursor: None,
-        })
+        let cursor = pagination::request_cursor(request);
+        let resource_templates: Vec<ResourceTemplate> = Vec::new(); // No templates defined yet
+        let (resource_templates, next_cursor) =
+            pagination::paginate(resource_templates, cursor.as_deref(), DEFAULT_PAGE_SIZE)?;
+        Ok(ListResourceTemplatesResult { resource_templates, next_cursor })
+    }
+}
+
+/// Number of documents returned when the caller doesn't ask for a specific amount
+const DEFAULT_MAX_RESULTS: usize = 5;
+/// Upper bound on `max_results` accepted by the `/query` endpoint
+const MAX_RESULTS_LIMIT: usize = 20;
+
+/// The search filter of a request's optional `module_prefix` and `kind`
+fn doc_filter<'a>(module_prefix: &'a Option<String>, kind: &'a Option<String>) -> DocFilter<'a> {
+    DocFilter { module_prefix: module_prefix.as_deref(), kind: kind.as_deref() }
+}
+
+impl McpHandler {
+    /// Embed the question once, search every requested crate concurrently and return the
+    /// closest documents across all of them, best first. Shared by the MCP tool and the plain
+    /// JSON `/query` route. `filter` limits every crate's search to a module and/or kind of item.
+    /// Logs one event with the time spent embedding and searching.
+    #[tracing::instrument(name = "search_docs", skip_all, fields(crate_name = %crate_names.join(", ")))]
+    async fn search_docs(
+        &self,
+        crate_names: &[String],
+        question: &str,
+        filter: DocFilter<'_>,
+        max_results: usize,
+    ) -> Result<Vec<CrateResult>, McpError> {
+        let question = require_non_empty("question", question)?;
+        let filter = DocFilter {
+            module_prefix: normalize_module_prefix(filter.module_prefix)?,
+            kind: normalize_doc_kind(filter.kind)?,
+        };
+
+        // Report every unknown crate at once rather than failing on the first
+        let unavailable: Vec<&str> = crate_names
+            .iter()
+            .filter(|name| !self.available_crates.contains(name))
+            .map(String::as_str)
+            .collect();
+        if !unavailable.is_empty() {
+            return Err(ServerError::NotFound(format!(
+                "Crate(s) not available: {}. Available crates: {}",
+                unavailable.join(", "),
+                self.available_crates.join(", ")
+            ))
+            .into_mcp_error("validation"));
+        }
+        require_crates_with_embeddings(self.database.as_ref(), crate_names).await?;
+
+        // Recently searched crates are served from the cache, without embedding the question
+        let cache_keys: Vec<SearchKey> = crate_names
+            .iter()
+            .map(|name| {
+                SearchKey::new(name, question, max_results as i32, false)
+                    .in_module(filter.module_prefix)
+                    .of_kind(filter.kind)
+            })
+            .collect();
+        let cached = join_all(cache_keys.iter().map(|key| self.search_cache.get(key))).await;
+
+        // Generate embedding for the question
+        let embedding_started = Instant::now();
+        let question_embedding = if cached.iter().all(Option::is_some) {
+            None
+        } else {
+            let embedding_client = EMBEDDING_CLIENT.get()
+                .ok_or_else(|| ServerError::Internal("Embedding client not initialized".to_string()).into_mcp_error("embedding"))?;
+
+            let (question_embeddings, _) = embedding_client.generate_embeddings(&[question.to_string()]).await
+                .map_err(|e| e.into_mcp_error("embedding"))?;
+
+            Some(Array1::from_vec(question_embeddings.first()
+                .ok_or_else(|| McpError::internal_error("No embedding generated".to_string(), None))?.clone()))
+        };
+
+        let embedding_ms = elapsed_ms(embedding_started);
+
+        // Perform semantic search in every crate, then re-rank globally by similarity
+        let search_started = Instant::now();
+        let searches = cache_keys.into_iter().zip(cached).zip(crate_names).map(|((key, cached), name)| {
+            let question_embedding = question_embedding.as_ref();
+            async move {
+                if let Some(found) = cached {
+                    return Ok(found);
+                }
+                // Only `None` when every crate was cached
+                let Some(question_embedding) = question_embedding else {
+                    return Ok(Vec::new());
+                };
+                let found = if filter.is_empty() {
+                    self.database.search_similar_docs(name, question_embedding, max_results as i32).await?
+                } else {
+                    self.database
+                        .search_similar_docs_filtered(name, question_embedding, max_results as i32, filter, None)
+                        .await?
+                };
+                self.search_cache.insert(key, found.clone()).await;
+                Ok::<_, ServerError>(found)
+            }
+        });
+        let mut results = Vec::new();
+        for (name, found) in crate_names.iter().zip(join_all(searches).await) {
+            let found = found.map_err(|e| e.into_mcp_error("search"))?;
+            results.extend(found.into_iter().map(|result| CrateResult {
+                crate_name: name.clone(),
+                doc_path: result.doc_path,
+                content: result.content,
+                similarity: result.similarity,
+                token_count: result.token_count,
+            }));
+        }
+        results.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(max_results);
+        let search_ms = elapsed_ms(search_started);
+        info!(embedding_ms, search_ms, documents = results.len(), "🔎 Searched in {} ms", embedding_ms + search_ms);
+        Ok(results)
     }
 }
 
+/// Render search results as the numbered text answer returned to clients
+/// Each result is labelled with its crate when several crates were searched.
+/// `snippet_chars` cuts each result's content (see [`truncate_snippet`])
+fn format_search_results(
+    crate_names: &[String],
+    question: &str,
+    results: &[CrateResult],
+    snippet_chars: Option<usize>,
+) -> String {
+    let crate_label = crate_names.join(", ");
+    if results.is_empty() {
+        return format!(
+            "No relevant documentation found for '{}' in crate '{}'",
+            question, crate_label
+        );
+    }
+
+    let formatted_results: Vec<String> = results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let content = match snippet_chars {
+                Some(max_chars) => truncate_snippet(&result.content, max_chars, &result.doc_path),
+                None => result.content.trim().to_string(),
+            };
+            if crate_names.len() > 1 {
+                format!("{}. [{}] {} (similarity: {:.3})", i + 1, result.crate_name, content, result.similarity)
+            } else {
+                format!("{}. {} (similarity: {:.3})", i + 1, content, result.similarity)
+            }
+        })
+        .collect();
+
+    let sources: Vec<String> = doc_loader::source_urls(results.iter().map(|result| result.doc_path.as_str()))
+        .into_iter()
+        .map(|url| format!("- {}", url))
+        .collect();
+
+    format!(
+        "From {} docs (via vector database search): {}\n\nSources:\n{}",
+        crate_label,
+        formatted_results.join("\n\n"),
+        sources.join("\n")
+    )
+}
+
 // Tool implementation
 #[tool(tool_box)]
 impl McpHandler {
     #[tool(
-        description = "Query documentation for a specific Rust crate using semantic search and LLM summarization."
+        description = "Search the documentation of one or more Rust crates and return the most similar doc snippets with their similarity and docs.rs links (no LLM summary)."
     )]
     async fn query_rust_docs(
         &self,
         #[tool(aggr)]
         args: QueryRustDocsArgs,
     ) -> Result<CallToolResult, McpError> {
-        // Check if crate is available
-        if !self.available_crates.contains(&args.crate_name) {
-            return Err(McpError::invalid_params(
-                format!(
-                    "Crate '{}' not available. Available crates: {}",
-                    args.crate_name,
-                    self.available_crates.join(", ")
-                ),
-                None,
-            ));
+        let crate_names = resolve_target_crates(args.crate_name.as_deref(), args.crate_names.as_deref())?;
+        let snippet_chars = validate_snippet_chars(args.snippet_chars)?;
+        self.rate_limiter.check_tool_call(&self.client)?;
+        let _permit = self.query_limiter.acquire().await.map_err(|e| e.into_mcp_error("queue"))?;
+        let search = self.search_docs(&crate_names, &args.question, doc_filter(&args.module_prefix, &args.kind), DEFAULT_MAX_RESULTS);
+        let results = match self.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, search).await.map_err(|_| {
+                ServerError::Timeout(format!("query_rust_docs took longer than {}s", timeout.as_secs()))
+                    .into_mcp_error("search")
+            })??,
+            None => search.await?,
+        };
+        let response = format_search_results(&crate_names, &args.question, &results, snippet_chars);
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Re-scrape one crate from docs.rs and re-embed only the pages that changed. Returns {pages_scanned, pages_changed, new_version}. Only available when the server runs with --allow-refresh."
+    )]
+    async fn refresh_crate(
+        &self,
+        #[tool(aggr)]
+        args: RefreshCrateArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        if !self.allow_refresh {
+            return Err(ServerError::Config(
+                "refresh_crate is disabled; start the server with --allow-refresh".to_string(),
+            )
+            .into_mcp_error("validation"));
         }
+        if !self.crate_filter.allows(crate_name) {
+            return Err(ServerError::NotFound(format!("Crate '{}' is not served by this server", crate_name))
+                .into_mcp_error("validation"));
+        }
+        self.rate_limiter.check_tool_call(&self.client)?;
+        require_crates_with_embeddings(self.database.as_ref(), &[crate_name.to_string()]).await?;
 
-        // Check if crate has embeddings in database
-        if !self.database.has_embeddings(&args.crate_name).await.map_err(|e| {
-            McpError::internal_error(e.to_string(), None)
-        })? {
-            return Err(McpError::invalid_params(
-                format!(
-                    "No embeddings found for crate '{}'. Please populate the database first.",
-                    args.crate_name
-                ),
-                None,
-            ));
+        info!("🔄 Refreshing crate {} from docs.rs", crate_name);
+        let report = refresh::refresh_crate(self.database.as_ref(), crate_name, None)
+            .await
+            .map_err(|e| e.into_mcp_error("refresh"))?;
+        self.search_cache.clear();
+        info!(
+            "✅ Refreshed {}: {} of {} pages changed, {} chunks embedded",
+            crate_name, report.pages_changed, report.pages_scanned, report.chunks_embedded
+        );
+        let response = serde_json::to_string_pretty(&report)
+            .map_err(|e| ServerError::Json(e).into_mcp_error("refresh"))?;
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Report the server's effective runtime configuration as JSON: version, embedding provider/model/dimensions, database backend and distance metric, and served crates. Secrets are masked."
+    )]
+    async fn server_info(&self) -> Result<CallToolResult, McpError> {
+        let info = self.runtime_info().await.map_err(|e| e.into_mcp_error("server_info"))?;
+        let response = serde_json::to_string_pretty(&info)
+            .map_err(|e| ServerError::Json(e).into_mcp_error("server_info"))?;
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+}
+
+// --- HTTP transport ---
+
+type SessionId = Arc<str>;
+
+/// Shared state for the axum router: the MCP handler plus the senders for
+/// every open SSE session, keyed by session id
+#[derive(Clone)]
+struct AppState {
+    handler: McpHandler,
+    sessions: Arc<RwLock<HashMap<SessionId, mpsc::Sender<ClientJsonRpcMessage>>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageQuery {
+    session_id: String,
+}
+
+/// Run one MCP session over a pair of channels until the client goes away.
+/// Both transports bridge their connection onto these channels.
+async fn run_session(
+    handler: McpHandler,
+    from_client_rx: mpsc::Receiver<ClientJsonRpcMessage>,
+    to_client_tx: mpsc::Sender<ServerJsonRpcMessage>,
+) {
+    let rate_limiter = handler.rate_limiter.clone();
+    let client = handler.client.clone();
+    let sink = PollSender::new(to_client_tx).sink_map_err(std::io::Error::other);
+    let transport = (sink, ReceiverStream::new(from_client_rx));
+    match handler.serve(transport).await {
+        Ok(service) => {
+            if let Err(e) = service.waiting().await {
+                tracing::error!("MCP service error: {}", e);
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to start MCP service: {}", e);
         }
+    }
+    rate_limiter.remove(&client);
+}
 
-        // Generate embedding for the question
-        let embedding_client = EMBEDDING_CLIENT.get()
-            .ok_or_else(|| McpError::internal_error("Embedding client not initialized".to_string(), None))?;
-        
-        let (question_embeddings, _) = embedding_client.generate_embeddings(&[args.question.clone()]).await
-            .map_err(|e| McpError::internal_error(format!("Failed to generate embedding: {}", e), None))?;
-        
-        let question_embedding = Array1::from_vec(question_embeddings.first()
-            .ok_or_else(|| McpError::internal_error("No embedding generated".to_string(), None))?.clone());
-
-        // Perform semantic search using the embedding
-        match self.database.search_similar_docs(&args.crate_name, &question_embedding, 10).await {
-            Ok(results) => {
-                if results.is_empty() {
-                    Ok(CallToolResult::success(vec![Content::text(format!(
-                        "No relevant documentation found for '{}' in crate '{}'", 
-                        args.question, args.crate_name
-                    ))]))
-                } else {
-                    // Format search results - results are tuples (id, content, similarity)
-                    let mut response = format!("From {} docs (via vector database search): ", args.crate_name);
-                    
-                    // Take top results and format them
-                    let formatted_results: Vec<String> = results.into_iter()
-                        .take(5) // Limit to top 5 results
-                        .enumerate()
-                        .map(|(i, (_, content, similarity))| {
-                            format!("{}. {} (similarity: {:.3})", 
-                                i + 1, 
-                                content.trim(), 
-                                similarity)
-                        })
-                        .collect();
-                    
-                    response.push_str(&formatted_results.join("\n\n"));
-                    Ok(CallToolResult::success(vec![Content::text(response)]))
+/// Open an SSE stream and start an MCP session on it. The first event tells
+/// the client where to POST its JSON-RPC messages.
+async fn sse_handler(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, std::io::Error>>> {
+    let session_id: SessionId = Arc::from(uuid::Uuid::new_v4().simple().to_string());
+    info!("🔗 New MCP connection established (session {})", session_id);
+
+    let (from_client_tx, from_client_rx) = mpsc::channel(64);
+    let (to_client_tx, to_client_rx) = mpsc::channel(64);
+    state.sessions.write().await.insert(session_id.clone(), from_client_tx);
+
+    let handler = state.handler.for_session(&session_id);
+    let sessions = state.sessions.clone();
+    let session = session_id.clone();
+    tokio::spawn(async move {
+        run_session(handler, from_client_rx, to_client_tx).await;
+        sessions.write().await.remove(&session);
+        info!("🔌 MCP session {} closed", session);
+    });
+
+    let endpoint = futures::stream::once(futures::future::ok(
+        Event::default()
+            .event("endpoint")
+            .data(format!("/message?sessionId={}", session_id)),
+    ));
+    let messages = ReceiverStream::new(to_client_rx).map(|message| {
+        serde_json::to_string(&message)
+            .map(|data| Event::default().event("message").data(data))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    });
+    Sse::new(endpoint.chain(messages))
+}
+
+/// Upgrade to a WebSocket and start an MCP session on it
+async fn ws_handler(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| ws_session(state.handler, socket))
+}
+
+/// Bridge a WebSocket onto an MCP session: each text frame is one JSON-RPC message
+async fn ws_session(handler: McpHandler, socket: WebSocket) {
+    let session_id: SessionId = Arc::from(uuid::Uuid::new_v4().simple().to_string());
+    info!("🔗 New MCP WebSocket connection established (session {})", session_id);
+
+    let (from_client_tx, from_client_rx) = mpsc::channel(64);
+    let (to_client_tx, mut to_client_rx) = mpsc::channel::<ServerJsonRpcMessage>(64);
+    let (mut ws_tx, mut ws_rx) = socket.split();
+
+    let outgoing = tokio::spawn(async move {
+        while let Some(message) = to_client_rx.recv().await {
+            let text = match serde_json::to_string(&message) {
+                Ok(text) => text,
+                Err(e) => {
+                    error!("Failed to serialize MCP message: {}", e);
+                    continue;
+                }
+            };
+            if ws_tx.send(Message::text(text)).await.is_err() {
+                break;
+            }
+        }
+        let _ = ws_tx.close().await;
+    });
+
+    let session = session_id.clone();
+    let incoming = tokio::spawn(async move {
+        while let Some(Ok(frame)) = ws_rx.next().await {
+            let text = match frame {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                // Pings are answered by axum; binary frames are not part of the protocol
+                _ => continue,
+            };
+            match serde_json::from_str::<ClientJsonRpcMessage>(text.as_str()) {
+                Ok(message) => {
+                    if from_client_tx.send(message).await.is_err() {
+                        break;
+                    }
                 }
+                Err(e) => warn!("⚠️  Ignoring malformed message on session {}: {}", session, e),
             }
-            Err(e) => Err(McpError::internal_error(format!("Database search error: {}", e), None))
         }
+        // Dropping the sender ends the session
+    });
+
+    run_session(handler.for_session(&session_id), from_client_rx, to_client_tx).await;
+    incoming.abort();
+    let _ = outgoing.await;
+    info!("🔌 MCP session {} closed", session_id);
+}
+
+/// Forward a client JSON-RPC message to the session it belongs to
+async fn message_handler(
+    State(state): State<AppState>,
+    Query(MessageQuery { session_id }): Query<MessageQuery>,
+    Json(message): Json<ClientJsonRpcMessage>,
+) -> StatusCode {
+    let tx = match state.sessions.read().await.get(session_id.as_str()) {
+        Some(tx) => tx.clone(),
+        None => return StatusCode::NOT_FOUND,
+    };
+    if tx.send(message).await.is_err() {
+        return StatusCode::GONE;
+    }
+    StatusCode::ACCEPTED
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryRequest {
+    #[serde(default)]
+    crate_name: Option<String>,
+    /// Several crates to search at once; results are merged by similarity
+    #[serde(default)]
+    crate_names: Option<Vec<String>>,
+    question: String,
+    /// Only search items in this module and its submodules (e.g. "tokio::sync")
+    #[serde(default)]
+    module_prefix: Option<String>,
+    /// Only search items of this kind (e.g. "trait")
+    #[serde(default)]
+    kind: Option<String>,
+    /// Number of documents to return (1-20, default 5)
+    max_results: Option<usize>,
+    /// Cut each snippet in `response` to about this many characters
+    #[serde(default)]
+    snippet_chars: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct QuerySource {
+    crate_name: String,
+    doc_path: String,
+    url: String,
+    similarity: f32,
+    /// Stored token count of the document, for clients packing their own context
+    token_count: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct QueryResponse {
+    response: String,
+    sources: Vec<QuerySource>,
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+/// Plain JSON endpoint for clients that don't speak MCP (used by `mcp_http_wrapper`)
+async fn query_handler(
+    State(state): State<AppState>,
+    ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
+    request: Result<Json<QueryRequest>, JsonRejection>,
+) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(rejection) => {
+            // Bodies over --max-body-bytes keep their 413; other bad bodies are a plain 400
+            let status = match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => StatusCode::PAYLOAD_TOO_LARGE,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            return error_response(status, rejection.body_text());
+        }
+    };
+
+    let max_results = request.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    if !(1..=MAX_RESULTS_LIMIT).contains(&max_results) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("max_results must be between 1 and {}", MAX_RESULTS_LIMIT),
+        );
+    }
+
+    let crate_names = match resolve_target_crates(request.crate_name.as_deref(), request.crate_names.as_deref()) {
+        Ok(crate_names) => crate_names,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.message),
+    };
+    if let Err(e) = validate_snippet_chars(request.snippet_chars) {
+        return error_response(StatusCode::BAD_REQUEST, e.message);
+    }
+
+    // /query has no session, so each client address gets its own bucket
+    if let Err(retry_after) = state.handler.rate_limiter.check(&format!("addr:{}", client_addr.ip())) {
+        let retry_after_secs = rate_limit::whole_seconds(retry_after);
+        let mut response = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Rate limit exceeded; retry in {}s", retry_after_secs),
+        );
+        response.headers_mut().insert(header::RETRY_AFTER, retry_after_secs.into());
+        return response;
+    }
+
+    let _permit = match state.handler.query_limiter.acquire().await {
+        Ok(permit) => permit,
+        Err(e) => {
+            let mut response = error_response(StatusCode::SERVICE_UNAVAILABLE, e.to_string());
+            response.headers_mut().insert(header::RETRY_AFTER, 1.into());
+            return response;
+        }
+    };
+
+    match state
+        .handler
+        .search_docs(&crate_names, &request.question, doc_filter(&request.module_prefix, &request.kind), max_results)
+        .await {
+        Ok(results) => {
+            let response = format_search_results(&crate_names, &request.question, &results, request.snippet_chars);
+            let sources = results
+                .into_iter()
+                .map(|result| QuerySource {
+                    url: doc_loader::docs_rs_url(&result.doc_path),
+                    crate_name: result.crate_name,
+                    doc_path: result.doc_path,
+                    similarity: result.similarity,
+                    token_count: result.token_count,
+                })
+                .collect();
+            Json(QueryResponse { response, sources }).into_response()
+        }
+        Err(e) => {
+            let status = if e.code == ErrorCode::INVALID_PARAMS {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            error_response(status, e.message)
+        }
+    }
+}
+
+/// Effective runtime configuration, the same JSON the `server_info` tool returns
+async fn info_handler(State(state): State<AppState>) -> Response {
+    match state.handler.runtime_info().await {
+        Ok(info) => Json(info).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Prometheus text-format gauges for the query limiter
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let limiter = &state.handler.query_limiter;
+    let mut body = format!(
+        "# HELP mcpdocs_queries_in_flight Queries currently executing\n\
+         # TYPE mcpdocs_queries_in_flight gauge\n\
+         mcpdocs_queries_in_flight {}\n\
+         # HELP mcpdocs_queries_rejected_total Queries rejected because the server was busy\n\
+         # TYPE mcpdocs_queries_rejected_total counter\n\
+         mcpdocs_queries_rejected_total {}\n",
+        limiter.in_flight(),
+        limiter.rejected()
+    );
+    if let Some(max_concurrent) = limiter.max_concurrent() {
+        body.push_str(&format!(
+            "# HELP mcpdocs_max_concurrent_queries Configured cap on concurrent queries\n\
+             # TYPE mcpdocs_max_concurrent_queries gauge\n\
+             mcpdocs_max_concurrent_queries {}\n",
+            max_concurrent
+        ));
     }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+/// The HTTP routes for `transports`, the REST endpoints, and the body-size and timeout limits
+fn router(state: AppState, transports: &[Transport], max_body_bytes: usize, request_timeout: Option<Duration>) -> Router {
+    let mut app = Router::new();
+    if transports.contains(&Transport::Sse) {
+        app = app
+            .route("/sse", get(sse_handler))
+            .route("/message", post(message_handler));
+    }
+    if transports.contains(&Transport::Ws) {
+        app = app.route("/ws", get(ws_handler));
+    }
+    let mut app = app
+        .route("/query", post(query_handler))
+        .route("/info", get(info_handler))
+        .route("/metrics", get(metrics_handler))
+        // RequestBodyLimitLayer replaces axum's fixed 2 MB default
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_bytes));
+    // Only the time to produce a response counts: SSE streams and WebSockets outlive it
+    if let Some(timeout) = request_timeout {
+        app = app.layer(TimeoutLayer::with_status_code(StatusCode::GATEWAY_TIMEOUT, timeout));
+    }
+    app.with_state(state)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "rustdocs_mcp_server_http=info,rmcp=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
     // Load .env file if present
     dotenvy::dotenv().ok();
 
     // Parse CLI arguments
     let cli = Cli::parse();
 
-    info!("🚀 Starting Rust Docs MCP HTTP SSE Server on {}:{}", cli.host, cli.port);
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
+
+    let transports = transports_label(&cli.transports);
+    info!("🚀 Starting Rust Docs MCP HTTP ({}) Server on {}:{}", transports, cli.host, cli.port);
 
     // Initialize database connection
     info!("🔌 Connecting to database...");
-    let db = Database::new().await?;
+    let db = database::connect_with_metric(cli.distance).await?;
     info!("✅ Database connected successfully");
 
     // Determine which crates to load
@@ -281,18 +975,27 @@ async fn main() -> Result<(), ServerError> {
         cli.crate_names
     };
 
+    let crate_filter = CrateFilter::new(cli.allow_crates, cli.deny_crates);
+    let (crate_names, refused) = crate_filter.partition(crate_names);
+    if !refused.is_empty() {
+        info!("🚫 Not serving {} crates outside --allow-crate/--deny-crate: {}", refused.len(), refused.join(", "));
+    }
+    if crate_names.is_empty() {
+        return Err(ServerError::Config(
+            "No crates left to serve after applying --allow-crate/--deny-crate".to_string(),
+        ));
+    }
+
     info!("Target crates: {:?}", crate_names);
 
     // Check if all crates exist in database
     info!("🔍 Checking if crates exist in database...");
-    let mut missing_crates = Vec::new();
-    for crate_name in &crate_names {
-        if !db.has_embeddings(crate_name).await? {
-            missing_crates.push(crate_name.clone());
-            warn!("❌ Missing: {}", crate_name);
-        } else {
-            info!("✅ Found: {}", crate_name);
-        }
+    let (found_crates, missing_crates) = db.filter_existing_crates(&crate_names).await?;
+    for crate_name in &found_crates {
+        info!("✅ Found: {}", crate_name);
+    }
+    for crate_name in &missing_crates {
+        warn!("❌ Missing: {}", crate_name);
     }
 
     if !missing_crates.is_empty() {
@@ -306,35 +1009,10 @@ async fn main() -> Result<(), ServerError> {
     let provider_name = cli.embedding_provider.to_lowercase();
     info!("🤖 Initializing {} embedding provider...", provider_name);
 
-    let embedding_config = match provider_name.as_str() {
-        "openai" => {
-            let model = cli.embedding_model.unwrap_or_else(|| "text-embedding-3-large".to_string());
-            let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-                let config = OpenAIConfig::new().with_api_base(api_base);
-                OpenAIClient::with_config(config)
-            } else {
-                OpenAIClient::new()
-            };
-            EmbeddingConfig::OpenAI {
-                client: openai_client,
-                model,
-            }
-        },
-        "voyage" => {
-            let api_key = env::var("VOYAGE_API_KEY")
-                .map_err(|_| ServerError::MissingEnvVar("VOYAGE_API_KEY".to_string()))?;
-            let model = cli.embedding_model.unwrap_or_else(|| "voyage-3.5".to_string());
-            EmbeddingConfig::VoyageAI { api_key, model }
-        },
-        _ => {
-            return Err(ServerError::Config(format!(
-                "Unsupported embedding provider: {}. Use 'openai' or 'voyage'",
-                provider_name
-            )));
-        }
-    };
+    let embedding_config = EmbeddingConfig::from_provider(&provider_name, cli.embedding_model)?;
 
     let provider = initialize_embedding_provider(embedding_config);
+    database::check_embedding_dimensions(db.as_ref(), provider.as_ref()).await?;
     if EMBEDDING_CLIENT.set(provider).is_err() {
         return Err(ServerError::Internal("Failed to set embedding provider".to_string()));
     }
@@ -356,8 +1034,8 @@ async fn main() -> Result<(), ServerError> {
     let startup_message = if crate_names.len() == 1 {
         let doc_count = crate_stats.get(&crate_names[0]).unwrap_or(&0);
         format!(
-            "HTTP SSE MCP server for crate '{}' initialized. {} documents available via database search.",
-            crate_names[0], doc_count
+            "HTTP ({}) MCP server for crate '{}' initialized. {} documents available via database search.",
+            transports, crate_names[0], doc_count
         )
     } else {
         let crate_summary: Vec<String> = crate_stats
@@ -365,7 +1043,8 @@ async fn main() -> Result<(), ServerError> {
             .map(|(name, count)| format!("{} ({})", name, count))
             .collect();
         format!(
-            "HTTP SSE MCP multi-crate server initialized. {} total documents available from {} crates: {}",
+            "HTTP ({}) MCP multi-crate server initialized. {} total documents available from {} crates: {}",
+            transports,
             total_docs,
             crate_names.len(),
             crate_summary.join(", ")
@@ -374,49 +1053,354 @@ async fn main() -> Result<(), ServerError> {
 
     info!("✅ {}", startup_message);
 
+    if cli.self_test {
+        info!("🧪 Running self-test for {} crates...", crate_names.len());
+        let provider = EMBEDDING_CLIENT
+            .get()
+            .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+        let checks = self_test::run_self_test(db.as_ref(), provider.as_ref(), &crate_names).await?;
+        for check in &checks {
+            match &check.problem {
+                None => info!(
+                    "  ✅ {}: {} results, best similarity {:.3}",
+                    check.crate_name,
+                    check.results,
+                    check.best_similarity.unwrap_or_default()
+                ),
+                Some(problem) => error!("  ❌ {}: {}", check.crate_name, problem),
+            }
+        }
+        self_test::require_all_passed(&checks)?;
+        info!("✅ Self-test passed");
+    }
+
+    let request_timeout = (cli.request_timeout_secs > 0).then(|| Duration::from_secs(cli.request_timeout_secs));
+
     // Create the MCP handler with database access
-    let handler = McpHandler::new(db, crate_names, startup_message);
+    let handler = McpHandler::new(db, crate_names, startup_message).with_rate_limit(cli.rate_limit_per_minute)
+        .with_refresh(cli.allow_refresh)
+        .with_crate_filter(crate_filter)
+        .with_query_limit(cli.max_concurrent_queries, Duration::from_secs(cli.query_queue_timeout_secs))
+        .with_request_timeout(request_timeout);
+    if let Some(per_minute) = cli.rate_limit_per_minute {
+        info!("🚦 Rate limit: {} queries per minute per client", per_minute);
+    }
+    if let Some(max_concurrent) = handler.query_limiter.max_concurrent() {
+        info!(
+            "🚧 At most {} concurrent queries; others wait up to {}s",
+            max_concurrent, cli.query_queue_timeout_secs
+        );
+    }
 
-    // Create SSE server config
     let bind_addr: SocketAddr = format!("{}:{}", cli.host, cli.port).parse()
         .map_err(|e| ServerError::Config(format!("Invalid bind address: {}", e)))?;
-    
-    let config = SseServerConfig {
-        bind: bind_addr,
-        sse_path: "/sse".to_string(),
-        post_path: "/message".to_string(),
-        ct: CancellationToken::new(),
+
+    let state = AppState {
+        handler,
+        sessions: Arc::new(RwLock::new(HashMap::new())),
     };
 
-    info!("🌐 Starting SSE server on {}", bind_addr);
-    info!("📡 SSE endpoint: http://{}/sse", bind_addr);
-    info!("📤 POST endpoint: http://{}/message", bind_addr);
-    
-    // Create and serve SSE server
-    let mut sse_server = SseServer::serve_with_config(config).await
-        .map_err(|e| ServerError::Internal(format!("Failed to start SSE server: {}", e)))?;
+    let app = router(state, &cli.transports, cli.max_body_bytes, request_timeout);
+
+    info!("🌐 Starting HTTP server on {}", bind_addr);
+    if cli.transports.contains(&Transport::Sse) {
+        info!("📡 SSE endpoint: http://{}/sse", bind_addr);
+        info!("📤 POST endpoint: http://{}/message", bind_addr);
+    }
+    if cli.transports.contains(&Transport::Ws) {
+        info!("🔌 WebSocket endpoint: ws://{}/ws", bind_addr);
+    }
+    info!("❓ Query endpoint: http://{}/query", bind_addr);
+    info!("ℹ️  Info endpoint: http://{}/info", bind_addr);
+    info!("📊 Metrics endpoint: http://{}/metrics", bind_addr);
+    match request_timeout {
+        Some(timeout) => info!(
+            "⏱️  Requests limited to {} bytes and {}s",
+            cli.max_body_bytes,
+            timeout.as_secs()
+        ),
+        None => info!("⏱️  Requests limited to {} bytes, no timeout", cli.max_body_bytes),
+    }
+
+    let listener = tokio::net::TcpListener::bind(bind_addr).await?;
 
-    info!("🔧 Server-Sent Events transport ready");
+    info!("🔧 MCP transports ready: {}", transports);
     info!("🎯 MCP server waiting for connections...");
 
-    // Handle incoming transports
-    while let Some(transport) = sse_server.next_transport().await {
-        info!("🔗 New MCP connection established");
-        let handler_clone = handler.clone();
-        
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustdocs_mcp_server::embeddings::EmbeddingProvider;
+
+    async fn test_state() -> AppState {
+        test_state_with(&[]).await
+    }
+
+    async fn test_state_with(crates: &[(&str, Vec<database::TestDoc>)]) -> AppState {
+        let database = database::test_store_with(crates).await;
+        AppState {
+            handler: McpHandler::new(database, vec!["tokio".to_string()], String::new()),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    async fn post_query(crate_name: &str, question: &str) -> StatusCode {
+        let request = QueryRequest {
+            crate_name: Some(crate_name.to_string()),
+            crate_names: None,
+            question: question.to_string(),
+            module_prefix: None,
+            kind: None,
+            max_results: None,
+            snippet_chars: None,
+        };
+        query_handler(State(test_state().await), ConnectInfo(test_client()), Ok(Json(request))).await.status()
+    }
+
+    fn test_client() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 40000))
+    }
+
+    #[tokio::test]
+    async fn search_rejects_blank_question_before_embedding() {
+        let state = test_state().await;
+        let err = state.handler.search_docs(&["tokio".to_string()], "  \n ", DocFilter::default(), 5).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("question"));
+    }
+
+    #[tokio::test]
+    async fn does_not_advertise_logging() {
+        let info = test_state().await.handler.get_info();
+        assert!(info.capabilities.logging.is_none());
+        assert!(info.capabilities.tools.is_some());
+    }
+
+    #[test]
+    fn startup_logs_name_the_selected_transports() {
+        assert_eq!(transports_label(&[Transport::Sse]), "SSE");
+        assert_eq!(transports_label(&[Transport::Ws]), "WebSocket");
+        assert_eq!(transports_label(&[Transport::Sse, Transport::Ws, Transport::Sse]), "SSE + WebSocket");
+    }
+
+    #[test]
+    fn search_rejects_empty_crate_name() {
+        let err = resolve_target_crates(Some(""), None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("crate_name"));
+    }
+
+    #[tokio::test]
+    async fn search_reports_every_missing_crate() {
+        let state = test_state().await;
+        let crate_names = vec!["tokio".to_string(), "axum".to_string(), "hyper".to_string()];
+        let err = state.handler.search_docs(&crate_names, "How do axum and tokio interact?", DocFilter::default(), 5).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("axum, hyper"));
+        assert!(!err.message.contains("not available: tokio"));
+    }
+
+    #[tokio::test]
+    async fn crate_filter_limits_served_crates() {
+        let database = database::test_store_with(&[]).await;
+        let filter = CrateFilter::new(Vec::new(), vec!["hyper".to_string()]);
+        let handler = McpHandler::new(database, vec!["tokio".to_string(), "hyper".to_string()], String::new())
+            .with_refresh(true)
+            .with_crate_filter(filter);
+        assert_eq!(*handler.available_crates, ["tokio"]);
+
+        let err = handler.search_docs(&["hyper".to_string()], "How do I serve HTTP?", DocFilter::default(), 5).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        let err = handler
+            .refresh_crate(RefreshCrateArgs { crate_name: "hyper".to_string() })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("not served"));
+    }
+
+    #[tokio::test]
+    async fn query_route_returns_bad_request_for_empty_arguments() {
+        assert_eq!(post_query("tokio", "").await, StatusCode::BAD_REQUEST);
+        assert_eq!(post_query("", "How do I spawn a task?").await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn query_route_rate_limits_each_client() {
+        let mut state = test_state().await;
+        state.handler = state.handler.with_rate_limit(Some(1));
+        let request = || QueryRequest {
+            crate_name: Some("tokio".to_string()),
+            crate_names: None,
+            question: "How do I spawn a task?".to_string(),
+            module_prefix: None,
+            kind: None,
+            max_results: None,
+            snippet_chars: None,
+        };
+
+        // The first call passes the limiter (and fails later: the test store has no embeddings)
+        let first = query_handler(State(state.clone()), ConnectInfo(test_client()), Ok(Json(request()))).await;
+        assert_ne!(first.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let second = query_handler(State(state.clone()), ConnectInfo(test_client()), Ok(Json(request()))).await;
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(second.headers().contains_key(header::RETRY_AFTER));
+
+        let other_client = SocketAddr::from(([127, 0, 0, 2], 40000));
+        let other = query_handler(State(state), ConnectInfo(other_client), Ok(Json(request()))).await;
+        assert_ne!(other.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn query_route_returns_service_unavailable_when_busy() {
+        let mut state = test_state().await;
+        state.handler = state.handler.with_query_limit(Some(1), Duration::from_millis(20));
+        let _running = state.handler.query_limiter.acquire().await.unwrap();
+
+        let request = QueryRequest {
+            crate_name: Some("tokio".to_string()),
+            crate_names: None,
+            question: "How do I spawn a task?".to_string(),
+            module_prefix: None,
+            kind: None,
+            max_results: None,
+            snippet_chars: None,
+        };
+        let response = query_handler(State(state.clone()), ConnectInfo(test_client()), Ok(Json(request))).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+        let response = metrics_handler(State(state)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let metrics = String::from_utf8(body.to_vec()).unwrap();
+        assert!(metrics.contains("mcpdocs_queries_in_flight 1\n"));
+        assert!(metrics.contains("mcpdocs_queries_rejected_total 1\n"));
+        assert!(metrics.contains("mcpdocs_max_concurrent_queries 1\n"));
+    }
+
+    /// Embeds every text as the same unit vector
+    struct FixedProvider;
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for FixedProvider {
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<(Vec<Vec<f32>>, usize), ServerError> {
+            Ok((texts.iter().map(|_| vec![1.0, 0.0]).collect(), texts.len()))
+        }
+
+        fn get_model_name(&self) -> &str {
+            "fixed"
+        }
+
+        fn provider_name(&self) -> &str {
+            "fixed"
+        }
+
+        fn embedding_dimensions(&self) -> usize {
+            2
+        }
+    }
+
+    #[tokio::test]
+    async fn mcp_http_wrapper_forwards_to_the_query_route() {
+        use rmcp::model::CallToolRequestParam;
+        use rustdocs_mcp_server::http_wrapper::HttpWrapper;
+
+        // Nothing else in these tests gets far enough to embed a question
+        let _ = EMBEDDING_CLIENT.set(Arc::new(FixedProvider));
+        let state = test_state_with(&[(
+            "tokio",
+            vec![database::test_doc("tokio/latest/tokio/fn.spawn.html", "Spawns a new asynchronous task.", &[1.0, 0.0])],
+        )])
+        .await;
+        let app = router(state, &[Transport::Sse], DEFAULT_MAX_BODY_BYTES, Some(DEFAULT_REQUEST_TIMEOUT));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
         tokio::spawn(async move {
-            match handler_clone.serve(transport).await {
-                Ok(service) => {
-                    if let Err(e) = service.waiting().await {
-                        tracing::error!("MCP service error: {}", e);
-                    }
-                }
-                Err(e) => {
-                    tracing::error!("Failed to start MCP service: {}", e);
-                }
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap()
+        });
+
+        let wrapper = HttpWrapper::new(format!("http://{}", addr));
+        let call = |crate_name: &str| CallToolRequestParam {
+            name: "query_rust_docs".into(),
+            arguments: json!({ "crate_name": crate_name, "question": "How do I spawn a task?" }).as_object().cloned(),
+        };
+
+        let result = wrapper.forward_tool_call(call("tokio")).await.unwrap();
+        let text = serde_json::to_value(&result.content).unwrap()[0]["text"].as_str().unwrap().to_string();
+        assert!(text.contains("tokio/latest/tokio/fn.spawn.html"), "{}", text);
+        assert!(text.contains("Spawns a new asynchronous task."), "{}", text);
+
+        // The route's 400 for an unknown crate reaches the MCP client as invalid params
+        let err = wrapper.forward_tool_call(call("hyper")).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("not available"), "{}", err.message);
+    }
+
+    #[tokio::test]
+    async fn websocket_transport_serves_mcp() {
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let app = router(test_state().await, &[Transport::Ws], DEFAULT_MAX_BODY_BYTES, Some(DEFAULT_REQUEST_TIMEOUT));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "0.0.0" }
             }
         });
+        socket.send(WsMessage::text(initialize.to_string())).await.unwrap();
+
+        let reply = loop {
+            match socket.next().await.unwrap().unwrap() {
+                WsMessage::Text(text) => break serde_json::from_str::<serde_json::Value>(text.as_str()).unwrap(),
+                _ => continue,
+            }
+        };
+        assert_eq!(reply["id"], 1);
+        assert!(reply["result"]["serverInfo"]["name"].is_string());
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn router_rejects_oversized_bodies() {
+        use tower::ServiceExt;
+
+        let app = router(test_state().await, &[Transport::Sse], 1024, Some(DEFAULT_REQUEST_TIMEOUT));
+        let question = "x".repeat(2048);
+        let body = json!({ "crate_name": "tokio", "question": question }).to_string();
+        for uri in ["/query", "/message?sessionId=missing"] {
+            let request = axum::http::Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_LENGTH, body.len())
+                .body(axum::body::Body::from(body.clone()))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn info_route_reports_runtime_config() {
+        let response = info_handler(State(test_state().await)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["server"], "rustdocs-mcp-server-http");
+        assert_eq!(info["database"]["backend"], "sqlite");
+        assert_eq!(info["crates"], json!(["tokio"]));
+        assert!(info["llm_model"].is_null());
+    }
+}
@@ -1,18 +1,112 @@
 use rustdocs_mcp_server::{
-    database::Database,
-    doc_loader,
-    embeddings::{generate_embeddings, EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
+    database::{self, VectorStore},
+    dedupe,
+    doc_loader::{self, Document},
+    embeddings::{count_embedding_tokens, estimate_cost, format_cost, generate_embeddings_with_progress, EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider_with_dimensions},
     error::ServerError,
+    progress::Progress,
+    verbosity::VerbosityArgs,
 };
-use async_openai::{Client as OpenAIClient, config::OpenAIConfig};
+use tracing::{debug, info, warn};
+use clap::Parser;
+use ndarray::Array1;
 use serde::{Deserialize, Serialize};
-use std::{env, fs};
-use futures::future::try_join_all;
-use tiktoken_rs;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{BufRead, IsTerminal, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Populate every enabled crate from proxy-config.json, or the most downloaded crates on crates.io", long_about = None)]
+struct Cli {
+    /// Bootstrap: populate the N crates with the most recent downloads on crates.io
+    /// instead of the crates in proxy-config.json (asks for confirmation first)
+    #[arg(long, value_name = "N")]
+    top_crates: Option<usize>,
+
+    /// Skip the confirmation prompt of --top-crates
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Crates crawled and embedded at once (default: all of them, or 4 with --top-crates)
+    #[arg(long, env = "POPULATE_CONCURRENCY")]
+    concurrency: Option<usize>,
+
+    /// Stop embedding new crates once the estimated embedding cost would exceed this many USD
+    #[arg(long, env = "POPULATE_MAX_COST", value_name = "USD")]
+    max_cost: Option<f64>,
+
+    /// Pages to crawl for crates without a `max_pages` in proxy-config.json
+    #[arg(long, default_value_t = 50)]
+    max_pages: usize,
+
+    /// Crawl crates without a `max_depth` in proxy-config.json by link depth instead of page count
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Store duplicate content under every path instead of skipping it
+    #[arg(long)]
+    no_dedupe: bool,
+
+    /// Cut page text beyond this many characters before counting tokens (0 = no limit)
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MAX_CONTENT_CHARS)]
+    max_content_length: usize,
+
+    /// Truncate pages above this many tokens (0 = no limit)
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MAX_PAGE_TOKENS)]
+    max_page_tokens: usize,
+
+    /// Skip pages above --max-page-tokens instead of truncating them
+    #[arg(long)]
+    skip_oversized_pages: bool,
+
+    /// Skip pages with less extracted text than this many characters
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MIN_PAGE_CHARS)]
+    min_page_chars: usize,
+
+    /// Stop following links from pages at least this cosine-similar (0-1] to a page already
+    /// crawled for the crate; costs one embedding request per page and may miss pages
+    #[arg(long, value_name = "THRESHOLD")]
+    stop_on_redundancy: Option<f32>,
+
+    /// Crawl paths the site's robots.txt disallows (only for self-hosted sites you own)
+    #[arg(long)]
+    ignore_robots: bool,
+
+    /// CSS selector for the content extracted from each page (default: rustdoc docblocks)
+    #[arg(long)]
+    content_selector: Option<String>,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Cli {
+    fn page_limits(&self) -> doc_loader::PageLimits {
+        doc_loader::PageLimits {
+            max_content_chars: (self.max_content_length > 0).then_some(self.max_content_length),
+            max_page_tokens: (self.max_page_tokens > 0).then_some(self.max_page_tokens),
+            min_page_chars: self.min_page_chars,
+            skip_oversized: self.skip_oversized_pages,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 struct ProxyConfig {
+    #[serde(default)]
     rustdocs_binary_path: String,
+    /// Embedding provider, model and dimensions used when the environment doesn't set them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embedding_dimensions: Option<usize>,
     crates: Vec<CrateConfig>,
 }
 
@@ -21,139 +115,393 @@ struct CrateConfig {
     name: String,
     features: Option<Vec<String>>,
     enabled: bool,
+    /// Pages to crawl for this crate, overriding --max-pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_pages: Option<usize>,
+    /// Link depth to crawl this crate to, overriding --max-depth and any page count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    /// Self-hosted rustdoc site to crawl instead of docs.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+}
+
+/// Embed `documents` with the global provider, showing a progress bar (or logging at every
+/// tenth of the chunks when stderr is not a terminal)
+async fn embed_with_progress(
+    label: &str,
+    documents: &[Document],
+) -> Result<(Vec<(String, String, Array1<f32>)>, usize), ServerError> {
+    let provider = EMBEDDING_CLIENT
+        .get()
+        .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+    let progress = Progress::new(format!("🧠 {}Embedding", label), "chunks", documents.len() as u64);
+    let result = generate_embeddings_with_progress(provider, documents, |done, total| {
+        progress.set(done as u64, total as u64)
+    })
+    .await;
+    progress.finish();
+    result
+}
+
+/// Rows stored per transaction, so the insert bar advances on large crates
+const INSERT_BATCH_ROWS: usize = 500;
+
+/// Store `rows` in slices of [`INSERT_BATCH_ROWS`], showing a progress bar
+async fn insert_with_progress(
+    db: &(dyn VectorStore + Send + Sync),
+    label: &str,
+    crate_id: i32,
+    crate_name: &str,
+    rows: &[(String, String, Array1<f32>, i32)],
+) -> Result<(), ServerError> {
+    let progress = Progress::new(format!("💾 {}Storing", label), "rows", rows.len() as u64);
+    let mut stored = 0;
+    for slice in rows.chunks(INSERT_BATCH_ROWS) {
+        if let Err(e) = db.insert_embeddings_batch(crate_id, crate_name, slice).await {
+            progress.finish();
+            return Err(e);
+        }
+        stored += slice.len();
+        progress.set(stored as u64, rows.len() as u64);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Tokens per stored document assumed by the --top-crates estimate when the database is empty
+const DEFAULT_TOKENS_PER_DOC: usize = 1000;
+
+/// Crates populated at once with --top-crates when --concurrency isn't given
+const DEFAULT_TOP_CRATES_CONCURRENCY: usize = 4;
+
+/// What happened to one crate
+struct CrateOutcome {
+    crate_name: String,
+    embeddings: usize,
+    cost: Option<f64>,
+    duplicates: usize,
+    /// Why the crate wasn't stored (failed docs.rs build, budget reached)
+    skipped: Option<String>,
+}
+
+impl CrateOutcome {
+    fn skipped(crate_name: String, reason: String) -> Self {
+        Self { crate_name, embeddings: 0, cost: Some(0.0), duplicates: 0, skipped: Some(reason) }
+    }
+}
+
+/// Log what populating `crate_names` could cost and ask to go on, prompting on stderr. Without
+/// a terminal the caller must have passed `--yes`.
+fn confirm_bootstrap(crate_names: &[String], max_pages: usize, tokens_per_doc: usize, model: &str) -> Result<bool, ServerError> {
+    let tokens = crate_names.len() * max_pages * tokens_per_doc;
+    info!("📦 Top crates to populate ({}): {}", crate_names.len(), crate_names.join(", "));
+    info!(
+        "💰 At most ~{} tokens ({} crates × {} pages × ~{} tokens), about {} with {}",
+        tokens,
+        crate_names.len(),
+        max_pages,
+        tokens_per_doc,
+        format_cost(estimate_cost(model, tokens)),
+        model
+    );
+    if !std::io::stdin().is_terminal() {
+        return Err(ServerError::Config(
+            "--top-crates asks for confirmation; pass --yes to run without a terminal".to_string(),
+        ));
+    }
+    eprint!("Continue? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// `usd` in whole micro-dollars, the unit the --max-cost budget is tracked in
+fn micro_dollars(usd: f64) -> u64 {
+    (usd * 1_000_000.0).round() as u64
+}
+
+/// `var` from the environment if set and non-empty, else the value from proxy-config.json
+fn env_or_config(var: &str, config: Option<String>) -> Option<String> {
+    env::var(var).ok().filter(|v| !v.trim().is_empty()).or(config)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
     dotenvy::dotenv().ok();
 
-    // Read proxy-config.json
-    println!("📋 Reading proxy-config.json...");
-    let config_content = fs::read_to_string("proxy-config.json")
-        .map_err(|e| ServerError::Config(format!("Failed to read proxy-config.json: {}", e)))?;
+    let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
+    // Fail on a bad --content-selector before crawling anything
+    doc_loader::parse_content_selector(cli.content_selector.as_deref())?;
+
+    // Read proxy-config.json; --top-crates only takes its embedding settings, if there is one
+    let config: ProxyConfig = if cli.top_crates.is_some() && !Path::new("proxy-config.json").exists() {
+        ProxyConfig::default()
+    } else {
+        info!("📋 Reading proxy-config.json...");
+        let config_content = fs::read_to_string("proxy-config.json")
+            .map_err(|e| ServerError::Config(format!("Failed to read proxy-config.json: {}", e)))?;
+        serde_json::from_str(&config_content)
+            .map_err(|e| ServerError::Config(format!("Failed to parse proxy-config.json: {}", e)))?
+    };
+
+    let ProxyConfig { embedding_provider, embedding_model, embedding_dimensions, crates, .. } = config;
 
-    let config: ProxyConfig = serde_json::from_str(&config_content)
-        .map_err(|e| ServerError::Config(format!("Failed to parse proxy-config.json: {}", e)))?;
+    // One client for every crate, so connections to docs.rs are reused
+    let crawler = &doc_loader::crawler_client()?;
 
     // Filter enabled crates
-    let enabled_crates: Vec<_> = config.crates.into_iter()
-        .filter(|c| c.enabled)
-        .collect();
+    let enabled_crates: Vec<CrateConfig> = match cli.top_crates {
+        Some(limit) => {
+            info!("🌍 Fetching the {} most downloaded crates from crates.io...", limit);
+            doc_loader::fetch_top_crates(crawler, limit)
+                .await?
+                .into_iter()
+                .map(|name| CrateConfig {
+                    name,
+                    features: None,
+                    enabled: true,
+                    max_pages: None,
+                    max_depth: None,
+                    base_url: None,
+                })
+                .collect()
+        }
+        None => crates.into_iter().filter(|c| c.enabled).collect(),
+    };
 
-    println!("📦 Found {} enabled crates to populate", enabled_crates.len());
+    info!("📦 Found {} enabled crates to populate", enabled_crates.len());
     for crate_config in &enabled_crates {
-        println!("  - {} {:?}", crate_config.name, crate_config.features);
+        debug!("  - {} {:?}", crate_config.name, crate_config.features);
     }
 
     // Initialize database
-    let db = Database::new().await?;
+    let db = database::connect().await?;
 
     // Check which crates already exist
     let mut crates_to_populate = Vec::new();
     for crate_config in &enabled_crates {
         if db.has_embeddings(&crate_config.name).await? {
-            println!("✅ {} already has embeddings", crate_config.name);
+            info!("✅ {} already has embeddings", crate_config.name);
         } else {
-            println!("❌ {} needs to be populated", crate_config.name);
+            info!("❌ {} needs to be populated", crate_config.name);
             crates_to_populate.push(crate_config);
         }
     }
 
     if crates_to_populate.is_empty() {
-        println!("✅ All crates already have embeddings!");
+        info!("✅ All crates already have embeddings!");
         return Ok(());
     }
 
-    // Initialize embedding provider (default to OpenAI for populate script)
-    let provider_type = env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "openai".to_string());
-    let embedding_config = match provider_type.to_lowercase().as_str() {
-        "openai" => {
-            let model = env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "text-embedding-3-large".to_string());
-            let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-                let config = OpenAIConfig::new().with_api_base(api_base);
-                OpenAIClient::with_config(config)
-            } else {
-                OpenAIClient::new()
-            };
-            EmbeddingConfig::OpenAI { client: openai_client, model }
-        },
-        "voyage" => {
-            let api_key = env::var("VOYAGE_API_KEY")
-                .map_err(|_| ServerError::MissingEnvVar("VOYAGE_API_KEY".to_string()))?;
-            let model = env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "voyage-3.5".to_string());
-            EmbeddingConfig::VoyageAI { api_key, model }
-        },
-        _ => {
-            return Err(ServerError::Config(format!(
-                "Unsupported embedding provider: {}. Use 'openai' or 'voyage'",
-                provider_type
-            )));
+    // Initialize embedding provider: environment, then proxy-config.json, then OpenAI defaults
+    let provider_type = env_or_config("EMBEDDING_PROVIDER", embedding_provider).unwrap_or_else(|| "openai".to_string());
+    let embedding_config = EmbeddingConfig::from_provider(&provider_type, env_or_config("EMBEDDING_MODEL", embedding_model))?;
+    let dimensions = embedding_dimensions.filter(|_| env_or_config("EMBEDDING_DIMENSIONS", None).is_none());
+
+    let provider = initialize_embedding_provider_with_dimensions(embedding_config, dimensions);
+    database::check_embedding_dimensions(db.as_ref(), provider.as_ref()).await?;
+    let model = provider.get_model_name().to_string();
+    if cli.max_cost.is_some() && estimate_cost(&model, 0).is_none() {
+        return Err(ServerError::Config(format!(
+            "--max-cost needs a price for '{}'; set EMBEDDING_COST_PER_MILLION",
+            model
+        )));
+    }
+    if cli.top_crates.is_some() && !cli.yes {
+        let stats = db.global_stats().await?;
+        let tokens_per_doc = match stats.total_docs {
+            0 => DEFAULT_TOKENS_PER_DOC,
+            docs => (stats.total_tokens / docs).max(1),
+        };
+        let names: Vec<String> = crates_to_populate.iter().map(|c| c.name.clone()).collect();
+        if !confirm_bootstrap(&names, cli.max_pages, tokens_per_doc, &model)? {
+            warn!("Aborted; nothing was populated.");
+            return Ok(());
         }
-    };
-
-    let provider = initialize_embedding_provider(embedding_config);
+    }
+    let redundancy = &cli
+        .stop_on_redundancy
+        .map(|threshold| doc_loader::RedundancyCheck::new(provider.clone(), threshold))
+        .transpose()?;
     if EMBEDDING_CLIENT.set(provider).is_err() {
         return Err(ServerError::Internal("Failed to set embedding provider".to_string()));
     }
 
-    let embedding_model = env::var("EMBEDDING_MODEL")
-        .unwrap_or_else(|_| "text-embedding-3-small".to_string());
-
-    println!("\n🚀 Starting parallel population of {} crates...", crates_to_populate.len());
+    // Configured crates all run at once unless bounded; a bootstrap defaults to a few at a time
+    let concurrency = cli
+        .concurrency
+        .or(cli.top_crates.map(|_| DEFAULT_TOP_CRATES_CONCURRENCY))
+        .unwrap_or(crates_to_populate.len())
+        .max(1);
+    info!(
+        "🚀 Starting population of {} crates, {} at a time...",
+        crates_to_populate.len(),
+        concurrency
+    );
     let start_time = std::time::Instant::now();
+    // Estimated embedding spend so far in micro-dollars, checked against --max-cost before each
+    // crate is embedded
+    let spent = &AtomicU64::new(0);
+    let max_cost = cli.max_cost;
 
     // Create tasks for parallel processing
-    let tasks: Vec<_> = crates_to_populate.into_iter().enumerate().map(|(i, crate_config)| {
+    let tasks = crates_to_populate.into_iter().enumerate().map(|(i, crate_config)| {
         let db = &db;
+        let model = &model;
         // Provider is now globally accessible, no cloning needed
         let crate_name = crate_config.name.clone();
         let features = crate_config.features.clone();
+        let crawl_limit = doc_loader::CrawlLimit::new(
+            Some(crate_config.max_pages.unwrap_or(cli.max_pages)),
+            crate_config.max_depth.or(cli.max_depth),
+        );
         let total = enabled_crates.len();
+        let no_dedupe = cli.no_dedupe;
+        let page_limits = cli.page_limits();
+        let content_selector = cli.content_selector.clone();
+        let base_url = crate_config.base_url.clone();
+        let ignore_robots = cli.ignore_robots;
 
         async move {
-            println!("\n📥 [{}/{}] Loading documentation for: {}", i + 1, total, crate_name);
+            info!("📥 [{}/{}] Loading documentation for: {} ({})", i + 1, total, crate_name, crawl_limit);
             let doc_start = std::time::Instant::now();
 
-            let load_result = doc_loader::load_documents_from_docs_rs(
+            let loading = Progress::spinner(format!("📥 [{}/{}] Loading {}", i + 1, total, crate_name));
+            let load_result = doc_loader::load_documents_from_docs_rs_with_limits(
+                crawler,
                 &crate_name,
                 "*",
                 features.as_ref(),
-                Some(50)  // Use smaller page limit for batch processing
-            ).await?;
+                crawl_limit,
+                &page_limits,
+                content_selector.as_deref(),
+                base_url.as_deref(),
+                redundancy.as_ref(),
+                ignore_robots,
+            ).await;
+            loading.finish();
+            let load_result = match load_result {
+                Ok(load_result) => load_result,
+                // Nothing to store; the rest of the run goes on
+                Err(e @ (doc_loader::DocLoaderError::BuildFailed(_) | doc_loader::DocLoaderError::NoDocumentation(_))) => {
+                    warn!("⏭️  [{}/{}] Skipping {}: {}", i + 1, total, crate_name, e);
+                    return Ok(CrateOutcome::skipped(crate_name, e.to_string()));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if load_result.pruned_links > 0 {
+                info!("✂️  [{}/{}] {}: pruned {} links from redundant pages",
+                    i + 1, total, crate_name, load_result.pruned_links);
+            }
+            // Already spent; added to the crate's cost once it is embedded
+            let redundancy_tokens = load_result.redundancy_tokens;
+            if redundancy_tokens > 0 {
+                info!("🔁 [{}/{}] {}: redundancy check embedded {} tokens",
+                    i + 1, total, crate_name, redundancy_tokens);
+            }
+            if !load_result.skipped_pages.is_empty() || !load_result.truncated_pages.is_empty() {
+                info!("⏭️  [{}/{}] {}: skipped {} and truncated {} pages by size limits",
+                    i + 1, total, crate_name, load_result.skipped_pages.len(), load_result.truncated_pages.len());
+            }
             let documents = load_result.documents;
             let crate_version = load_result.version;
+            let features = load_result.features;
 
             let doc_time = doc_start.elapsed();
-            println!("✅ [{}/{}] Loaded {} documents for {} in {:.2}s",
+            info!("✅ [{}/{}] Loaded {} documents for {} in {:.2}s",
                 i + 1, total, documents.len(), crate_name, doc_time.as_secs_f64());
 
             if let Some(ref version) = crate_version {
-                println!("📦 [{}/{}] Detected version for {}: {}", i + 1, total, crate_name, version);
+                debug!("📦 [{}/{}] Detected version for {}: {}", i + 1, total, crate_name, version);
             }
 
             if documents.is_empty() {
-                println!("⚠️  No documents found for {}", crate_name);
-                return Ok::<_, ServerError>((crate_name, 0, 0.0));
+                warn!("⚠️  No documents found for {}", crate_name);
+                return Ok::<_, ServerError>(CrateOutcome::skipped(crate_name, "no documents found".to_string()));
             }
 
+            // Skip content already stored (or repeated) under another path before embedding
+            let existing_hashes = if no_dedupe {
+                HashMap::new()
+            } else {
+                dedupe::existing_content_hashes(db.as_ref(), &crate_name).await?
+            };
+            let mut duplicates_skipped = 0;
+            let documents = if no_dedupe {
+                documents
+            } else {
+                let (documents, skipped) = dedupe::dedupe_by_content(
+                    documents,
+                    |doc| (doc.path.as_str(), doc.content.as_str()),
+                    &existing_hashes,
+                );
+                duplicates_skipped += skipped;
+                documents
+            };
+
+            // Initialize tokenizer for accurate token counting
+            let bpe = tiktoken_rs::cl100k_base()
+                .map_err(|e| ServerError::Tiktoken(e.to_string()))?;
+
+            // Reserve this crate's estimated cost, or skip it if that would exceed the budget
+            let reserved = match max_cost {
+                Some(max_cost) => {
+                    // Counted over the chunks that will be sent, like the cost reported afterwards
+                    let tokens = count_embedding_tokens(&documents) + redundancy_tokens;
+                    let cost = estimate_cost(model, tokens).unwrap_or_default();
+                    let reserved = micro_dollars(cost);
+                    let fits = spent.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spent| {
+                        Some(spent + reserved).filter(|&total| total <= micro_dollars(max_cost))
+                    });
+                    if fits.is_err() {
+                        warn!("💸 [{}/{}] Skipping {}: ~{} more would exceed --max-cost (${:.2})",
+                            i + 1, total, crate_name, format_cost(Some(cost)), max_cost);
+                        return Ok(CrateOutcome::skipped(crate_name, "over --max-cost".to_string()));
+                    }
+                    reserved
+                }
+                None => 0,
+            };
+
             // Generate embeddings
-            println!("🧠 [{}/{}] Generating embeddings for {}...", i + 1, total, crate_name);
+            info!("🧠 [{}/{}] Generating embeddings for {}...", i + 1, total, crate_name);
             let embed_start = std::time::Instant::now();
-            let (embeddings, total_tokens) = generate_embeddings(&documents).await?;
+            let (embeddings, total_tokens) = embed_with_progress(&format!("[{}/{}] ", i + 1, total), &documents).await?;
             let embed_time = embed_start.elapsed();
 
-            let cost_per_million = 0.02;
-            let estimated_cost = (total_tokens as f64 / 1_000_000.0) * cost_per_million;
-            println!("✅ [{}/{}] Generated {} embeddings for {} in {:.2}s (${:.6})",
-                i + 1, total, embeddings.len(), crate_name, embed_time.as_secs_f64(), estimated_cost);
+            let embeddings = if no_dedupe {
+                embeddings
+            } else {
+                let (embeddings, skipped) = dedupe::dedupe_by_content(
+                    embeddings,
+                    |(path, content, _)| (path.as_str(), content.as_str()),
+                    &existing_hashes,
+                );
+                duplicates_skipped += skipped;
+                embeddings
+            };
+            if duplicates_skipped > 0 {
+                info!("🧹 [{}/{}] Skipped {} duplicate documents/chunks for {}",
+                    i + 1, total, duplicates_skipped, crate_name);
+            }
+
+            let estimated_cost = estimate_cost(model, total_tokens + redundancy_tokens);
+            // Settle the reservation with what was actually embedded
+            let actual = micro_dollars(estimated_cost.unwrap_or_default());
+            if actual >= reserved {
+                spent.fetch_add(actual - reserved, Ordering::SeqCst);
+            } else {
+                spent.fetch_sub(reserved - actual, Ordering::SeqCst);
+            }
+            info!("✅ [{}/{}] Generated {} embeddings for {} in {:.2}s ({})",
+                i + 1, total, embeddings.len(), crate_name, embed_time.as_secs_f64(), format_cost(estimated_cost));
 
             // Store in database
             let crate_id = db.upsert_crate(&crate_name, crate_version.as_deref()).await?;
 
-            // Initialize tokenizer for accurate token counting
-            let bpe = tiktoken_rs::cl100k_base()
-                .map_err(|e| ServerError::Tiktoken(e.to_string()))?;
-
             let mut batch_data = Vec::new();
             for (path, content, embedding) in embeddings.iter() {
                 // Calculate actual token count for this chunk
@@ -166,20 +514,33 @@ async fn main() -> Result<(), ServerError> {
                 ));
             }
 
-            db.insert_embeddings_batch(crate_id, &crate_name, &batch_data).await?;
+            // Running servers refuse the crate until every batch is stored
+            database::populate_with_status(db.as_ref(), &crate_name, async {
+                insert_with_progress(db.as_ref(), &format!("[{}/{}] ", i + 1, total), crate_id, &crate_name, &batch_data).await?;
+                db.set_crate_features(&crate_name, &features).await?;
+                db.set_crate_embedding_model(&crate_name, model).await?;
+                database::record_crate_dependencies(db.as_ref(), crawler, &crate_name, crate_version.as_deref()).await
+            })
+            .await?;
 
             // Add delay between crates to be respectful to docs.rs
             if i < total - 1 {
-                println!("⏱️  Waiting 2 seconds before next crate...");
+                debug!("⏱️  Waiting 2 seconds before next crate...");
                 tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
             }
 
-            Ok((crate_name, embeddings.len(), estimated_cost))
+            Ok(CrateOutcome {
+                crate_name,
+                embeddings: embeddings.len(),
+                cost: estimated_cost,
+                duplicates: duplicates_skipped,
+                skipped: None,
+            })
         }
-    }).collect();
+    });
 
-    // Execute all tasks in parallel
-    let results = try_join_all(tasks).await?;
+    // At most `concurrency` crates at once; the first error stops the run
+    let results: Vec<CrateOutcome> = stream::iter(tasks).buffer_unordered(concurrency).try_collect().await?;
     let total_time = start_time.elapsed();
 
     // Summary
@@ -187,16 +548,31 @@ async fn main() -> Result<(), ServerError> {
     println!("📊 Summary:");
 
     let mut total_embeddings = 0;
-    let mut total_cost = 0.0;
-
-    for (crate_name, embedding_count, cost) in results {
-        println!("  ✅ {}: {} embeddings (${:.6})", crate_name, embedding_count, cost);
-        total_embeddings += embedding_count;
-        total_cost += cost;
+    // Unknown as soon as any crate's cost is unknown
+    let mut total_cost = Some(0.0);
+    let mut total_duplicates = 0;
+
+    let mut skipped = 0;
+    for outcome in results {
+        if let Some(reason) = &outcome.skipped {
+            println!("  ⏭️  {}: skipped ({})", outcome.crate_name, reason);
+            skipped += 1;
+            continue;
+        }
+        println!("  ✅ {}: {} embeddings ({})", outcome.crate_name, outcome.embeddings, format_cost(outcome.cost));
+        total_embeddings += outcome.embeddings;
+        total_cost = total_cost.zip(outcome.cost).map(|(total, cost)| total + cost);
+        total_duplicates += outcome.duplicates;
     }
 
     println!("\n📈 Total: {} embeddings across {} crates", total_embeddings, enabled_crates.len());
-    println!("💰 Total estimated cost: ${:.6}", total_cost);
+    println!("💰 Total estimated cost: {}", format_cost(total_cost));
+    if skipped > 0 {
+        println!("⏭️  Crates skipped: {}", skipped);
+    }
+    if !cli.no_dedupe {
+        println!("🧹 Total duplicates skipped: {}", total_duplicates);
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -1,13 +1,17 @@
 use rustdocs_mcp_server::{
-    database::Database,
-    doc_loader,
-    embeddings::{generate_embeddings, EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
+    database::{self, VectorStore},
+    dedupe,
+    doc_loader::{self, Document},
+    embeddings::{estimate_cost, format_cost, generate_embeddings_with_progress, EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
     error::ServerError,
+    progress::Progress,
+    refresh,
+    verbosity::VerbosityArgs,
 };
-use async_openai::{Client as OpenAIClient, config::OpenAIConfig};
+use tracing::{debug, info, warn};
 use clap::Parser;
-use std::env;
-use tiktoken_rs;
+use ndarray::Array1;
+use std::{collections::HashMap, env};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Populate Rust docs database with embeddings", long_about = None)]
@@ -24,10 +28,22 @@ struct Cli {
     #[arg(short, long)]
     delete: Option<String>,
 
+    /// Fill in module_path for documents stored before the column existed (no crawling)
+    #[arg(long)]
+    backfill_module_paths: bool,
+
+    /// Fill in doc_kind (struct, trait, fn, ...) for documents stored before the column existed (no crawling)
+    #[arg(long)]
+    backfill_doc_kinds: bool,
+
     /// Force regeneration even if embeddings exist
     #[arg(short, long)]
     force: bool,
 
+    /// Only re-embed pages whose content changed since the last run, and delete pages that are gone
+    #[arg(long)]
+    incremental: bool,
+
     /// Test mode - only load docs, don't generate embeddings
     #[arg(short, long)]
     test: bool,
@@ -36,9 +52,115 @@ struct Cli {
     #[arg(short = 'F', long, value_delimiter = ',', num_args = 0..)]
     features: Option<Vec<String>>,
 
+    /// Store the docs as a separate `crate+feature` entry instead of replacing the crate's docs
+    #[arg(long, requires = "features")]
+    as_variant: bool,
+
+    /// Load this release from docs.rs and store it as `crate@version` next to the other stored versions
+    #[arg(long, conflicts_with = "base_url")]
+    crate_version: Option<String>,
+
     /// Maximum number of pages to crawl (default: 200)
     #[arg(long, default_value_t = 200)]
     max_pages: usize,
+
+    /// Crawl by link depth from the crate root instead, following links this many hops regardless of --max-pages
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Store duplicate content under every path instead of skipping it
+    #[arg(long)]
+    no_dedupe: bool,
+
+    /// Cut page text beyond this many characters before counting tokens (0 = no limit)
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MAX_CONTENT_CHARS)]
+    max_content_length: usize,
+
+    /// Truncate pages above this many tokens (0 = no limit)
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MAX_PAGE_TOKENS)]
+    max_page_tokens: usize,
+
+    /// Skip pages above --max-page-tokens instead of truncating them
+    #[arg(long)]
+    skip_oversized_pages: bool,
+
+    /// Skip pages with less extracted text than this many characters
+    #[arg(long, default_value_t = doc_loader::DEFAULT_MIN_PAGE_CHARS)]
+    min_page_chars: usize,
+
+    /// Stop following links from pages at least this cosine-similar (0-1] to a page already
+    /// crawled for the crate; costs one embedding request per page and may miss pages
+    #[arg(long, value_name = "THRESHOLD")]
+    stop_on_redundancy: Option<f32>,
+
+    /// Crawl paths the site's robots.txt disallows (only for self-hosted sites you own)
+    #[arg(long)]
+    ignore_robots: bool,
+
+    /// CSS selector for the content extracted from each page (default: rustdoc docblocks)
+    #[arg(long)]
+    content_selector: Option<String>,
+
+    /// Crawl a self-hosted rustdoc site (e.g. https://docs.mycorp.internal/mycrate/) instead of docs.rs
+    #[arg(long)]
+    base_url: Option<String>,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+impl Cli {
+    fn page_limits(&self) -> doc_loader::PageLimits {
+        doc_loader::PageLimits {
+            max_content_chars: (self.max_content_length > 0).then_some(self.max_content_length),
+            max_page_tokens: (self.max_page_tokens > 0).then_some(self.max_page_tokens),
+            min_page_chars: self.min_page_chars,
+            skip_oversized: self.skip_oversized_pages,
+        }
+    }
+}
+
+/// Embed `documents` with the global provider, showing a progress bar (or logging at every
+/// tenth of the chunks when stderr is not a terminal)
+async fn embed_with_progress(
+    label: &str,
+    documents: &[Document],
+) -> Result<(Vec<(String, String, Array1<f32>)>, usize), ServerError> {
+    let provider = EMBEDDING_CLIENT
+        .get()
+        .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+    let progress = Progress::new(format!("🧠 {}Embedding", label), "chunks", documents.len() as u64);
+    let result = generate_embeddings_with_progress(provider, documents, |done, total| {
+        progress.set(done as u64, total as u64)
+    })
+    .await;
+    progress.finish();
+    result
+}
+
+/// Rows stored per transaction, so the insert bar advances on large crates
+const INSERT_BATCH_ROWS: usize = 500;
+
+/// Store `rows` in slices of [`INSERT_BATCH_ROWS`], showing a progress bar
+async fn insert_with_progress(
+    db: &(dyn VectorStore + Send + Sync),
+    label: &str,
+    crate_id: i32,
+    crate_name: &str,
+    rows: &[(String, String, Array1<f32>, i32)],
+) -> Result<(), ServerError> {
+    let progress = Progress::new(format!("💾 {}Storing", label), "rows", rows.len() as u64);
+    let mut stored = 0;
+    for slice in rows.chunks(INSERT_BATCH_ROWS) {
+        if let Err(e) = db.insert_embeddings_batch(crate_id, crate_name, slice).await {
+            progress.finish();
+            return Err(e);
+        }
+        stored += slice.len();
+        progress.set(stored as u64, rows.len() as u64);
+    }
+    progress.finish();
+    Ok(())
 }
 
 #[tokio::main]
@@ -46,9 +168,15 @@ async fn main() -> Result<(), ServerError> {
     dotenvy::dotenv().ok();
 
     let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
+    // Fail on a bad --content-selector before crawling anything
+    doc_loader::parse_content_selector(cli.content_selector.as_deref())?;
+    if let Some(crate_name) = &cli.crate_name {
+        doc_loader::crawl_start_url(crate_name, cli.base_url.as_deref())?;
+    }
 
     // Initialize database
-    let db = Database::new().await?;
+    let db = database::connect().await?;
 
     // Handle list command
     if cli.list {
@@ -56,16 +184,18 @@ async fn main() -> Result<(), ServerError> {
         if stats.is_empty() {
             println!("No crates in database.");
         } else {
-            println!("{:<20} {:<15} {:<10} {:<10} {:<20}", "Crate", "Version", "Docs", "Tokens", "Last Updated");
-            println!("{:-<80}", "");
+            println!("{:<20} {:<15} {:<10} {:<10} {:<20} {:<11} Features", "Crate", "Version", "Docs", "Tokens", "Last Updated", "Status");
+            println!("{:-<112}", "");
             for stat in stats {
                 println!(
-                    "{:<20} {:<15} {:<10} {:<10} {:<20}",
+                    "{:<20} {:<15} {:<10} {:<10} {:<20} {:<11} {}",
                     stat.name,
                     stat.version.unwrap_or_else(|| "N/A".to_string()),
                     stat.total_docs,
                     stat.total_tokens,
-                    stat.last_updated.format("%Y-%m-%d %H:%M")
+                    stat.last_updated.format("%Y-%m-%d %H:%M"),
+                    stat.status.as_str(),
+                    stat.features.join(",")
                 );
             }
         }
@@ -74,48 +204,63 @@ async fn main() -> Result<(), ServerError> {
 
     // Handle delete command
     if let Some(crate_to_delete) = cli.delete {
-        println!("Deleting embeddings for crate: {}", crate_to_delete);
+        info!("Deleting embeddings for crate: {}", crate_to_delete);
         db.delete_crate_embeddings(&crate_to_delete).await?;
         println!("Successfully deleted embeddings for {}", crate_to_delete);
         return Ok(());
     }
 
+    if cli.backfill_module_paths {
+        info!("🔄 Backfilling module paths from stored doc paths...");
+        let updated = db.backfill_module_paths().await?;
+        println!("✅ Set module_path on {} documents", updated);
+        return Ok(());
+    }
+
+    if cli.backfill_doc_kinds {
+        info!("🔄 Backfilling doc kinds from stored doc paths...");
+        let updated = db.backfill_doc_kinds().await?;
+        println!("✅ Set doc_kind on {} documents", updated);
+        return Ok(());
+    }
+
     // Handle populate command
-    if let Some(crate_name) = cli.crate_name {
+    if let Some(crate_name) = cli.crate_name.clone() {
+        let requested_features = cli.features.clone().unwrap_or_default();
+        let mut stored_name = if cli.as_variant {
+            doc_loader::crate_variant_name(&crate_name, &requested_features)
+        } else {
+            crate_name.clone()
+        };
+        if let Some(version) = &cli.crate_version {
+            stored_name = doc_loader::crate_version_name(&stored_name, version);
+        }
+
         // Check if embeddings already exist
-        if !cli.force && db.has_embeddings(&crate_name).await? {
-            println!("Embeddings already exist for {}. Use --force to regenerate.", crate_name);
+        if !cli.force && !cli.incremental && db.has_embeddings(&stored_name).await? {
+            println!("Embeddings already exist for {}. Use --force to regenerate.", stored_name);
             return Ok(());
         }
+        if let Some(stored) = db.get_crate_stats().await?.into_iter().find(|stat| stat.name == stored_name) {
+            let mut requested = requested_features.clone();
+            requested.sort();
+            requested.dedup();
+            if stored.total_docs > 0 && stored.features != requested {
+                println!("⚠️  {} is stored with features [{}]; replacing it with [{}] (use --as-variant to keep both)",
+                    stored_name, stored.features.join(", "), requested.join(", "));
+            }
+        }
 
         // Initialize embedding provider (default to OpenAI for populate script)
         let provider_type = env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "openai".to_string());
-        let embedding_config = match provider_type.to_lowercase().as_str() {
-            "openai" => {
-                let model = env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "text-embedding-3-large".to_string());
-                let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-                    let config = OpenAIConfig::new().with_api_base(api_base);
-                    OpenAIClient::with_config(config)
-                } else {
-                    OpenAIClient::new()
-                };
-                EmbeddingConfig::OpenAI { client: openai_client, model }
-            },
-            "voyage" => {
-                let api_key = env::var("VOYAGE_API_KEY")
-                    .map_err(|_| ServerError::MissingEnvVar("VOYAGE_API_KEY".to_string()))?;
-                let model = env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "voyage-3.5".to_string());
-                EmbeddingConfig::VoyageAI { api_key, model }
-            },
-            _ => {
-                return Err(ServerError::Config(format!(
-                    "Unsupported embedding provider: {}. Use 'openai' or 'voyage'",
-                    provider_type
-                )));
-            }
-        };
+        let embedding_config = EmbeddingConfig::from_provider(&provider_type, env::var("EMBEDDING_MODEL").ok())?;
 
         let provider = initialize_embedding_provider(embedding_config);
+        database::check_embedding_dimensions(db.as_ref(), provider.as_ref()).await?;
+        let redundancy = cli
+            .stop_on_redundancy
+            .map(|threshold| doc_loader::RedundancyCheck::new(provider.clone(), threshold))
+            .transpose()?;
         if EMBEDDING_CLIENT.set(provider).is_err() {
             return Err(ServerError::Internal("Failed to set embedding provider".to_string()));
         }
@@ -124,19 +269,57 @@ async fn main() -> Result<(), ServerError> {
         let bpe = tiktoken_rs::cl100k_base()
             .map_err(|e| ServerError::Tiktoken(e.to_string()))?;
 
-        println!("📥 Loading documentation for crate: {} (max {} pages)", crate_name, cli.max_pages);
+        let crawler = doc_loader::crawler_client()?;
+        let crawl_limit = doc_loader::CrawlLimit::new(Some(cli.max_pages), cli.max_depth);
+        info!("📥 Loading documentation for crate: {} ({})", crate_name, crawl_limit);
         let doc_start = std::time::Instant::now();
-        let load_result = doc_loader::load_documents_from_docs_rs(&crate_name, "*", cli.features.as_ref(), Some(cli.max_pages)).await?;
+        let loading = Progress::spinner(format!("📥 Loading {}", crate_name));
+        let load_result = doc_loader::load_documents_from_docs_rs_with_limits(
+            &crawler,
+            &crate_name,
+            cli.crate_version.as_deref().unwrap_or("*"),
+            cli.features.as_ref(),
+            crawl_limit,
+            &cli.page_limits(),
+            cli.content_selector.as_deref(),
+            cli.base_url.as_deref(),
+            redundancy.as_ref(),
+            cli.ignore_robots,
+        ).await;
+        loading.finish();
+        let load_result = load_result.inspect_err(|e| {
+            if matches!(e, doc_loader::DocLoaderError::BuildFailed(_) | doc_loader::DocLoaderError::NoDocumentation(_)) {
+                warn!("❌ docs.rs has no built documentation for this crate/version: {}", e);
+            }
+        })?;
         let documents = load_result.documents;
         let crate_version = load_result.version;
+        let features = load_result.features;
         let doc_time = doc_start.elapsed();
 
         let total_content_size: usize = documents.iter().map(|doc| doc.content.len()).sum();
-        println!("✅ Loaded {} documents in {:.2}s ({:.1} KB total)",
+        info!("✅ Loaded {} documents in {:.2}s ({:.1} KB total)",
             documents.len(), doc_time.as_secs_f64(), total_content_size as f64 / 1024.0);
 
         if let Some(ref version) = crate_version {
-            println!("📦 Detected version: {}", version);
+            info!("📦 Detected version: {}", version);
+        }
+
+        if !load_result.truncated_pages.is_empty() {
+            println!("✂️  Truncated {} oversized pages by size limits", load_result.truncated_pages.len());
+        }
+        if load_result.pruned_links > 0 {
+            println!("✂️  Pruned {} links from redundant pages (--stop-on-redundancy)", load_result.pruned_links);
+        }
+        let redundancy_tokens = load_result.redundancy_tokens;
+        if redundancy_tokens > 0 {
+            info!("🔁 Redundancy check embedded {} tokens", redundancy_tokens);
+        }
+        if !load_result.skipped_pages.is_empty() {
+            println!("⏭️  Skipped {} pages by size limits", load_result.skipped_pages.len());
+            for page in &load_result.skipped_pages {
+                debug!("  - {} ({})", page.path, page.reason);
+            }
         }
 
         if documents.is_empty() {
@@ -159,23 +342,83 @@ async fn main() -> Result<(), ServerError> {
             return Ok(());
         }
 
+        if cli.incremental {
+            // A crawl cut short by --max-pages can't tell a removed page from one it never reached
+            let crawl_capped = cli.max_depth.is_none()
+                && documents.len() + load_result.skipped_pages.len() >= cli.max_pages;
+            if crawl_capped {
+                println!("⚠️  Crawl reached --max-pages; keeping stored pages it didn't visit");
+            }
+            info!("🔁 Comparing with stored documents...");
+            let options = refresh::SyncOptions { remove_missing: !crawl_capped, dedupe: !cli.no_dedupe };
+            let report = refresh::sync_crate(db.as_ref(), &stored_name, documents, crate_version.as_deref(), options).await?;
+            db.set_crate_features(&stored_name, &features).await?;
+            database::record_crate_dependencies(db.as_ref(), &crawler, &stored_name, crate_version.as_deref()).await?;
+
+            let model = EMBEDDING_CLIENT.get().map(|provider| provider.get_model_name()).unwrap_or_default();
+            println!("\n🎉 Incremental update complete! Total time: {:.2}s", doc_start.elapsed().as_secs_f64());
+            println!("📊 Final Summary:");
+            println!("  ➕ Added: {}", report.added);
+            println!("  ✏️  Updated: {}", report.updated);
+            println!("  ➖ Removed: {}", report.removed);
+            println!("  💤 Unchanged: {}", report.unchanged);
+            println!("  🧠 Chunks embedded: {} ({} tokens)", report.chunks_embedded, report.embedding_tokens);
+            println!("  💰 Estimated cost: {}", format_cost(estimate_cost(model, report.embedding_tokens)));
+            return Ok(());
+        }
+
+        // Skip content that is already stored (or repeated) under another path before paying for embeddings
+        let existing_hashes = if cli.no_dedupe {
+            HashMap::new()
+        } else {
+            dedupe::existing_content_hashes(db.as_ref(), &stored_name).await?
+        };
+        let mut duplicates_skipped = 0;
+        let documents = if cli.no_dedupe {
+            documents
+        } else {
+            let (documents, skipped) = dedupe::dedupe_by_content(
+                documents,
+                |doc| (doc.path.as_str(), doc.content.as_str()),
+                &existing_hashes,
+            );
+            duplicates_skipped += skipped;
+            documents
+        };
+
         // Generate embeddings
-        println!("\n🧠 Generating embeddings...");
+        info!("🧠 Generating embeddings...");
         let embedding_start = std::time::Instant::now();
-        let (embeddings, total_tokens) = generate_embeddings(&documents).await?;
+        let (embeddings, total_tokens) = embed_with_progress("", &documents).await?;
         let embedding_time = embedding_start.elapsed();
 
-        let cost_per_million = 0.02;
-        let estimated_cost = (total_tokens as f64 / 1_000_000.0) * cost_per_million;
-        println!(
-            "✅ Generated {} embeddings using {} tokens in {:.2}s (Est. Cost: ${:.6})",
-            embeddings.len(), total_tokens, embedding_time.as_secs_f64(), estimated_cost
+        // Chunks of different pages can still repeat each other
+        let embeddings = if cli.no_dedupe {
+            embeddings
+        } else {
+            let (embeddings, skipped) = dedupe::dedupe_by_content(
+                embeddings,
+                |(path, content, _)| (path.as_str(), content.as_str()),
+                &existing_hashes,
+            );
+            duplicates_skipped += skipped;
+            embeddings
+        };
+        if duplicates_skipped > 0 {
+            info!("🧹 Skipped {} duplicate documents/chunks", duplicates_skipped);
+        }
+
+        let model = EMBEDDING_CLIENT.get().map(|provider| provider.get_model_name()).unwrap_or_default();
+        let estimated_cost = estimate_cost(model, total_tokens);
+        info!(
+            "✅ Generated {} embeddings using {} tokens in {:.2}s (Est. Cost: {})",
+            embeddings.len(), total_tokens, embedding_time.as_secs_f64(), format_cost(estimated_cost)
         );
 
         // Insert into database
-        println!("\n💾 Storing in database...");
+        info!("💾 Storing in database...");
         let db_start = std::time::Instant::now();
-        let crate_id = db.upsert_crate(&crate_name, crate_version.as_deref()).await?;
+        let crate_id = db.upsert_crate(&stored_name, crate_version.as_deref()).await?;
 
         // Prepare batch data
         let mut batch_data = Vec::new();
@@ -190,19 +433,30 @@ async fn main() -> Result<(), ServerError> {
             ));
         }
 
-        db.insert_embeddings_batch(crate_id, &crate_name, &batch_data).await?;
+        // Running servers refuse the crate until every batch is stored
+        database::populate_with_status(db.as_ref(), &stored_name, async {
+            insert_with_progress(db.as_ref(), "", crate_id, &stored_name, &batch_data).await?;
+            db.set_crate_features(&stored_name, &features).await?;
+            db.set_crate_embedding_model(&stored_name, model).await?;
+            database::record_crate_dependencies(db.as_ref(), &crawler, &stored_name, crate_version.as_deref()).await
+        })
+        .await?;
         let db_time = db_start.elapsed();
         let total_time = doc_start.elapsed();
 
-        println!("✅ Successfully stored {} embeddings for {} in {:.2}s",
-            embeddings.len(), crate_name, db_time.as_secs_f64());
+        info!("✅ Successfully stored {} embeddings for {} in {:.2}s",
+            embeddings.len(), stored_name, db_time.as_secs_f64());
 
         println!("\n🎉 Complete! Total time: {:.2}s", total_time.as_secs_f64());
         println!("📊 Final Summary:");
         println!("  📥 Document loading: {:.2}s", doc_time.as_secs_f64());
         println!("  🧠 Embedding generation: {:.2}s", embedding_time.as_secs_f64());
         println!("  💾 Database storage: {:.2}s", db_time.as_secs_f64());
-        println!("  💰 Estimated cost: ${:.6}", estimated_cost);
+        // Includes the samples the redundancy check embedded while crawling
+        println!("  💰 Estimated cost: {}", format_cost(estimate_cost(model, total_tokens + redundancy_tokens)));
+        if !cli.no_dedupe {
+            println!("  🧹 Duplicates skipped: {}", duplicates_skipped);
+        }
     } else {
         println!("Please specify a crate name with --crate-name or use --list to see existing crates");
     }
@@ -0,0 +1,51 @@
+use rustdocs_mcp_server::{database, error::ServerError, verbosity::VerbosityArgs};
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Summarize what the documentation database holds", long_about = None)]
+struct Cli {
+    /// Print the stats as JSON instead of a summary
+    #[arg(long)]
+    json: bool,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), ServerError> {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
+
+    let db = database::connect().await?;
+    let stats = db.global_stats().await?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let format_time = |time: Option<chrono::NaiveDateTime>| {
+        time.map_or_else(|| "N/A".to_string(), |time| time.format("%Y-%m-%d %H:%M").to_string())
+    };
+    let dimensions = stats.embedding_dimensions.iter().map(usize::to_string).collect::<Vec<_>>().join(", ");
+    println!("📊 Database stats");
+    println!("  📦 Crates:            {}", stats.total_crates);
+    println!("  📄 Documents:         {}", stats.total_docs);
+    println!("  🔤 Tokens:            {}", stats.total_tokens);
+    println!("  📈 Docs per crate:    {:.1}", stats.avg_docs_per_crate);
+    println!("  📐 Vector dimensions: {}", if dimensions.is_empty() { "N/A" } else { &dimensions });
+    println!("  🕰️  Oldest update:     {}", format_time(stats.oldest_update));
+    println!("  🆕 Newest update:     {}", format_time(stats.newest_update));
+
+    if stats.embedding_dimensions.len() > 1 {
+        println!(
+            "\n⚠️  Embeddings of {} different dimensions are stored: crates were populated with different models \
+             and can't all be searched with one. Re-populate them with the same model.",
+            stats.embedding_dimensions.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+// Operator-configured scope of the crates a server answers for, independent of what the
+// database holds (e.g. to keep a public server to a few crates without re-populating).
+
+use crate::doc_loader;
+
+/// Crates a server may serve: any crate in `allow` (every crate when it is empty) that is
+/// not in `deny`. Entries match a stored name exactly, or every variant and version of a
+/// crate by its plain name (`tokio` also matches `tokio@1.37.0` and `serde` matches `serde+derive`).
+#[derive(Debug, Clone, Default)]
+pub struct CrateFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl CrateFilter {
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        let clean = |names: Vec<String>| {
+            names
+                .into_iter()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+        Self { allow: clean(allow), deny: clean(deny) }
+    }
+
+    /// Whether neither list is set
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether `crate_name` may be served; the deny list wins over the allow list
+    pub fn allows(&self, crate_name: &str) -> bool {
+        let (base, _) = doc_loader::split_crate_variant(crate_name);
+        let listed = |names: &[String]| names.iter().any(|name| name == crate_name || name == base);
+        (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.deny)
+    }
+
+    /// Split `crate_names` into the allowed ones and the rest, keeping their order
+    pub fn partition(&self, crate_names: Vec<String>) -> (Vec<String>, Vec<String>) {
+        crate_names.into_iter().partition(|name| self.allows(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        let stored = names(&["axum", "serde+derive", "tokio", "tokio@1.37.0", "hyper"]);
+        assert_eq!(CrateFilter::default().partition(stored.clone()).0, stored);
+
+        let filter = CrateFilter::new(names(&["tokio", "serde", "axum "]), names(&["tokio@1.37.0", "axum"]));
+        let (allowed, refused) = filter.partition(stored);
+        assert_eq!(allowed, ["serde+derive", "tokio"]);
+        assert_eq!(refused, ["axum", "tokio@1.37.0", "hyper"]);
+
+        let filter = CrateFilter::new(Vec::new(), names(&["hyper", ""]));
+        assert!(filter.allows("tokio"));
+        assert!(!filter.allows("hyper"));
+    }
+}
@@ -1,71 +1,212 @@
-use crate::error::ServerError;
+use crate::{dedupe, doc_loader, embeddings::EmbeddingProvider, error::ServerError, examples, qdrant_store::QdrantStore, secrets, sqlite_store::SqliteStore};
+use tracing::{debug, warn};
+use async_trait::async_trait;
+use clap::ValueEnum;
 use ndarray::Array1;
 use pgvector::Vector;
-use sqlx::{postgres::PgPoolOptions, PgPool, Row};
-use std::env;
+use serde::Serialize;
+use sqlx::{postgres::PgPoolOptions, Executor, PgPool, Row};
+use std::{collections::HashSet, env, future::Future, sync::Arc, time::Duration};
 
-#[derive(Clone)]
-pub struct Database {
-    pool: PgPool,
+/// Distance used to rank documents in `search_similar_docs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DistanceMetric {
+    /// Cosine distance (`<=>`), similarity = 1 - distance
+    #[default]
+    Cosine,
+    /// Euclidean distance (`<->`), similarity = 1 - distance² / 2 (equals cosine for unit vectors)
+    L2,
+    /// Negative inner product (`<#>`), similarity = inner product
+    InnerProduct,
 }
 
-impl Database {
-    pub async fn new() -> Result<Self, ServerError> {
-        let database_url = env::var("MCPDOCS_DATABASE_URL")
-            .unwrap_or_else(|_| "postgresql://jonathonfritz@localhost/rust_docs_vectors".to_string());
+impl DistanceMetric {
+    /// Read `MCPDOCS_DISTANCE` (cosine, l2 or inner-product), defaulting to cosine
+    pub fn from_env() -> Result<Self, ServerError> {
+        match env::var("MCPDOCS_DISTANCE") {
+            Ok(value) => Self::from_str(&value, true).map_err(|_| {
+                ServerError::Config(format!(
+                    "Invalid MCPDOCS_DISTANCE: {}. Use 'cosine', 'l2' or 'inner-product'",
+                    value
+                ))
+            }),
+            Err(_) => Ok(Self::default()),
+        }
+    }
 
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&database_url)
-            .await
-            .map_err(|e| ServerError::Database(format!("Failed to connect to database: {}", e)))?;
+    /// pgvector distance operator
+    pub fn operator(self) -> &'static str {
+        match self {
+            Self::Cosine => "<=>",
+            Self::L2 => "<->",
+            Self::InnerProduct => "<#>",
+        }
+    }
 
-        Ok(Self { pool })
+    /// pgvector operator class an index must use for this metric
+    pub fn index_ops(self) -> &'static str {
+        match self {
+            Self::Cosine => "vector_cosine_ops",
+            Self::L2 => "vector_l2_ops",
+            Self::InnerProduct => "vector_ip_ops",
+        }
     }
 
-    /// Insert or update a crate in the database
-    pub async fn upsert_crate(&self, crate_name: &str, version: Option<&str>) -> Result<i32, ServerError> {
-        let result = sqlx::query(
-            r#"
-            INSERT INTO crates (name, version)
-            VALUES ($1, $2)
-            ON CONFLICT (name)
-            DO UPDATE SET
-                version = COALESCE($2, crates.version),
-                last_updated = CURRENT_TIMESTAMP
-            RETURNING id
-            "#
-        )
-        .bind(crate_name)
-        .bind(version)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to upsert crate: {}", e)))?;
+    /// SQL expression turning `distance` into a higher-is-better similarity score
+    pub fn similarity_sql(self, distance: &str) -> String {
+        match self {
+            Self::Cosine => format!("1 - ({})", distance),
+            Self::L2 => format!("1 - power({}, 2) / 2", distance),
+            Self::InnerProduct => format!("-({})", distance),
+        }
+    }
+}
 
-        let id: i32 = result.get("id");
-        Ok(id)
+/// Where a crate's population stands. Searches only serve `Ready` crates, so a crate that
+/// is being populated isn't answered from the first batches of its documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrateStatus {
+    /// Documents are being stored; the crate may be incomplete
+    Populating,
+    /// Fully stored (also crates populated before the status was recorded)
+    #[default]
+    Ready,
+    /// The last population stopped with an error
+    Failed,
+}
+
+impl CrateStatus {
+    /// Value stored in `crates.status`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Populating => "populating",
+            Self::Ready => "ready",
+            Self::Failed => "failed",
+        }
     }
 
-    /// Check if embeddings exist for a crate
-    pub async fn has_embeddings(&self, crate_name: &str) -> Result<bool, ServerError> {
-        let result = sqlx::query(
-            r#"
-            SELECT EXISTS(
-                SELECT 1 FROM doc_embeddings WHERE crate_name = $1
-            ) as exists
-            "#
-        )
-        .bind(crate_name)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to check embeddings: {}", e)))?;
+    /// Read a stored `crates.status` and `crates.status_updated_at`; anything unrecognised
+    /// counts as ready. A `populating` status that its run stopped refreshing (see
+    /// [`populate_with_status`]) was left by a run that was killed, so it counts as failed.
+    pub fn from_db(value: &str, updated_at: Option<chrono::NaiveDateTime>) -> Self {
+        match value {
+            "populating" => {
+                // A status set in the future (clock skew between hosts) is still fresh
+                let fresh = updated_at.is_some_and(|updated_at| {
+                    (chrono::Utc::now().naive_utc() - updated_at)
+                        .to_std()
+                        .map_or(true, |age| age < POPULATING_STALE_AFTER)
+                });
+                if fresh {
+                    Self::Populating
+                } else {
+                    Self::Failed
+                }
+            }
+            "failed" => Self::Failed,
+            _ => Self::Ready,
+        }
+    }
+}
 
-        let exists: bool = result.get("exists");
-        Ok(exists)
+/// How often [`populate_with_status`] refreshes a crate's `populating` status while it runs
+const POPULATING_HEARTBEAT: Duration = Duration::from_secs(60);
+
+/// A `populating` status that hasn't been refreshed for this long is treated as failed
+pub const POPULATING_STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Run `populate` (storing a crate's documents) with the crate marked `populating`, then
+/// `ready` on success or `failed` on error, so servers don't answer from a partial crate.
+/// The status is refreshed every minute while `populate` runs, so a run
+/// that is killed leaves a status that goes stale rather than blocking the crate forever.
+/// The crate row must already exist (see [`VectorStore::upsert_crate`]).
+pub async fn populate_with_status<T>(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+    populate: impl Future<Output = Result<T, ServerError>>,
+) -> Result<T, ServerError> {
+    store.set_crate_status(crate_name, CrateStatus::Populating).await?;
+    let mut populate = std::pin::pin!(populate);
+    let mut heartbeat = tokio::time::interval(POPULATING_HEARTBEAT);
+    heartbeat.tick().await;
+    let result = loop {
+        tokio::select! {
+            result = &mut populate => break result,
+            _ = heartbeat.tick() => {
+                if let Err(e) = store.set_crate_status(crate_name, CrateStatus::Populating).await {
+                    warn!("⚠️  Could not refresh {}'s populating status: {}", crate_name, e);
+                }
+            }
+        }
+    };
+    match result {
+        Ok(value) => {
+            store.set_crate_status(crate_name, CrateStatus::Ready).await?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(status_error) = store.set_crate_status(crate_name, CrateStatus::Failed).await {
+                warn!("⚠️  Could not mark {} as failed: {}", crate_name, status_error);
+            }
+            Err(e)
+        }
     }
+}
 
-    /// Insert a document embedding
-    pub async fn insert_embedding(
+/// Check that the store answers and serves at least one ready crate, returning how many
+/// it serves. Used by the `healthcheck` binary for container probes.
+pub async fn check_health(store: &(dyn VectorStore + Send + Sync)) -> Result<usize, ServerError> {
+    store.ping().await?;
+    let ready = store
+        .get_crate_stats()
+        .await?
+        .iter()
+        .filter(|stats| stats.status == CrateStatus::Ready)
+        .count();
+    if ready == 0 {
+        return Err(ServerError::NotFound("no ready crates in the database".to_string()));
+    }
+    Ok(ready)
+}
+
+/// Storage backend for crates and their document embeddings.
+///
+/// `Database` implements this on top of PostgreSQL + pgvector and
+/// `SqliteStore` on top of a local SQLite file with `sqlite-vec`.
+#[async_trait]
+pub trait VectorStore {
+    /// Insert or update a crate, returning its id
+    async fn upsert_crate(&self, crate_name: &str, version: Option<&str>) -> Result<i32, ServerError>;
+
+    /// Record the features a crate's docs were loaded with (empty for the docs.rs defaults)
+    async fn set_crate_features(&self, crate_name: &str, features: &[String]) -> Result<(), ServerError>;
+
+    /// Record the embedding model a crate's documents were embedded with
+    async fn set_crate_embedding_model(&self, crate_name: &str, model: &str) -> Result<(), ServerError>;
+
+    /// Record where a crate's population stands (see [`populate_with_status`])
+    async fn set_crate_status(&self, crate_name: &str, status: CrateStatus) -> Result<(), ServerError>;
+
+    /// Population status of a crate, `None` if it isn't stored
+    async fn crate_status(&self, crate_name: &str) -> Result<Option<CrateStatus>, ServerError>;
+
+    /// Replace the recorded direct dependencies of a crate
+    async fn set_crate_dependencies(&self, crate_name: &str, dependencies: &[String]) -> Result<(), ServerError>;
+
+    /// Direct dependencies recorded for a crate, in name order (empty if none were recorded)
+    async fn crate_dependencies(&self, crate_name: &str) -> Result<Vec<String>, ServerError>;
+
+    /// Check if embeddings exist for a crate
+    async fn has_embeddings(&self, crate_name: &str) -> Result<bool, ServerError>;
+
+    /// Split `crate_names` into those with stored embeddings and the rest, keeping their
+    /// order, in one query instead of a `has_embeddings` call per crate
+    async fn filter_existing_crates(&self, crate_names: &[String]) -> Result<(Vec<String>, Vec<String>), ServerError>;
+
+    /// Insert a single document embedding
+    async fn insert_embedding(
         &self,
         crate_id: i32,
         crate_name: &str,
@@ -73,255 +214,1395 @@ impl Database {
         content: &str,
         embedding: &Array1<f32>,
         token_count: i32,
-    ) -> Result<(), ServerError> {
-        let embedding_vec = Vector::from(embedding.to_vec());
-
-        sqlx::query(
-            r#"
-            INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            ON CONFLICT (crate_name, doc_path)
-            DO UPDATE SET
-                content = $4,
-                embedding = $5,
-                token_count = $6,
-                created_at = CURRENT_TIMESTAMP
-            "#
-        )
-        .bind(crate_id)
-        .bind(crate_name)
-        .bind(doc_path)
-        .bind(content)
-        .bind(embedding_vec)
-        .bind(token_count)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to insert embedding: {}", e)))?;
-
-        Ok(())
-    }
+    ) -> Result<(), ServerError>;
 
-    /// Batch insert multiple embeddings (more efficient)
-    pub async fn insert_embeddings_batch(
+    /// Insert many embeddings in one transaction and refresh the crate statistics
+    async fn insert_embeddings_batch(
         &self,
         crate_id: i32,
         crate_name: &str,
         embeddings: &[(String, String, Array1<f32>, i32)], // (path, content, embedding, token_count)
-    ) -> Result<(), ServerError> {
-        let mut tx = self.pool.begin().await
-            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+    ) -> Result<(), ServerError>;
 
-        for (doc_path, content, embedding, token_count) in embeddings {
-            let embedding_vec = Vector::from(embedding.to_vec());
+    /// Return the `limit` most similar documents, best first
+    async fn search_similar_docs(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+    ) -> Result<Vec<DocMatch>, ServerError>;
 
-            sqlx::query(
-                r#"
-                INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count)
-                VALUES ($1, $2, $3, $4, $5, $6)
-                ON CONFLICT (crate_name, doc_path)
-                DO UPDATE SET
-                    content = $4,
-                    embedding = $5,
-                    token_count = $6,
-                    created_at = CURRENT_TIMESTAMP
-                "#
-            )
-            .bind(crate_id)
-            .bind(crate_name)
-            .bind(doc_path)
-            .bind(content)
-            .bind(embedding_vec)
-            .bind(*token_count)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| ServerError::Database(format!("Failed to insert embedding: {}", e)))?;
-        }
+    /// Like `search_similar_docs`, but ranks documents flagged `has_code_example` as if their
+    /// similarity were `example_boost` higher. Returned similarities are unboosted.
+    async fn search_similar_docs_preferring_examples(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: f32,
+    ) -> Result<Vec<DocMatch>, ServerError>;
 
-        tx.commit().await
-            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+    /// Like `search_similar_docs`, limited to the documents `filter` matches (a module and its
+    /// submodules, a kind of item), optionally with an example boost
+    async fn search_similar_docs_filtered(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        filter: DocFilter<'_>,
+        example_boost: Option<f32>,
+    ) -> Result<Vec<DocMatch>, ServerError>;
 
-        // Update crate statistics
-        self.update_crate_stats(crate_id).await?;
+    /// Get all documents (with embeddings) for a crate: path, content, embedding and the
+    /// stored token count (`None` if not stored)
+    async fn get_crate_documents(
+        &self,
+        crate_name: &str,
+    ) -> Result<Vec<(String, String, Array1<f32>, Option<usize>)>, ServerError>;
 
-        Ok(())
-    }
+    /// Stored vectors of the given documents of a crate, for re-ranking search results;
+    /// paths that aren't stored are left out
+    async fn get_doc_embeddings(
+        &self,
+        crate_name: &str,
+        doc_paths: &[String],
+    ) -> Result<Vec<(String, Array1<f32>)>, ServerError>;
 
-    /// Update crate statistics
-    async fn update_crate_stats(&self, crate_id: i32) -> Result<(), ServerError> {
-        sqlx::query(
-            r#"
-            UPDATE crates
-            SET total_docs = (
-                SELECT COUNT(*) FROM doc_embeddings WHERE crate_id = $1
-            ),
-            total_tokens = (
-                SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings WHERE crate_id = $1
-            )
-            WHERE id = $1
-            "#
-        )
-        .bind(crate_id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to update crate stats: {}", e)))?;
+    /// Get (path, content) for every stored document of a crate, without embeddings
+    async fn get_crate_doc_contents(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError>;
 
-        Ok(())
-    }
+    /// Get (path, content hash) for every stored document of a crate. Rows stored before
+    /// `content_hash` existed are hashed from their content.
+    async fn get_content_hashes(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError>;
 
-    /// Search for similar documents using vector similarity
-    pub async fn search_similar_docs(
+    /// Up to `limit` stored paths of a crate in path order, starting after `after`
+    async fn list_doc_paths(
         &self,
         crate_name: &str,
-        query_embedding: &Array1<f32>,
-        limit: i32,
-    ) -> Result<Vec<(String, String, f32)>, ServerError> {
-        let embedding_vec = Vector::from(query_embedding.to_vec());
-
-        let results = sqlx::query(
-            r#"
-            SELECT
-                doc_path,
-                content,
-                1 - (embedding <=> $1) as similarity
-            FROM doc_embeddings
-            WHERE crate_name = $2
-            ORDER BY embedding <=> $1
-            LIMIT $3
-            "#
-        )
-        .bind(embedding_vec)
-        .bind(crate_name)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to search documents: {}", e)))?;
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ServerError>;
 
-        Ok(results
-            .into_iter()
-            .map(|row| {
-                let doc_path: String = row.get("doc_path");
-                let content: String = row.get("content");
-                let similarity: f64 = row.get("similarity");
-                let similarity = similarity as f32; // Convert to f32 for compatibility
-                (doc_path, content, similarity)
-            })
-            .collect())
-    }
+    /// Content stored under one path of a crate
+    async fn get_doc_content(&self, crate_name: &str, doc_path: &str) -> Result<Option<String>, ServerError>;
 
-    /// Get all documents for a crate (for loading into memory if needed)
-    pub async fn get_crate_documents(
+    /// The page stored under a rustdoc path relative to the docs root (e.g.
+    /// `tokio/sync/struct.Mutex.html`) as its stored path and full content, chunks joined in order
+    async fn get_document_by_path(
         &self,
         crate_name: &str,
-    ) -> Result<Vec<(String, String, Array1<f32>)>, ServerError> {
-        eprintln!("    🔍 Querying database for crate: {}", crate_name);
-        let query_start = std::time::Instant::now();
+        doc_path: &str,
+    ) -> Result<Option<(String, String)>, ServerError>;
 
-        let results = sqlx::query(
-            r#"
-            SELECT doc_path, content, embedding
-            FROM doc_embeddings
-            WHERE crate_name = $1
-            ORDER BY doc_path
-            "#
-        )
-        .bind(crate_name)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to get crate documents: {}", e)))?;
+    /// Up to `limit` distinct stored paths of a crate containing `term`, ignoring case, in path order
+    async fn find_doc_paths(&self, crate_name: &str, term: &str, limit: usize) -> Result<Vec<String>, ServerError>;
 
-        let query_time = query_start.elapsed();
-        eprintln!("    📊 Found {} documents for {} in {:.3}s", results.len(), crate_name, query_time.as_secs_f64());
+    /// Set `module_path` (see [`doc_loader::module_path`]) on rows stored before the column
+    /// existed. Returns the number of rows updated.
+    async fn backfill_module_paths(&self) -> Result<usize, ServerError>;
 
-                let mut documents = Vec::new();
-        for (i, row) in results.iter().enumerate() {
-            let doc_path: String = row.get("doc_path");
-            let content: String = row.get("content");
-            let embedding_vec: Vector = row.get("embedding");
-            let embedding_array = Array1::from_vec(embedding_vec.to_vec());
+    /// Set `doc_kind` (see [`doc_loader::doc_kind`]) on rows stored before the column existed.
+    /// Returns the number of rows updated.
+    async fn backfill_doc_kinds(&self) -> Result<usize, ServerError>;
 
-            if i < 3 || (i + 1) % 5 == 0 {
-                eprintln!("    📄 [{}/{}] Processed: {} ({} chars, {} dims)",
-                    i + 1, results.len(), doc_path, content.len(), embedding_array.len());
-            }
+    /// Recompute every crate's `total_docs` and `total_tokens` from its stored documents,
+    /// e.g. after deletes that skipped the stats update. Returns the number of crates fixed.
+    async fn repair_crate_stats(&self) -> Result<usize, ServerError>;
 
-            documents.push((doc_path, content, embedding_array));
-        }
+    /// Delete all embeddings for a crate
+    async fn delete_crate_embeddings(&self, crate_name: &str) -> Result<(), ServerError>;
 
-        Ok(documents)
-    }
+    /// Delete the embeddings stored under the given paths of a crate
+    async fn delete_doc_embeddings(&self, crate_name: &str, doc_paths: &[String]) -> Result<(), ServerError>;
 
-    /// Delete all embeddings for a crate
-    pub async fn delete_crate_embeddings(&self, crate_name: &str) -> Result<(), ServerError> {
-        sqlx::query(
-            r#"
-            DELETE FROM doc_embeddings WHERE crate_name = $1
-            "#
-        )
-        .bind(crate_name)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to delete embeddings: {}", e)))?;
+    /// Get statistics for every stored crate
+    async fn get_crate_stats(&self) -> Result<Vec<CrateStats>, ServerError>;
 
-        Ok(())
-    }
+    /// Totals across every stored crate, counted from the documents themselves
+    async fn global_stats(&self) -> Result<GlobalStats, ServerError>;
 
-    /// Get crate statistics
-    pub async fn get_crate_stats(&self) -> Result<Vec<CrateStats>, ServerError> {
-        let results = sqlx::query(
-            r#"
-            SELECT
-                name,
-                version,
-                last_updated,
-                total_docs,
-                total_tokens
-            FROM crates
-            ORDER BY name
-            "#
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to get crate stats: {}", e)))?;
+    /// Count documents for a specific crate
+    async fn count_crate_documents(&self, crate_name: &str) -> Result<usize, ServerError>;
 
-        Ok(results
-            .into_iter()
-            .map(|row| {
-                let name: String = row.get("name");
-                let version: Option<String> = row.get("version");
-                let last_updated: chrono::NaiveDateTime = row.get("last_updated");
-                let total_docs: Option<i32> = row.get("total_docs");
-                let total_tokens: Option<i32> = row.get("total_tokens");
+    /// Dimension of the stored embeddings, if the backend knows it
+    /// (the declared column type for pgvector, existing rows for SQLite)
+    async fn stored_embedding_dimensions(&self) -> Result<Option<usize>, ServerError>;
 
-                CrateStats {
-                    name,
-                    version,
-                    last_updated,
-                    total_docs: total_docs.unwrap_or(0),
-                    total_tokens: total_tokens.unwrap_or(0),
-                }
-            })
-            .collect())
+    /// Dimension of one crate's stored embeddings, `None` if it has none
+    async fn crate_embedding_dimensions(&self, crate_name: &str) -> Result<Option<usize>, ServerError>;
+
+    /// Check that the configured distance metric can use the backend's vector index,
+    /// so searches don't silently degrade to sequential scans
+    async fn validate_distance_metric(&self) -> Result<(), ServerError>;
+
+    /// Cheapest round trip to the backend (`SELECT 1`), for health checks
+    async fn ping(&self) -> Result<(), ServerError>;
+
+    /// Which implementation this store is
+    fn backend(&self) -> Backend;
+
+    /// Metric search results are ranked by
+    fn distance_metric(&self) -> DistanceMetric;
+}
+
+/// Fail fast when the embedding model doesn't match the dimension of the stored vectors,
+/// instead of hitting cryptic insert/search errors later on.
+pub async fn check_embedding_dimensions(
+    store: &(dyn VectorStore + Send + Sync),
+    provider: &(dyn EmbeddingProvider + Send + Sync),
+) -> Result<(), ServerError> {
+    let model_dimensions = provider.embedding_dimensions();
+    match store.stored_embedding_dimensions().await? {
+        Some(stored) if stored != model_dimensions => Err(ServerError::Config(format!(
+            "Embedding model '{}' produces {}-dimensional vectors but the database stores {}-dimensional embeddings. \
+             Use a model with {} dimensions (set EMBEDDING_DIMENSIONS if the model is not recognised) \
+             or migrate doc_embeddings (see sql/migrations).",
+            provider.get_model_name(),
+            model_dimensions,
+            stored,
+            stored
+        ))),
+        _ => Ok(()),
     }
+}
 
-    /// Count documents for a specific crate
-    pub async fn count_crate_documents(&self, crate_name: &str) -> Result<usize, ServerError> {
-        let result = sqlx::query(
-            r#"
-            SELECT COUNT(*) as count
-            FROM doc_embeddings
-            WHERE crate_name = $1
-            "#
-        )
-        .bind(crate_name)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| ServerError::Database(format!("Failed to count crate documents: {}", e)))?;
+/// Why a crate's stored vectors can't be searched with `provider`, if they can't: they were
+/// embedded with another model (when that was recorded) or have another dimension.
+/// Unlike a database-wide mismatch this doesn't fail searches, they just return poor matches.
+pub async fn crate_embedding_mismatch(
+    store: &(dyn VectorStore + Send + Sync),
+    provider: &(dyn EmbeddingProvider + Send + Sync),
+    stats: &CrateStats,
+) -> Result<Option<String>, ServerError> {
+    let model = provider.get_model_name();
+    if let Some(stored_model) = stats.embedding_model.as_deref().filter(|stored| *stored != model) {
+        return Ok(Some(format!(
+            "embedded with '{}' but queries are embedded with '{}'",
+            stored_model, model
+        )));
+    }
 
-        let count: i64 = result.get("count");
-        Ok(count as usize)
+    let model_dimensions = provider.embedding_dimensions();
+    match store.crate_embedding_dimensions(&stats.name).await? {
+        Some(stored) if stored != model_dimensions => Ok(Some(format!(
+            "has {}-dimensional embeddings but '{}' produces {}-dimensional vectors",
+            stored, model, model_dimensions
+        ))),
+        _ => Ok(None),
     }
 }
 
+/// Fetch the direct dependencies of a populated release from crates.io and record them under
+/// its stored name. Without a detected `version`, or if crates.io can't be reached, this only
+/// warns: dependencies widen weak searches, they aren't worth failing a population run over.
+pub async fn record_crate_dependencies(
+    store: &(dyn VectorStore + Send + Sync),
+    client: &reqwest::Client,
+    stored_name: &str,
+    version: Option<&str>,
+) -> Result<(), ServerError> {
+    let Some(version) = version else {
+        warn!("⚠️  No version detected for {}; not recording its dependencies", stored_name);
+        return Ok(());
+    };
+    let (crate_name, _) = doc_loader::split_crate_variant(stored_name);
+    match doc_loader::fetch_crate_dependencies(client, crate_name, version).await {
+        Ok(dependencies) => {
+            debug!("🔗 {} {} depends on: {}", crate_name, version, dependencies.join(", "));
+            store.set_crate_dependencies(stored_name, &dependencies).await
+        }
+        Err(e) => {
+            warn!("⚠️  Could not fetch dependencies of {} {} from crates.io: {}", crate_name, version, e);
+            Ok(())
+        }
+    }
+}
+
+/// Nearest neighbours fetched per requested result when re-ranking with an example boost
+pub(crate) const EXAMPLE_CANDIDATE_FACTOR: i32 = 4;
+
+/// Attempts for a statement that fails because the connection dropped
+const DB_RETRY_ATTEMPTS: u32 = 3;
+/// Base delay between attempts (multiplied by the attempt number)
+const DB_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// What a failed statement's SQLSTATE says about retrying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SqlStateClass {
+    /// The connection was lost or the server is going away; another connection may work
+    ConnectionLost,
+    /// The statement was cancelled, e.g. for exceeding `statement_timeout`
+    StatementTimeout,
+    /// Anything else is about the statement itself
+    Other,
+}
+
+/// Classify a Postgres SQLSTATE: class 08 (connection exception) and 57P01-57P03 (server
+/// shutting down / starting) lose the connection, 57014 (query_canceled) is a timeout
+fn classify_sqlstate(code: &str) -> SqlStateClass {
+    match code {
+        "57014" => SqlStateClass::StatementTimeout,
+        "57P01" | "57P02" | "57P03" => SqlStateClass::ConnectionLost,
+        code if code.starts_with("08") => SqlStateClass::ConnectionLost,
+        _ => SqlStateClass::Other,
+    }
+}
+
+/// The [`SqlStateClass`] of a database error's SQLSTATE, if it has one
+fn sqlstate_class(err: &sqlx::Error) -> Option<SqlStateClass> {
+    match err {
+        sqlx::Error::Database(db_err) => db_err.code().map(|code| classify_sqlstate(&code)),
+        _ => None,
+    }
+}
+
+/// Whether an sqlx error means the connection was lost (failover, idle timeout, restart)
+/// rather than the statement itself being wrong
+fn is_connection_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::Protocol(_) | sqlx::Error::PoolTimedOut => true,
+        _ => sqlstate_class(err) == Some(SqlStateClass::ConnectionLost),
+    }
+}
+
+/// Whether Postgres cancelled the statement, e.g. for exceeding `statement_timeout`
+fn is_statement_timeout(err: &sqlx::Error) -> bool {
+    sqlstate_class(err) == Some(SqlStateClass::StatementTimeout)
+}
+
+/// `ServerError` for a failed statement: `Timeout` when it was cancelled, `Database` otherwise
+fn query_error(operation: &str, err: sqlx::Error) -> ServerError {
+    if is_statement_timeout(&err) {
+        ServerError::Timeout(format!("Failed to {}: statement timed out ({})", operation, err))
+    } else {
+        ServerError::Database(format!("Failed to {}: {}", operation, err))
+    }
+}
+
+/// Run a statement, re-acquiring a connection from the pool and retrying a couple of
+/// times with a short backoff if it fails on a connection-level error.
+/// Query errors are returned immediately (see [`query_error`]).
+async fn with_retry<T, F, Fut>(operation: &str, mut run: F) -> Result<T, ServerError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match run().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < DB_RETRY_ATTEMPTS && is_connection_error(&e) => {
+                warn!(
+                    "⚠️  Database connection error during {} (attempt {}/{}): {}. Retrying...",
+                    operation, attempt, DB_RETRY_ATTEMPTS, e
+                );
+                tokio::time::sleep(DB_RETRY_BACKOFF * attempt).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(query_error(operation, e)),
+        }
+    }
+}
+
+/// Vector store implementation behind [`VectorStore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// PostgreSQL with pgvector ([`Database`])
+    Postgres,
+    /// A local SQLite file with sqlite-vec ([`SqliteStore`])
+    Sqlite,
+    /// A Qdrant server ([`QdrantStore`])
+    Qdrant,
+}
+
+/// Database used by the SQLite backend when `MCPDOCS_DATABASE_URL` isn't set
+pub const DEFAULT_SQLITE_URL: &str = "sqlite://rust_docs.db";
+
+impl Backend {
+    /// Read `MCPDOCS_BACKEND` (postgres, sqlite or qdrant), if set
+    pub fn from_env() -> Result<Option<Self>, ServerError> {
+        match env::var("MCPDOCS_BACKEND").ok().filter(|v| !v.trim().is_empty()) {
+            Some(value) => Self::from_str(value.trim(), true).map(Some).map_err(|_| {
+                ServerError::Config(format!("Invalid MCPDOCS_BACKEND: {}. Use 'postgres', 'sqlite' or 'qdrant'", value))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// The backend a database URL's scheme selects
+    pub fn from_url(url: &str) -> Option<Self> {
+        if url.starts_with("sqlite:") {
+            Some(Self::Sqlite)
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some(Self::Postgres)
+        } else if url.starts_with("qdrant://") || url.starts_with("qdrant+https://") {
+            Some(Self::Qdrant)
+        } else {
+            None
+        }
+    }
+}
+
+/// The database URL for an optional backend choice and URL. The URL's scheme always selects
+/// the backend; `backend` only checks it (a URL of another or unknown scheme is a `Config`
+/// error) and, for SQLite, supplies [`DEFAULT_SQLITE_URL`] when there is no URL. Every other
+/// backend requires a URL.
+pub fn resolve_database_url(backend: Option<Backend>, url: Option<String>) -> Result<String, ServerError> {
+    match (backend, url) {
+        (Some(backend), Some(url)) => match Backend::from_url(&url) {
+            Some(scheme) if scheme == backend => Ok(url),
+            Some(scheme) => Err(ServerError::Config(format!(
+                "MCPDOCS_BACKEND is {:?} but MCPDOCS_DATABASE_URL is a {:?} URL",
+                backend, scheme
+            ))),
+            None => Err(ServerError::Config(format!(
+                "MCPDOCS_BACKEND is {:?} but MCPDOCS_DATABASE_URL has an unsupported scheme",
+                backend
+            ))),
+        },
+        (_, Some(url)) => Ok(url),
+        (Some(Backend::Sqlite), None) => Ok(DEFAULT_SQLITE_URL.to_string()),
+        (_, None) => Err(ServerError::MissingEnvVar(
+            "MCPDOCS_DATABASE_URL (or MCPDOCS_DATABASE_URL_FILE)".to_string(),
+        )),
+    }
+}
+
+/// Database URL from `MCPDOCS_DATABASE_URL` (or the file named by `MCPDOCS_DATABASE_URL_FILE`),
+/// checked against `MCPDOCS_BACKEND`. With `MCPDOCS_BACKEND=sqlite` the URL is optional.
+pub fn database_url() -> Result<String, ServerError> {
+    resolve_database_url(Backend::from_env()?, secrets::env_or_file("MCPDOCS_DATABASE_URL")?)
+}
+
+/// Connect to the backend selected by `MCPDOCS_BACKEND` or the `MCPDOCS_DATABASE_URL` scheme:
+/// `sqlite://` opens a local SQLite file, `postgres://`/`postgresql://` uses pgvector and
+/// `qdrant://host:6333/collection` (or `qdrant+https://`) a Qdrant server.
+/// The distance metric comes from `MCPDOCS_DISTANCE`.
+pub async fn connect() -> Result<Arc<dyn VectorStore + Send + Sync>, ServerError> {
+    connect_with_metric(DistanceMetric::from_env()?).await
+}
+
+/// Like [`connect`], with an explicit distance metric (e.g. from a `--distance` flag)
+pub async fn connect_with_metric(
+    metric: DistanceMetric,
+) -> Result<Arc<dyn VectorStore + Send + Sync>, ServerError> {
+    connect_url(&database_url()?, metric).await
+}
+
+/// Connect to the backend for an explicit URL (see [`connect`])
+pub async fn connect_url(
+    url: &str,
+    metric: DistanceMetric,
+) -> Result<Arc<dyn VectorStore + Send + Sync>, ServerError> {
+    let store: Arc<dyn VectorStore + Send + Sync> = match Backend::from_url(url) {
+        Some(Backend::Sqlite) => Arc::new(SqliteStore::connect(url).await?.with_distance_metric(metric)?),
+        Some(Backend::Postgres) => Arc::new(Database::connect(url).await?.with_distance_metric(metric)),
+        Some(Backend::Qdrant) => Arc::new(QdrantStore::connect(url).await?.with_distance_metric(metric)),
+        None => {
+            return Err(ServerError::Config(format!(
+                "Unsupported MCPDOCS_DATABASE_URL scheme: {}. Use 'sqlite://', 'postgres://' or 'qdrant://'",
+                url.split(':').next().unwrap_or(url)
+            )))
+        }
+    };
+
+    store.validate_distance_metric().await?;
+    Ok(store)
+}
+
+/// Default number of IVFFlat lists probed per query (pgvector's own default is 1)
+pub const DEFAULT_IVFFLAT_PROBES: u32 = 10;
+
+/// Default Postgres pool size (`DB_MAX_CONNECTIONS`)
+pub const DEFAULT_DB_MAX_CONNECTIONS: u32 = 5;
+/// Default per-statement timeout (`DB_STATEMENT_TIMEOUT_MS`, 0 disables it)
+pub const DEFAULT_DB_STATEMENT_TIMEOUT_MS: u64 = 30_000;
+
+/// A number from the environment, or `default` when unset or invalid
+fn env_or<T: std::str::FromStr + Copy>(name: &str, default: T) -> T {
+    env::var(name).ok().and_then(|v| v.parse::<T>().ok()).unwrap_or(default)
+}
+
+#[derive(Clone)]
+pub struct Database {
+    pool: PgPool,
+    ivfflat_probes: u32,
+    distance: DistanceMetric,
+}
+
+impl Database {
+    pub async fn new() -> Result<Self, ServerError> {
+        Self::connect(&database_url()?).await
+    }
+
+    /// Connect to a PostgreSQL (pgvector) database at the given URL.
+    /// The pool holds up to `DB_MAX_CONNECTIONS` connections, and every connection gets a
+    /// `statement_timeout` of `DB_STATEMENT_TIMEOUT_MS` so one slow query can't hold it for minutes.
+    pub async fn connect(database_url: &str) -> Result<Self, ServerError> {
+        let max_connections = env_or("DB_MAX_CONNECTIONS", DEFAULT_DB_MAX_CONNECTIONS).max(1);
+        let statement_timeout_ms = env_or("DB_STATEMENT_TIMEOUT_MS", DEFAULT_DB_STATEMENT_TIMEOUT_MS);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .after_connect(move |conn, _meta| {
+                Box::pin(async move {
+                    if statement_timeout_ms > 0 {
+                        conn.execute(format!("SET statement_timeout = {}", statement_timeout_ms).as_str())
+                            .await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect(database_url)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to connect to database: {}", e)))?;
+
+        let ivfflat_probes = env::var("IVFFLAT_PROBES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_IVFFLAT_PROBES);
+
+        Ok(Self { pool, ivfflat_probes, distance: DistanceMetric::default() })
+    }
+
+    /// Override the number of IVFFlat lists probed by `search_similar_docs`.
+    /// More probes improve recall (especially for per-crate filters) at the cost of latency.
+    pub fn with_ivfflat_probes(mut self, probes: u32) -> Self {
+        self.ivfflat_probes = probes.max(1);
+        self
+    }
+
+    /// Rank search results with `metric` instead of cosine distance
+    pub fn with_distance_metric(mut self, metric: DistanceMetric) -> Self {
+        self.distance = metric;
+        self
+    }
+
+    /// Nearest-neighbour search, optionally re-ranking the candidates with an example boost
+    async fn search(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: Option<f32>,
+        filter: DocFilter<'_>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        let embedding_vec = Vector::from(query_embedding.to_vec());
+
+        let distance = format!("embedding {} $1", self.distance.operator());
+        let nearest = format!(
+            r#"
+            SELECT
+                doc_path,
+                content,
+                token_count,
+                has_code_example,
+                {} as similarity
+            FROM doc_embeddings
+            WHERE crate_name = $2
+              AND ($4::text IS NULL OR module_path = $4 OR left(module_path, length($4) + 2) = $4 || '::')
+              AND ($5::text IS NULL OR doc_kind = $5)
+            ORDER BY {}
+            LIMIT $3
+            "#,
+            self.distance.similarity_sql(&distance),
+            distance
+        );
+        // The inner query still walks the vector index; only its candidates are re-ranked
+        let (sql, candidates) = match example_boost {
+            None => (nearest, limit),
+            Some(boost) => (
+                format!(
+                    r#"
+                    SELECT doc_path, content, token_count, similarity
+                    FROM ({}) candidates
+                    ORDER BY similarity + CASE WHEN has_code_example THEN {} ELSE 0 END DESC
+                    LIMIT {}
+                    "#,
+                    nearest, boost as f64, limit
+                ),
+                limit.saturating_mul(EXAMPLE_CANDIDATE_FACTOR),
+            ),
+        };
+
+        let probes_sql = format!("SET LOCAL ivfflat.probes = {}", self.ivfflat_probes);
+        let (embedding_vec, sql, probes_sql) = (&embedding_vec, &sql, &probes_sql);
+
+        // SET LOCAL only lasts for the transaction, so the pooled connection keeps its defaults.
+        // SET doesn't accept bind parameters; the value is a plain integer.
+        let results = with_retry("search documents", move || async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(probes_sql).execute(&mut *tx).await?;
+            let rows = sqlx::query(sql)
+                .bind(embedding_vec)
+                .bind(crate_name)
+                .bind(candidates)
+                .bind(filter.module_prefix)
+                .bind(filter.kind)
+                .fetch_all(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            Ok(rows)
+        })
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let token_count: Option<i32> = row.get("token_count");
+                let similarity: f64 = row.get("similarity");
+                DocMatch {
+                    doc_path: row.get("doc_path"),
+                    content: row.get("content"),
+                    similarity: similarity as f32, // Convert to f32 for compatibility
+                    token_count: token_count.map(|count| count.max(0) as usize),
+                }
+            })
+            .collect())
+    }
+
+    /// Update crate statistics
+    async fn update_crate_stats(&self, crate_id: i32) -> Result<(), ServerError> {
+        with_retry("update crate stats", move || {
+            sqlx::query(
+                r#"
+                UPDATE crates
+                SET total_docs = (
+                    SELECT COUNT(*) FROM doc_embeddings WHERE crate_id = $1
+                ),
+                total_tokens = (
+                    SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings WHERE crate_id = $1
+                )
+                WHERE id = $1
+                "#
+            )
+            .bind(crate_id)
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VectorStore for Database {
+    /// Insert or update a crate in the database
+    async fn upsert_crate(&self, crate_name: &str, version: Option<&str>) -> Result<i32, ServerError> {
+        let result = with_retry("upsert crate", move || {
+            sqlx::query(
+                r#"
+                INSERT INTO crates (name, version)
+                VALUES ($1, $2)
+                ON CONFLICT (name)
+                DO UPDATE SET
+                    version = COALESCE($2, crates.version),
+                    last_updated = CURRENT_TIMESTAMP
+                RETURNING id
+                "#
+            )
+            .bind(crate_name)
+            .bind(version)
+            .fetch_one(&self.pool)
+        })
+        .await?;
+
+        let id: i32 = result.get("id");
+        Ok(id)
+    }
+
+    async fn set_crate_features(&self, crate_name: &str, features: &[String]) -> Result<(), ServerError> {
+        let features = join_features(features);
+        let features = features.as_deref();
+        with_retry("set crate features", move || {
+            sqlx::query("UPDATE crates SET features = $2 WHERE name = $1")
+                .bind(crate_name)
+                .bind(features)
+                .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_crate_embedding_model(&self, crate_name: &str, model: &str) -> Result<(), ServerError> {
+        with_retry("set crate embedding model", move || {
+            sqlx::query("UPDATE crates SET embedding_model = $2 WHERE name = $1")
+                .bind(crate_name)
+                .bind(model)
+                .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_crate_status(&self, crate_name: &str, status: CrateStatus) -> Result<(), ServerError> {
+        with_retry("set crate status", move || {
+            sqlx::query("UPDATE crates SET status = $2, status_updated_at = $3 WHERE name = $1")
+                .bind(crate_name)
+                .bind(status.as_str())
+                .bind(chrono::Utc::now().naive_utc())
+                .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn crate_status(&self, crate_name: &str) -> Result<Option<CrateStatus>, ServerError> {
+        let status: Option<(String, Option<chrono::NaiveDateTime>)> = with_retry("get crate status", move || {
+            sqlx::query_as("SELECT status, status_updated_at FROM crates WHERE name = $1")
+                .bind(crate_name)
+                .fetch_optional(&self.pool)
+        })
+        .await?;
+
+        Ok(status.map(|(status, updated_at)| CrateStatus::from_db(&status, updated_at)))
+    }
+
+    async fn set_crate_dependencies(&self, crate_name: &str, dependencies: &[String]) -> Result<(), ServerError> {
+        with_retry("set crate dependencies", move || async move {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("DELETE FROM crate_deps WHERE crate_name = $1")
+                .bind(crate_name)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO crate_deps (crate_name, dependency)
+                SELECT $1, dependency FROM unnest($2::text[]) AS dependency
+                ON CONFLICT DO NOTHING
+                "#
+            )
+            .bind(crate_name)
+            .bind(dependencies)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn crate_dependencies(&self, crate_name: &str) -> Result<Vec<String>, ServerError> {
+        let results = with_retry("get crate dependencies", move || {
+            sqlx::query("SELECT dependency FROM crate_deps WHERE crate_name = $1 ORDER BY dependency")
+                .bind(crate_name)
+                .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results.into_iter().map(|row| row.get("dependency")).collect())
+    }
+
+    /// Check if embeddings exist for a crate
+    async fn has_embeddings(&self, crate_name: &str) -> Result<bool, ServerError> {
+        let result = with_retry("check embeddings", move || {
+            sqlx::query(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM doc_embeddings WHERE crate_name = $1
+                ) as exists
+                "#
+            )
+            .bind(crate_name)
+            .fetch_one(&self.pool)
+        })
+        .await?;
+
+        let exists: bool = result.get("exists");
+        Ok(exists)
+    }
+
+    /// Insert a document embedding
+    async fn insert_embedding(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        doc_path: &str,
+        content: &str,
+        embedding: &Array1<f32>,
+        token_count: i32,
+    ) -> Result<(), ServerError> {
+        let embedding_vec = Vector::from(embedding.to_vec());
+
+        sqlx::query(
+            r#"
+            INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count, has_code_example, module_path, content_hash, anchor, doc_kind)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (crate_name, doc_path)
+            DO UPDATE SET
+                content = $4,
+                embedding = $5,
+                token_count = $6,
+                has_code_example = $7,
+                module_path = $8,
+                content_hash = $9,
+                anchor = $10,
+                doc_kind = $11,
+                created_at = CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(crate_id)
+        .bind(crate_name)
+        .bind(doc_path)
+        .bind(content)
+        .bind(embedding_vec)
+        .bind(token_count)
+        .bind(examples::is_code_example(content))
+        .bind(doc_loader::module_path(crate_name, doc_path))
+        .bind(dedupe::content_hash(content))
+        .bind(doc_loader::doc_anchor(doc_path))
+        .bind(doc_loader::doc_kind(crate_name, doc_path))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| query_error("insert embedding", e))?;
+
+        Ok(())
+    }
+
+    /// Batch insert multiple embeddings (more efficient)
+    async fn insert_embeddings_batch(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        embeddings: &[(String, String, Array1<f32>, i32)], // (path, content, embedding, token_count)
+    ) -> Result<(), ServerError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+
+        for (doc_path, content, embedding, token_count) in embeddings {
+            let embedding_vec = Vector::from(embedding.to_vec());
+
+            sqlx::query(
+                r#"
+                INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count, has_code_example, module_path, content_hash, anchor, doc_kind)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (crate_name, doc_path)
+                DO UPDATE SET
+                    content = $4,
+                    embedding = $5,
+                    token_count = $6,
+                    has_code_example = $7,
+                    module_path = $8,
+                    content_hash = $9,
+                    anchor = $10,
+                    doc_kind = $11,
+                    created_at = CURRENT_TIMESTAMP
+                "#
+            )
+            .bind(crate_id)
+            .bind(crate_name)
+            .bind(doc_path)
+            .bind(content)
+            .bind(embedding_vec)
+            .bind(*token_count)
+            .bind(examples::is_code_example(content))
+            .bind(doc_loader::module_path(crate_name, doc_path))
+            .bind(dedupe::content_hash(content))
+            .bind(doc_loader::doc_anchor(doc_path))
+            .bind(doc_loader::doc_kind(crate_name, doc_path))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| query_error("insert embedding", e))?;
+        }
+
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        // Update crate statistics
+        self.update_crate_stats(crate_id).await?;
+
+        Ok(())
+    }
+
+    /// Search for similar documents using vector similarity
+    async fn search_similar_docs(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, None, DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_preferring_examples(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: f32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, Some(example_boost), DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_filtered(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        filter: DocFilter<'_>,
+        example_boost: Option<f32>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, example_boost, filter).await
+    }
+
+    /// Get all documents for a crate (for loading into memory if needed)
+    async fn get_crate_documents(
+        &self,
+        crate_name: &str,
+    ) -> Result<Vec<(String, String, Array1<f32>, Option<usize>)>, ServerError> {
+        debug!("🔍 Querying database for crate: {}", crate_name);
+        let query_start = std::time::Instant::now();
+
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, content, embedding, token_count
+            FROM doc_embeddings
+            WHERE crate_name = $1
+            ORDER BY doc_path
+            "#
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| query_error("get crate documents", e))?;
+
+        let query_time = query_start.elapsed();
+        debug!("📊 Found {} documents for {} in {:.3}s", results.len(), crate_name, query_time.as_secs_f64());
+
+                let mut documents = Vec::new();
+        for (i, row) in results.iter().enumerate() {
+            let doc_path: String = row.get("doc_path");
+            let content: String = row.get("content");
+            let embedding_vec: Vector = row.get("embedding");
+            let embedding_array = Array1::from_vec(embedding_vec.to_vec());
+            let token_count: Option<i32> = row.get("token_count");
+
+            if i < 3 || (i + 1) % 5 == 0 {
+                debug!("📄 [{}/{}] Processed: {} ({} chars, {} dims)",
+                    i + 1, results.len(), doc_path, content.len(), embedding_array.len());
+            }
+
+            documents.push((doc_path, content, embedding_array, token_count.map(|count| count.max(0) as usize)));
+        }
+
+        Ok(documents)
+    }
+
+    async fn get_doc_embeddings(
+        &self,
+        crate_name: &str,
+        doc_paths: &[String],
+    ) -> Result<Vec<(String, Array1<f32>)>, ServerError> {
+        let results = with_retry("get document embeddings", move || {
+            sqlx::query("SELECT doc_path, embedding FROM doc_embeddings WHERE crate_name = $1 AND doc_path = ANY($2)")
+                .bind(crate_name)
+                .bind(doc_paths)
+                .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let embedding: Vector = row.get("embedding");
+                (row.get("doc_path"), Array1::from_vec(embedding.to_vec()))
+            })
+            .collect())
+    }
+
+    /// Get document paths and contents for a crate (no embeddings)
+    async fn get_crate_doc_contents(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let results = with_retry("get crate documents", move || {
+            sqlx::query(
+                r#"
+                SELECT doc_path, content
+                FROM doc_embeddings
+                WHERE crate_name = $1
+                ORDER BY doc_path
+                "#
+            )
+            .bind(crate_name)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| (row.get("doc_path"), row.get("content")))
+            .collect())
+    }
+
+    async fn get_content_hashes(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let results = with_retry("get content hashes", move || {
+            sqlx::query(
+                r#"
+                SELECT doc_path, content_hash, CASE WHEN content_hash IS NULL THEN content END AS content
+                FROM doc_embeddings
+                WHERE crate_name = $1
+                ORDER BY doc_path
+                "#
+            )
+            .bind(crate_name)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let hash: Option<String> = row.get("content_hash");
+                let hash = hash.unwrap_or_else(|| dedupe::content_hash(row.get("content")));
+                (row.get("doc_path"), hash)
+            })
+            .collect())
+    }
+
+    /// Page through a crate's document paths without loading their content
+    async fn list_doc_paths(
+        &self,
+        crate_name: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ServerError> {
+        let results = with_retry("list document paths", move || {
+            sqlx::query(
+                r#"
+                SELECT doc_path
+                FROM doc_embeddings
+                WHERE crate_name = $1 AND ($2::text IS NULL OR doc_path > $2)
+                ORDER BY doc_path
+                LIMIT $3
+                "#
+            )
+            .bind(crate_name)
+            .bind(after)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results.into_iter().map(|row| row.get("doc_path")).collect())
+    }
+
+    async fn get_doc_content(&self, crate_name: &str, doc_path: &str) -> Result<Option<String>, ServerError> {
+        let row = with_retry("get document content", move || {
+            sqlx::query("SELECT content FROM doc_embeddings WHERE crate_name = $1 AND doc_path = $2")
+                .bind(crate_name)
+                .bind(doc_path)
+                .fetch_optional(&self.pool)
+        })
+        .await?;
+
+        Ok(row.map(|row| row.get("content")))
+    }
+
+    async fn get_document_by_path(
+        &self,
+        crate_name: &str,
+        doc_path: &str,
+    ) -> Result<Option<(String, String)>, ServerError> {
+        let results = with_retry("get document by path", move || {
+            sqlx::query(
+                r#"
+                SELECT doc_path, content
+                FROM doc_embeddings
+                WHERE crate_name = $1 AND strpos('/' || doc_path, '/' || $2) > 0
+                ORDER BY id
+                "#
+            )
+            .bind(crate_name)
+            .bind(doc_path)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        let rows = results.into_iter().map(|row| (row.get("doc_path"), row.get("content"))).collect();
+        Ok(assemble_page(rows, doc_path))
+    }
+
+    async fn find_doc_paths(&self, crate_name: &str, term: &str, limit: usize) -> Result<Vec<String>, ServerError> {
+        let results = with_retry("find document paths", move || {
+            sqlx::query(
+                r#"
+                SELECT DISTINCT doc_path
+                FROM doc_embeddings
+                WHERE crate_name = $1 AND strpos(lower(doc_path), lower($2)) > 0
+                ORDER BY doc_path
+                LIMIT $3
+                "#
+            )
+            .bind(crate_name)
+            .bind(term)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results.into_iter().map(|row| row.get("doc_path")).collect())
+    }
+
+    /// Backfill `module_path` in one `UPDATE ... FROM UNNEST` round trip
+    async fn backfill_module_paths(&self) -> Result<usize, ServerError> {
+        let rows = with_retry("read document paths", move || {
+            sqlx::query("SELECT id, crate_name, doc_path FROM doc_embeddings WHERE module_path IS NULL")
+                .fetch_all(&self.pool)
+        })
+        .await?;
+
+        let (ids, module_paths): (Vec<i32>, Vec<String>) = rows
+            .iter()
+            .filter_map(|row| {
+                let crate_name: String = row.get("crate_name");
+                let doc_path: String = row.get("doc_path");
+                doc_loader::module_path(&crate_name, &doc_path).map(|module| (row.get::<i32, _>("id"), module))
+            })
+            .unzip();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let (ids, module_paths) = (&ids, &module_paths);
+        let result = with_retry("backfill module paths", move || {
+            sqlx::query(
+                r#"
+                UPDATE doc_embeddings d
+                SET module_path = v.module_path
+                FROM UNNEST($1::int[], $2::text[]) AS v(id, module_path)
+                WHERE d.id = v.id
+                "#
+            )
+            .bind(ids)
+            .bind(module_paths)
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Backfill `doc_kind` in one `UPDATE ... FROM UNNEST` round trip
+    async fn backfill_doc_kinds(&self) -> Result<usize, ServerError> {
+        let rows = with_retry("read document paths", move || {
+            sqlx::query("SELECT id, crate_name, doc_path FROM doc_embeddings WHERE doc_kind IS NULL")
+                .fetch_all(&self.pool)
+        })
+        .await?;
+
+        let (ids, doc_kinds): (Vec<i32>, Vec<String>) = rows
+            .iter()
+            .filter_map(|row| {
+                let crate_name: String = row.get("crate_name");
+                let doc_path: String = row.get("doc_path");
+                doc_loader::doc_kind(&crate_name, &doc_path).map(|kind| (row.get::<i32, _>("id"), kind.to_string()))
+            })
+            .unzip();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let (ids, doc_kinds) = (&ids, &doc_kinds);
+        let result = with_retry("backfill doc kinds", move || {
+            sqlx::query(
+                r#"
+                UPDATE doc_embeddings d
+                SET doc_kind = v.doc_kind
+                FROM UNNEST($1::int[], $2::text[]) AS v(id, doc_kind)
+                WHERE d.id = v.id
+                "#
+            )
+            .bind(ids)
+            .bind(doc_kinds)
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Repair every crate's stats in one `UPDATE ... FROM` over the aggregated documents
+    async fn repair_crate_stats(&self) -> Result<usize, ServerError> {
+        let result = with_retry("repair crate stats", move || {
+            sqlx::query(
+                r#"
+                UPDATE crates c
+                SET total_docs = s.total_docs, total_tokens = s.total_tokens
+                FROM (
+                    SELECT crates.id, COUNT(d.id) AS total_docs, COALESCE(SUM(d.token_count), 0) AS total_tokens
+                    FROM crates
+                    LEFT JOIN doc_embeddings d ON d.crate_id = crates.id
+                    GROUP BY crates.id
+                ) s
+                WHERE c.id = s.id
+                  AND (c.total_docs IS DISTINCT FROM s.total_docs OR c.total_tokens IS DISTINCT FROM s.total_tokens)
+                "#
+            )
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Delete all embeddings for a crate
+    async fn delete_crate_embeddings(&self, crate_name: &str) -> Result<(), ServerError> {
+        with_retry("delete embeddings", move || {
+            sqlx::query(
+                r#"
+                DELETE FROM doc_embeddings WHERE crate_name = $1
+                "#
+            )
+            .bind(crate_name)
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Split crate names into those with stored embeddings and the rest
+    async fn filter_existing_crates(&self, crate_names: &[String]) -> Result<(Vec<String>, Vec<String>), ServerError> {
+        let results = with_retry("check embeddings", move || {
+            sqlx::query(
+                r#"
+                SELECT DISTINCT crate_name FROM doc_embeddings WHERE crate_name = ANY($1)
+                "#
+            )
+            .bind(crate_names)
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        let present: HashSet<String> = results.into_iter().map(|row| row.get("crate_name")).collect();
+        Ok(crate_names.iter().cloned().partition(|name| present.contains(name)))
+    }
+
+    /// Delete the embeddings stored under the given paths of a crate
+    async fn delete_doc_embeddings(&self, crate_name: &str, doc_paths: &[String]) -> Result<(), ServerError> {
+        with_retry("delete document embeddings", move || {
+            sqlx::query(
+                r#"
+                DELETE FROM doc_embeddings WHERE crate_name = $1 AND doc_path = ANY($2)
+                "#
+            )
+            .bind(crate_name)
+            .bind(doc_paths)
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get crate statistics
+    async fn get_crate_stats(&self) -> Result<Vec<CrateStats>, ServerError> {
+        let results = with_retry("get crate stats", move || {
+            sqlx::query(
+                r#"
+                SELECT
+                    name,
+                    version,
+                    last_updated,
+                    total_docs,
+                    total_tokens,
+                    features,
+                    embedding_model,
+                    status,
+                    status_updated_at
+                FROM crates
+                ORDER BY name
+                "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let name: String = row.get("name");
+                let version: Option<String> = row.get("version");
+                let last_updated: chrono::NaiveDateTime = row.get("last_updated");
+                let total_docs: Option<i32> = row.get("total_docs");
+                let total_tokens: Option<i32> = row.get("total_tokens");
+
+                CrateStats {
+                    name,
+                    version,
+                    last_updated,
+                    total_docs: total_docs.unwrap_or(0),
+                    total_tokens: total_tokens.unwrap_or(0),
+                    features: split_features(row.get("features")),
+                    embedding_model: row.get("embedding_model"),
+                    status: CrateStatus::from_db(row.get("status"), row.get("status_updated_at")),
+                }
+            })
+            .collect())
+    }
+
+    /// Crate, document and token totals in one round trip, with the oldest and newest
+    /// crate update; tokens are summed from the documents, not the crates' stored totals
+    async fn global_stats(&self) -> Result<GlobalStats, ServerError> {
+        let totals = with_retry("get global stats", move || {
+            sqlx::query(
+                r#"
+                SELECT
+                    (SELECT COUNT(*) FROM crates) AS total_crates,
+                    (SELECT COUNT(*) FROM doc_embeddings) AS total_docs,
+                    (SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings) AS total_tokens,
+                    (SELECT MIN(last_updated) FROM crates) AS oldest_update,
+                    (SELECT MAX(last_updated) FROM crates) AS newest_update
+                "#
+            )
+            .fetch_one(&self.pool)
+        })
+        .await?;
+        let dimensions = with_retry("get embedding dimensions", move || {
+            sqlx::query(
+                "SELECT DISTINCT vector_dims(embedding) AS dims FROM doc_embeddings WHERE embedding IS NOT NULL ORDER BY dims"
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?;
+
+        Ok(GlobalStats::new(
+            totals.get("total_crates"),
+            totals.get("total_docs"),
+            totals.get("total_tokens"),
+            dimensions.iter().map(|row| row.get::<i32, _>("dims") as usize).collect(),
+            totals.get("oldest_update"),
+            totals.get("newest_update"),
+        ))
+    }
+
+    /// Count documents for a specific crate
+    async fn count_crate_documents(&self, crate_name: &str) -> Result<usize, ServerError> {
+        let result = with_retry("count crate documents", move || {
+            sqlx::query(
+                r#"
+                SELECT COUNT(*) as count
+                FROM doc_embeddings
+                WHERE crate_name = $1
+                "#
+            )
+            .bind(crate_name)
+            .fetch_one(&self.pool)
+        })
+        .await?;
+
+        let count: i64 = result.get("count");
+        Ok(count as usize)
+    }
+
+    async fn stored_embedding_dimensions(&self) -> Result<Option<usize>, ServerError> {
+        // pgvector keeps the declared dimension in atttypmod (-1 for an unconstrained `vector`)
+        let result = with_retry("read embedding column type", move || {
+            sqlx::query(
+                r#"
+                SELECT atttypmod
+                FROM pg_attribute
+                WHERE attrelid = 'doc_embeddings'::regclass
+                  AND attname = 'embedding'
+                "#
+            )
+            .fetch_optional(&self.pool)
+        })
+        .await?;
+
+        Ok(result
+            .map(|row| row.get::<i32, _>("atttypmod"))
+            .filter(|dims| *dims > 0)
+            .map(|dims| dims as usize))
+    }
+
+    async fn crate_embedding_dimensions(&self, crate_name: &str) -> Result<Option<usize>, ServerError> {
+        let result = with_retry("read crate embedding dimensions", move || {
+            sqlx::query("SELECT vector_dims(embedding) AS dims FROM doc_embeddings WHERE crate_name = $1 LIMIT 1")
+                .bind(crate_name)
+                .fetch_optional(&self.pool)
+        })
+        .await?;
+
+        Ok(result.map(|row| row.get::<i32, _>("dims") as usize))
+    }
+
+    async fn ping(&self) -> Result<(), ServerError> {
+        with_retry("ping database", move || sqlx::query("SELECT 1").execute(&self.pool)).await?;
+        Ok(())
+    }
+
+    async fn validate_distance_metric(&self) -> Result<(), ServerError> {
+        let index_defs: Vec<String> = with_retry("inspect vector indexes", move || {
+            sqlx::query(
+                r#"
+                SELECT indexdef
+                FROM pg_indexes
+                WHERE tablename = 'doc_embeddings'
+                  AND (indexdef ILIKE '%USING hnsw%' OR indexdef ILIKE '%USING ivfflat%')
+                "#
+            )
+            .fetch_all(&self.pool)
+        })
+        .await?
+        .into_iter()
+        .map(|row| row.get("indexdef"))
+        .collect();
+
+        // No vector index (e.g. 3072 dims on older pgvector): every metric scans anyway
+        if index_defs.is_empty() || index_defs.iter().any(|def| def.contains(self.distance.index_ops())) {
+            return Ok(());
+        }
+
+        Err(ServerError::Config(format!(
+            "Distance metric {:?} needs an index using {}, but doc_embeddings is indexed with: {}. \
+             Searches would fall back to sequential scans. Create a matching index, e.g. \
+             CREATE INDEX ON doc_embeddings USING hnsw (embedding {}), or choose another --distance.",
+            self.distance,
+            self.distance.index_ops(),
+            index_defs.join("; "),
+            self.distance.index_ops()
+        )))
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Postgres
+    }
+
+    fn distance_metric(&self) -> DistanceMetric {
+        self.distance
+    }
+}
+
+/// Narrows a similarity search to part of a crate; the default matches every document
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DocFilter<'a> {
+    /// Documents in this module or its submodules (`tokio::sync` also matches `tokio::sync::mpsc`)
+    pub module_prefix: Option<&'a str>,
+    /// Documents of this kind, one of [`doc_loader::DOC_KINDS`]
+    pub kind: Option<&'a str>,
+}
+
+impl DocFilter<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.module_prefix.is_none() && self.kind.is_none()
+    }
+}
+
+/// A document returned by a similarity search
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocMatch {
+    pub doc_path: String,
+    pub content: String,
+    pub similarity: f32,
+    /// cl100k_base tokens in `content`, as counted when it was embedded; `None` if not stored
+    pub token_count: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct CrateStats {
     pub name: String,
@@ -329,4 +1610,491 @@ pub struct CrateStats {
     pub last_updated: chrono::NaiveDateTime,
     pub total_docs: i32,
     pub total_tokens: i32,
-}
\ No newline at end of file
+    /// Features the docs were loaded with
+    pub features: Vec<String>,
+    /// Model the docs were embedded with, if recorded
+    pub embedding_model: Option<String>,
+    pub status: CrateStatus,
+}
+
+/// Database-wide aggregates (see [`VectorStore::global_stats`])
+#[derive(Debug, Clone, Serialize)]
+pub struct GlobalStats {
+    pub total_crates: usize,
+    pub total_docs: usize,
+    pub total_tokens: usize,
+    pub avg_docs_per_crate: f64,
+    /// Distinct lengths of the stored vectors; more than one means models were mixed
+    pub embedding_dimensions: Vec<usize>,
+    pub oldest_update: Option<chrono::NaiveDateTime>,
+    pub newest_update: Option<chrono::NaiveDateTime>,
+}
+
+impl GlobalStats {
+    pub(crate) fn new(
+        total_crates: i64,
+        total_docs: i64,
+        total_tokens: i64,
+        embedding_dimensions: Vec<usize>,
+        oldest_update: Option<chrono::NaiveDateTime>,
+        newest_update: Option<chrono::NaiveDateTime>,
+    ) -> Self {
+        let avg_docs_per_crate = if total_crates > 0 { total_docs as f64 / total_crates as f64 } else { 0.0 };
+        Self {
+            total_crates: total_crates as usize,
+            total_docs: total_docs as usize,
+            total_tokens: total_tokens as usize,
+            avg_docs_per_crate,
+            embedding_dimensions,
+            oldest_update,
+            newest_update,
+        }
+    }
+}
+
+/// Join the chunks and sections of the first stored page (by path) ending in `/{doc_path}`
+/// from `(doc_path, content)` rows, returning the page path without chunk suffix or anchor.
+/// The top-level docs come first, then the sections in stored order.
+pub(crate) fn assemble_page(mut rows: Vec<(String, String)>, doc_path: &str) -> Option<(String, String)> {
+    let suffix = format!("/{}", doc_path.trim_start_matches('/'));
+    rows.retain(|(path, _)| format!("/{}", doc_loader::page_path(path)).ends_with(&suffix));
+    // Stable, so sections keep the order they were stored in
+    rows.sort_by(|(a, _), (b, _)| {
+        let key = |path| (doc_loader::page_path(path), doc_loader::doc_anchor(path).is_some());
+        key(a).cmp(&key(b))
+    });
+
+    let page = doc_loader::page_path(&rows.first()?.0).to_string();
+    let mut sections: Vec<(&str, Vec<(usize, &str)>)> = Vec::new();
+    for (path, content) in rows.iter().filter(|(path, _)| doc_loader::page_path(path) == page) {
+        let (section, index) = doc_loader::chunk_order(path);
+        match sections.iter_mut().find(|(name, _)| *name == section) {
+            Some((_, chunks)) => chunks.push((index, content)),
+            None => sections.push((section, vec![(index, content)])),
+        }
+    }
+    let content = sections
+        .into_iter()
+        .flat_map(|(_, mut chunks)| {
+            chunks.sort_by_key(|(index, _)| *index);
+            chunks.into_iter().map(|(_, content)| content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some((page, content))
+}
+
+/// Features as stored in `crates.features`: comma-separated, NULL when there are none
+pub(crate) fn join_features(features: &[String]) -> Option<String> {
+    (!features.is_empty()).then(|| features.join(","))
+}
+
+pub(crate) fn split_features(features: Option<String>) -> Vec<String> {
+    features
+        .map(|features| features.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+// --- Test support ---
+// Public but hidden so the binaries' tests can seed stores too; they link the library as
+// built without cfg(test).
+
+/// A document as inserted by [`VectorStore::insert_embeddings_batch`], for test stores
+#[doc(hidden)]
+pub type TestDoc = (String, String, Array1<f32>, i32);
+
+/// A [`TestDoc`] of one token at `path` with `content` and `embedding`
+#[doc(hidden)]
+pub fn test_doc(path: &str, content: &str, embedding: &[f32]) -> TestDoc {
+    (path.to_string(), content.to_string(), Array1::from(embedding.to_vec()), 1)
+}
+
+/// In-memory SQLite store (cosine) with each crate upserted and its documents inserted
+#[doc(hidden)]
+pub async fn test_store_with(crates: &[(&str, Vec<TestDoc>)]) -> Arc<dyn VectorStore + Send + Sync> {
+    test_store_with_metric(DistanceMetric::Cosine, crates).await
+}
+
+/// [`test_store_with`], searching with `metric`
+#[doc(hidden)]
+pub async fn test_store_with_metric(
+    metric: DistanceMetric,
+    crates: &[(&str, Vec<TestDoc>)],
+) -> Arc<dyn VectorStore + Send + Sync> {
+    let store = connect_url("sqlite::memory:", metric).await.unwrap();
+    for (crate_name, docs) in crates {
+        let crate_id = store.upsert_crate(crate_name, None).await.unwrap();
+        if !docs.is_empty() {
+            store.insert_embeddings_batch(crate_id, crate_name, docs).await.unwrap();
+        }
+    }
+    store
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn dropped_connection() -> sqlx::Error {
+        sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset by peer"))
+    }
+
+    #[test]
+    fn backend_choice_and_url_must_agree() {
+        let postgres = Some("postgres://localhost/docs".to_string());
+        assert_eq!(resolve_database_url(Some(Backend::Sqlite), None).unwrap(), DEFAULT_SQLITE_URL);
+        assert_eq!(resolve_database_url(None, postgres.clone()).unwrap(), "postgres://localhost/docs");
+        assert_eq!(resolve_database_url(Some(Backend::Postgres), postgres.clone()).unwrap(), "postgres://localhost/docs");
+        assert!(matches!(resolve_database_url(Some(Backend::Sqlite), postgres), Err(ServerError::Config(_))));
+        assert!(matches!(resolve_database_url(Some(Backend::Postgres), None), Err(ServerError::MissingEnvVar(_))));
+        assert!(matches!(resolve_database_url(Some(Backend::Qdrant), None), Err(ServerError::MissingEnvVar(_))));
+    }
+
+    #[test]
+    fn url_scheme_selects_the_backend_and_must_match_mcpdocs_backend() {
+        let qdrant = Some("qdrant://localhost:6333/docs".to_string());
+        assert_eq!(resolve_database_url(Some(Backend::Qdrant), qdrant.clone()).unwrap(), "qdrant://localhost:6333/docs");
+        assert_eq!(resolve_database_url(None, qdrant.clone()).unwrap(), "qdrant://localhost:6333/docs");
+        for backend in [Backend::Postgres, Backend::Sqlite] {
+            let err = resolve_database_url(Some(backend), qdrant.clone()).unwrap_err();
+            assert!(matches!(&err, ServerError::Config(message) if message.contains("Qdrant URL")), "{}", err);
+        }
+        let mysql = Some("mysql://localhost/docs".to_string());
+        let err = resolve_database_url(Some(Backend::Postgres), mysql).unwrap_err();
+        assert!(matches!(&err, ServerError::Config(message) if message.contains("unsupported scheme")), "{}", err);
+        assert_eq!(Backend::from_url("sqlite::memory:"), Some(Backend::Sqlite));
+        assert_eq!(Backend::from_url("qdrant://localhost:6333/docs"), Some(Backend::Qdrant));
+        assert_eq!(Backend::from_url("mysql://localhost"), None);
+    }
+
+    #[tokio::test]
+    async fn example_boost_prefers_code_examples() {
+        let prose = "Spawns a new asynchronous task, returning a JoinHandle for it.\nThe future starts running in the background immediately when spawn is called.";
+        let example = "use tokio::task;\nlet handle = task::spawn(async {\n    42\n});\nlet out = handle.await?;";
+        let store = test_store_with(&[(
+            "tokio",
+            vec![
+                ("tokio/fn.spawn.html".to_string(), prose.to_string(), Array1::from(vec![1.0, 0.0]), 10),
+                ("tokio/task/index.html".to_string(), example.to_string(), Array1::from(vec![0.99, 0.14]), 10),
+            ],
+        )])
+        .await;
+        let query = Array1::from(vec![1.0, 0.0]);
+
+        let plain = store.search_similar_docs("tokio", &query, 1).await.unwrap();
+        assert_eq!(plain[0].doc_path, "tokio/fn.spawn.html");
+        assert_eq!(plain[0].token_count, Some(10));
+
+        let boosted = store.search_similar_docs_preferring_examples("tokio", &query, 1, 0.05).await.unwrap();
+        assert_eq!(boosted[0].doc_path, "tokio/task/index.html");
+        assert!(boosted[0].similarity < 1.0, "returned similarity should not include the boost");
+    }
+
+    #[tokio::test]
+    async fn splits_crates_by_stored_embeddings_in_one_query() {
+        let crate_docs = |name: &str| vec![test_doc(&format!("{}/index.html", name), "Crate docs.", &[1.0, 0.0])];
+        let store = test_store_with(&[("tokio", crate_docs("tokio")), ("serde", crate_docs("serde")), ("axum", vec![])]).await;
+
+        let names: Vec<String> = ["serde", "axum", "tokio", "hyper"].iter().map(|name| name.to_string()).collect();
+        let (present, missing) = store.filter_existing_crates(&names).await.unwrap();
+        assert_eq!(present, ["serde", "tokio"]);
+        assert_eq!(missing, ["axum", "hyper"]);
+        assert_eq!(store.filter_existing_crates(&[]).await.unwrap(), (vec![], vec![]));
+    }
+
+    #[tokio::test]
+    async fn content_hashes_are_stored_with_documents() {
+        let rows = vec![
+            test_doc("tokio/fn.spawn.html", "Spawns a new task.", &[1.0, 0.0]),
+            test_doc("tokio/index.html", "A runtime for async Rust.", &[0.0, 1.0]),
+        ];
+        let store = test_store_with(&[("tokio", rows.clone())]).await;
+        let expected: Vec<(String, String)> = rows
+            .iter()
+            .map(|(path, content, _, _)| (path.clone(), dedupe::content_hash(content)))
+            .collect();
+        assert_eq!(store.get_content_hashes("tokio").await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn records_crate_features() {
+        let store = test_store_with(&[("serde", vec![]), ("tokio", vec![])]).await;
+        store.set_crate_features("serde", &["derive".to_string(), "rc".to_string()]).await.unwrap();
+
+        let features: Vec<Vec<String>> = store.get_crate_stats().await.unwrap().into_iter().map(|stat| stat.features).collect();
+        assert_eq!(features, [vec!["derive".to_string(), "rc".to_string()], vec![]]);
+    }
+
+    #[tokio::test]
+    async fn flags_crates_embedded_with_another_model() {
+        struct TwoDimensions;
+
+        #[async_trait]
+        impl EmbeddingProvider for TwoDimensions {
+            async fn generate_embeddings(&self, texts: &[String]) -> Result<(Vec<Vec<f32>>, usize), ServerError> {
+                Ok((texts.iter().map(|_| vec![1.0, 0.0]).collect(), 0))
+            }
+
+            fn get_model_name(&self) -> &str {
+                "two-dims"
+            }
+
+            fn provider_name(&self) -> &str {
+                "test"
+            }
+
+            fn embedding_dimensions(&self) -> usize {
+                2
+            }
+        }
+
+        let store = test_store_with(&[
+            ("tokio", vec![test_doc("tokio/index.html", "Crate docs.", &[1.0, 0.0])]),
+            ("axum", vec![test_doc("axum/index.html", "Crate docs.", &[1.0, 0.0, 0.0])]),
+            ("serde", vec![]),
+        ])
+        .await;
+        store.set_crate_embedding_model("serde", "other-model").await.unwrap();
+        store.set_crate_embedding_model("tokio", "two-dims").await.unwrap();
+
+        let mut problems = Vec::new();
+        for stat in store.get_crate_stats().await.unwrap() {
+            let problem = crate_embedding_mismatch(store.as_ref(), &TwoDimensions, &stat).await.unwrap();
+            problems.push((stat.name, problem.is_some()));
+        }
+        assert_eq!(
+            problems,
+            [("axum".to_string(), true), ("serde".to_string(), true), ("tokio".to_string(), false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn stores_section_anchors_and_reassembles_pages() {
+        let page = "axum/latest/axum/struct.Router.html";
+        let row = |path: String, content: &str| test_doc(&path, content, &[1.0, 0.0]);
+        let rows = vec![
+            row(page.to_string(), "The router type."),
+            row(format!("{}#method.route [chunk 1/2]", page), "Add another route"),
+            row(format!("{}#method.route [chunk 2/2]", page), "to the router."),
+            row(format!("{}#method.nest", page), "Nest a router at a path."),
+        ];
+        let store = test_store_with(&[("axum", rows)]).await;
+
+        let hits = store.search_similar_docs("axum", &Array1::from(vec![1.0, 0.0]), 10).await.unwrap();
+        let mut anchors: Vec<_> = hits.iter().map(|hit| doc_loader::doc_anchor(&hit.doc_path)).collect();
+        anchors.sort();
+        assert_eq!(anchors, [None, Some("method.nest"), Some("method.route"), Some("method.route")]);
+
+        let (path, content) = store.get_document_by_path("axum", "axum/struct.Router.html").await.unwrap().unwrap();
+        assert_eq!(path, page);
+        assert_eq!(content, "The router type.\nAdd another route\nto the router.\nNest a router at a path.");
+    }
+
+    #[tokio::test]
+    async fn repairs_stale_crate_stats() {
+        let rows = vec![
+            ("tokio/fn.spawn.html".to_string(), "Spawns a new task.".to_string(), Array1::from(vec![1.0, 0.0]), 5),
+            ("tokio/index.html".to_string(), "A runtime for async Rust.".to_string(), Array1::from(vec![0.0, 1.0]), 7),
+        ];
+        let store = test_store_with(&[("tokio", rows), ("serde", vec![])]).await;
+        // Deleting pages leaves the totals as they were
+        store.delete_doc_embeddings("tokio", &["tokio/fn.spawn.html".to_string()]).await.unwrap();
+        let totals = |stats: Vec<CrateStats>| stats.into_iter().map(|s| (s.name, s.total_docs, s.total_tokens)).collect::<Vec<_>>();
+        assert_eq!(totals(store.get_crate_stats().await.unwrap())[1], ("tokio".to_string(), 2, 12));
+
+        assert_eq!(store.repair_crate_stats().await.unwrap(), 1);
+        assert_eq!(
+            totals(store.get_crate_stats().await.unwrap()),
+            [("serde".to_string(), 0, 0), ("tokio".to_string(), 1, 7)]
+        );
+        assert_eq!(store.repair_crate_stats().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn global_stats_total_every_crate() {
+        let empty = test_store_with(&[]).await.global_stats().await.unwrap();
+        assert_eq!((empty.total_crates, empty.avg_docs_per_crate, empty.newest_update), (0, 0.0, None));
+
+        let row = |path: &str, dims: usize| (path.to_string(), "docs".to_string(), Array1::from(vec![0.5; dims]), 4);
+        let store = test_store_with(&[
+            ("tokio", vec![row("tokio/a.html", 2), row("tokio/b.html", 2)]),
+            ("serde", vec![row("serde/a.html", 3)]),
+        ])
+        .await;
+
+        let stats = store.global_stats().await.unwrap();
+        assert_eq!((stats.total_crates, stats.total_docs, stats.total_tokens), (2, 3, 12));
+        assert_eq!(stats.avg_docs_per_crate, 1.5);
+        assert_eq!(stats.embedding_dimensions, [2, 3]);
+        assert!(stats.oldest_update.is_some() && stats.oldest_update <= stats.newest_update);
+    }
+
+    #[tokio::test]
+    async fn module_prefix_scopes_search_to_submodules() {
+        let doc = |path: &str, embedding: &[f32]| test_doc(path, &format!("Documentation for {}", path), embedding);
+        let store = test_store_with(&[(
+            "tokio",
+            vec![
+                doc("tokio/latest/tokio/task/fn.spawn.html", &[1.0, 0.0]),
+                doc("tokio/latest/tokio/sync/struct.Mutex.html", &[0.8, 0.6]),
+                doc("tokio/latest/tokio/sync/mpsc/fn.channel.html", &[0.6, 0.8]),
+                doc("tokio/latest/tokio/synchronize/index.html", &[0.9, 0.44]),
+            ],
+        )])
+        .await;
+        let query = Array1::from(vec![1.0, 0.0]);
+
+        let all = store.search_similar_docs("tokio", &query, 4).await.unwrap();
+        assert_eq!(all[0].doc_path, "tokio/latest/tokio/task/fn.spawn.html");
+
+        let in_sync = DocFilter { module_prefix: Some("tokio::sync"), kind: None };
+        let in_sync = store.search_similar_docs_filtered("tokio", &query, 4, in_sync, None).await.unwrap();
+        let paths: Vec<&str> = in_sync.iter().map(|result| result.doc_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["tokio/latest/tokio/sync/struct.Mutex.html", "tokio/latest/tokio/sync/mpsc/fn.channel.html"]
+        );
+        assert_eq!(store.backfill_module_paths().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn kind_filter_keeps_one_kind_of_item() {
+        let doc = |path: &str, embedding: &[f32]| test_doc(path, &format!("Documentation for {}", path), embedding);
+        let store = test_store_with(&[(
+            "tokio",
+            vec![
+                doc("tokio/latest/tokio/io/struct.BufReader.html", &[1.0, 0.0]),
+                doc("tokio/latest/tokio/io/trait.AsyncRead.html", &[0.8, 0.6]),
+                doc("tokio/latest/tokio/net/trait.ToSocketAddrs.html", &[0.6, 0.8]),
+                doc("tokio/latest/tokio/io/index.html", &[0.9, 0.44]),
+            ],
+        )])
+        .await;
+        let query = Array1::from(vec![1.0, 0.0]);
+
+        let traits = DocFilter { module_prefix: None, kind: Some("trait") };
+        let hits = store.search_similar_docs_filtered("tokio", &query, 4, traits, None).await.unwrap();
+        let paths: Vec<&str> = hits.iter().map(|hit| hit.doc_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["tokio/latest/tokio/io/trait.AsyncRead.html", "tokio/latest/tokio/net/trait.ToSocketAddrs.html"]
+        );
+
+        // Both filters apply together
+        let io_traits = DocFilter { module_prefix: Some("tokio::io"), kind: Some("trait") };
+        let hits = store.search_similar_docs_filtered("tokio", &query, 4, io_traits, None).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(store.backfill_doc_kinds().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn retries_after_dropped_connection() {
+        let calls = AtomicU32::new(0);
+        let result = with_retry("search documents", || async {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(dropped_connection())
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = with_retry("get crate stats", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+
+        assert!(matches!(result, Err(ServerError::Database(msg)) if msg.starts_with("Failed to get crate stats")));
+        assert_eq!(calls.load(Ordering::SeqCst), DB_RETRY_ATTEMPTS);
+    }
+
+    #[test]
+    fn classifies_sqlstates_for_retries_and_timeouts() {
+        for code in ["08000", "08006", "57P01", "57P03"] {
+            assert_eq!(classify_sqlstate(code), SqlStateClass::ConnectionLost, "{}", code);
+        }
+        assert_eq!(classify_sqlstate("57014"), SqlStateClass::StatementTimeout);
+        for code in ["23505", "42P01", "57P04", "XX000"] {
+            assert_eq!(classify_sqlstate(code), SqlStateClass::Other, "{}", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_query_errors() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = with_retry("count crate documents", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(sqlx::Error::ColumnNotFound("count".to_string()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_reconnects_after_connections_are_dropped() {
+        // A file-backed pool whose only connection is closed underneath it must hand out
+        // a fresh connection on the retry
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("retry.db").display());
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&url)
+            .await
+            .unwrap();
+
+        let calls = AtomicU32::new(0);
+        let result = with_retry("count crate documents", || async {
+            let mut conn = pool.acquire().await?;
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                // Simulate the server dropping the connection mid-query
+                conn.detach();
+                return Err(dropped_connection());
+            }
+            sqlx::query_scalar::<_, i64>("SELECT 1").fetch_one(&mut *conn).await
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn health_check_needs_a_ready_crate() {
+        let store = test_store_with(&[]).await;
+        let err = check_health(store.as_ref()).await.unwrap_err();
+        assert!(err.to_string().contains("no ready crates"));
+
+        store.upsert_crate("tokio", None).await.unwrap();
+        store.set_crate_status("tokio", CrateStatus::Populating).await.unwrap();
+        assert!(check_health(store.as_ref()).await.is_err());
+
+        store.set_crate_status("tokio", CrateStatus::Ready).await.unwrap();
+        assert_eq!(check_health(store.as_ref()).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn populating_status_left_by_a_killed_run_counts_as_failed() {
+        let store = test_store_with(&[]).await;
+        store.upsert_crate("tokio", None).await.unwrap();
+        store.set_crate_status("tokio", CrateStatus::Populating).await.unwrap();
+        assert_eq!(store.crate_status("tokio").await.unwrap(), Some(CrateStatus::Populating));
+
+        let now = chrono::Utc::now().naive_utc();
+        let stale = now - chrono::Duration::from_std(POPULATING_STALE_AFTER).unwrap() - chrono::Duration::seconds(1);
+        assert_eq!(CrateStatus::from_db("populating", Some(stale)), CrateStatus::Failed);
+        // Rows from before the time was recorded, and times ahead of this host's clock
+        assert_eq!(CrateStatus::from_db("populating", None), CrateStatus::Failed);
+        assert_eq!(CrateStatus::from_db("populating", Some(now + chrono::Duration::minutes(5))), CrateStatus::Populating);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{database::VectorStore, error::ServerError};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Hex-encoded sha256 of a document's or chunk's content
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Content hashes already stored for a crate, mapped to the path that holds them
+pub async fn existing_content_hashes(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+) -> Result<HashMap<String, String>, ServerError> {
+    Ok(store
+        .get_content_hashes(crate_name)
+        .await?
+        .into_iter()
+        .map(|(path, hash)| (hash, path))
+        .collect())
+}
+
+/// Drop items whose content duplicates another item, or a row already stored under a
+/// different path. Re-exports and glob imports put the same docblock under several paths;
+/// the stored path wins, otherwise the shortest path (usually the public re-export) is kept.
+/// Items keep their original order. Returns the kept items and how many were skipped.
+pub fn dedupe_by_content<T>(
+    items: Vec<T>,
+    path_and_content: impl Fn(&T) -> (&str, &str),
+    existing: &HashMap<String, String>,
+) -> (Vec<T>, usize) {
+    let hashes: Vec<String> = items
+        .iter()
+        .map(|item| content_hash(path_and_content(item).1))
+        .collect();
+
+    // Index of the item kept for each hash that isn't already stored
+    let mut canonical: HashMap<&str, usize> = HashMap::new();
+    for (i, hash) in hashes.iter().enumerate() {
+        if existing.contains_key(hash) {
+            continue;
+        }
+        let path_len = path_and_content(&items[i]).0.len();
+        canonical
+            .entry(hash.as_str())
+            .and_modify(|best| {
+                if path_len < path_and_content(&items[*best]).0.len() {
+                    *best = i;
+                }
+            })
+            .or_insert(i);
+    }
+
+    let keep: Vec<bool> = hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| match existing.get(hash) {
+            // Same path as the stored row: an update, not a duplicate
+            Some(stored_path) => stored_path == path_and_content(&items[i]).0,
+            None => canonical.get(hash.as_str()) == Some(&i),
+        })
+        .collect();
+
+    let total = items.len();
+    let kept: Vec<T> = items
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(item, keep)| keep.then_some(item))
+        .collect();
+    let skipped = total - kept.len();
+    (kept, skipped)
+}
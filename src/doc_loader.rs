@@ -1,9 +1,18 @@
-use scraper::{Html, Selector};
+use crate::{
+    embeddings::{cosine_similarity, EmbeddingProvider},
+    error::ServerError,
+    robots::{self, RobotsRules},
+};
+use ndarray::Array1;
+use scraper::{ElementRef, Html, Selector};
 use thiserror::Error;
 use reqwest;
 use tokio;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Error)]
 pub enum DocLoaderError {
@@ -17,13 +26,48 @@ pub enum DocLoaderError {
     Network(String),
     #[error("Rate limited: {0}")]
     RateLimited(String),
+    #[error("docs.rs failed to build documentation for {0}")]
+    BuildFailed(String),
+    #[error("docs.rs has no documentation for {0}")]
+    NoDocumentation(String),
+    #[error("Unknown features: {0}")]
+    UnknownFeatures(String),
+    #[error("robots.txt disallows crawling {0}")]
+    Disallowed(String),
+}
+
+/// Text docs.rs shows on a crate page whose rustdoc build failed
+const BUILD_FAILED_MARKER: &str = "docs.rs failed to build";
+
+/// Check the first page of a crawl for docs.rs's failed-build page, or a redirect
+/// from the rustdoc URL to the crate overview (`/crate/<name>/<version>`), which
+/// docs.rs does when there is no documentation to show (e.g. binary-only crates).
+pub fn check_docs_available(crate_name: &str, final_url: &str, html: &str) -> Result<(), DocLoaderError> {
+    if html.contains(BUILD_FAILED_MARKER) {
+        // The marker is followed by the release that failed, e.g. "docs.rs failed to build foo-0.1.0"
+        let text = Html::parse_document(html).root_element().text().collect::<Vec<_>>().join(" ");
+        let release = text
+            .split(BUILD_FAILED_MARKER)
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap_or(crate_name);
+        return Err(DocLoaderError::BuildFailed(release.to_string()));
+    }
+    if final_url.starts_with("https://docs.rs/crate/") {
+        return Err(DocLoaderError::NoDocumentation(format!("{} (redirected to {})", crate_name, final_url)));
+    }
+    Ok(())
 }
 
 // Simple struct to hold document content
 #[derive(Debug, Clone)]
 pub struct Document {
+    /// Stored path; sections of a page end in `#{anchor}`
     pub path: String,
     pub content: String,
+    /// HTML anchor of the page section this document holds (e.g. `method.route`),
+    /// `None` for the page's top-level docs
+    pub anchor: Option<String>,
 }
 
 // Result struct that includes version information
@@ -31,39 +75,888 @@ pub struct Document {
 pub struct LoadResult {
     pub documents: Vec<Document>,
     pub version: Option<String>,
+    /// Pages dropped by the size limits, with the reason
+    pub skipped_pages: Vec<SkippedPage>,
+    /// Paths of pages cut down to `max_content_chars` or `max_page_tokens`
+    pub truncated_pages: Vec<String>,
+    /// Requested features, checked against the crate's feature list on docs.rs
+    pub features: Vec<String>,
+    /// Links not queued because the page they were found on repeated earlier pages
+    pub pruned_links: usize,
+    /// Tokens the redundancy check embedded, on top of the documents embedded afterwards
+    pub redundancy_tokens: usize,
+    /// Queued pages not fetched because the site's robots.txt disallows them
+    pub disallowed_pages: usize,
+}
+
+/// A page left out of the load by `PageLimits`
+#[derive(Debug, Clone)]
+pub struct SkippedPage {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Default cap on a single page, in cl100k_base tokens
+pub const DEFAULT_MAX_PAGE_TOKENS: usize = 20_000;
+/// Default cap on a single page's extracted text, in characters, checked before tokenizing
+pub const DEFAULT_MAX_CONTENT_CHARS: usize = 200_000;
+/// Default floor below which a page is considered empty
+pub const DEFAULT_MIN_PAGE_CHARS: usize = 50;
+/// Invisible characters left in rustdoc HTML that only add noise to embeddings
+const ZERO_WIDTH_CHARS: &[char] = &['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Clean extracted page text before it is stored or embedded: NFC normalization, no
+/// zero-width characters, whitespace runs within a line collapsed to one space, and at
+/// most one blank line between blocks. Line breaks are kept so code stays line-based.
+pub fn normalize_text(text: &str) -> String {
+    let text: String = text.nfc().filter(|c| !ZERO_WIDTH_CHARS.contains(c)).collect();
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        normalized.push_str(&line);
+        blank_lines = 0;
+    }
+    normalized
+}
+
+/// Elements whose text is extracted from each rustdoc page
+pub const DEFAULT_CONTENT_SELECTOR: &str = "div.docblock, section.docblock, .rustdoc .docblock";
+
+/// Parse a content selector override, or the default one when `None`
+pub fn parse_content_selector(selector: Option<&str>) -> Result<Selector, DocLoaderError> {
+    let selector = selector.unwrap_or(DEFAULT_CONTENT_SELECTOR);
+    Selector::parse(selector)
+        .map_err(|e| DocLoaderError::Selector(format!("invalid content selector '{}': {}", selector, e)))
+}
+
+/// Size limits applied to each extracted page before it is kept.
+/// Giant auto-generated pages (huge enums, trait impl lists) otherwise dominate the
+/// database and the embedding bill, and near-empty pages add nothing to search.
+#[derive(Debug, Clone)]
+pub struct PageLimits {
+    /// Text beyond this many characters is dropped before the token cap is checked, so
+    /// multi-megabyte pages are never tokenized whole; `None` disables the cap
+    pub max_content_chars: Option<usize>,
+    /// Pages above this many tokens are truncated, or skipped with `skip_oversized`; `None` disables the cap
+    pub max_page_tokens: Option<usize>,
+    /// Pages with fewer characters of extracted text are skipped
+    pub min_page_chars: usize,
+    /// Skip oversized pages instead of truncating them
+    pub skip_oversized: bool,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self {
+            max_content_chars: Some(DEFAULT_MAX_CONTENT_CHARS),
+            max_page_tokens: Some(DEFAULT_MAX_PAGE_TOKENS),
+            min_page_chars: DEFAULT_MIN_PAGE_CHARS,
+            skip_oversized: false,
+        }
+    }
+}
+
+/// What `PageLimits::apply` decided for a page
+#[derive(Debug, PartialEq)]
+pub enum PageOutcome {
+    Keep(String),
+    /// Content cut down to the cap, with the page's original token count
+    Truncated(String, usize),
+    /// Reason the page was skipped
+    Skip(String),
+}
+
+impl PageLimits {
+    /// Cut `content` to `max_content_chars` on a char boundary, ending it with a note of what
+    /// was left out. Returns the content and how many characters were dropped.
+    pub fn clip_content(&self, content: String) -> (String, usize) {
+        let Some(max_chars) = self.max_content_chars else {
+            return (content, 0);
+        };
+        let Some((end, _)) = content.char_indices().nth(max_chars) else {
+            return (content, 0);
+        };
+        let dropped = content[end..].chars().count();
+        let mut clipped = content;
+        clipped.truncate(end);
+        clipped.push_str(&format!("\n\n[Truncated: {} more characters not stored]", dropped));
+        (clipped, dropped)
+    }
+
+    /// Apply the limits to a page's extracted content
+    pub fn apply(&self, content: String) -> PageOutcome {
+        let chars = content.chars().count();
+        if chars < self.min_page_chars {
+            return PageOutcome::Skip(format!(
+                "only {} chars (min {})",
+                chars, self.min_page_chars
+            ));
+        }
+
+        let max_tokens = match self.max_page_tokens {
+            // Every token covers at least one byte, so short pages can't exceed the cap
+            Some(max_tokens) if content.len() > max_tokens => max_tokens,
+            _ => return PageOutcome::Keep(content),
+        };
+
+        let bpe = tiktoken_rs::cl100k_base_singleton();
+        let tokens = bpe.encode_with_special_tokens(&content);
+        if tokens.len() <= max_tokens {
+            return PageOutcome::Keep(content);
+        }
+        if self.skip_oversized {
+            return PageOutcome::Skip(format!(
+                "{} tokens (max {})",
+                tokens.len(),
+                max_tokens
+            ));
+        }
+
+        // A cut can land inside a multi-byte character; back off until it decodes
+        let mut end = max_tokens;
+        while end > 0 {
+            if let Ok(text) = bpe.decode(tokens[..end].to_vec()) {
+                return PageOutcome::Truncated(text, tokens.len());
+            }
+            end -= 1;
+        }
+        PageOutcome::Skip(format!("{} tokens could not be truncated", tokens.len()))
+    }
+}
+
+/// Strip the ` [chunk x/y]` suffix that chunking appends to a document path
+pub fn strip_chunk_suffix(doc_path: &str) -> &str {
+    match doc_path.rfind(" [chunk ") {
+        Some(idx) if doc_path.ends_with(']') => &doc_path[..idx],
+        _ => doc_path,
+    }
+}
+
+/// The section anchor of a stored `doc_path` (`method.route` for
+/// `axum/latest/axum/struct.Router.html#method.route [chunk 1/2]`)
+pub fn doc_anchor(doc_path: &str) -> Option<&str> {
+    strip_chunk_suffix(doc_path).split_once('#').map(|(_, anchor)| anchor).filter(|anchor| !anchor.is_empty())
+}
+
+/// The page a stored `doc_path` belongs to, without chunk suffix or section anchor
+pub fn page_path(doc_path: &str) -> &str {
+    let path = strip_chunk_suffix(doc_path);
+    path.split_once('#').map_or(path, |(page, _)| page)
+}
+
+/// Anchor of the rustdoc section a docblock documents: the `id` of the heading right before
+/// it (`<section id="variant.Foo">`, `<span id="structfield.x">`), or of the heading inside
+/// the `<summary>` of a collapsible method (`<summary><section id="method.route">`)
+pub fn section_anchor(docblock: ElementRef) -> Option<String> {
+    let heading = docblock.prev_siblings().find_map(ElementRef::wrap)?;
+    if let Some(id) = heading.value().id() {
+        return Some(id.to_string());
+    }
+    if heading.value().name() != "summary" {
+        return None;
+    }
+    heading
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find_map(|element| element.value().id())
+        .map(str::to_string)
+}
+
+/// Sort key that keeps a page's chunks in order (`[chunk 2/12]` before `[chunk 10/12]`)
+pub fn chunk_order(doc_path: &str) -> (&str, usize) {
+    let page = strip_chunk_suffix(doc_path);
+    let index = doc_path[page.len()..]
+        .trim_start_matches(" [chunk ")
+        .split('/')
+        .next()
+        .and_then(|index| index.parse().ok())
+        .unwrap_or(0);
+    (page, index)
+}
+
+/// rustdoc file prefixes of item pages (`struct.Mutex.html`), most common first
+const ITEM_KINDS: &[&str] = &[
+    "struct", "enum", "trait", "fn", "macro", "type", "constant", "static", "union",
+    "derive", "attr", "primitive", "keyword", "traitalias",
+];
+
+/// rustdoc pages an item path such as `tokio::sync::Mutex` may be documented on,
+/// relative to the docs root: one per item kind, then the index page in case it is a module
+pub fn item_doc_paths(item_path: &str) -> Vec<String> {
+    let mut segments: Vec<String> = item_path
+        .split("::")
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    let Some(first) = segments.first_mut() else {
+        return Vec::new();
+    };
+    *first = first.replace('-', "_");
+    if segments.len() == 1 {
+        return vec![format!("{}/index.html", segments[0])];
+    }
+
+    let name = segments.pop().unwrap_or_default();
+    let dir = segments.join("/");
+    ITEM_KINDS
+        .iter()
+        .map(|kind| format!("{}/{}.{}.html", dir, kind, name))
+        .chain(std::iter::once(format!("{}/{}/index.html", dir, name)))
+        .collect()
+}
+
+/// A rustdoc path such as `sync/struct.Mutex.html` or `tokio/sync/struct.Mutex.html`,
+/// made relative to the docs root (starting with the crate's directory)
+pub fn relative_doc_path(crate_name: &str, doc_path: &str) -> String {
+    let (crate_name, _) = split_crate_variant(crate_name);
+    let ident = crate_name.replace('-', "_");
+    let path = doc_path.trim().trim_start_matches('/');
+    if path.starts_with(&format!("{}/", ident)) {
+        path.to_string()
+    } else {
+        format!("{}/{}", ident, path)
+    }
+}
+
+/// The item a stored `doc_path` documents, e.g. `tokio::sync::Mutex` for
+/// `tokio/latest/tokio/sync/struct.Mutex.html`, the module for an `index.html`, or the
+/// member for a section (`axum::Router::route` for `.../struct.Router.html#method.route`)
+pub fn doc_item_path(crate_name: &str, doc_path: &str) -> Option<String> {
+    let module = module_path(crate_name, doc_path)?;
+    let file = page_path(doc_path).rsplit('/').next()?;
+    if file == "index.html" {
+        return Some(module);
+    }
+    let (_, name) = file.strip_suffix(".html")?.split_once('.')?;
+    // Sections of an item page document its members (`method.route`)
+    match doc_anchor(doc_path).and_then(|anchor| anchor.split_once('.')) {
+        Some((_, member)) => Some(format!("{}::{}::{}", module, name, member)),
+        None => Some(format!("{}::{}", module, name)),
+    }
+}
+
+/// Separates a crate from its features in the name of a feature variant
+pub const VARIANT_SEPARATOR: char = '+';
+
+/// Name a crate's docs are stored under when populated as a feature variant, e.g.
+/// `serde+derive+rc`, so it doesn't replace the crate's default-feature docs.
+/// `+` can't appear in crate names, so the name splits back unambiguously.
+pub fn crate_variant_name(crate_name: &str, features: &[String]) -> String {
+    let mut features = features.to_vec();
+    features.sort();
+    features.dedup();
+    std::iter::once(crate_name.to_string())
+        .chain(features)
+        .collect::<Vec<_>>()
+        .join(&VARIANT_SEPARATOR.to_string())
+}
+
+/// Separates a crate (or feature variant) from the release its docs were loaded from
+pub const VERSION_SEPARATOR: char = '@';
+
+/// Name a specific release's docs are stored under, e.g. `tokio@1.38.0`, so several
+/// versions of a crate can be kept side by side (and compared)
+pub fn crate_version_name(name: &str, version: &str) -> String {
+    format!("{}{}{}", name, VERSION_SEPARATOR, version)
+}
+
+/// The release pinned in a stored crate name, if any (`1.38.0` for `tokio@1.38.0`).
+/// The version comes last since semver build metadata can contain `+`.
+pub fn stored_crate_version(name: &str) -> Option<&str> {
+    name.split_once(VERSION_SEPARATOR).map(|(_, version)| version)
+}
+
+/// The crate and features of a stored crate name (no features for a plain crate name)
+pub fn split_crate_variant(name: &str) -> (&str, Vec<String>) {
+    let name = name.split_once(VERSION_SEPARATOR).map_or(name, |(name, _)| name);
+    let mut parts = name.split(VARIANT_SEPARATOR);
+    let crate_name = parts.next().unwrap_or(name);
+    (crate_name, parts.map(str::to_string).collect())
+}
+
+/// Rust module a stored `doc_path` documents, e.g. `tokio::sync` for
+/// `tokio/latest/tokio/sync/struct.Mutex.html` (and for `tokio/latest/tokio/sync/index.html`).
+/// Full URLs from self-hosted sites are matched on the crate's directory in rustdoc's output.
+pub fn module_path(crate_name: &str, doc_path: &str) -> Option<String> {
+    let (crate_name, _) = split_crate_variant(crate_name);
+    let path = strip_chunk_suffix(doc_path);
+    let path = match reqwest::Url::parse(path) {
+        Ok(url) if url.has_host() => url.path().to_string(),
+        _ => path.to_string(),
+    };
+    let mut dirs: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    dirs.pop(); // the page itself
+    // docs.rs paths start with `{crate}/{version}/`
+    let skip = if path.starts_with(&format!("{}/", crate_name)) { 2 } else { 0 };
+
+    let ident = crate_name.replace('-', "_");
+    let dirs = dirs.get(skip..)?;
+    let root = dirs.iter().position(|dir| *dir == ident)?;
+    Some(dirs[root..].join("::"))
+}
+
+/// Kinds of rustdoc pages `doc_kind` recognises, as stored in `doc_embeddings.doc_kind`
+pub const DOC_KINDS: [&str; 7] = ["struct", "trait", "fn", "enum", "macro", "mod", "type"];
+
+/// Kind of item a stored `doc_path` documents, from rustdoc's file name prefix
+/// (`trait` for `tokio/latest/tokio/io/trait.AsyncRead.html`, `mod` for a module's
+/// `index.html`). Other pages (constants, unions, settings) have none.
+pub fn doc_kind(crate_name: &str, doc_path: &str) -> Option<&'static str> {
+    let file = page_path(doc_path).rsplit('/').next()?;
+    if file == "index.html" {
+        return module_path(crate_name, doc_path).map(|_| "mod");
+    }
+    let (prefix, _) = file.strip_suffix(".html")?.split_once('.')?;
+    DOC_KINDS.into_iter().find(|kind| *kind == prefix)
+}
+
+/// Rebuild the docs.rs URL for a stored `doc_path` (e.g. `axum/latest/axum/struct.Router.html`)
+pub fn docs_rs_url(doc_path: &str) -> String {
+    let path = strip_chunk_suffix(doc_path);
+    if path.starts_with("https://") || path.starts_with("http://") {
+        path.to_string()
+    } else {
+        format!("https://docs.rs/{}", path.trim_start_matches('/'))
+    }
+}
+
+/// Unique docs.rs URLs for a list of document paths, keeping their order
+pub fn source_urls<'a>(doc_paths: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for path in doc_paths {
+        let url = docs_rs_url(path);
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Identifies the crawler to docs.rs, so its operators can see who is fetching pages
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "rustdocs-mcp-server/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/5dlabs/rust-docs-mcp-server)"
+);
+
+/// User-Agent for crawl requests: `MCPDOCS_USER_AGENT`, or [`DEFAULT_USER_AGENT`]
+pub fn user_agent() -> String {
+    std::env::var("MCPDOCS_USER_AGENT")
+        .ok()
+        .filter(|agent| !agent.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string())
+}
+
+/// HTTP client for fetching rustdoc pages, sending [`user_agent`]. Responses may be gzip or
+/// deflate compressed, and connections are kept alive, so build one per run and pass it to
+/// every [`load_documents_from_docs_rs_with_limits`] call.
+pub fn crawler_client() -> Result<reqwest::Client, DocLoaderError> {
+    reqwest::Client::builder()
+        .user_agent(user_agent())
+        .timeout(Duration::from_secs(30))
+        .gzip(true)
+        .deflate(true)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .build()
+        .map_err(|e| DocLoaderError::Network(e.to_string()))
+}
+
+/// Longest `Retry-After` the crawler will wait for on a 429
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Parse a `Retry-After` header, given either in seconds or as an HTTP-date
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past means "retry now"
+    Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// How long to wait after a 429: the `backoff` delay, or longer if the server asked for it
+/// with `Retry-After` (capped at [`MAX_RETRY_AFTER`])
+pub fn rate_limit_wait(headers: &reqwest::header::HeaderMap, backoff: Duration) -> Duration {
+    retry_after(headers).map_or(backoff, |requested| requested.min(MAX_RETRY_AFTER).max(backoff))
+}
+
+/// docs.rs segment for a version requirement: a concrete version, or `latest` for `*`
+fn docs_rs_version(version: &str) -> &str {
+    match version.trim() {
+        "" | "*" => "latest",
+        version => version,
+    }
+}
+
+/// The release in a docs.rs URL (`https://docs.rs/tokio/1.38.0/tokio/` gives `1.38.0`),
+/// or `None` for `latest`, version requirements and other hosts
+pub fn version_from_docs_rs_url(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    if url.host_str() != Some("docs.rs") {
+        return None;
+    }
+    let version = url.path_segments()?.nth(1)?;
+    is_release_version(version).then(|| version.to_string())
+}
+
+/// Whether `text` reads as a release number (`1.38.0`, `0.1.5-alpha.2`, `1.0.0+build.1`)
+fn is_release_version(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit())
+        && text.contains('.')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+}
+
+/// The release named in a version label: `1.38.0`, `Version 1.0.197`, `v2.0.0`, or docs.rs's
+/// `crate-name-1.2.3` (where the version starts after the first `-` followed by a release)
+fn release_in_label(label: &str) -> Option<String> {
+    label.split_whitespace().rev().find_map(|token| {
+        let token = token.trim_start_matches(['v', 'V']);
+        if is_release_version(token) {
+            return Some(token.to_string());
+        }
+        token
+            .match_indices('-')
+            .map(|(i, _)| &token[i + 1..])
+            .find(|version| is_release_version(version))
+            .map(str::to_string)
+    })
+}
+
+/// The release a rustdoc page documents: from a docs.rs release URL when `url` is one, else
+/// from the page, in rustdoc's sidebar `.version` or docs.rs's crate menu (`tokio-1.38.0`)
+pub fn extract_version(html: &str, url: &str) -> Option<String> {
+    if let Some(version) = version_from_docs_rs_url(url) {
+        return Some(version);
+    }
+    let document = Html::parse_document(html);
+    [".version", "a.crate-name"].iter().find_map(|selector| {
+        let selector = Selector::parse(selector).ok()?;
+        document
+            .select(&selector)
+            .find_map(|element| release_in_label(&element.text().collect::<String>()))
+    })
+}
+
+/// The rustdoc root of one release of a crate on docs.rs (`latest` or `*` for the newest)
+pub fn docs_rs_start_url(crate_name: &str, version: &str) -> Result<reqwest::Url, DocLoaderError> {
+    reqwest::Url::parse(&format!("https://docs.rs/{}/{}/{}/", crate_name, docs_rs_version(version), crate_name))
+        .map_err(|e| DocLoaderError::Parsing(format!("invalid docs.rs URL for {} {}: {}", crate_name, version, e)))
+}
+
+/// Where a crawl starts: `base_url` (e.g. a self-hosted rustdoc site such as
+/// `https://docs.mycorp.internal/mycrate/`) or the crate's docs.rs page
+pub fn crawl_start_url(crate_name: &str, base_url: Option<&str>) -> Result<reqwest::Url, DocLoaderError> {
+    let Some(base_url) = base_url else {
+        return docs_rs_start_url(crate_name, "latest");
+    };
+
+    let mut url = reqwest::Url::parse(base_url.trim())
+        .map_err(|e| DocLoaderError::Parsing(format!("invalid base URL '{}': {}", base_url, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(DocLoaderError::Parsing(format!("base URL must be an http(s) URL: {}", base_url)));
+    }
+    // `.../mycrate` means the directory, so relative links resolve inside it
+    let last_segment = url.path().rsplit('/').next().unwrap_or_default();
+    if !last_segment.is_empty() && !last_segment.contains('.') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.set_fragment(None);
+    Ok(url)
+}
+
+/// Whether a link stays on the start URL's host and under its directory
+pub fn in_crawl_scope(start: &reqwest::Url, url: &reqwest::Url) -> bool {
+    let prefix = &start.path()[..start.path().rfind('/').map_or(0, |i| i + 1)];
+    url.scheme() == start.scheme()
+        && url.host_str() == start.host_str()
+        && url.port_or_known_default() == start.port_or_known_default()
+        && url.path().starts_with(prefix)
+}
+
+/// rustdoc's index of every public item, next to the crate root page
+pub const ALL_ITEMS_PAGE: &str = "all.html";
+
+/// Item pages linked from rustdoc's `all.html`, resolved against `all_items_url`
+/// (where the index was served from) and limited to the crawl scope of `start`
+pub fn all_items_links(all_items_url: &reqwest::Url, start: &reqwest::Url, html: &str) -> Vec<reqwest::Url> {
+    // Current rustdoc lists items in `ul.all-items`, older versions in `ul.docblock`
+    let selector = Selector::parse(".all-items a[href], ul.docblock a[href]").unwrap();
+    let mut seen = HashSet::new();
+    Html::parse_document(html)
+        .select(&selector)
+        .filter_map(|link| link.value().attr("href"))
+        .filter(|href| !href.starts_with('#') && href.ends_with(".html"))
+        .filter_map(|href| all_items_url.join(href).ok())
+        .filter(|url| in_crawl_scope(start, url) && seen.insert(url.to_string()))
+        .collect()
+}
+
+/// Fetch `all.html` for the crawl starting at `start`, or nothing if the site has none
+pub async fn fetch_all_items(client: &reqwest::Client, start: &reqwest::Url) -> Vec<reqwest::Url> {
+    let Ok(url) = start.join(ALL_ITEMS_PAGE) else {
+        return Vec::new();
+    };
+    // No retries: a missing index is expected for non-rustdoc sites and old builds
+    match fetch_with_retry(client, url.as_str(), 0).await {
+        Ok((final_url, html)) => {
+            let final_url = reqwest::Url::parse(&final_url).unwrap_or(url);
+            all_items_links(&final_url, start, &html)
+        }
+        Err(e) => {
+            debug!("No {} index at {} ({}), following links only", ALL_ITEMS_PAGE, url, e);
+            Vec::new()
+        }
+    }
+}
+
+/// docs.rs page listing the feature flags of a release of a crate
+pub fn docs_rs_features_url(crate_name: &str, version: &str) -> String {
+    format!("https://docs.rs/crate/{}/{}/features", crate_name, docs_rs_version(version))
+}
+
+/// Feature names from a docs.rs features page, where each feature is an `h3` with its name as id
+pub fn parse_docs_rs_features(html: &str) -> Vec<String> {
+    let selector = Selector::parse("h3[id]").unwrap();
+    Html::parse_document(html)
+        .select(&selector)
+        .filter_map(|heading| heading.value().attr("id"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// crates.io API listing the dependencies of one release of a crate
+pub fn crates_io_dependencies_url(crate_name: &str, version: &str) -> String {
+    format!("https://crates.io/api/v1/crates/{}/{}/dependencies", crate_name, version)
+}
+
+#[derive(serde::Deserialize)]
+struct CratesIoDependencies {
+    dependencies: Vec<CratesIoDependency>,
+}
+
+#[derive(serde::Deserialize)]
+struct CratesIoDependency {
+    crate_id: String,
+    kind: String,
+}
+
+/// Names of the direct normal dependencies (optional ones included, dev and build ones not)
+/// in a crates.io dependencies response, sorted and deduplicated
+pub fn parse_crates_io_dependencies(json: &str) -> Result<Vec<String>, DocLoaderError> {
+    let response: CratesIoDependencies = serde_json::from_str(json)
+        .map_err(|e| DocLoaderError::Parsing(format!("Invalid crates.io dependencies response: {}", e)))?;
+    let names: BTreeSet<String> = response
+        .dependencies
+        .into_iter()
+        .filter(|dependency| dependency.kind == "normal")
+        .map(|dependency| dependency.crate_id)
+        .collect();
+    Ok(names.into_iter().collect())
+}
+
+/// Direct dependencies of a release (an exact version, as detected by the crawl) from crates.io
+pub async fn fetch_crate_dependencies(
+    client: &reqwest::Client,
+    crate_name: &str,
+    version: &str,
+) -> Result<Vec<String>, DocLoaderError> {
+    let (_, json) = fetch_with_retry(client, &crates_io_dependencies_url(crate_name, version), 1).await?;
+    parse_crates_io_dependencies(&json)
+}
+
+/// Crates the crates.io API returns per page of a listing (its maximum)
+const CRATES_IO_PAGE_SIZE: usize = 100;
+
+/// crates.io API listing crates by downloads in the last 90 days, most downloaded first
+pub fn crates_io_top_crates_url(page: usize, per_page: usize) -> String {
+    format!("https://crates.io/api/v1/crates?sort=recent-downloads&page={}&per_page={}", page, per_page)
+}
+
+#[derive(serde::Deserialize)]
+struct CratesIoListing {
+    crates: Vec<CratesIoCrate>,
+}
+
+#[derive(serde::Deserialize)]
+struct CratesIoCrate {
+    name: String,
+}
+
+/// Crate names in a crates.io listing response, in the listing's order
+pub fn parse_crates_io_listing(json: &str) -> Result<Vec<String>, DocLoaderError> {
+    let response: CratesIoListing = serde_json::from_str(json)
+        .map_err(|e| DocLoaderError::Parsing(format!("Invalid crates.io crates response: {}", e)))?;
+    Ok(response.crates.into_iter().map(|krate| krate.name).collect())
+}
+
+/// The `limit` crates with the most recent downloads on crates.io, most downloaded first
+pub async fn fetch_top_crates(client: &reqwest::Client, limit: usize) -> Result<Vec<String>, DocLoaderError> {
+    let mut names = Vec::new();
+    let mut page = 1;
+    while names.len() < limit {
+        let per_page = (limit - names.len()).min(CRATES_IO_PAGE_SIZE);
+        let (_, json) = fetch_with_retry(client, &crates_io_top_crates_url(page, per_page), 1).await?;
+        let listed = parse_crates_io_listing(&json)?;
+        if listed.is_empty() {
+            break;
+        }
+        names.extend(listed);
+        page += 1;
+    }
+    names.truncate(limit);
+    Ok(names)
+}
+
+/// rustdoc's "Available on crate feature `x` only" notes, anywhere on a page
+const FEATURE_NOTES: &str = ".stab.portability";
+/// The note of the page's own item, rather than of the items it lists
+const ITEM_FEATURE_NOTES: &str = "#main-content > .item-info .stab.portability";
+
+/// Features named by the `notes` (a selector of rustdoc portability notes) on a page
+pub fn feature_gates(document: &Html, notes: &str) -> BTreeSet<String> {
+    let selector = Selector::parse(notes).unwrap();
+    let code = Selector::parse("code").unwrap();
+    document
+        .select(&selector)
+        // Item lists only show the feature name, with the full note as the title
+        .filter(|note| {
+            note.value().attr("title").is_some_and(|title| title.contains("feature"))
+                || note.text().any(|text| text.contains("feature"))
+        })
+        .flat_map(|note| note.select(&code).map(|name| name.text().collect::<String>()))
+        .collect()
+}
+
+/// Check `features` against the crate's docs.rs features page. docs.rs builds each
+/// release once, with the features from its `[package.metadata.docs.rs]`, so features
+/// can't be switched on per request; this only rejects names the crate doesn't have.
+async fn check_features(
+    client: &reqwest::Client,
+    crate_name: &str,
+    version: &str,
+    features: &[String],
+) -> Result<(), DocLoaderError> {
+    let url = docs_rs_features_url(crate_name, version);
+    let known = match fetch_with_retry(client, &url, 1).await {
+        Ok((_, html)) => parse_docs_rs_features(&html),
+        Err(e) => {
+            warn!("Could not fetch {} ({}), not checking features", url, e);
+            return Ok(());
+        }
+    };
+    if known.is_empty() {
+        return Ok(());
+    }
+    let unknown: Vec<&str> = features
+        .iter()
+        .filter(|feature| !known.contains(feature))
+        .map(String::as_str)
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(DocLoaderError::UnknownFeatures(format!(
+        "{} has no feature(s) {} (available: {})",
+        crate_name,
+        unknown.join(", "),
+        known.join(", ")
+    )))
+}
+
+/// Pages crawled when no page count is given
+pub const DEFAULT_MAX_PAGES: usize = 200;
+
+/// How far a crawl goes from the crate root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlLimit {
+    /// Stop after this many pages, following links only for the first 75% of them
+    Pages(usize),
+    /// Follow links up to this many hops from the crate root, regardless of page count
+    Depth(usize),
+}
+
+impl CrawlLimit {
+    /// `max_depth` takes precedence over `max_pages` when both are given
+    pub fn new(max_pages: Option<usize>, max_depth: Option<usize>) -> Self {
+        match max_depth {
+            Some(depth) => CrawlLimit::Depth(depth),
+            None => CrawlLimit::Pages(max_pages.unwrap_or(DEFAULT_MAX_PAGES)),
+        }
+    }
+
+    /// Whether the crawl stops before processing another page
+    fn reached(&self, processed: usize) -> bool {
+        matches!(self, CrawlLimit::Pages(max_pages) if processed >= *max_pages)
+    }
+
+    /// Whether links found on a page at `depth` are queued
+    fn follows_links(&self, processed: usize, depth: usize) -> bool {
+        match self {
+            CrawlLimit::Pages(max_pages) => processed < max_pages * 3 / 4,
+            CrawlLimit::Depth(max_depth) => depth < *max_depth,
+        }
+    }
+}
+
+impl std::fmt::Display for CrawlLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CrawlLimit::Pages(max_pages) => write!(f, "max {} pages", max_pages),
+            CrawlLimit::Depth(max_depth) => write!(f, "max depth {}", max_depth),
+        }
+    }
+}
+
+/// Characters of a page's text embedded to judge whether it repeats earlier pages
+const REDUNDANCY_SAMPLE_CHARS: usize = 4_000;
+
+/// Opt-in crawl pruning: each page linking to pages not yet visited or queued is embedded, and
+/// when it is at least `threshold` cosine-similar to a page already embedded in the same crawl,
+/// those links are not queued. Bounds crawls of crates with thousands of near-identical pages,
+/// at the cost of one embedding request per such page and of pages only reachable through its
+/// links. Pages whose links are all queued already (most of them, once the crawl is seeded
+/// from `all.html`) are neither embedded nor compared against later.
+/// Documents are only chunked and embedded once the crawl is over, so there are no page
+/// embeddings to reuse yet: each check embeds a [`REDUNDANCY_SAMPLE_CHARS`] sample of its own,
+/// and the tokens spent are reported in [`LoadResult::redundancy_tokens`].
+pub struct RedundancyCheck {
+    provider: Arc<dyn EmbeddingProvider + Send + Sync>,
+    threshold: f32,
 }
 
-/// Load documentation from docs.rs for a given crate
+impl RedundancyCheck {
+    /// `threshold` must be in (0, 1]
+    pub fn new(provider: Arc<dyn EmbeddingProvider + Send + Sync>, threshold: f32) -> Result<Self, ServerError> {
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            return Err(ServerError::Config(format!(
+                "Redundancy threshold must be in (0, 1], got {}",
+                threshold
+            )));
+        }
+        Ok(Self { provider, threshold })
+    }
+
+    /// Embed the start of `text`, remember it in `seen`, and return its highest similarity to
+    /// the pages already there when that reaches the threshold, with the tokens embedded
+    async fn redundant_with(&self, text: &str, seen: &mut Vec<Array1<f32>>) -> Result<(Option<f32>, usize), ServerError> {
+        let sample: String = text.chars().take(REDUNDANCY_SAMPLE_CHARS).collect();
+        let (vectors, tokens) = self.provider.generate_embeddings(&[sample]).await?;
+        let vector = match vectors.into_iter().next() {
+            Some(vector) => Array1::from(vector),
+            None => return Ok((None, tokens)),
+        };
+        let best = seen
+            .iter()
+            .map(|other| cosine_similarity(vector.view(), other.view()))
+            .fold(None, |best: Option<f32>, similarity| Some(best.map_or(similarity, |best| best.max(similarity))));
+        seen.push(vector);
+        Ok((best.filter(|similarity| *similarity >= self.threshold), tokens))
+    }
+}
+
+/// Load documentation from docs.rs for a given crate, with the default `PageLimits` and content selector
 pub async fn load_documents_from_docs_rs(
     crate_name: &str,
-    _version: &str,
-    _features: Option<&Vec<String>>,
+    version: &str,
+    features: Option<&Vec<String>>,
     max_pages: Option<usize>,
 ) -> Result<LoadResult, DocLoaderError> {
-    println!("Fetching documentation from docs.rs for crate: {}", crate_name);
+    load_documents_from_docs_rs_with_limits(
+        &crawler_client()?,
+        crate_name,
+        version,
+        features,
+        CrawlLimit::new(max_pages, None),
+        &PageLimits::default(),
+        None,
+        None,
+        None,
+        false,
+    )
+    .await
+}
 
-    let base_url = format!("https://docs.rs/{}/latest/{}/", crate_name, crate_name);
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| DocLoaderError::Network(e.to_string()))?;
+/// Load documentation from docs.rs for a given crate, applying `limits` to every page.
+/// `crawl_limit` bounds the crawl by page count or by link depth (see [`CrawlLimit`]).
+/// `content_selector` overrides [`DEFAULT_CONTENT_SELECTOR`] (e.g. to add `.method` signatures),
+/// and `base_url` crawls a self-hosted rustdoc site instead of docs.rs (see [`crawl_start_url`]).
+/// `redundancy` stops following links from pages that repeat earlier ones (see [`RedundancyCheck`]).
+/// Paths the site's robots.txt disallows are skipped unless `ignore_robots` is set.
+/// `client` should come from [`crawler_client`] and be shared by every crate of a run.
+#[allow(clippy::too_many_arguments)]
+pub async fn load_documents_from_docs_rs_with_limits(
+    client: &reqwest::Client,
+    crate_name: &str,
+    version: &str,
+    features: Option<&Vec<String>>,
+    crawl_limit: CrawlLimit,
+    limits: &PageLimits,
+    content_selector: Option<&str>,
+    base_url: Option<&str>,
+    redundancy: Option<&RedundancyCheck>,
+    ignore_robots: bool,
+) -> Result<LoadResult, DocLoaderError> {
+    let start_url = match base_url {
+        Some(_) => crawl_start_url(crate_name, base_url)?,
+        None => docs_rs_start_url(crate_name, version)?,
+    };
+    info!("Fetching documentation from {} for crate: {}", start_url, crate_name);
+
+    let mut features: Vec<String> = features.cloned().unwrap_or_default();
+    features.sort();
+    features.dedup();
+    if !features.is_empty() && base_url.is_none() {
+        check_features(client, crate_name, version, &features).await?;
+    }
+    let mut gated_features = BTreeSet::new();
+
+    let robots = match ignore_robots {
+        true => Arc::new(RobotsRules::default()),
+        false => robots::rules_for(client, &start_url).await,
+    };
+    let allowed = |url: &reqwest::Url| match url.query() {
+        Some(query) => robots.allows(&format!("{}?{}", url.path(), query)),
+        None => robots.allows(url.path()),
+    };
+    if !allowed(&start_url) {
+        return Err(DocLoaderError::Disallowed(format!(
+            "{} (pass --ignore-robots for sites you own)",
+            start_url
+        )));
+    }
 
     let mut documents = Vec::new();
+    let mut skipped_pages = Vec::new();
+    let mut truncated_pages = Vec::new();
     let mut visited = HashSet::new();
     let mut to_visit = VecDeque::new();
-    to_visit.push_back(base_url.clone());
+    to_visit.push_back((start_url.to_string(), 0));
     let mut extracted_version = None;
+    let mut seen_pages = Vec::new();
+    let mut pruned_links = 0;
+    let mut redundancy_tokens = 0;
+    let mut disallowed_pages = 0;
 
     // Define the CSS selector for the main content area
-    let content_selector = Selector::parse("div.docblock, section.docblock, .rustdoc .docblock")
-        .map_err(|e| DocLoaderError::Selector(e.to_string()))?;
+    let content_selector = parse_content_selector(content_selector)?;
 
-    let max_pages = max_pages.unwrap_or(200); // Default to 200 pages if not specified
     let mut processed = 0;
 
-    while let Some(url) = to_visit.pop_front() {
-        if processed >= max_pages {
-            eprintln!("Reached maximum page limit ({}), stopping", max_pages);
+    while let Some((url, depth)) = to_visit.pop_front() {
+        if crawl_limit.reached(processed) {
+            info!("Reached crawl limit ({}), stopping", crawl_limit);
             break;
         }
 
@@ -72,128 +965,268 @@ pub async fn load_documents_from_docs_rs(
         }
 
         visited.insert(url.clone());
+        if reqwest::Url::parse(&url).is_ok_and(|parsed| !allowed(&parsed)) {
+            debug!("Skipping {}: disallowed by robots.txt", url);
+            disallowed_pages += 1;
+            continue;
+        }
         processed += 1;
 
-        eprintln!("Processing page {}/{}: {}", processed, max_pages, url);
+        match crawl_limit {
+            CrawlLimit::Pages(max_pages) => debug!("Processing page {}/{}: {}", processed, max_pages, url),
+            CrawlLimit::Depth(_) => debug!("Processing page {} (depth {}): {}", processed, depth, url),
+        }
 
         // Fetch the page with retry logic
-        let html_content = match fetch_with_retry(&client, &url, 3).await {
-            Ok(content) => content,
+        let (final_url, html_content) = match fetch_with_retry(client, &url, 3).await {
+            Ok(page) => page,
             Err(e) => {
-                eprintln!("Failed to fetch {} after retries: {}", url, e);
+                warn!("Failed to fetch {} after retries: {}", url, e);
                 continue;
             }
         };
 
-        let document = Html::parse_document(&html_content);
+        // A failed build still serves a page; crawling it would store junk or nothing
+        if processed == 1 {
+            check_docs_available(crate_name, &final_url, &html_content)?;
+
+            // Queue every item from the all.html index ahead of links found by following
+            // pages, so deeply nested items are reached before the page cap
+            let items = match start_url.join(ALL_ITEMS_PAGE) {
+                Ok(all_items_url) if allowed(&all_items_url) => fetch_all_items(client, &start_url).await,
+                _ => Vec::new(),
+            };
+            if !items.is_empty() {
+                debug!("Queued {} items from {}", items.len(), ALL_ITEMS_PAGE);
+            }
+            if let Ok(all_items_url) = start_url.join(ALL_ITEMS_PAGE) {
+                // The index itself is only a list of names
+                visited.insert(all_items_url.to_string());
+            }
+            to_visit.extend(items.into_iter().map(|item| (item.to_string(), depth + 1)));
+        }
 
-        // Extract version from the first page (usually in the header)
+        // Links found on this page and its text, queued after the redundancy check
+        let mut page_links = Vec::new();
+        let mut page_text = String::new();
+
+        // docs.rs redirects `latest` to the release it resolves to, so the URL the first
+        // page was served from names the version; the page header is a fallback for sites
+        // that serve `latest` directly
         if extracted_version.is_none() && processed == 1 {
-            // Try to find version in the docs.rs header
-            // docs.rs shows version in format "crate-name 1.2.3"
-            if let Ok(version_selector) = Selector::parse(".version") {
-                if let Some(version_elem) = document.select(&version_selector).next() {
-                    let version_text = version_elem.text().collect::<String>();
-                    extracted_version = Some(version_text.trim().to_string());
-                    eprintln!("Extracted version: {:?}", extracted_version);
+            extracted_version = extract_version(&html_content, &final_url);
+            debug!("Resolved version {:?} from {}", extracted_version, final_url);
+        }
+
+        // Html isn't Send, so keep it in a block that ends before the next await
+        {
+            let document = Html::parse_document(&html_content);
+
+            // Keep "Available on crate feature x only" with the page, so feature-gated
+            // items can be found by their feature name
+            let item_gates = feature_gates(&document, ITEM_FEATURE_NOTES);
+            let gate_note = (!item_gates.is_empty()).then(|| {
+                let names: Vec<&str> = item_gates.iter().map(String::as_str).collect();
+                format!("Available on crate feature {} only.", names.join(", "))
+            });
+            gated_features.extend(feature_gates(&document, FEATURE_NOTES));
+
+            // Extract text content from documentation blocks, with the section each belongs to
+            let mut blocks = Vec::new();
+            for element in document.select(&content_selector) {
+                let text_content: String = element
+                    .text()
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+
+                if !text_content.is_empty() {
+                    blocks.push((section_anchor(element), text_content));
                 }
             }
 
-            // Alternative: Look in the title or URL path
-            if extracted_version.is_none() {
-                // The URL might contain version like /crate-name/1.2.3/
-                if let Some(version_match) = url.split('/').nth_back(2) {
-                    if version_match != "latest" && version_match.chars().any(|c| c.is_numeric()) {
-                        extracted_version = Some(version_match.to_string());
-                        eprintln!("Extracted version from URL: {:?}", extracted_version);
+            if !blocks.is_empty() {
+                let relative_path = url.strip_prefix("https://docs.rs/")
+                    .unwrap_or(&url)
+                    .to_string();
+                let sections = split_sections(blocks, gate_note, limits.min_page_chars);
+                debug!("Extracted content from: {} ({} sections, {} chars)",
+                      relative_path, sections.len(), sections.iter().map(|(_, content)| content.len()).sum::<usize>());
+                if redundancy.is_some() {
+                    page_text = sections.iter().map(|(_, content)| content.as_str()).collect::<Vec<_>>().join("\n\n");
+                }
+
+                for (anchor, content) in sections {
+                    let path = match &anchor {
+                        Some(anchor) => format!("{}#{}", relative_path, anchor),
+                        None => relative_path.clone(),
+                    };
+                    let (content, dropped_chars) = limits.clip_content(content);
+                    if dropped_chars > 0 {
+                        warn!("✂️  Cut {} to {} chars, dropping {} chars",
+                                 path, limits.max_content_chars.unwrap_or_default(), dropped_chars);
+                    }
+                    match limits.apply(content) {
+                        PageOutcome::Keep(content) => {
+                            if dropped_chars > 0 {
+                                truncated_pages.push(path.clone());
+                            }
+                            documents.push(Document { path, content, anchor });
+                        }
+                        PageOutcome::Truncated(content, original_tokens) => {
+                            warn!("⚠️  Truncated {} from {} to {} tokens",
+                                     path, original_tokens, limits.max_page_tokens.unwrap_or_default());
+                            truncated_pages.push(path.clone());
+                            documents.push(Document { path, content, anchor });
+                        }
+                        PageOutcome::Skip(reason) => {
+                            warn!("⚠️  Skipping {}: {}", path, reason);
+                            skipped_pages.push(SkippedPage { path, reason });
+                        }
                     }
                 }
+            } else {
+                debug!("No content extracted from: {}", url);
             }
-        }
 
-        // Extract text content from documentation blocks
-        let mut page_content = Vec::new();
-        for element in document.select(&content_selector) {
-            let text_content: String = element
-                .text()
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<&str>>()
-                .join("\n");
+            // Extract links to other documentation pages within the same crate
+            // Page mode follows links for the first 75% of pages to get deeper coverage;
+            // depth mode follows them until the page is `max_depth` hops from the root
+            if crawl_limit.follows_links(processed, depth) {
+                let link_selector = Selector::parse("a").unwrap();
+                let mut found_links = 0;
+                let mut added_links = 0;
 
-            if !text_content.is_empty() {
-                page_content.push(text_content);
-            }
-        }
+                for link in document.select(&link_selector) {
+                    if let Some(href) = link.value().attr("href") {
+                        found_links += 1;
 
-        if !page_content.is_empty() {
-            let relative_path = url.strip_prefix("https://docs.rs/")
-                .unwrap_or(&url)
-                .to_string();
+                        // Follow various types of relative links
+                        let should_follow = href.starts_with("./") ||
+                                           href.starts_with("../") ||
+                                           // Add support for simple relative paths
+                                           (!href.starts_with("http") &&
+                                            !href.starts_with("#") &&
+                                            !href.starts_with("/") &&
+                                            href.ends_with(".html"));
 
-            eprintln!("  -> Extracted content from: {} ({} blocks, {} chars)",
-                     relative_path, page_content.len(), page_content.join("\n\n").len());
-
-            documents.push(Document {
-                path: relative_path,
-                content: page_content.join("\n\n"),
-            });
-        } else {
-            eprintln!("  -> No content extracted from: {}", url);
-        }
-
-        // Extract links to other documentation pages within the same crate
-        // Follow links for first 75% of pages to get deeper coverage
-        if processed < (max_pages * 3 / 4) {
-            let link_selector = Selector::parse("a").unwrap();
-            let mut found_links = 0;
-            let mut added_links = 0;
-
-            for link in document.select(&link_selector) {
-                if let Some(href) = link.value().attr("href") {
-                    found_links += 1;
-
-                    // Follow various types of relative links
-                    let should_follow = href.starts_with("./") ||
-                                       href.starts_with("../") ||
-                                       // Add support for simple relative paths
-                                       (!href.starts_with("http") &&
-                                        !href.starts_with("#") &&
-                                        !href.starts_with("/") &&
-                                        href.ends_with(".html"));
-
-                    if should_follow {
-                        if let Ok(absolute_url) = reqwest::Url::parse(&url) {
-                            if let Ok(new_url) = absolute_url.join(href) {
-                                let new_url_str = new_url.to_string();
-                                if new_url_str.contains("docs.rs") &&
-                                   new_url_str.contains(crate_name) &&
-                                   !visited.contains(&new_url_str) {
-                                    to_visit.push_back(new_url_str.clone());
-                                    added_links += 1;
-                                    if added_links <= 5 { // Only show first 5 for brevity
-                                        eprintln!("  -> Adding link: {}", href);
+                        if should_follow {
+                            if let Ok(absolute_url) = reqwest::Url::parse(&url) {
+                                if let Ok(new_url) = absolute_url.join(href) {
+                                    let new_url_str = new_url.to_string();
+                                    if in_crawl_scope(&start_url, &new_url) &&
+                                       !visited.contains(&new_url_str) {
+                                        page_links.push(new_url_str.clone());
+                                        added_links += 1;
+                                        if added_links <= 5 { // Only show first 5 for brevity
+                                            debug!("Adding link: {}", href);
+                                        }
                                     }
                                 }
                             }
                         }
                     }
                 }
+                debug!("Found {} links, added {} new ones to visit", found_links, added_links);
             }
-            eprintln!("  Found {} links, added {} new ones to visit", found_links, added_links);
         }
 
+        if let Some(check) = redundancy.filter(|_| !page_text.is_empty()) {
+            // Links already visited or queued are crawled whatever this page is like, and with
+            // the queue seeded from all.html that is nearly all of them: only a page adding
+            // new links is worth an embedding request. Each new link is counted once.
+            let new_links = page_links
+                .iter()
+                .filter(|link| !visited.contains(*link) && !to_visit.iter().any(|(queued, _)| queued == *link))
+                .collect::<HashSet<&String>>()
+                .len();
+            if new_links > 0 {
+                match check.redundant_with(&page_text, &mut seen_pages).await {
+                    Ok((Some(similarity), tokens)) => {
+                        debug!("Not following {} new links from {}: {:.3} similar to an earlier page",
+                               new_links, url, similarity);
+                        redundancy_tokens += tokens;
+                        pruned_links += new_links;
+                        page_links.clear();
+                    }
+                    Ok((None, tokens)) => redundancy_tokens += tokens,
+                    Err(e) => warn!("⚠️  Redundancy check failed for {}, following its links: {}", url, e),
+                }
+            }
+        }
+        to_visit.extend(page_links.into_iter().map(|link| (link, depth + 1)));
+
         // Add a longer delay to be respectful to docs.rs and avoid rate limiting
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     }
 
-    eprintln!("Finished loading {} documents from docs.rs", documents.len());
+    info!("Finished loading {} documents from docs.rs ({} skipped, {} truncated by page limits)",
+          documents.len(), skipped_pages.len(), truncated_pages.len());
+    if pruned_links > 0 {
+        info!("✂️  Pruned {} links from pages that repeated earlier ones", pruned_links);
+    }
+    if disallowed_pages > 0 {
+        info!("🤖 Skipped {} pages disallowed by robots.txt", disallowed_pages);
+    }
+    let ungated: Vec<&str> = features
+        .iter()
+        .filter(|feature| !gated_features.contains(*feature))
+        .map(String::as_str)
+        .collect();
+    if !ungated.is_empty() {
+        warn!("⚠️  No crawled items are marked as gated by feature(s) {}. If the docs were built without them, \
+                   build them with `cargo doc --features ...` and crawl that with a base URL.", ungated.join(", "));
+    }
     Ok(LoadResult {
         documents,
         version: extracted_version,
+        skipped_pages,
+        truncated_pages,
+        features,
+        pruned_links,
+        redundancy_tokens,
+        disallowed_pages,
     })
 }
 
+/// Group a page's `(anchor, text)` docblocks into one document per section, in page order:
+/// the page's top-level docs (led by `gate_note`) first, then each anchored section.
+/// Sections shorter than `min_chars` are kept with the top-level docs instead of on their own.
+pub fn split_sections(
+    blocks: Vec<(Option<String>, String)>,
+    gate_note: Option<String>,
+    min_chars: usize,
+) -> Vec<(Option<String>, String)> {
+    let mut sections: Vec<(Option<String>, Vec<String>)> = vec![(None, gate_note.into_iter().collect())];
+    for (anchor, text) in blocks {
+        match sections.iter_mut().find(|(section, _)| *section == anchor) {
+            Some((_, texts)) => texts.push(text),
+            None => sections.push((anchor, vec![text])),
+        }
+    }
+
+    let mut sections: Vec<(Option<String>, String)> = sections
+        .into_iter()
+        .map(|(anchor, texts)| (anchor, normalize_text(&texts.join("\n\n"))))
+        .collect();
+    let (short, mut kept): (Vec<_>, Vec<_>) = sections
+        .drain(1..)
+        .partition(|(_, content)| content.chars().count() < min_chars);
+    let top = &mut sections[0].1;
+    for (_, content) in short {
+        if !top.is_empty() {
+            top.push_str("\n\n");
+        }
+        top.push_str(&content);
+    }
+    if sections[0].1.is_empty() {
+        sections.clear();
+    }
+    sections.append(&mut kept);
+    sections
+}
+
 /// Synchronous wrapper that uses current tokio runtime
 pub fn load_documents(
     crate_name: &str,
@@ -221,34 +1254,38 @@ async fn fetch_with_retry(
     client: &reqwest::Client,
     url: &str,
     max_retries: usize,
-) -> Result<String, DocLoaderError> {
+) -> Result<(String, String), DocLoaderError> {
     let mut attempts = 0;
     let mut delay = Duration::from_millis(1000); // Start with 1 second
 
     loop {
+        let mut wait = delay;
         match client.get(url).send().await {
             Ok(response) => {
                 if response.status().is_success() {
+                    // Keep the URL after redirects so callers can tell where docs.rs sent us
+                    let final_url = response.url().to_string();
                     match response.text().await {
-                        Ok(text) => return Ok(text),
+                        Ok(text) => return Ok((final_url, text)),
                         Err(e) => {
-                            eprintln!("Failed to read response body for {}: {}", url, e);
+                            warn!("Failed to read response body for {}: {}", url, e);
                             if attempts >= max_retries {
                                 return Err(DocLoaderError::Http(e));
                             }
                         }
                     }
                 } else if response.status() == 429 {
-                    // Rate limited
-                    eprintln!("Rate limited for {}, waiting {:?} before retry {}/{}",
-                             url, delay, attempts + 1, max_retries + 1);
+                    // Rate limited; docs.rs may say how long to back off for
+                    wait = rate_limit_wait(response.headers(), delay);
+                    warn!("Rate limited for {}, waiting {:?} before retry {}/{}",
+                             url, wait, attempts + 1, max_retries + 1);
                     if attempts >= max_retries {
                         return Err(DocLoaderError::RateLimited(
                             format!("Rate limited after {} attempts", attempts + 1)
                         ));
                     }
                 } else {
-                    eprintln!("HTTP error for {}: {}", url, response.status());
+                    warn!("HTTP error for {}: {}", url, response.status());
                     if attempts >= max_retries {
                         return Err(DocLoaderError::Network(
                             format!("HTTP {}", response.status())
@@ -257,7 +1294,7 @@ async fn fetch_with_retry(
                 }
             }
             Err(e) => {
-                eprintln!("Network error for {}: {}", url, e);
+                warn!("Network error for {}: {}", url, e);
                 if attempts >= max_retries {
                     return Err(DocLoaderError::Http(e));
                 }
@@ -265,8 +1302,479 @@ async fn fetch_with_retry(
         }
 
         // Wait before retrying with exponential backoff
-        tokio::time::sleep(delay).await;
+        tokio::time::sleep(wait).await;
         delay = std::cmp::min(delay * 2, Duration::from_secs(30)); // Cap at 30 seconds
         attempts += 1;
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILD_FAILED_PAGE: &str = include_str!("../tests/fixtures/docs_rs_build_failed.html");
+
+    #[test]
+    fn detects_failed_docs_rs_build() {
+        let err = check_docs_available(
+            "broken-crate",
+            "https://docs.rs/broken-crate/latest/broken-crate/",
+            BUILD_FAILED_PAGE,
+        )
+        .unwrap_err();
+        assert!(matches!(err, DocLoaderError::BuildFailed(ref release) if release == "broken-crate-0.3.1"));
+    }
+
+    #[test]
+    fn normalizes_scraped_whitespace_and_unicode() {
+        let messy = "  Creates a\u{00A0}new   \t Router.\u{200B}  \n\n\n\n  let app =\u{FEFF} Router::new();\r\n  Cafe\u{0301}  \n";
+        assert_eq!(
+            normalize_text(messy),
+            "Creates a new Router.\n\nlet app = Router::new();\nCaf\u{00E9}"
+        );
+    }
+
+    #[test]
+    fn crawl_stays_under_base_url() {
+        let start = crawl_start_url("mycrate", Some("https://docs.mycorp.internal/mycrate")).unwrap();
+        assert_eq!(start.as_str(), "https://docs.mycorp.internal/mycrate/");
+
+        let link = |href: &str| start.join(href).unwrap();
+        assert!(in_crawl_scope(&start, &link("struct.Client.html")));
+        assert!(in_crawl_scope(&start, &link("./net/index.html")));
+        assert!(!in_crawl_scope(&start, &link("../othercrate/index.html")));
+        assert!(!in_crawl_scope(&start, &reqwest::Url::parse("https://docs.rs/mycrate/latest/mycrate/").unwrap()));
+
+        let docs_rs = crawl_start_url("tokio", None).unwrap();
+        assert!(in_crawl_scope(&docs_rs, &docs_rs.join("sync/struct.Mutex.html").unwrap()));
+        assert!(!in_crawl_scope(&docs_rs, &docs_rs.join("../../../tokio-util/latest/tokio_util/").unwrap()));
+
+        assert!(crawl_start_url("mycrate", Some("ftp://docs.mycorp.internal/mycrate/")).is_err());
+    }
+
+    #[test]
+    fn honours_retry_after_seconds_and_dates() {
+        use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+        let backoff = Duration::from_secs(1);
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+
+        assert_eq!(rate_limit_wait(&HeaderMap::new(), backoff), backoff);
+        assert_eq!(rate_limit_wait(&headers("7"), backoff), Duration::from_secs(7));
+        assert_eq!(rate_limit_wait(&headers("0"), backoff), backoff);
+        assert_eq!(rate_limit_wait(&headers("86400"), backoff), MAX_RETRY_AFTER);
+        assert_eq!(rate_limit_wait(&headers("Sun, 06 Nov 1994 08:49:37 GMT"), backoff), backoff);
+
+        let in_a_minute = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let wait = retry_after(&headers(&in_a_minute)).unwrap();
+        assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn reads_feature_lists_and_feature_gates() {
+        let features_page = r#"<div id="main"><h3 id="default">default</h3><h3 id="derive">derive</h3></div>"#;
+        assert_eq!(parse_docs_rs_features(features_page), ["default", "derive"]);
+
+        let page = Html::parse_document(
+            r#"<section id="main-content">
+                <span class="item-info"><div class="stab portability">Available on <strong>crate feature <code>derive</code></strong> only.</div></span>
+                <ul class="item-table"><li><span class="stab portability" title="Available on crate feature std only"><code>std</code></span></li></ul>
+                <div class="stab portability">Available on <strong>Unix</strong> only.</div>
+            </section>"#,
+        );
+        assert_eq!(feature_gates(&page, ITEM_FEATURE_NOTES).into_iter().collect::<Vec<_>>(), ["derive"]);
+        assert_eq!(feature_gates(&page, FEATURE_NOTES).into_iter().collect::<Vec<_>>(), ["derive", "std"]);
+    }
+
+    #[test]
+    fn reads_normal_dependencies_from_crates_io() {
+        let json = r#"{"dependencies": [
+            {"crate_id": "tower", "kind": "normal", "optional": false, "req": "^0.4"},
+            {"crate_id": "hyper", "kind": "normal", "optional": true, "req": "^1"},
+            {"crate_id": "tokio", "kind": "dev", "optional": false, "req": "^1"},
+            {"crate_id": "rustversion", "kind": "build", "optional": false, "req": "^1"},
+            {"crate_id": "tower", "kind": "normal", "optional": false, "req": "^0.4"}
+        ]}"#;
+        assert_eq!(parse_crates_io_dependencies(json).unwrap(), ["hyper", "tower"]);
+        assert!(parse_crates_io_dependencies("<html>Not found</html>").is_err());
+    }
+
+    #[test]
+    fn reads_crate_names_from_a_crates_io_listing() {
+        let json = r#"{"crates": [{"name": "syn", "recent_downloads": 90000000}, {"name": "serde"}], "meta": {"total": 2}}"#;
+        assert_eq!(parse_crates_io_listing(json).unwrap(), ["syn", "serde"]);
+        assert!(parse_crates_io_listing(r#"{"errors": [{"detail": "Not Found"}]}"#).is_err());
+        assert_eq!(
+            crates_io_top_crates_url(2, 100),
+            "https://crates.io/api/v1/crates?sort=recent-downloads&page=2&per_page=100"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_content_selector() {
+        assert!(parse_content_selector(None).is_ok());
+        assert!(parse_content_selector(Some(".docblock, .method")).is_ok());
+        assert!(matches!(parse_content_selector(Some("div[")), Err(DocLoaderError::Selector(_))));
+    }
+
+    #[test]
+    fn detects_redirect_to_crate_overview() {
+        let err = check_docs_available("some-cli", "https://docs.rs/crate/some-cli/latest", "<html></html>").unwrap_err();
+        assert!(matches!(err, DocLoaderError::NoDocumentation(_)));
+
+        assert!(check_docs_available("tokio", "https://docs.rs/tokio/latest/tokio/", "<html></html>").is_ok());
+    }
+
+    fn limits(max_page_tokens: Option<usize>, skip_oversized: bool) -> PageLimits {
+        PageLimits { max_content_chars: None, max_page_tokens, min_page_chars: 20, skip_oversized }
+    }
+
+    #[test]
+    fn feature_variant_names_round_trip() {
+        let features = vec!["rc".to_string(), "derive".to_string()];
+        let name = crate_variant_name("serde", &features);
+        assert_eq!(name, "serde+derive+rc");
+        assert_eq!(split_crate_variant(&name), ("serde", vec!["derive".to_string(), "rc".to_string()]));
+        assert_eq!(split_crate_variant("tokio"), ("tokio", vec![]));
+
+        let pinned = crate_version_name(&name, "1.0.200+build.1");
+        assert_eq!(pinned, "serde+derive+rc@1.0.200+build.1");
+        assert_eq!(stored_crate_version(&pinned), Some("1.0.200+build.1"));
+        assert_eq!(split_crate_variant(&pinned).0, "serde");
+        assert_eq!(docs_rs_start_url("serde", "1.0.200").unwrap().as_str(), "https://docs.rs/serde/1.0.200/serde/");
+        assert_eq!(docs_rs_start_url("serde", "*").unwrap().as_str(), "https://docs.rs/serde/latest/serde/");
+        assert_eq!(module_path(&name, "serde/latest/serde/de/trait.Deserialize.html").as_deref(), Some("serde::de"));
+    }
+
+    #[test]
+    fn converts_between_item_paths_and_doc_paths() {
+        let candidates = item_doc_paths("tokio::sync::Mutex");
+        assert_eq!(candidates[0], "tokio/sync/struct.Mutex.html");
+        assert!(candidates.contains(&"tokio/sync/fn.Mutex.html".to_string()));
+        assert_eq!(candidates.last().unwrap(), "tokio/sync/Mutex/index.html");
+        assert_eq!(item_doc_paths("tokio-util"), ["tokio_util/index.html"]);
+        assert!(item_doc_paths(" :: ").is_empty());
+
+        assert_eq!(relative_doc_path("tokio", "sync/struct.Mutex.html"), "tokio/sync/struct.Mutex.html");
+        assert_eq!(relative_doc_path("tokio", "/tokio/sync/struct.Mutex.html"), "tokio/sync/struct.Mutex.html");
+
+        assert_eq!(
+            doc_item_path("tokio", "tokio/latest/tokio/sync/struct.Mutex.html [chunk 2/3]").as_deref(),
+            Some("tokio::sync::Mutex")
+        );
+        assert_eq!(doc_item_path("tokio", "tokio/latest/tokio/sync/index.html").as_deref(), Some("tokio::sync"));
+
+        let mut chunks = vec!["a.html [chunk 10/12]", "a.html [chunk 2/12]", "a.html"];
+        chunks.sort_by_key(|path| chunk_order(path));
+        assert_eq!(chunks, ["a.html", "a.html [chunk 2/12]", "a.html [chunk 10/12]"]);
+    }
+
+    #[test]
+    fn splits_pages_into_anchored_sections() {
+        let html = Html::parse_document(
+            r#"<details class="toggle top-doc"><summary class="hideme"><span>Expand description</span></summary>
+               <div class="docblock"><p>The router type for composing handlers and services.</p></div></details>
+               <details class="toggle method-toggle"><summary><section id="method.route" class="method"><h4>pub fn route</h4></section></summary>
+               <div class="docblock"><p>Add another route to the router, matching a path and method router.</p></div></details>
+               <details class="toggle method-toggle"><summary><section id="method.new" class="method"><h4>pub fn new</h4></section></summary>
+               <div class="docblock"><p>Create a new router.</p></div></details>
+               <span id="structfield.inner" class="structfield section-header">inner</span>
+               <div class="docblock"><p>The wrapped service, called for every request that matches.</p></div>"#,
+        );
+        let selector = parse_content_selector(None).unwrap();
+        let blocks: Vec<_> = html
+            .select(&selector)
+            .map(|block| (section_anchor(block), block.text().collect::<String>()))
+            .collect();
+        let anchors: Vec<_> = blocks.iter().map(|(anchor, _)| anchor.as_deref()).collect();
+        assert_eq!(anchors, [None, Some("method.route"), Some("method.new"), Some("structfield.inner")]);
+
+        // "Create a new router." is too short to stand alone and joins the top-level docs
+        let sections = split_sections(blocks, Some("Available on crate feature json only.".to_string()), 40);
+        let anchors: Vec<_> = sections.iter().map(|(anchor, _)| anchor.as_deref()).collect();
+        assert_eq!(anchors, [None, Some("method.route"), Some("structfield.inner")]);
+        assert!(sections[0].1.starts_with("Available on crate feature json only.\n\nThe router type"));
+        assert!(sections[0].1.ends_with("Create a new router."));
+
+        let path = "axum/latest/axum/struct.Router.html#method.route [chunk 2/3]";
+        assert_eq!(doc_anchor(path), Some("method.route"));
+        assert_eq!(page_path(path), "axum/latest/axum/struct.Router.html");
+        assert_eq!(doc_item_path("axum", path).as_deref(), Some("axum::Router::route"));
+        assert_eq!(docs_rs_url(path), "https://docs.rs/axum/latest/axum/struct.Router.html#method.route");
+        assert_eq!(doc_anchor("axum/latest/axum/struct.Router.html"), None);
+    }
+
+    #[test]
+    fn module_path_from_doc_path() {
+        assert_eq!(
+            module_path("tokio", "tokio/latest/tokio/sync/struct.Mutex.html").as_deref(),
+            Some("tokio::sync")
+        );
+        assert_eq!(
+            module_path("tokio-util", "tokio-util/0.7.10/tokio_util/codec/index.html [chunk 2/3]").as_deref(),
+            Some("tokio_util::codec")
+        );
+        assert_eq!(module_path("axum", "axum/latest/axum/index.html").as_deref(), Some("axum"));
+        assert_eq!(
+            module_path("mycrate", "https://docs.mycorp.internal/mycrate/net/fn.dial.html").as_deref(),
+            Some("mycrate::net")
+        );
+        assert_eq!(module_path("axum", "axum/latest/settings.html"), None);
+    }
+
+    #[test]
+    fn doc_kind_from_file_name() {
+        assert_eq!(doc_kind("tokio", "tokio/latest/tokio/io/trait.AsyncRead.html"), Some("trait"));
+        assert_eq!(doc_kind("axum", "axum/latest/axum/struct.Router.html#method.route [chunk 1/2]"), Some("struct"));
+        assert_eq!(doc_kind("tokio", "tokio/latest/tokio/sync/index.html"), Some("mod"));
+        assert_eq!(doc_kind("serde", "serde/latest/serde/macro.forward_to_deserialize_any.html"), Some("macro"));
+        assert_eq!(doc_kind("tokio", "tokio/latest/tokio/constant.MAX.html"), None);
+        assert_eq!(doc_kind("axum", "axum/latest/settings.html"), None);
+    }
+
+    #[test]
+    fn seeds_crawl_from_all_items_index() {
+        let start = crawl_start_url("axum", None).unwrap();
+        let all_items_url = start.join(ALL_ITEMS_PAGE).unwrap();
+        let html = r##"<html><body><section id="main-content">
+            <h3 id="structs"><a href="#structs">Structs</a></h3>
+            <ul class="all-items">
+                <li><a href="struct.Router.html">Router</a></li>
+                <li><a href="extract/ws/struct.WebSocket.html">extract::ws::WebSocket</a></li>
+                <li><a href="struct.Router.html">Router</a></li>
+                <li><a href="https://docs.rs/tower/latest/tower/trait.Service.html">tower::Service</a></li>
+            </ul>
+        </section></body></html>"##;
+
+        let links: Vec<String> = all_items_links(&all_items_url, &start, html)
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        assert_eq!(
+            links,
+            [
+                "https://docs.rs/axum/latest/axum/struct.Router.html",
+                "https://docs.rs/axum/latest/axum/extract/ws/struct.WebSocket.html",
+            ]
+        );
+        assert!(all_items_links(&all_items_url, &start, "<html><body>Not found</body></html>").is_empty());
+    }
+
+    #[test]
+    fn depth_limit_ignores_page_count() {
+        let pages = CrawlLimit::new(Some(8), None);
+        assert!(pages.follows_links(5, 10) && !pages.follows_links(6, 0));
+        assert!(pages.reached(8));
+
+        let depth = CrawlLimit::new(Some(8), Some(2));
+        assert_eq!(depth, CrawlLimit::Depth(2));
+        assert!(depth.follows_links(500, 1) && !depth.follows_links(1, 2));
+        assert!(!depth.reached(500));
+    }
+
+    #[test]
+    fn skips_near_empty_pages() {
+        let outcome = limits(None, false).apply("Re-exports".to_string());
+        assert_eq!(outcome, PageOutcome::Skip("only 10 chars (min 20)".to_string()));
+    }
+
+    #[test]
+    fn keeps_pages_within_limits() {
+        let content = "A router that dispatches requests to handlers.".to_string();
+        assert_eq!(limits(Some(100), false).apply(content.clone()), PageOutcome::Keep(content));
+    }
+
+    #[test]
+    fn resolves_the_version_from_the_final_docs_rs_url() {
+        assert_eq!(version_from_docs_rs_url("https://docs.rs/tokio/1.38.0/tokio/").as_deref(), Some("1.38.0"));
+        assert_eq!(
+            version_from_docs_rs_url("https://docs.rs/rmcp/0.1.5-alpha.2/rmcp/model/index.html").as_deref(),
+            Some("0.1.5-alpha.2")
+        );
+        assert_eq!(version_from_docs_rs_url("https://docs.rs/tokio/latest/tokio/"), None);
+        assert_eq!(version_from_docs_rs_url("https://docs.rs/tokio/%5E1.3/tokio/"), None);
+        assert_eq!(version_from_docs_rs_url("https://docs.example.com/tokio/1.38.0/tokio/"), None);
+    }
+
+    #[test]
+    fn extracts_the_version_from_page_headers() {
+        let latest = "https://docs.rs/tokio/latest/tokio/";
+        // Current rustdoc sidebar
+        let sidebar = r#"<nav class="sidebar"><div class="sidebar-crate"><h2><a href="../tokio/index.html">tokio</a><span class="version">1.38.0</span></h2></div></nav>"#;
+        assert_eq!(extract_version(sidebar, latest).as_deref(), Some("1.38.0"));
+        // Older rustdoc
+        let old = r#"<div class="block version"><div class="narrow-helper"></div><p>Version 1.0.197</p></div>"#;
+        assert_eq!(extract_version(old, "https://docs.rs/serde/latest/serde/").as_deref(), Some("1.0.197"));
+        // docs.rs crate menu only, with a hyphenated crate name and a prerelease
+        let menu = r#"<div class="nav-container"><ul class="pure-menu-list"><li class="pure-menu-item">
+            <a href="/crate/tokio-util/latest" class="pure-menu-link crate-name" title="Additional utilities for working with Tokio.">
+            <span class="fa fa-solid fa-cube" aria-hidden="true"></span> tokio-util-0.7.11</a></li></ul></div>"#;
+        assert_eq!(extract_version(menu, "https://docs.rs/tokio-util/latest/tokio_util/").as_deref(), Some("0.7.11"));
+        let prerelease = r#"<a href="/crate/rmcp/latest" class="pure-menu-link crate-name"> rmcp-0.1.5-alpha.2</a>"#;
+        assert_eq!(extract_version(prerelease, "https://docs.example.com/rmcp/").as_deref(), Some("0.1.5-alpha.2"));
+
+        // A release in the URL wins over the page; no version anywhere gives None
+        assert_eq!(extract_version(sidebar, "https://docs.rs/tokio/1.37.0/tokio/").as_deref(), Some("1.37.0"));
+        assert_eq!(extract_version(r#"<span class="version">main</span>"#, latest), None);
+        assert_eq!(extract_version("<html></html>", latest), None);
+    }
+
+    #[tokio::test]
+    async fn crawls_gzip_compressed_pages() {
+        use axum::{http::{header, HeaderMap, StatusCode}, response::IntoResponse, routing::get, Router};
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let page = r#"<html><body><div class="docblock"><p>A <code>Router</code> dispatches requests to handlers, served gzipped.</p></div></body></html>"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(page.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let app = Router::new()
+            .route(
+                "/mycrate/",
+                get(move |headers: HeaderMap| async move {
+                    let accepts_gzip = headers
+                        .get(header::ACCEPT_ENCODING)
+                        .and_then(|value| value.to_str().ok())
+                        .is_some_and(|value| value.contains("gzip"));
+                    if !accepts_gzip {
+                        return StatusCode::NOT_ACCEPTABLE.into_response();
+                    }
+                    ([(header::CONTENT_ENCODING, "gzip"), (header::CONTENT_TYPE, "text/html")], gzipped).into_response()
+                }),
+            )
+            .fallback(|| async { StatusCode::NOT_FOUND });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/mycrate/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let result = load_documents_from_docs_rs_with_limits(
+            &crawler_client().unwrap(),
+            "mycrate",
+            "*",
+            None,
+            CrawlLimit::new(Some(1), None),
+            &PageLimits::default(),
+            None,
+            Some(&base_url),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.documents.len(), 1);
+        assert_eq!(result.documents[0].content, "A\nRouter\ndispatches requests to handlers, served gzipped.");
+    }
+
+    /// Embeds texts about spawning and everything else as two orthogonal vectors
+    struct TopicProvider;
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for TopicProvider {
+        async fn generate_embeddings(&self, texts: &[String]) -> Result<(Vec<Vec<f32>>, usize), ServerError> {
+            let vector = |text: &String| if text.contains("spawn") { vec![1.0, 0.0] } else { vec![0.0, 1.0] };
+            Ok((texts.iter().map(vector).collect(), texts.len()))
+        }
+
+        fn get_model_name(&self) -> &str {
+            "topic"
+        }
+
+        fn provider_name(&self) -> &str {
+            "topic"
+        }
+
+        fn embedding_dimensions(&self) -> usize {
+            2
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_following_links_from_redundant_pages() {
+        use axum::{extract::Path, http::StatusCode, response::Html as HtmlBody, routing::get, Router};
+
+        // The root and a.html repeat each other, so c.html is only linked from a redundant page;
+        // a.html's links to the already queued b.html and its repeated link aren't counted as pruned.
+        // e.html only links to pages already visited or queued, so it isn't embedded at all.
+        let page = |text: &str, links: &[&str]| {
+            let links: String = links.iter().map(|link| format!(r#"<a href="{}">{}</a>"#, link, link)).collect();
+            format!(r#"<html><body><div class="docblock"><p>{}</p></div>{}</body></html>"#, text, links)
+        };
+        let spawning = "Use spawn to run a future as a new task on the runtime.";
+        let pages = [
+            ("a.html", page(spawning, &["c.html", "c.html", "b.html"])),
+            ("b.html", page("Channels send values between tasks without shared state.", &["d.html", "e.html"])),
+            ("c.html", page("Never crawled because only a redundant page links here.", &[])),
+            ("d.html", page("Reached through a page that added something new to the crawl.", &[])),
+            ("e.html", page("Only links to pages that the crawl reaches anyway through others.", &["b.html", "d.html"])),
+        ];
+        let app = Router::new()
+            .route("/mycrate/", get(move || async move { HtmlBody(page(spawning, &["a.html", "b.html", "e.html"])) }))
+            .route(
+                "/mycrate/{page}",
+                get(move |Path(name): Path<String>| async move {
+                    match pages.iter().find(|(page, _)| *page == name) {
+                        Some((_, html)) => Ok(HtmlBody(html.clone())),
+                        None => Err(StatusCode::NOT_FOUND),
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/mycrate/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let check = RedundancyCheck::new(Arc::new(TopicProvider), 0.95).unwrap();
+        let result = load_documents_from_docs_rs_with_limits(
+            &crawler_client().unwrap(),
+            "mycrate",
+            "*",
+            None,
+            CrawlLimit::new(None, Some(3)),
+            &PageLimits::default(),
+            None,
+            Some(&base_url),
+            Some(&check),
+            false,
+        )
+        .await
+        .unwrap();
+        let crawled: Vec<&str> = result.documents.iter().map(|doc| doc.path.rsplit('/').next().unwrap()).collect();
+        assert_eq!(crawled, ["", "a.html", "b.html", "e.html", "d.html"]);
+        assert_eq!(result.pruned_links, 1);
+        // One single-token sample per page with new links: the root, a.html and b.html
+        assert_eq!(result.redundancy_tokens, 3);
+
+        assert!(RedundancyCheck::new(Arc::new(TopicProvider), 0.0).is_err());
+        assert!(RedundancyCheck::new(Arc::new(TopicProvider), f32::NAN).is_err());
+    }
+
+    #[test]
+    fn truncates_or_skips_oversized_pages() {
+        let content = "Variant documentation for a generated enum. ".repeat(200);
+
+        match limits(Some(50), false).apply(content.clone()) {
+            PageOutcome::Truncated(text, original_tokens) => {
+                assert!(original_tokens > 50);
+                assert!(content.starts_with(&text));
+                assert!(tiktoken_rs::cl100k_base_singleton().encode_with_special_tokens(&text).len() <= 50);
+            }
+            other => panic!("expected truncation, got {:?}", other),
+        }
+
+        assert!(matches!(limits(Some(50), true).apply(content), PageOutcome::Skip(_)));
+    }
+
+    #[test]
+    fn clips_huge_pages_on_a_char_boundary() {
+        let limits = PageLimits { max_content_chars: Some(10), ..limits(None, false) };
+        let (clipped, dropped) = limits.clip_content("Variantes ééé du enum".to_string());
+        assert_eq!(dropped, 11);
+        assert_eq!(clipped, "Variantes \n\n[Truncated: 11 more characters not stored]");
+
+        let short = "Ünïcode".to_string();
+        assert_eq!(limits.clip_content(short.clone()), (short, 0));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{doc_loader::Document, error::ServerError};
+use crate::{doc_loader::{self, Document}, error::ServerError, secrets};
 use async_openai::{
     config::OpenAIConfig, types::CreateEmbeddingRequestArgs,
     Client as OpenAIClient,
@@ -6,7 +6,8 @@ use async_openai::{
 use ndarray::{Array1, ArrayView1};
 use std::sync::OnceLock;
 use std::sync::Arc;
-use tiktoken_rs::cl100k_base;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +27,33 @@ pub enum EmbeddingConfig {
     },
 }
 
+impl EmbeddingConfig {
+    /// Configuration for `provider` ("openai" or "voyage", any case), using `model` or the
+    /// provider's default model. Reads the provider's credentials from the environment.
+    pub fn from_provider(provider: &str, model: Option<String>) -> Result<Self, ServerError> {
+        match provider.to_lowercase().as_str() {
+            "openai" => Ok(EmbeddingConfig::OpenAI {
+                client: build_openai_client()?,
+                model: model.unwrap_or_else(|| "text-embedding-3-large".to_string()),
+            }),
+            "voyage" => Ok(EmbeddingConfig::VoyageAI {
+                api_key: secrets::require_env_or_file("VOYAGE_API_KEY")?,
+                model: model.unwrap_or_else(|| "voyage-3.5".to_string()),
+            }),
+            _ => Err(ServerError::Config(format!(
+                "Unsupported embedding provider: {}. Use 'openai' or 'voyage'",
+                provider
+            ))),
+        }
+    }
+
+    /// Configuration from `EMBEDDING_PROVIDER` (default openai) and `EMBEDDING_MODEL`
+    pub fn from_env() -> Result<Self, ServerError> {
+        let provider = std::env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "openai".to_string());
+        Self::from_provider(&provider, std::env::var("EMBEDDING_MODEL").ok())
+    }
+}
+
 /// Trait for embedding providers
 #[async_trait::async_trait]
 pub trait EmbeddingProvider {
@@ -35,12 +63,156 @@ pub trait EmbeddingProvider {
     ) -> Result<(Vec<Vec<f32>>, usize), ServerError>;
 
     fn get_model_name(&self) -> &str;
+
+    /// Short name of the service behind this provider (e.g. `openai`)
+    fn provider_name(&self) -> &str;
+
+    /// Length of the vectors this provider's model produces
+    fn embedding_dimensions(&self) -> usize;
+}
+
+/// Output dimensions of well-known embedding models
+pub fn known_model_dimensions(model: &str) -> Option<usize> {
+    match model {
+        "text-embedding-3-large" => Some(3072),
+        "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+        "voyage-3.5" | "voyage-3.5-lite" | "voyage-3" | "voyage-3-large" | "voyage-code-3"
+        | "voyage-2" | "voyage-finance-2" | "voyage-law-2" | "voyage-multilingual-2" => Some(1024),
+        "voyage-3-lite" => Some(512),
+        "voyage-code-2" | "voyage-large-2" => Some(1536),
+        _ => None,
+    }
+}
+
+/// List price in USD per million input tokens for well-known embedding models
+pub fn model_pricing(model: &str) -> Option<f64> {
+    match model {
+        "text-embedding-3-small" => Some(0.02),
+        "text-embedding-3-large" => Some(0.13),
+        "text-embedding-ada-002" => Some(0.10),
+        "voyage-3.5-lite" | "voyage-3-lite" => Some(0.02),
+        "voyage-3.5" | "voyage-3" => Some(0.06),
+        "voyage-2" => Some(0.10),
+        "voyage-code-2" | "voyage-large-2" | "voyage-finance-2" | "voyage-law-2"
+        | "voyage-multilingual-2" => Some(0.12),
+        "voyage-3-large" | "voyage-code-3" => Some(0.18),
+        _ => None,
+    }
+}
+
+/// Price per million tokens used for cost estimates: `EMBEDDING_COST_PER_MILLION` overrides
+/// (for self-hosted models or negotiated prices), then the [`model_pricing`] table. Models in
+/// neither have no price and are reported as "cost unknown" rather than guessed.
+pub fn embedding_cost_per_million(model: &str) -> Option<f64> {
+    std::env::var("EMBEDDING_COST_PER_MILLION")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .or_else(|| model_pricing(model))
+}
+
+/// Estimated cost in USD of embedding `tokens` tokens with `model`, if its price is known
+pub fn estimate_cost(model: &str, tokens: usize) -> Option<f64> {
+    embedding_cost_per_million(model).map(|per_million| tokens as f64 / 1_000_000.0 * per_million)
+}
+
+/// Format an estimated cost as `$0.001234`, or "cost unknown" for unpriced models
+pub fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("${:.6}", cost),
+        None => "cost unknown".to_string(),
+    }
+}
+
+/// Dimensions for `model`: `EMBEDDING_DIMENSIONS` overrides, then the known table, then `default`
+fn resolve_dimensions(model: &str, default: usize) -> usize {
+    std::env::var("EMBEDDING_DIMENSIONS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .or_else(|| known_model_dimensions(model))
+        .unwrap_or(default)
+}
+
+/// Default number of retries for a failed embedding request
+pub const DEFAULT_EMBEDDING_MAX_RETRIES: u32 = 3;
+/// First backoff delay; doubles on every retry
+const EMBEDDING_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound on a single backoff delay, including one requested via `Retry-After`
+const EMBEDDING_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+const VOYAGE_API_BASE: &str = "https://api.voyageai.com/v1";
+
+/// Retries for rate-limited or unavailable embedding requests, from `EMBEDDING_MAX_RETRIES`
+fn embedding_max_retries() -> u32 {
+    std::env::var("EMBEDDING_MAX_RETRIES")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_EMBEDDING_MAX_RETRIES)
+}
+
+/// A failed embedding attempt, with the delay the provider asked for (if any)
+struct AttemptError {
+    error: ServerError,
+    retry_after: Option<Duration>,
+}
+
+impl From<ServerError> for AttemptError {
+    fn from(error: ServerError) -> Self {
+        Self { error, retry_after: None }
+    }
+}
+
+/// Run `attempt` until it succeeds, fails with a non-transient error, or `max_retries` is used up.
+/// Waits for the provider's `Retry-After` when given, otherwise backs off exponentially.
+async fn with_embedding_retries<T, F, Fut>(
+    provider: &str,
+    max_retries: u32,
+    mut attempt: F,
+) -> Result<T, ServerError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, AttemptError>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(AttemptError { error, retry_after }) => {
+                let transient = matches!(error.kind(), "rate_limited" | "provider_unavailable");
+                if !transient || retries >= max_retries {
+                    return Err(error);
+                }
+                let delay = retry_after
+                    .unwrap_or_else(|| EMBEDDING_RETRY_BASE_DELAY * 2u32.saturating_pow(retries))
+                    .min(EMBEDDING_RETRY_MAX_DELAY);
+                retries += 1;
+                warn!(
+                    "⚠️  {} embedding request failed ({}), retry {}/{} in {:?}",
+                    provider, error, retries, max_retries, delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Map an OpenAI error so rate limits and outages are distinguishable from bad requests
+fn classify_openai_error(error: async_openai::error::OpenAIError) -> ServerError {
+    let error = ServerError::OpenAI(error);
+    match error.kind() {
+        "rate_limited" => ServerError::RateLimited(error.to_string()),
+        "provider_unavailable" => ServerError::ProviderUnavailable(error.to_string()),
+        _ => error,
+    }
 }
 
 /// OpenAI embedding provider
 pub struct OpenAIEmbeddingProvider {
     client: OpenAIClient<OpenAIConfig>,
     model: String,
+    dimensions: usize,
+    max_retries: u32,
 }
 
 /// Voyage AI embedding provider
@@ -48,6 +220,9 @@ pub struct VoyageAIEmbeddingProvider {
     client: reqwest::Client,
     api_key: String,
     model: String,
+    dimensions: usize,
+    api_base: String,
+    max_retries: u32,
 }
 
 /// Voyage AI API response structures
@@ -60,7 +235,6 @@ struct VoyageEmbeddingResponse {
 #[derive(Deserialize)]
 struct VoyageEmbeddingData {
     embedding: Vec<f32>,
-    #[allow(dead_code)]
     index: usize,
 }
 
@@ -87,9 +261,22 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
             .input(texts.to_vec())
             .build()?;
 
-        let response = self.client.embeddings().create(request).await?;
+        let response = with_embedding_retries("OpenAI", self.max_retries, || {
+            let request = request.clone();
+            async move {
+                self.client
+                    .embeddings()
+                    .create(request)
+                    .await
+                    .map_err(|e| AttemptError::from(classify_openai_error(e)))
+            }
+        })
+        .await?;
 
-        let embeddings: Vec<Vec<f32>> = response.data
+        // Put embeddings back in input order
+        let mut data = response.data;
+        data.sort_by_key(|data| data.index);
+        let embeddings: Vec<Vec<f32>> = data
             .into_iter()
             .map(|data| data.embedding)
             .collect();
@@ -102,6 +289,14 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
     fn get_model_name(&self) -> &str {
         &self.model
     }
+
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+
+    fn embedding_dimensions(&self) -> usize {
+        self.dimensions
+    }
 }
 
 #[async_trait::async_trait]
@@ -116,71 +311,215 @@ impl EmbeddingProvider for VoyageAIEmbeddingProvider {
             input_type: "document".to_string(), // Default to document type
         };
 
+        let voyage_response = with_embedding_retries("Voyage AI", self.max_retries, || {
+            self.request_embeddings(&request)
+        })
+        .await?;
+
+        // Put embeddings back in input order
+        let mut data = voyage_response.data;
+        data.sort_by_key(|data| data.index);
+        let embeddings: Vec<Vec<f32>> = data
+            .into_iter()
+            .map(|data| data.embedding)
+            .collect();
+
+        Ok((embeddings, voyage_response.usage.total_tokens))
+    }
+
+    fn get_model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider_name(&self) -> &str {
+        "voyage"
+    }
+
+    fn embedding_dimensions(&self) -> usize {
+        self.dimensions
+    }
+}
+
+impl OpenAIEmbeddingProvider {
+    pub fn new(client: OpenAIClient<OpenAIConfig>, model: String) -> Self {
+        let dimensions = resolve_dimensions(&model, 3072);
+        Self { client, model, dimensions, max_retries: embedding_max_retries() }
+    }
+
+    /// Override `EMBEDDING_MAX_RETRIES`
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Override `EMBEDDING_DIMENSIONS` and the known model dimensions
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+}
+
+impl VoyageAIEmbeddingProvider {
+    pub fn new(api_key: String, model: String) -> Self {
+        let dimensions = resolve_dimensions(&model, 1024);
+        Self {
+            client: reqwest::Client::new(),
+            api_key,
+            model,
+            dimensions,
+            api_base: VOYAGE_API_BASE.to_string(),
+            max_retries: embedding_max_retries(),
+        }
+    }
+
+    /// Send requests to another Voyage-compatible endpoint (e.g. a proxy)
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Override `EMBEDDING_MAX_RETRIES`
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Override `EMBEDDING_DIMENSIONS` and the known model dimensions
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// A single embeddings request, classifying failures for the retry loop
+    async fn request_embeddings(
+        &self,
+        request: &VoyageEmbeddingRequest,
+    ) -> Result<VoyageEmbeddingResponse, AttemptError> {
         let response = self
             .client
-            .post("https://api.voyageai.com/v1/embeddings")
+            .post(format!("{}/embeddings", self.api_base))
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&request)
+            .json(request)
             .send()
             .await
-            .map_err(|e| ServerError::Network(format!("Voyage AI API request failed: {}", e)))?;
+            .map_err(|e| ServerError::ProviderUnavailable(format!("Voyage AI API request failed: {}", e)))?;
 
         if !response.status().is_success() {
             let status = response.status();
+            let retry_after = doc_loader::retry_after(response.headers());
             let error_text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ServerError::Network(format!(
-                "Voyage AI API error {}: {}",
-                status,
-                error_text
-            )));
+            let message = format!("Voyage AI API error {}: {}", status, error_text);
+            let error = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                ServerError::RateLimited(message)
+            } else if status.is_server_error() {
+                ServerError::ProviderUnavailable(message)
+            } else {
+                ServerError::Network(message)
+            };
+            return Err(AttemptError { error, retry_after });
         }
 
-        let voyage_response: VoyageEmbeddingResponse = response
+        Ok(response
             .json()
             .await
-            .map_err(|e| ServerError::Parsing(format!("Failed to parse Voyage AI response: {}", e)))?;
+            .map_err(|e| ServerError::Parsing(format!("Failed to parse Voyage AI response: {}", e)))?)
+    }
+}
 
-        let embeddings: Vec<Vec<f32>> = voyage_response.data
-            .into_iter()
-            .map(|data| data.embedding)
-            .collect();
+/// Validate an `OPENAI_API_BASE` value, returning it without a trailing slash
+fn normalize_api_base(api_base: &str) -> Result<String, ServerError> {
+    normalize_api_base_var("OPENAI_API_BASE", api_base)
+}
 
-        Ok((embeddings, voyage_response.usage.total_tokens))
+/// Validate the API base read from env var `name`
+fn normalize_api_base_var(name: &str, api_base: &str) -> Result<String, ServerError> {
+    let api_base = api_base.trim().trim_end_matches('/');
+    let url = reqwest::Url::parse(api_base).map_err(|e| {
+        ServerError::Config(format!("Invalid {} '{}': {}", name, api_base, e))
+    })?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(ServerError::Config(format!(
+            "Invalid {} '{}': expected an http(s) URL such as https://api.openai.com/v1",
+            name, api_base
+        )));
     }
+    Ok(api_base.to_string())
+}
 
-    fn get_model_name(&self) -> &str {
-        &self.model
+/// Build the OpenAI client shared by the embedding provider and the chat completions.
+/// `OPENAI_API_BASE` points it at a compatible server; `OPENAI_API_KEY` is required
+/// unless a custom base is set (local servers often need no key).
+pub fn build_openai_client() -> Result<OpenAIClient<OpenAIConfig>, ServerError> {
+    let api_key = secrets::env_or_file("OPENAI_API_KEY")?;
+    let api_base = std::env::var("OPENAI_API_BASE").ok().filter(|b| !b.trim().is_empty());
+
+    let mut config = OpenAIConfig::new();
+    match (api_base, api_key) {
+        (None, None) => return Err(ServerError::MissingEnvVar("OPENAI_API_KEY (or OPENAI_API_KEY_FILE)".to_string())),
+        (Some(api_base), api_key) => {
+            config = config.with_api_base(normalize_api_base(&api_base)?);
+            if let Some(api_key) = api_key {
+                config = config.with_api_key(api_key);
+            }
+        }
+        (None, Some(api_key)) => config = config.with_api_key(api_key),
     }
+    Ok(OpenAIClient::with_config(config))
 }
 
-impl OpenAIEmbeddingProvider {
-    pub fn new(client: OpenAIClient<OpenAIConfig>, model: String) -> Self {
-        Self { client, model }
+/// Build the client for chat completions. `LLM_API_BASE` and `LLM_API_KEY` give chat its own
+/// OpenAI-compatible endpoint (e.g. a local vLLM or LiteLLM gateway), separate from the
+/// embeddings; without either, chat shares [`build_openai_client`].
+pub fn build_chat_client() -> Result<OpenAIClient<OpenAIConfig>, ServerError> {
+    let api_base = std::env::var("LLM_API_BASE").ok().filter(|b| !b.trim().is_empty());
+    match chat_client_config(api_base, secrets::env_or_file("LLM_API_KEY")?)? {
+        Some(config) => Ok(OpenAIClient::with_config(config)),
+        None => build_openai_client(),
     }
 }
 
-impl VoyageAIEmbeddingProvider {
-    pub fn new(api_key: String, model: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            api_key,
-            model,
-        }
+/// The dedicated chat endpoint's config, `None` when neither `LLM_API_*` variable is set
+fn chat_client_config(api_base: Option<String>, api_key: Option<String>) -> Result<Option<OpenAIConfig>, ServerError> {
+    if api_base.is_none() && api_key.is_none() {
+        return Ok(None);
     }
+    // Set the key even when empty, so OPENAI_API_KEY is never sent to the chat endpoint
+    let mut config = OpenAIConfig::new().with_api_key(api_key.unwrap_or_default());
+    if let Some(api_base) = api_base {
+        config = config.with_api_base(normalize_api_base_var("LLM_API_BASE", &api_base)?);
+    }
+    Ok(Some(config))
 }
 
 /// Initialize the embedding provider based on configuration
 pub fn initialize_embedding_provider(config: EmbeddingConfig) -> Arc<dyn EmbeddingProvider + Send + Sync> {
+    initialize_embedding_provider_with_dimensions(config, None)
+}
+
+/// Like [`initialize_embedding_provider`], with `dimensions` (if given) overriding
+/// `EMBEDDING_DIMENSIONS` and the known model dimensions
+pub fn initialize_embedding_provider_with_dimensions(
+    config: EmbeddingConfig,
+    dimensions: Option<usize>,
+) -> Arc<dyn EmbeddingProvider + Send + Sync> {
     match config {
         EmbeddingConfig::OpenAI { client, model } => {
-            Arc::new(OpenAIEmbeddingProvider::new(client, model))
+            let provider = OpenAIEmbeddingProvider::new(client, model);
+            Arc::new(match dimensions {
+                Some(dimensions) => provider.with_dimensions(dimensions),
+                None => provider,
+            })
         }
         EmbeddingConfig::VoyageAI { api_key, model } => {
-            Arc::new(VoyageAIEmbeddingProvider::new(api_key, model))
+            let provider = VoyageAIEmbeddingProvider::new(api_key, model);
+            Arc::new(match dimensions {
+                Some(dimensions) => provider.with_dimensions(dimensions),
+                None => provider,
+            })
         }
     }
 }
@@ -243,7 +582,7 @@ fn _chunk_content(content: &str, bpe: &tiktoken_rs::CoreBPE, token_limit: usize)
         // If a single sentence exceeds the limit, we need to split it further
         if sentence_tokens.len() > token_limit {
             // For now, skip sentences that are too long
-            eprintln!("Warning: Single sentence exceeds token limit, splitting by tokens");
+            warn!("Single sentence exceeds token limit, splitting by tokens");
 
             // Split by tokens directly
             let mut start = 0;
@@ -271,126 +610,342 @@ fn _chunk_content(content: &str, bpe: &tiktoken_rs::CoreBPE, token_limit: usize)
     chunks
 }
 
-/// Generates embeddings for a list of documents using the configured provider with chunking support.
-#[allow(dead_code)]
-pub async fn generate_embeddings(
-    documents: &[Document],
-) -> Result<(Vec<(String, String, Array1<f32>)>, usize), ServerError> { // Return tuple: (path, content, embedding), total_tokens
-    // Get the embedding provider
-    let provider = EMBEDDING_CLIENT
-        .get()
-        .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
-
-    let model = provider.get_model_name();
-    eprintln!("Generating embeddings for {} documents using model '{}'...", documents.len(), model);
-
-    // Get the tokenizer for the model and wrap in Arc
-    let bpe = Arc::new(cl100k_base().map_err(|e| ServerError::Tiktoken(e.to_string()))?);
+const CHUNK_TOKEN_LIMIT: usize = 8000; // Keep a buffer below the 8192 limit
+const CHUNK_OVERLAP: usize = 200; // Token overlap between chunks for context
 
-    const CONCURRENCY_LIMIT: usize = 8; // Number of concurrent requests
-    const TOKEN_LIMIT: usize = 8000; // Keep a buffer below the 8192 limit
-    const CHUNK_OVERLAP: usize = 200; // Token overlap between chunks for context
+/// Split documents into the `(path, content)` pieces that get embedded. Documents over the
+/// token limit become `path [chunk i/n]` entries; the split is deterministic, so the same
+/// content always produces the same chunks.
+pub fn chunk_documents(documents: &[Document]) -> Vec<(String, String)> {
+    let bpe = tiktoken_rs::cl100k_base_singleton();
 
-    // First, prepare all chunks with their metadata
     let mut all_chunks = Vec::new();
     for (doc_index, doc) in documents.iter().enumerate() {
         let token_count = bpe.encode_with_special_tokens(&doc.content).len();
 
-        if token_count > TOKEN_LIMIT {
-            eprintln!(
-                "    Document {}/{} ({} tokens) exceeds limit, chunking: {}",
+        if token_count > CHUNK_TOKEN_LIMIT {
+            let chunks = _chunk_content(&doc.content, bpe, CHUNK_TOKEN_LIMIT - CHUNK_OVERLAP);
+            let chunk_count = chunks.len();
+            debug!(
+                "Document {}/{} ({} tokens) exceeds limit, split into {} chunks: {}",
                 doc_index + 1,
                 documents.len(),
                 token_count,
+                chunk_count,
                 doc.path
             );
 
-            let chunks = _chunk_content(&doc.content, &bpe, TOKEN_LIMIT - CHUNK_OVERLAP);
-            let chunk_count = chunks.len();
-            eprintln!("    Split into {} chunks", chunk_count);
-
             for (chunk_index, chunk) in chunks.into_iter().enumerate() {
                 let chunk_path = if chunk_count > 1 {
                     format!("{} [chunk {}/{}]", doc.path, chunk_index + 1, chunk_count)
                 } else {
                     doc.path.clone()
                 };
-                all_chunks.push((doc_index, chunk_path, chunk));
+                all_chunks.push((chunk_path, chunk));
             }
         } else {
-            all_chunks.push((doc_index, doc.path.clone(), doc.content.clone()));
+            all_chunks.push((doc.path.clone(), doc.content.clone()));
         }
     }
+    all_chunks
+}
 
-    let total_chunks = all_chunks.len();
-    eprintln!("Total chunks to process: {} (from {} documents)", total_chunks, documents.len());
+/// cl100k token count of each chunk, as summed into the `total_tokens` embedding reports
+fn chunk_token_counts(chunks: &[(String, String)]) -> Vec<usize> {
+    let bpe = tiktoken_rs::cl100k_base_singleton();
+    chunks.iter().map(|(_, content)| bpe.encode_with_special_tokens(content).len()).collect()
+}
 
-    let results = stream::iter(all_chunks.into_iter().enumerate())
-        .map(|(chunk_index, (_doc_index, path, content))| {
-            // Clone provider and other data for the async block
-            let provider = Arc::clone(&provider);
-            let bpe = Arc::clone(&bpe); // Clone the Arc pointer
-            let content_clone = content.clone(); // Clone content for returning
+/// Tokens that embedding `documents` will report, counted over the same chunks
+/// [`generate_embeddings_with_progress`] sends, without calling the provider
+pub fn count_embedding_tokens(documents: &[Document]) -> usize {
+    chunk_token_counts(&chunk_documents(documents)).iter().sum()
+}
 
-            async move {
-                // Calculate token count for this chunk
-                let token_count = bpe.encode_with_special_tokens(&content).len();
-
-                // Prepare input for this chunk
-                let inputs: Vec<String> = vec![content];
-
-                if chunk_index % 10 == 0 || chunk_index == total_chunks - 1 {
-                    eprintln!(
-                        "    Processing chunk {}/{} ({} tokens): {}",
-                        chunk_index + 1,
-                        total_chunks,
-                        token_count,
-                        path
-                    );
-                }
+/// Generates embeddings for a list of documents using the configured provider with chunking support,
+/// logging progress. Use [`generate_embeddings_with_progress`] to report progress yourself.
+pub async fn generate_embeddings(
+    documents: &[Document],
+) -> Result<(Vec<(String, String, Array1<f32>)>, usize), ServerError> { // Return tuple: (path, content, embedding), total_tokens
+    // Get the embedding provider
+    let provider = EMBEDDING_CLIENT
+        .get()
+        .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+
+    info!("Generating embeddings for {} documents using model '{}'...", documents.len(), provider.get_model_name());
+    let (embeddings, total_tokens) = generate_embeddings_with_progress(provider, documents, |done, total| {
+        debug!("Embedded {}/{} chunks", done, total);
+    })
+    .await?;
+    info!(
+        "Finished generating embeddings. Successfully processed {} chunks/documents ({} tokens).",
+        embeddings.len(), total_tokens
+    );
+    Ok((embeddings, total_tokens))
+}
+
+/// Chunks sent per embedding request, unless `EMBEDDING_BATCH_SIZE` says otherwise
+pub const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 96;
+/// Most tokens sent in one embedding request, below OpenAI's and Voyage's per-request caps
+const BATCH_TOKEN_LIMIT: usize = 100_000;
+
+/// Chunks per embedding request, from `EMBEDDING_BATCH_SIZE`
+fn embedding_batch_size() -> usize {
+    std::env::var("EMBEDDING_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_EMBEDDING_BATCH_SIZE)
+}
+
+/// Split chunks with the given token counts into consecutive batches of at most
+/// `batch_size` chunks and `token_limit` tokens (a larger chunk gets a batch of its own).
+/// Returns the number of chunks in each batch.
+fn batch_sizes(token_counts: &[usize], batch_size: usize, token_limit: usize) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    let (mut chunks, mut tokens) = (0, 0);
+    for &count in token_counts {
+        if chunks > 0 && (chunks == batch_size || tokens + count > token_limit) {
+            sizes.push(chunks);
+            (chunks, tokens) = (0, 0);
+        }
+        chunks += 1;
+        tokens += count;
+    }
+    if chunks > 0 {
+        sizes.push(chunks);
+    }
+    sizes
+}
+
+/// Chunk `documents` and embed the chunks with `provider`, [`DEFAULT_EMBEDDING_BATCH_SIZE`]
+/// chunks per request, calling `progress(done, total)` after each batch is embedded.
+/// Doesn't log; stops at the first failed batch.
+pub async fn generate_embeddings_with_progress<F>(
+    provider: &Arc<dyn EmbeddingProvider + Send + Sync>,
+    documents: &[Document],
+    mut progress: F,
+) -> Result<(Vec<(String, String, Array1<f32>)>, usize), ServerError>
+where
+    F: FnMut(usize, usize) + Send,
+{
+    const CONCURRENCY_LIMIT: usize = 8; // Number of concurrent requests
 
-                // Use the provider to generate embeddings
+    // First, prepare all chunks with their metadata
+    let all_chunks = chunk_documents(documents);
+    let total_chunks = all_chunks.len();
+    let token_counts = chunk_token_counts(&all_chunks);
+
+    let mut chunks = all_chunks.into_iter();
+    let mut counts = token_counts.iter();
+    let batches: Vec<(Vec<(String, String)>, usize)> = batch_sizes(&token_counts, embedding_batch_size(), BATCH_TOKEN_LIMIT)
+        .into_iter()
+        .map(|size| (chunks.by_ref().take(size).collect(), counts.by_ref().take(size).sum()))
+        .collect();
+
+    let mut results = stream::iter(batches)
+        .map(|(batch, batch_tokens)| {
+            let provider = Arc::clone(provider);
+            async move {
+                let inputs: Vec<String> = batch.iter().map(|(_, content)| content.clone()).collect();
                 let (embeddings, _tokens) = provider.generate_embeddings(&inputs).await?;
 
-                if embeddings.len() != 1 {
+                if embeddings.len() != batch.len() {
                     return Err(ServerError::Internal(format!(
-                        "Mismatch in response length for chunk {}. Expected 1, got {}.",
-                        chunk_index + 1, embeddings.len()
+                        "Mismatch in response length for a batch starting at {}. Expected {}, got {}.",
+                        batch[0].0, batch.len(), embeddings.len()
                     )));
                 }
 
-                // Process result
-                let embedding_data = embeddings.into_iter().next().unwrap(); // Safe unwrap due to check above
-                let embedding_array = Array1::from(embedding_data);
-                // Return successful embedding with path, content, and token count
-                Ok((path, content_clone, embedding_array, token_count))
+                // Providers return embeddings in input order
+                let embedded: Vec<(String, String, Array1<f32>)> = batch
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|((path, content), embedding)| (path, content, Array1::from(embedding)))
+                    .collect();
+                Ok((embedded, batch_tokens))
             }
         })
-        .buffer_unordered(CONCURRENCY_LIMIT) // Run up to CONCURRENCY_LIMIT futures concurrently
-        .collect::<Vec<Result<(String, String, Array1<f32>, usize), ServerError>>>() // Update collected result type
-        .await;
+        .buffer_unordered(CONCURRENCY_LIMIT); // Run up to CONCURRENCY_LIMIT requests concurrently
 
-    // Process collected results, filtering out errors and summing tokens
-    let mut embeddings_vec = Vec::new();
+    // Collect batches as they complete, summing tokens; the first error stops the rest
+    let mut embeddings_vec = Vec::with_capacity(total_chunks);
     let mut total_processed_tokens: usize = 0;
-    for result in results {
-        match result {
-            Ok((path, content, embedding, tokens)) => {
-                embeddings_vec.push((path, content, embedding)); // Keep successful embeddings with content
-                total_processed_tokens += tokens; // Add tokens for successful ones
-            }
-            Err(e) => {
-                // Log error but potentially continue? Or return the first error?
-                // For now, let's return the first error encountered.
-                eprintln!("Error during concurrent embedding generation: {}", e);
-                return Err(e);
-            }
-        }
+    while let Some(result) = results.next().await {
+        let (embedded, tokens) = result?;
+        embeddings_vec.extend(embedded);
+        total_processed_tokens += tokens;
+        progress(embeddings_vec.len(), total_chunks);
     }
 
-    eprintln!(
-        "Finished generating embeddings. Successfully processed {} chunks/documents ({} tokens).",
-        embeddings_vec.len(), total_processed_tokens
-    );
     Ok((embeddings_vec, total_processed_tokens)) // Return tuple
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_cost_from_model_pricing() {
+        assert_eq!(format_cost(estimate_cost("text-embedding-3-large", 2_000_000)), "$0.260000");
+        assert_eq!(format_cost(estimate_cost("voyage-3.5", 500_000)), "$0.030000");
+        assert_eq!(format_cost(estimate_cost("my-local-model", 1_000)), "cost unknown");
+    }
+    use axum::{http::StatusCode, response::IntoResponse, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serve a mock Voyage endpoint answering with `responses` in order; returns its base URL
+    async fn mock_voyage(responses: Vec<(StatusCode, serde_json::Value)>) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let responses = Arc::new(responses);
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/embeddings",
+            post(move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = responses[n.min(responses.len() - 1)].clone();
+                async move { (status, [("retry-after", "0")], Json(body)).into_response() }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), calls)
+    }
+
+    fn embedding_response() -> serde_json::Value {
+        serde_json::json!({
+            "data": [{ "embedding": [0.1, 0.2, 0.3], "index": 0 }],
+            "usage": { "total_tokens": 4 }
+        })
+    }
+
+    #[tokio::test]
+    async fn voyage_retries_after_rate_limit() {
+        let (api_base, calls) = mock_voyage(vec![
+            (StatusCode::TOO_MANY_REQUESTS, serde_json::json!({ "detail": "rate limited" })),
+            (StatusCode::OK, embedding_response()),
+        ])
+        .await;
+        let provider = VoyageAIEmbeddingProvider::new("key".to_string(), "voyage-3.5".to_string())
+            .with_api_base(api_base)
+            .with_max_retries(2);
+
+        let (embeddings, tokens) = provider.generate_embeddings(&["hello".to_string()]).await.unwrap();
+        assert_eq!(embeddings, vec![vec![0.1, 0.2, 0.3]]);
+        assert_eq!(tokens, 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn voyage_gives_up_after_max_retries() {
+        let (api_base, calls) = mock_voyage(vec![(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({ "detail": "overloaded" }),
+        )])
+        .await;
+        let provider = VoyageAIEmbeddingProvider::new("key".to_string(), "voyage-3.5".to_string())
+            .with_api_base(api_base)
+            .with_max_retries(1);
+
+        let err = provider.generate_embeddings(&["hello".to_string()]).await.unwrap_err();
+        assert!(matches!(err, ServerError::ProviderUnavailable(_)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn voyage_does_not_retry_bad_requests() {
+        let (api_base, calls) = mock_voyage(vec![(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({ "detail": "invalid model" }),
+        )])
+        .await;
+        let provider = VoyageAIEmbeddingProvider::new("key".to_string(), "voyage-3.5".to_string())
+            .with_api_base(api_base)
+            .with_max_retries(3);
+
+        assert!(provider.generate_embeddings(&["hello".to_string()]).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn batches_respect_size_and_token_limits() {
+        assert_eq!(batch_sizes(&[10; 5], 2, 1_000), [2, 2, 1]);
+        assert_eq!(batch_sizes(&[400, 400, 400, 100], 10, 1_000), [2, 2]);
+        assert_eq!(batch_sizes(&[5_000, 10], 10, 1_000), [1, 1]);
+        assert!(batch_sizes(&[], 10, 1_000).is_empty());
+    }
+
+    #[tokio::test]
+    async fn embeds_chunks_in_batches_and_maps_results_back() {
+        // Answers every request with one embedding per input ([input length]), listed in reverse
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/embeddings",
+            post(move |Json(request): Json<serde_json::Value>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let inputs = request["input"].as_array().unwrap().clone();
+                let data: Vec<serde_json::Value> = inputs
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, input)| serde_json::json!({ "embedding": [input.as_str().unwrap().len()], "index": index }))
+                    .collect();
+                async move { Json(serde_json::json!({ "data": data, "usage": { "total_tokens": 1 } })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let provider: Arc<dyn EmbeddingProvider + Send + Sync> = Arc::new(
+            VoyageAIEmbeddingProvider::new("key".to_string(), "voyage-3.5".to_string()).with_api_base(api_base),
+        );
+        let documents: Vec<Document> = (0..3)
+            .map(|i| Document { path: format!("tokio/fn.f{}.html", i), content: format!("function {}", "f".repeat(i)), anchor: None })
+            .collect();
+
+        let mut reports = Vec::new();
+        let (embeddings, tokens) =
+            generate_embeddings_with_progress(&provider, &documents, |done, total| reports.push((done, total)))
+                .await
+                .unwrap();
+        assert_eq!(tokens, count_embedding_tokens(&documents));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(reports, [(3, 3)]);
+        for (path, content, embedding) in &embeddings {
+            assert_eq!(embedding[0], content.len() as f32, "{}", path);
+        }
+    }
+
+    #[test]
+    fn accepts_http_api_bases_without_trailing_slash() {
+        assert_eq!(normalize_api_base("https://api.openai.com/v1/").unwrap(), "https://api.openai.com/v1");
+        assert_eq!(normalize_api_base(" http://localhost:11434/v1 ").unwrap(), "http://localhost:11434/v1");
+    }
+
+    #[test]
+    fn configures_a_separate_chat_endpoint() {
+        use async_openai::config::Config;
+        let authorization = |config: &OpenAIConfig| config.headers()[reqwest::header::AUTHORIZATION].to_str().unwrap().to_string();
+
+        assert!(chat_client_config(None, None).unwrap().is_none());
+
+        let config = chat_client_config(Some("http://localhost:4000/v1/".to_string()), None).unwrap().unwrap();
+        assert_eq!(config.api_base(), "http://localhost:4000/v1");
+        assert_eq!(authorization(&config).trim(), "Bearer");
+
+        let config = chat_client_config(None, Some("sk-chat".to_string())).unwrap().unwrap();
+        assert_eq!(config.api_base(), "https://api.openai.com/v1");
+        assert_eq!(authorization(&config), "Bearer sk-chat");
+
+        let err = chat_client_config(Some("localhost:4000".to_string()), None).unwrap_err();
+        assert!(err.to_string().contains("LLM_API_BASE"));
+    }
+
+    #[test]
+    fn rejects_malformed_api_bases() {
+        for api_base in ["localhost:11434/v1", "api.openai.com", "ftp://example.com/v1"] {
+            assert!(matches!(normalize_api_base(api_base), Err(ServerError::Config(_))), "{}", api_base);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use rmcp::ServiceError; // Assuming ServiceError is the correct top-level error
+use async_openai::error::OpenAIError;
+use rmcp::{Error as McpError, ServiceError}; // Assuming ServiceError is the correct top-level error
+use serde_json::json;
 use thiserror::Error;
 use crate::doc_loader::DocLoaderError; // Need to import DocLoaderError from the sibling module
 
@@ -17,7 +19,7 @@ pub enum ServerError {
     #[error("Document Loading Error: {0}")]
     DocLoader(#[from] DocLoaderError),
     #[error("OpenAI Error: {0}")]
-    OpenAI(#[from] async_openai::error::OpenAIError),
+    OpenAI(#[from] OpenAIError),
     #[error("JSON Error: {0}")]
     Json(#[from] serde_json::Error), // Add error for JSON deserialization
     #[error("Tiktoken Error: {0}")]
@@ -34,4 +36,149 @@ pub enum ServerError {
     Network(String),
     #[error("Parsing Error: {0}")]
     Parsing(String),
+    #[error("Not Found: {0}")]
+    NotFound(String),
+    #[error("Not Ready: {0}")]
+    NotReady(String),
+    #[error("Provider Unavailable: {0}")]
+    ProviderUnavailable(String),
+    #[error("Rate Limited: {0}")]
+    RateLimited(String),
+    #[error("Timeout: {0}")]
+    Timeout(String),
+}
+
+impl ServerError {
+    /// Stable, machine-readable category reported to MCP clients in the error `data`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerError::MissingEnvVar(_) | ServerError::Config(_) => "configuration",
+            ServerError::NotFound(_) => "not_found",
+            ServerError::NotReady(_) => "not_ready",
+            ServerError::ProviderUnavailable(_) => "provider_unavailable",
+            ServerError::RateLimited(_) => "rate_limited",
+            ServerError::Timeout(_) => "timeout",
+            ServerError::Database(_) => "database",
+            ServerError::Network(_) => "network",
+            ServerError::OpenAI(OpenAIError::Reqwest(_)) => "provider_unavailable",
+            ServerError::OpenAI(OpenAIError::ApiError(api)) => {
+                let is = |needle: &str| {
+                    [&api.code, &api.r#type]
+                        .iter()
+                        .any(|field| field.as_deref().is_some_and(|f| f.contains(needle)))
+                };
+                if is("rate_limit") || is("insufficient_quota") {
+                    "rate_limited"
+                } else if is("server_error") {
+                    "provider_unavailable"
+                } else {
+                    "provider_error"
+                }
+            }
+            ServerError::OpenAI(_) => "provider_error",
+            _ => "internal",
+        }
+    }
+
+    /// Whether the same request may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            "provider_unavailable" | "rate_limited" | "timeout" | "database" | "network" | "not_ready"
+        )
+    }
+
+    /// Convert into the `McpError` returned from a tool call. `phase` names the step that
+    /// failed (e.g. "embedding", "search", "generation") and is reported with the kind in `data`,
+    /// so clients can choose between retrying, giving up and picking another crate.
+    /// The failure is logged with the same fields, inside the tool's span: at `debug` for the
+    /// `invalid_params` cases below, at `warn` otherwise.
+    pub fn into_mcp_error(self, phase: &str) -> McpError {
+        macro_rules! log_failure {
+            ($level:ident) => {
+                tracing::$level!(
+                    error_kind = self.kind(),
+                    phase,
+                    retryable = self.is_retryable(),
+                    "❌ Tool call failed during {}: {}",
+                    phase,
+                    self
+                )
+            };
+        }
+        match self {
+            ServerError::NotFound(_) | ServerError::Config(_) => log_failure!(debug),
+            _ => log_failure!(warn),
+        }
+        let data = Some(json!({
+            "kind": self.kind(),
+            "phase": phase,
+            "retryable": self.is_retryable(),
+        }));
+        match self {
+            // The caller asked for something that isn't there, or that this server is configured
+            // not to do (e.g. --no-llm); a different argument or tool may work
+            ServerError::NotFound(_) | ServerError::Config(_) => McpError::invalid_params(self.to_string(), data),
+            _ => McpError::internal_error(self.to_string(), data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::error::ApiError;
+    use rmcp::model::ErrorCode;
+
+    fn api_error(r#type: Option<&str>, code: Option<&str>) -> ServerError {
+        ServerError::OpenAI(OpenAIError::ApiError(ApiError {
+            message: "error".to_string(),
+            r#type: r#type.map(str::to_string),
+            param: None,
+            code: code.map(str::to_string),
+        }))
+    }
+
+    #[test]
+    fn not_found_maps_to_invalid_params_with_phase() {
+        let err = ServerError::NotFound("crate 'foo'".to_string()).into_mcp_error("search");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(
+            err.data,
+            Some(json!({ "kind": "not_found", "phase": "search", "retryable": false }))
+        );
+    }
+
+    #[test]
+    fn config_errors_map_to_invalid_params() {
+        let err = ServerError::Config("The chat model is disabled on this server (--no-llm)".to_string())
+            .into_mcp_error("generation");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(
+            err.data,
+            Some(json!({ "kind": "configuration", "phase": "generation", "retryable": false }))
+        );
+    }
+
+    #[test]
+    fn upstream_failures_map_to_retryable_internal_errors() {
+        for (error, kind) in [
+            (ServerError::Timeout("chat".to_string()), "timeout"),
+            (ServerError::ProviderUnavailable("voyage".to_string()), "provider_unavailable"),
+            (api_error(Some("requests"), Some("rate_limit_exceeded")), "rate_limited"),
+            (api_error(Some("server_error"), None), "provider_unavailable"),
+        ] {
+            let err = error.into_mcp_error("embedding");
+            assert_eq!(err.code, ErrorCode::INTERNAL_ERROR);
+            let data = err.data.unwrap();
+            assert_eq!(data["kind"], kind);
+            assert_eq!(data["retryable"], true);
+        }
+    }
+
+    #[test]
+    fn client_errors_are_not_retryable() {
+        assert_eq!(api_error(Some("invalid_request_error"), None).kind(), "provider_error");
+        assert!(!ServerError::Config("bad".to_string()).is_retryable());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+// Heuristics for spotting code samples in extracted documentation text.
+//
+// Rustdoc syntax highlighting splits code into many small text nodes, so stored
+// content for an example is a run of short, punctuation-heavy lines rather than
+// a fenced block. We classify line by line and look at the overall ratio.
+
+/// Minimum share of code-like lines for a document to count as an example
+pub const MIN_CODE_DENSITY: f32 = 0.5;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "const", "crate", "dyn", "else", "enum", "extern", "fn", "for", "if",
+    "impl", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+    "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+];
+
+const CODE_MARKERS: &[&str] = &[";", "{", "}", "::", "=>", "->", "=", "#[", "&", "<", ">", "!("];
+
+/// Whether a single line of extracted text looks like Rust code rather than prose
+pub fn is_code_line(line: &str) -> bool {
+    let line = line.trim();
+    if line.is_empty() {
+        return false;
+    }
+
+    // Long sentences ending in a full stop are prose even if they mention `foo::bar`
+    let words = line.split_whitespace().count();
+    if line.ends_with('.') && words >= 6 {
+        return false;
+    }
+
+    if line.starts_with("//") {
+        return true;
+    }
+
+    let first_word = line
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or("");
+    if RUST_KEYWORDS.contains(&first_word) {
+        return true;
+    }
+
+    line.len() <= 80 && CODE_MARKERS.iter().any(|marker| line.contains(marker))
+}
+
+/// Share of non-empty lines in `content` that look like code (0.0 - 1.0)
+pub fn code_density(content: &str) -> f32 {
+    let mut total = 0usize;
+    let mut code = 0usize;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        total += 1;
+        if is_code_line(line) {
+            code += 1;
+        }
+    }
+
+    if total == 0 {
+        0.0
+    } else {
+        code as f32 / total as f32
+    }
+}
+
+/// Whether a document is predominantly code and worth returning as an example
+pub fn is_code_example(content: &str) -> bool {
+    code_density(content) >= MIN_CODE_DENSITY
+}
+
+const USAGE_QUESTION_MARKERS: &[&str] = &[
+    "how do i", "how can i", "how to", "how would i", "how should i", "example", "show me",
+    "usage", "snippet", "sample code",
+];
+
+/// Whether a question asks how to use something, where code examples beat prose
+pub fn is_usage_question(question: &str) -> bool {
+    let question = question.to_lowercase();
+    USAGE_QUESTION_MARKERS.iter().any(|marker| question.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_usage_questions() {
+        assert!(is_usage_question("How do I spawn a task?"));
+        assert!(is_usage_question("Show me an example of a custom extractor"));
+        assert!(!is_usage_question("What is the difference between Arc and Rc?"));
+    }
+
+    #[test]
+    fn flags_code_heavy_documents() {
+        let example = "use tokio::task;\nlet handle = task::spawn(async {\n    42\n});\nlet out = handle.await?;";
+        assert!(is_code_example(example));
+        assert!(!is_code_example(
+            "Spawns a new asynchronous task, returning a JoinHandle for it.\nThe provided future will start running in the background immediately when spawn is called."
+        ));
+    }
+}
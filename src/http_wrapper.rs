@@ -0,0 +1,238 @@
+// The stdio side of `mcp_http_wrapper`: an MCP server whose `query_rust_docs` tool is
+// answered by the HTTP server's `/query` endpoint, for stdio clients talking to a shared
+// HTTP deployment.
+
+use rmcp::{
+    ServerHandler,
+    service::{RequestContext, RoleServer},
+    model::{
+        CallToolResult, Content,
+        ListResourcesResult, ListPromptsResult,
+        ListResourceTemplatesResult, ReadResourceResult, GetPromptResult,
+        PaginatedRequestParam, ReadResourceRequestParam, GetPromptRequestParam,
+        ProtocolVersion, ServerCapabilities, ServerInfo, Implementation,
+        CallToolRequestParam,
+    },
+    Error as McpError,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Simple wrapper that forwards MCP requests to HTTP server
+#[derive(Clone)]
+pub struct HttpWrapper {
+    http_base_url: String,
+    client: reqwest::Client,
+}
+
+/// Arguments of the `query_rust_docs` tool, mirrored from the HTTP server
+#[derive(Debug, Deserialize)]
+struct QueryRustDocsArgs {
+    crate_name: String,
+    question: String,
+}
+
+impl HttpWrapper {
+    /// A wrapper forwarding to the HTTP server at `http_base_url` (e.g. `http://localhost:3000`)
+    pub fn new(http_base_url: String) -> Self {
+        Self {
+            http_base_url: http_base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Run a `tools/call` against the HTTP server; only `query_rust_docs` is forwarded
+    pub async fn forward_tool_call(&self, params: CallToolRequestParam) -> Result<CallToolResult, McpError> {
+        match params.name.as_ref() {
+            "query_rust_docs" => {
+                let args: QueryRustDocsArgs = serde_json::from_value(
+                    serde_json::Value::Object(params.arguments.unwrap_or_default()),
+                )
+                .map_err(|e| McpError::invalid_params(format!("Invalid arguments for query_rust_docs: {}", e), None))?;
+
+                let response = self.query(&args.crate_name, &args.question).await?;
+                Ok(CallToolResult::success(vec![Content::text(response)]))
+            }
+            other => Err(McpError::invalid_params(format!("Unknown tool: {}", other), None)),
+        }
+    }
+
+    /// POST the question to the HTTP server's `/query` endpoint and return its `response` text.
+    /// A 400 from the backend (unknown crate, bad input) is surfaced as invalid params.
+    async fn query(&self, crate_name: &str, question: &str) -> Result<String, McpError> {
+        let request_body = json!({
+            "crate_name": crate_name,
+            "question": question
+        });
+
+        let response = self.client
+            .post(format!("{}/query", self.http_base_url))
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| McpError::internal_error(format!("HTTP request failed: {}", e), None))?;
+
+        let status = response.status();
+        let json: serde_json::Value = response.json().await
+            .map_err(|e| McpError::internal_error(format!("Failed to parse JSON from backend ({}): {}", status, e), None))?;
+
+        if !status.is_success() {
+            let error_text = json.get("error")
+                .and_then(|e| e.as_str())
+                .unwrap_or("Unknown error")
+                .to_string();
+            return Err(if status == reqwest::StatusCode::BAD_REQUEST {
+                McpError::invalid_params(error_text, None)
+            } else {
+                McpError::internal_error(format!("HTTP API error ({}): {}", status, error_text), None)
+            });
+        }
+
+        json.get("response")
+            .and_then(|r| r.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| McpError::internal_error(
+                format!(
+                    "Failed to parse HTTP response. Got: {}",
+                    serde_json::to_string_pretty(&json).unwrap_or_else(|_| "unparseable".to_string())
+                ),
+                None,
+            ))
+    }
+}
+
+impl ServerHandler for HttpWrapper {
+    fn get_info(&self) -> ServerInfo {
+        let capabilities = ServerCapabilities::builder()
+            .enable_tools()
+            .build();
+
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities,
+            server_info: Implementation {
+                name: "rust-docs-http-wrapper".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            instructions: Some("HTTP wrapper for Rust documentation MCP server. Forwards requests to HTTP backend.".to_string()),
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::service::RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        Ok(ListResourcesResult {
+            resources: vec![],
+            next_cursor: None,
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        _request: ReadResourceRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::service::RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        Err(McpError::invalid_request("No resources available".to_string(), None))
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::service::RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        Ok(ListPromptsResult {
+            prompts: vec![],
+            next_cursor: None,
+        })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::service::RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        Err(McpError::invalid_params(
+            format!("Prompt not found: {}", request.name),
+            None,
+        ))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::service::RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        Ok(ListResourceTemplatesResult {
+            resource_templates: vec![],
+            next_cursor: None,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        params: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.forward_tool_call(params).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<rmcp::model::ListToolsResult, McpError> {
+        Ok(rmcp::model::ListToolsResult {
+            tools: vec![rmcp::model::Tool {
+                name: "query_rust_docs".to_string().into(),
+                description: "Search the documentation of a Rust crate and return the most similar doc snippets (no LLM summary).".to_string().into(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "crate_name": {
+                            "type": "string",
+                            "description": "The crate to search in (e.g., \"axum\", \"tokio\", \"serde\")"
+                        },
+                        "question": {
+                            "type": "string", 
+                            "description": "The specific question about the crate's API or usage."
+                        }
+                    },
+                    "required": ["crate_name", "question"]
+                }).as_object().unwrap().clone().into(),
+            }],
+            next_cursor: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Forwarding itself is tested against the real `/query` route in the HTTP server's tests
+
+    fn tool_call(name: &'static str, arguments: serde_json::Value) -> CallToolRequestParam {
+        CallToolRequestParam {
+            name: name.into(),
+            arguments: arguments.as_object().cloned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_tools_and_bad_arguments() {
+        let wrapper = HttpWrapper::new("http://127.0.0.1:9".to_string());
+        let err = wrapper
+            .forward_tool_call(tool_call("other_tool", json!({})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+
+        let err = wrapper
+            .forward_tool_call(tool_call("query_rust_docs", json!({ "crate_name": "tokio" })))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+    }
+}
@@ -1,6 +1,31 @@
+// Library code never prints to stdout, which the stdio server uses for MCP frames
+#![deny(clippy::print_stdout)]
+
 // Expose modules for use by binaries
+pub mod client;
+pub mod crate_filter;
 pub mod database;
+pub mod dedupe;
 pub mod doc_loader;
 pub mod embeddings;
 pub mod error;
-pub mod server;
\ No newline at end of file
+pub mod examples;
+pub mod http_wrapper;
+pub mod pagination;
+pub mod progress;
+pub mod prompt_guard;
+pub mod qdrant_store;
+pub mod query_limit;
+pub mod rate_limit;
+pub mod refresh;
+pub mod resources;
+pub mod robots;
+pub mod runtime_info;
+pub mod search_cache;
+pub mod self_test;
+pub mod secrets;
+pub mod server;
+pub mod sqlite_store;
+pub mod verbosity;
+pub mod vector_cache;
+pub mod version_diff;
\ No newline at end of file
@@ -1,21 +1,17 @@
-// Declare modules
-mod database;
-mod doc_loader;
-mod embeddings;
-mod error;
-mod server;
-
-// Use necessary items from modules and crates
-use crate::{
-    database::Database,
+// Stdout carries the MCP stdio transport; a stray print there corrupts the client's stream
+#![deny(clippy::print_stdout)]
+
+// Use necessary items from the library crate
+use rustdocs_mcp_server::{
+    database::{self, DistanceMetric},
     embeddings::{EMBEDDING_CLIENT, EmbeddingConfig, initialize_embedding_provider},
     error::ServerError,
+    self_test,
     server::RustDocsServer,
+    verbosity::VerbosityArgs,
 };
 use serde::{Deserialize, Serialize};
-use async_openai::{Client as OpenAIClient, config::OpenAIConfig};
 use clap::Parser;
-use std::env;
 use rmcp::{
     transport::io::stdio,
     ServiceExt,
@@ -24,6 +20,31 @@ use rmcp::{
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use tracing::{debug, error, info, warn};
+
+/// `--list` output. Everything else on stdout must be an MCP frame, so this is the only
+/// place allowed to print there, and it runs instead of (never alongside) the server.
+#[allow(clippy::print_stdout)]
+fn print_crate_list(stats: Vec<database::CrateStats>) {
+    if stats.is_empty() {
+        println!("No crates found in database.");
+        println!("Use the 'populate_db' tool to add crates first:");
+        println!("  cargo run --bin populate_db -- <crate_name>");
+    } else {
+        println!("{:<20} {:<15} {:<10} {:<10} {:<20}", "Crate", "Version", "Docs", "Tokens", "Last Updated");
+        println!("{:-<80}", "");
+        for stat in stats {
+            println!(
+                "{:<20} {:<15} {:<10} {:<10} {:<20}",
+                stat.name,
+                stat.version.unwrap_or_else(|| "N/A".to_string()),
+                stat.total_docs,
+                stat.total_tokens,
+                stat.last_updated.format("%Y-%m-%d %H:%M")
+            );
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 struct ProxyConfig {
@@ -37,10 +58,16 @@ struct CrateConfig {
     enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     expected_docs: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_pages: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Rust documentation MCP server using PostgreSQL vector database", long_about = None)]
+#[command(author, version, about = "Rust documentation MCP server using a PostgreSQL or SQLite vector database", long_about = None)]
 struct Cli {
     /// The crate names to serve documentation for (space-separated)
     crate_names: Vec<String>,
@@ -60,6 +87,33 @@ struct Cli {
     /// Embedding model to use
     #[arg(long)]
     embedding_model: Option<String>,
+
+    /// Distance metric for vector search (must match the database's vector index)
+    #[arg(long, value_enum, default_value_t = DistanceMetric::Cosine, env = "MCPDOCS_DISTANCE")]
+    distance: DistanceMetric,
+
+    /// Maximum tool calls per minute (unlimited if not set)
+    #[arg(long, env = "MCPDOCS_RATE_LIMIT_PER_MINUTE")]
+    rate_limit_per_minute: Option<u32>,
+
+    /// Enable the refresh_crate tool, which re-scrapes docs.rs and updates the database
+    #[arg(long, env = "MCPDOCS_ALLOW_REFRESH")]
+    allow_refresh: bool,
+
+    /// Answer query_rust_docs with the top matching documents verbatim, never calling the chat model
+    #[arg(long, env = "MCPDOCS_NO_LLM")]
+    no_llm: bool,
+
+    /// Embed a test question and search every served crate before serving; exit non-zero on failure
+    #[arg(long, env = "MCPDOCS_SELF_TEST")]
+    self_test: bool,
+
+    /// Serve crates embedded with a different model than the configured one (only warn)
+    #[arg(long, env = "MCPDOCS_ALLOW_MODEL_MISMATCH")]
+    allow_model_mismatch: bool,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
 }
 
 #[tokio::main]
@@ -69,126 +123,111 @@ async fn main() -> Result<(), ServerError> {
 
     // Parse CLI arguments
     let cli = Cli::parse();
+    // Logs go to stderr; stdout carries the MCP stdio transport
+    cli.verbosity.apply(env!("CARGO_CRATE_NAME"))?;
 
     // Initialize database connection
-    eprintln!("🔌 Connecting to database...");
-    let db = Database::new().await?;
-    eprintln!("✅ Database connected successfully");
+    info!("🔌 Connecting to database...");
+    let db = database::connect_with_metric(cli.distance).await?;
+    info!("✅ Database connected successfully");
 
     // Handle list command
     if cli.list {
-        let stats = db.get_crate_stats().await?;
-        if stats.is_empty() {
-            println!("No crates found in database.");
-            println!("Use the 'populate_db' tool to add crates first:");
-            println!("  cargo run --bin populate_db -- <crate_name>");
-        } else {
-            println!("{:<20} {:<15} {:<10} {:<10} {:<20}", "Crate", "Version", "Docs", "Tokens", "Last Updated");
-            println!("{:-<80}", "");
-            for stat in stats {
-                println!(
-                    "{:<20} {:<15} {:<10} {:<10} {:<20}",
-                    stat.name,
-                    stat.version.unwrap_or_else(|| "N/A".to_string()),
-                    stat.total_docs,
-                    stat.total_tokens,
-                    stat.last_updated.format("%Y-%m-%d %H:%M")
-                );
-            }
-        }
+        print_crate_list(db.get_crate_stats().await?);
         return Ok(());
     }
 
     // Determine which crates to load
-    let crate_names = if cli.all {
-        eprintln!("Loading all available crates from database...");
+    let mut crate_names: Vec<String> = if cli.all {
+        info!("Loading all available crates from database...");
         let stats = db.get_crate_stats().await?;
         if stats.is_empty() {
-            eprintln!("No crates found in database. Use 'populate_db' to add some first.");
+            warn!("No crates found in database. Use 'populate_db' to add some first.");
             return Ok(());
         }
         stats.into_iter().map(|stat| stat.name).collect()
     } else if cli.crate_names.is_empty() {
-        eprintln!("Error: Please specify crate names or use --all to load all crates");
-        eprintln!("Usage examples:");
-        eprintln!("  cargo run --bin rustdocs_mcp_server -- anyhow tokio serde");
-        eprintln!("  cargo run --bin rustdocs_mcp_server -- --all");
-        eprintln!("  cargo run --bin rustdocs_mcp_server -- --list");
+        error!("Please specify crate names or use --all to load all crates");
+        info!("Usage examples:");
+        info!("cargo run --bin rustdocs_mcp_server -- anyhow tokio serde");
+        info!("cargo run --bin rustdocs_mcp_server -- --all");
+        info!("cargo run --bin rustdocs_mcp_server -- --list");
         return Err(ServerError::Config("No crate names specified".to_string()));
     } else {
         cli.crate_names
     };
 
-    eprintln!("Target crates: {:?}", crate_names);
+    info!("Target crates: {:?}", crate_names);
 
     // Check if all crates exist in database
-    eprintln!("🔍 Checking if crates exist in database...");
-    let mut missing_crates = Vec::new();
-    for crate_name in &crate_names {
-        eprintln!("  Checking: {}", crate_name);
-        if !db.has_embeddings(crate_name).await? {
-            missing_crates.push(crate_name.clone());
-            eprintln!("  ❌ Missing: {}", crate_name);
-        } else {
-            eprintln!("  ✅ Found: {}", crate_name);
-        }
+    info!("🔍 Checking if crates exist in database...");
+    let (found_crates, missing_crates) = db.filter_existing_crates(&crate_names).await?;
+    for crate_name in &found_crates {
+        debug!("✅ Found: {}", crate_name);
+    }
+    for crate_name in &missing_crates {
+        warn!("❌ Missing: {}", crate_name);
     }
 
     if !missing_crates.is_empty() {
-        eprintln!("Error: The following crates are not found in the database:");
+        error!("The following crates are not found in the database:");
         for crate_name in &missing_crates {
-            eprintln!("  - {}", crate_name);
+            info!("- {}", crate_name);
         }
-        eprintln!("\nPlease populate them first using:");
+        info!("Please populate them first using:");
         for crate_name in &missing_crates {
-            eprintln!("  cargo run --bin populate_db -- --crate-name {}", crate_name);
+            info!("cargo run --bin populate_db -- --crate-name {}", crate_name);
         }
-        eprintln!("\nOr see available crates with:");
-        eprintln!("  cargo run --bin rustdocs_mcp_server -- --list");
+        info!("Or see available crates with:");
+        info!("cargo run --bin rustdocs_mcp_server -- --list");
         return Err(ServerError::Config(format!("Missing crates: {:?}", missing_crates)));
     }
 
     // Initialize embedding provider (needed for query embedding)
     let provider_name = cli.embedding_provider.to_lowercase();
-    eprintln!("🤖 Initializing {} embedding provider...", provider_name);
-
-    let embedding_config = match provider_name.as_str() {
-        "openai" => {
-            let model = cli.embedding_model.unwrap_or_else(|| "text-embedding-3-large".to_string());
-            let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-                let config = OpenAIConfig::new().with_api_base(api_base);
-                OpenAIClient::with_config(config)
-            } else {
-                OpenAIClient::new()
-            };
-            EmbeddingConfig::OpenAI {
-                client: openai_client,
-                model,
-            }
-        },
-        "voyage" => {
-            let api_key = env::var("VOYAGE_API_KEY")
-                .map_err(|_| ServerError::MissingEnvVar("VOYAGE_API_KEY".to_string()))?;
-            let model = cli.embedding_model.unwrap_or_else(|| "voyage-3.5".to_string());
-            EmbeddingConfig::VoyageAI { api_key, model }
-        },
-        _ => {
-            return Err(ServerError::Config(format!(
-                "Unsupported embedding provider: {}. Use 'openai' or 'voyage'",
-                provider_name
-            )));
-        }
-    };
+    info!("🤖 Initializing {} embedding provider...", provider_name);
+
+    let embedding_config = EmbeddingConfig::from_provider(&provider_name, cli.embedding_model)?;
 
     let provider = initialize_embedding_provider(embedding_config);
+    database::check_embedding_dimensions(db.as_ref(), provider.as_ref()).await?;
+
+    // Read once for the model check, the backfill check and the startup summary below
+    let stats = db.get_crate_stats().await?;
+    // A crate embedded with another model still returns results, just poor ones
+    let mut mismatched = Vec::new();
+    for stat in stats.iter().filter(|stat| crate_names.contains(&stat.name)) {
+        if let Some(problem) = database::crate_embedding_mismatch(db.as_ref(), provider.as_ref(), stat).await? {
+            warn!("⚠️  {}: {}", stat.name, problem);
+            mismatched.push(stat.name.clone());
+        }
+    }
+    if !mismatched.is_empty() {
+        if cli.allow_model_mismatch {
+            warn!("⚠️  Serving {} crates with mismatched embeddings (--allow-model-mismatch)", mismatched.len());
+        } else {
+            crate_names.retain(|name| !mismatched.contains(name));
+            warn!("⚠️  Not serving {} crates with mismatched embeddings. Re-populate them with the configured model:", mismatched.len());
+            for crate_name in &mismatched {
+                info!("cargo run --bin populate_db -- --crate-name {}", crate_name);
+            }
+            if crate_names.is_empty() {
+                return Err(ServerError::Config(format!(
+                    "Every requested crate was embedded with a different model than '{}'; \
+                     re-populate them or pass --allow-model-mismatch",
+                    provider.get_model_name()
+                )));
+            }
+        }
+    }
     if EMBEDDING_CLIENT.set(provider).is_err() {
         return Err(ServerError::Internal("Failed to set embedding provider".to_string()));
     }
-    eprintln!("✅ {} embedding provider initialized", provider_name);
+    info!("✅ {} embedding provider initialized", provider_name);
 
     // Check for automatic backfill requirements
     if Path::new("proxy-config.json").exists() {
-        eprintln!("📋 Checking proxy-config.json for automatic backfill requirements...");
+        info!("📋 Checking proxy-config.json for automatic backfill requirements...");
         
         let config_content = fs::read_to_string("proxy-config.json")
             .map_err(|e| ServerError::Config(format!("Failed to read proxy-config.json: {}", e)))?;
@@ -209,7 +248,10 @@ async fn main() -> Result<(), ServerError> {
             }
             
             if let Some(expected_docs) = crate_config.expected_docs {
-                let current_count = db.count_crate_documents(&crate_config.name).await?;
+                let current_count = stats
+                    .iter()
+                    .find(|stat| stat.name == crate_config.name)
+                    .map_or(0, |stat| stat.total_docs as usize);
                 
                 if current_count < expected_docs {
                     needs_backfill.push((
@@ -218,59 +260,58 @@ async fn main() -> Result<(), ServerError> {
                         expected_docs,
                         crate_config.features.clone(),
                     ));
-                    eprintln!("  ⚠️  {}: {} docs in DB < {} expected", 
+                    warn!("⚠️  {}: {} docs in DB < {} expected", 
                         crate_config.name, current_count, expected_docs);
                 } else {
-                    eprintln!("  ✅ {}: {} docs in DB >= {} expected", 
+                    info!("✅ {}: {} docs in DB >= {} expected", 
                         crate_config.name, current_count, expected_docs);
                 }
             }
         }
 
         if !needs_backfill.is_empty() {
-            eprintln!("\n🔄 Automatic backfill required for {} crates:", needs_backfill.len());
+            info!("🔄 Automatic backfill required for {} crates:", needs_backfill.len());
             for (crate_name, current, expected, features) in &needs_backfill {
-                eprintln!("  📦 {}: {} -> {} docs", crate_name, current, expected);
+                info!("📦 {}: {} -> {} docs", crate_name, current, expected);
                 if let Some(features) = features {
-                    eprintln!("     Features: {:?}", features);
+                    info!("Features: {:?}", features);
                 }
             }
             
-            eprintln!("\n💡 To trigger backfill, run:");
+            info!("💡 To trigger backfill, run:");
             for (crate_name, _, _, features) in &needs_backfill {
                 if let Some(features) = features {
-                    eprintln!("  cargo run --bin populate_db -- --crate-name {} --features {}", 
+                    info!("cargo run --bin populate_db -- --crate-name {} --features {}", 
                         crate_name, features.join(","));
                 } else {
-                    eprintln!("  cargo run --bin populate_db -- --crate-name {}", crate_name);
+                    info!("cargo run --bin populate_db -- --crate-name {}", crate_name);
                 }
             }
-            eprintln!("⚠️  Server will continue with current document counts");
+            warn!("⚠️  Server will continue with current document counts");
         } else {
-            eprintln!("✅ All crates have sufficient documentation in database");
+            info!("✅ All crates have sufficient documentation in database");
         }
     }
 
     // Verify crates exist in database (no loading into memory)
-    eprintln!("🔍 Verifying {} crates are available in database...", crate_names.len());
+    info!("🔍 Verifying {} crates are available in database...", crate_names.len());
     let mut crate_stats = HashMap::new();
-    
+
     for crate_name in &crate_names {
-        let stats = db.get_crate_stats().await?;
         let crate_stat = stats.iter().find(|s| &s.name == crate_name);
         if let Some(stat) = crate_stat {
             crate_stats.insert(crate_name.clone(), stat.total_docs);
-            eprintln!("  ✅ {}: {} documents available", crate_name, stat.total_docs);
+            debug!("✅ {}: {} documents available", crate_name, stat.total_docs);
         } else {
-            eprintln!("  ❌ {}: not found in database", crate_name);
+            warn!("❌ {}: not found in database", crate_name);
         }
     }
 
     let total_available_docs: i64 = crate_stats.values().map(|&v| v as i64).sum();
     
-    eprintln!("\n📊 Database Summary:");
-    eprintln!("  📚 Total available documents: {}", total_available_docs);
-    eprintln!("  🗄️  Database-driven search (no memory loading)");
+    info!("📊 Database Summary:");
+    info!("📚 Total available documents: {}", total_available_docs);
+    info!("🗄️  Database-driven search (no memory loading)");
 
     let startup_message = if crate_names.len() == 1 {
         let doc_count = crate_stats.get(&crate_names[0]).unwrap_or(&0);
@@ -291,9 +332,30 @@ async fn main() -> Result<(), ServerError> {
         )
     };
 
-    eprintln!("\n✅ {}", startup_message);
+    info!("✅ {}", startup_message);
+
+    if cli.self_test {
+        info!("🧪 Running self-test for {} crates...", crate_names.len());
+        let provider = EMBEDDING_CLIENT
+            .get()
+            .ok_or_else(|| ServerError::Internal("Embedding provider not initialized".to_string()))?;
+        let checks = self_test::run_self_test(db.as_ref(), provider.as_ref(), &crate_names).await?;
+        for check in &checks {
+            match &check.problem {
+                None => info!(
+                    "✅ {}: {} results, best similarity {:.3}",
+                    check.crate_name,
+                    check.results,
+                    check.best_similarity.unwrap_or_default()
+                ),
+                Some(problem) => warn!("❌ {}: {}", check.crate_name, problem),
+            }
+        }
+        self_test::require_all_passed(&checks)?;
+        info!("✅ Self-test passed");
+    }
 
-    // Create the service instance (no documents/embeddings in memory)
+    // Create the service instance (no embeddings in memory)
     let combined_crate_name = if crate_names.len() == 1 {
         crate_names[0].clone()
     } else {
@@ -302,28 +364,35 @@ async fn main() -> Result<(), ServerError> {
 
     let service = RustDocsServer::new(
         combined_crate_name.clone(),
-        vec![], // No documents in memory - use database search
         vec![], // No embeddings in memory - generate on demand
         db,
         startup_message,
-    )?;
+    )?
+    .with_crate_names(crate_names.clone())
+    .with_rate_limit(cli.rate_limit_per_minute)
+    .with_refresh(cli.allow_refresh)
+    .with_no_llm(cli.no_llm);
+
+    if cli.no_llm {
+        info!("📄 --no-llm: answers are the retrieved documents verbatim, not LLM summaries");
+    }
 
-    eprintln!("Rust Docs MCP server starting via stdio...");
+    info!("Rust Docs MCP server starting via stdio...");
 
     // Serve the server using stdio transport
     let server_handle = service.serve(stdio()).await.map_err(|e| {
-        eprintln!("Failed to start server: {:?}", e);
+        error!("Failed to start server: {:?}", e);
         ServerError::McpRuntime(e.to_string())
     })?;
 
-    eprintln!("Rust Docs MCP server running for: {}", combined_crate_name);
+    info!("Rust Docs MCP server running for: {}", combined_crate_name);
 
     // Wait for the server to complete
     server_handle.waiting().await.map_err(|e| {
-        eprintln!("Server encountered an error while running: {:?}", e);
+        error!("Server encountered an error while running: {:?}", e);
         ServerError::McpRuntime(e.to_string())
     })?;
 
-    eprintln!("Rust Docs MCP server stopped.");
+    info!("Rust Docs MCP server stopped.");
     Ok(())
 }
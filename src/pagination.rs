@@ -0,0 +1,85 @@
+// Opaque cursors for MCP list requests. A cursor is the URL-safe base64 of the last key
+// returned (a resource URI, or an offset for in-memory lists), so clients pass it back
+// unchanged instead of building their own.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rmcp::{model::PaginatedRequestParam, Error as McpError};
+use serde_json::json;
+
+/// Items returned per page of an in-memory list
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+pub fn encode_cursor(key: &str) -> String {
+    URL_SAFE_NO_PAD.encode(key)
+}
+
+/// The key inside a cursor from [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Result<String, McpError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| invalid_cursor(cursor))
+}
+
+/// `invalid_params` for a cursor this server didn't issue (or no longer recognizes)
+pub fn invalid_cursor(cursor: &str) -> McpError {
+    McpError::invalid_params(
+        format!("Invalid pagination cursor: {}", cursor),
+        Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "cursor" })),
+    )
+}
+
+/// The cursor of a list request, if the client sent one
+pub fn request_cursor(request: PaginatedRequestParam) -> Option<String> {
+    request.and_then(|param| param.cursor)
+}
+
+/// One page of `items` starting at `cursor`, and the cursor of the page after it
+pub fn paginate<T>(
+    items: Vec<T>,
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<T>, Option<String>), McpError> {
+    let offset = match cursor {
+        None => 0,
+        Some(cursor) => decode_cursor(cursor)?
+            .parse::<usize>()
+            .ok()
+            .filter(|offset| *offset <= items.len())
+            .ok_or_else(|| invalid_cursor(cursor))?,
+    };
+    let end = offset.saturating_add(page_size).min(items.len());
+    let next_cursor = (end < items.len()).then(|| encode_cursor(&end.to_string()));
+    let page = items.into_iter().skip(offset).take(end - offset).collect();
+    Ok((page, next_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ErrorCode;
+
+    #[test]
+    fn pages_through_a_list_in_two_requests() {
+        let items: Vec<u32> = (0..150).collect();
+
+        let (first, cursor) = paginate(items.clone(), None, DEFAULT_PAGE_SIZE).unwrap();
+        assert_eq!(first, (0..100).collect::<Vec<_>>());
+        let cursor = cursor.expect("a second page");
+
+        let (second, cursor) = paginate(items, Some(&cursor), DEFAULT_PAGE_SIZE).unwrap();
+        assert_eq!(second, (100..150).collect::<Vec<_>>());
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn rejects_cursors_it_did_not_issue() {
+        let items = vec!["a", "b"];
+        for cursor in ["not base64!", &encode_cursor("3"), &encode_cursor("doc://axum/x.html")] {
+            let err = paginate(items.clone(), Some(cursor), 1).unwrap_err();
+            assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        }
+        assert_eq!(decode_cursor(&encode_cursor("doc://axum/x.html")).unwrap(), "doc://axum/x.html");
+    }
+}
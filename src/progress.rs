@@ -0,0 +1,119 @@
+// Progress display for the populate tools. When stderr is a terminal each phase (loading
+// documents, embedding chunks, inserting rows) gets an indicatif bar with throughput and ETA;
+// otherwise, and under `--quiet`, a log line is written at every tenth of the work instead.
+// Log lines written while bars are drawn are printed above them.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::LazyLock,
+    time::Duration,
+};
+use tracing::{info, Level};
+
+/// Every bar, so concurrent populations (`populate_all`) stack instead of overwriting each other
+static BARS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Progress of one phase, counted in `unit`s
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    label: String,
+    unit: &'static str,
+}
+
+impl Progress {
+    /// A bar for `total` units (0 if not known yet; [`Self::set`] updates it)
+    pub fn new(label: impl Into<String>, unit: &'static str, total: u64) -> Self {
+        let label = label.into();
+        let bar = bars_enabled().then(|| {
+            let bar = BARS.add(ProgressBar::new(total));
+            bar.set_style(
+                ProgressStyle::with_template(&format!(
+                    "{{prefix}} [{{bar:30}}] {{pos}}/{{len}} {} ({{per_sec}}, ETA {{eta}})",
+                    unit
+                ))
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            );
+            bar.set_prefix(label.clone());
+            bar
+        });
+        Self { bar, label, unit }
+    }
+
+    /// A spinner for work of unknown size, showing the elapsed time
+    pub fn spinner(label: impl Into<String>) -> Self {
+        let label = label.into();
+        let bar = bars_enabled().then(|| {
+            let bar = BARS.add(ProgressBar::new_spinner());
+            bar.set_style(ProgressStyle::with_template("{spinner} {prefix} ({elapsed})").expect("valid progress template"));
+            bar.set_prefix(label.clone());
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+        Self { bar, label, unit: "" }
+    }
+
+    /// `done` of `total` units are complete
+    pub fn set(&self, done: u64, total: u64) {
+        match &self.bar {
+            Some(bar) => {
+                bar.set_length(total);
+                bar.set_position(done);
+            }
+            None => {
+                if let Some(percent) = crossed_tenth(done, total) {
+                    info!("{} {}/{} {} ({}%)", self.label, done, total, self.unit, percent);
+                }
+            }
+        }
+    }
+
+    /// Remove the bar; the caller logs the phase's summary
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+            BARS.remove(bar);
+        }
+    }
+}
+
+/// Bars need a terminal to draw on, and are noise when only warnings are wanted
+fn bars_enabled() -> bool {
+    io::stderr().is_terminal() && tracing::enabled!(Level::INFO)
+}
+
+/// The percentage to log when `done` is the first unit into a new tenth of `total`
+fn crossed_tenth(done: u64, total: u64) -> Option<u64> {
+    if total == 0 || done == 0 || done > total {
+        return None;
+    }
+    (done == total || done * 10 / total != (done - 1) * 10 / total).then(|| done * 100 / total)
+}
+
+/// stderr for the log subscriber, clearing any bars while a line is written
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        BARS.suspend(|| io::stderr().write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_once_per_tenth_without_a_terminal() {
+        let logged: Vec<u64> = (1..=25).filter_map(|done| crossed_tenth(done, 25)).collect();
+        assert_eq!(logged, [12, 20, 32, 40, 52, 60, 72, 80, 92, 100]);
+        assert_eq!(crossed_tenth(3, 3), Some(100));
+        assert_eq!(crossed_tenth(0, 0), None);
+    }
+}
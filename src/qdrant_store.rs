@@ -0,0 +1,1237 @@
+// Qdrant backend, selected by a `qdrant://` MCPDOCS_DATABASE_URL. Documents are points of
+// one collection, carrying the crate name, path and content as payload; crate metadata
+// (version, stats, status, dependencies) lives in a second, `_crates` collection. It speaks
+// Qdrant's REST API over the reqwest client the crate already uses. `qdrant-client` is avoided
+// on purpose: it talks gRPC and would add tonic and prost to every build for an optional
+// backend. The tests below pin the request and response shapes to recorded Qdrant responses.
+
+use crate::{
+    database::{
+        assemble_page, join_features, split_features, Backend, CrateStats, CrateStatus, DistanceMetric, DocFilter, DocMatch,
+        GlobalStats, VectorStore, EXAMPLE_CANDIDATE_FACTOR,
+    },
+    dedupe,
+    doc_loader,
+    error::ServerError,
+    examples,
+    secrets,
+};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use ndarray::Array1;
+use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use tokio::sync::OnceCell;
+use tracing::info;
+
+/// Collection used when the URL names none (`qdrant://localhost:6333`)
+pub const DEFAULT_QDRANT_COLLECTION: &str = "rust_docs";
+
+/// Points sent per upsert request
+const UPSERT_BATCH_POINTS: usize = 256;
+/// Points fetched per scroll request
+const SCROLL_PAGE_POINTS: usize = 1000;
+/// Timeout for a single Qdrant request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The REST endpoint and collection named by a Qdrant URL: `qdrant://host:6333/collection`
+/// talks plain HTTP, `qdrant+https://host/collection` HTTPS
+pub fn parse_qdrant_url(url: &str) -> Result<(String, String), ServerError> {
+    let (scheme, rest) = if let Some(rest) = url.strip_prefix("qdrant+https://") {
+        ("https", rest)
+    } else if let Some(rest) = url.strip_prefix("qdrant://") {
+        ("http", rest)
+    } else {
+        return Err(ServerError::Config(format!("Not a Qdrant URL: {}", url)));
+    };
+    let (host, collection) = rest.split_once('/').unwrap_or((rest, ""));
+    let collection = collection.trim_matches('/');
+    if host.is_empty() {
+        return Err(ServerError::Config(format!("Qdrant URL has no host: {}", url)));
+    }
+    let collection = if collection.is_empty() { DEFAULT_QDRANT_COLLECTION } else { collection };
+    Ok((format!("{}://{}", scheme, host), collection.to_string()))
+}
+
+/// Stable point id for a key (Qdrant ids are integers or UUIDs), so writing the same
+/// crate or document again replaces its point
+fn point_id(key: &str) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Uuid::from_bytes(bytes).to_string()
+}
+
+fn doc_point_id(crate_name: &str, doc_path: &str) -> String {
+    point_id(&format!("doc\0{}\0{}", crate_name, doc_path))
+}
+
+fn crate_point_id(crate_name: &str) -> String {
+    point_id(&format!("crate\0{}", crate_name))
+}
+
+/// `tokio::sync::mpsc` and its parents, so a `module_prefix` search is one keyword match
+fn module_ancestors(module_path: Option<&str>) -> Vec<String> {
+    let Some(module_path) = module_path else {
+        return Vec::new();
+    };
+    let parts: Vec<&str> = module_path.split("::").collect();
+    (1..=parts.len()).map(|end| parts[..end].join("::")).collect()
+}
+
+/// Qdrant distance name for a metric
+fn qdrant_distance(metric: DistanceMetric) -> &'static str {
+    match metric {
+        DistanceMetric::Cosine => "Cosine",
+        DistanceMetric::L2 => "Euclid",
+        DistanceMetric::InnerProduct => "Dot",
+    }
+}
+
+/// A search score as the same higher-is-better similarity the SQL backends report:
+/// Qdrant scores cosine and dot as similarities, but Euclid as the distance itself
+fn score_to_similarity(metric: DistanceMetric, score: f32) -> f32 {
+    match metric {
+        DistanceMetric::L2 => 1.0 - score * score / 2.0,
+        _ => score,
+    }
+}
+
+/// Filter matching every condition `(key, value)` exactly
+fn match_all(conditions: &[(&str, Value)]) -> Value {
+    let must: Vec<Value> = conditions
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "match": { "value": value } }))
+        .collect();
+    json!({ "must": must })
+}
+
+/// Payload of a document point; searches and scrolls only fetch some of the fields
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DocPayload {
+    crate_id: i32,
+    crate_name: String,
+    doc_path: String,
+    content: String,
+    token_count: Option<i32>,
+    has_code_example: bool,
+    module_path: Option<String>,
+    module_ancestors: Vec<String>,
+    content_hash: Option<String>,
+    anchor: Option<String>,
+    doc_kind: Option<String>,
+}
+
+/// Payload of a crate point
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CratePayload {
+    id: i32,
+    name: String,
+    version: Option<String>,
+    last_updated: chrono::NaiveDateTime,
+    total_docs: i32,
+    total_tokens: i32,
+    features: Option<String>,
+    embedding_model: Option<String>,
+    status: String,
+    status_updated_at: Option<chrono::NaiveDateTime>,
+    dependencies: Vec<String>,
+}
+
+impl CratePayload {
+    fn into_stats(self) -> CrateStats {
+        CrateStats {
+            status: CrateStatus::from_db(&self.status, self.status_updated_at),
+            name: self.name,
+            version: self.version,
+            last_updated: self.last_updated,
+            total_docs: self.total_docs,
+            total_tokens: self.total_tokens,
+            features: split_features(self.features),
+            embedding_model: self.embedding_model,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Point<P> {
+    payload: P,
+    #[serde(default)]
+    vector: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScoredPoint {
+    score: f32,
+    payload: DocPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrollPage<P> {
+    points: Vec<Point<P>>,
+    next_page_offset: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountResult {
+    count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionInfo {
+    points_count: Option<usize>,
+    config: CollectionConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionConfig {
+    params: CollectionParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionParams {
+    vectors: VectorParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct VectorParams {
+    size: usize,
+    distance: String,
+}
+
+/// Vector store backed by a Qdrant server
+pub struct QdrantStore {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    collection: String,
+    distance: DistanceMetric,
+    /// Set once the documents collection is known to exist; it is created on first insert,
+    /// when the vector size is known
+    documents_ready: OnceCell<()>,
+}
+
+impl QdrantStore {
+    /// Connect to the Qdrant server at `url` (see [`parse_qdrant_url`]), creating the crates
+    /// collection if needed. `QDRANT_API_KEY` (or `QDRANT_API_KEY_FILE`) is sent when set.
+    pub async fn connect(url: &str) -> Result<Self, ServerError> {
+        let (base_url, collection) = parse_qdrant_url(url)?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| ServerError::Network(format!("Failed to build Qdrant client: {}", e)))?;
+        let store = Self {
+            client,
+            base_url,
+            api_key: secrets::env_or_file("QDRANT_API_KEY")?,
+            collection,
+            distance: DistanceMetric::default(),
+            documents_ready: OnceCell::new(),
+        };
+
+        let crates = store.crates_collection();
+        if store.collection_info(&crates).await?.is_none() {
+            // Crate records are looked up by id and filtered by payload, never searched,
+            // so their collection holds a one-dimensional placeholder vector
+            store
+                .call::<Value>(Method::PUT, &format!("/collections/{}", crates), Some(json!({
+                    "vectors": { "size": 1, "distance": "Dot" }
+                })))
+                .await?;
+            store.create_keyword_index(&crates, "name").await?;
+            info!("🗂️  Created Qdrant collection {}", crates);
+        }
+        Ok(store)
+    }
+
+    /// Rank search results with `metric`; it must match the distance of an existing collection
+    pub fn with_distance_metric(mut self, metric: DistanceMetric) -> Self {
+        self.distance = metric;
+        self
+    }
+
+    fn crates_collection(&self) -> String {
+        format!("{}_crates", self.collection)
+    }
+
+    /// Send a request and return the `result` of Qdrant's response, or `None` on a 404
+    /// (a collection or point that doesn't exist)
+    async fn call<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<Value>) -> Result<Option<T>, ServerError> {
+        let mut request = self.client.request(method.clone(), format!("{}{}", self.base_url, path));
+        if let Some(api_key) = &self.api_key {
+            request = request.header("api-key", api_key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ServerError::Database(format!("Qdrant request {} {} failed: {}", method, path, e)))?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ServerError::Database(format!("Qdrant {} {} returned {}: {}", method, path, status, body)));
+        }
+
+        #[derive(Deserialize)]
+        struct Envelope<T> {
+            result: T,
+        }
+        let envelope: Envelope<T> = response
+            .json()
+            .await
+            .map_err(|e| ServerError::Database(format!("Unexpected Qdrant response to {} {}: {}", method, path, e)))?;
+        Ok(Some(envelope.result))
+    }
+
+    async fn collection_info(&self, collection: &str) -> Result<Option<CollectionInfo>, ServerError> {
+        self.call(Method::GET, &format!("/collections/{}", collection), None).await
+    }
+
+    async fn create_keyword_index(&self, collection: &str, field: &str) -> Result<(), ServerError> {
+        self.call::<Value>(
+            Method::PUT,
+            &format!("/collections/{}/index?wait=true", collection),
+            Some(json!({ "field_name": field, "field_schema": "keyword" })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Create the documents collection for `dimensions`-sized vectors if it doesn't exist
+    async fn ensure_documents_collection(&self, dimensions: usize) -> Result<(), ServerError> {
+        self.documents_ready
+            .get_or_try_init(|| async {
+                if self.collection_info(&self.collection).await?.is_none() {
+                    self.call::<Value>(Method::PUT, &format!("/collections/{}", self.collection), Some(json!({
+                        "vectors": { "size": dimensions, "distance": qdrant_distance(self.distance) }
+                    })))
+                    .await?;
+                    for field in ["crate_name", "doc_path", "module_ancestors", "doc_kind"] {
+                        self.create_keyword_index(&self.collection, field).await?;
+                    }
+                    info!("🗂️  Created Qdrant collection {} ({} dimensions)", self.collection, dimensions);
+                }
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// Every point of `collection` matching `filter`, with the `fields` of its payload
+    async fn scroll<P: DeserializeOwned>(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        with_vector: bool,
+    ) -> Result<Vec<Point<P>>, ServerError> {
+        let mut points = Vec::new();
+        let mut offset: Option<Value> = None;
+        loop {
+            let mut body = json!({
+                "filter": filter,
+                "limit": SCROLL_PAGE_POINTS,
+                "with_payload": if fields.is_empty() { json!(true) } else { json!(fields) },
+                "with_vector": with_vector,
+            });
+            if let Some(offset) = offset.take() {
+                body["offset"] = offset;
+            }
+            let page: Option<ScrollPage<P>> = self
+                .call(Method::POST, &format!("/collections/{}/points/scroll", collection), Some(body))
+                .await?;
+            let Some(page) = page else {
+                return Ok(points);
+            };
+            points.extend(page.points);
+            match page.next_page_offset.filter(|offset| !offset.is_null()) {
+                Some(next) => offset = Some(next),
+                None => return Ok(points),
+            }
+        }
+    }
+
+    async fn count(&self, filter: Value) -> Result<usize, ServerError> {
+        let result: Option<CountResult> = self
+            .call(
+                Method::POST,
+                &format!("/collections/{}/points/count", self.collection),
+                Some(json!({ "filter": filter, "exact": true })),
+            )
+            .await?;
+        Ok(result.map(|result| result.count).unwrap_or(0))
+    }
+
+    /// `doc_path` of every document of a crate, sorted
+    async fn crate_doc_paths(&self, crate_name: &str) -> Result<Vec<String>, ServerError> {
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(&self.collection, match_all(&[("crate_name", json!(crate_name))]), &["doc_path"], false)
+            .await?;
+        let mut paths: Vec<String> = points.into_iter().map(|point| point.payload.doc_path).collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    async fn get_crate(&self, crate_name: &str) -> Result<Option<CratePayload>, ServerError> {
+        let points: Option<Vec<Point<CratePayload>>> = self
+            .call(
+                Method::POST,
+                &format!("/collections/{}/points", self.crates_collection()),
+                Some(json!({ "ids": [crate_point_id(crate_name)], "with_payload": true })),
+            )
+            .await?;
+        Ok(points.and_then(|points| points.into_iter().next()).map(|point| point.payload))
+    }
+
+    /// Merge `payload` into a crate's record; like an SQL `UPDATE`, a missing crate is a no-op
+    async fn update_crate(&self, crate_name: &str, payload: Value) -> Result<(), ServerError> {
+        self.call::<Value>(
+            Method::POST,
+            &format!("/collections/{}/points/payload?wait=true", self.crates_collection()),
+            Some(json!({ "payload": payload, "filter": match_all(&[("name", json!(crate_name))]) })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Recount a crate's documents and tokens into its record, returning whether they changed
+    async fn update_crate_stats(&self, crate_name: &str) -> Result<bool, ServerError> {
+        let Some(stored) = self.get_crate(crate_name).await? else {
+            return Ok(false);
+        };
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(&self.collection, match_all(&[("crate_name", json!(crate_name))]), &["token_count"], false)
+            .await?;
+        let total_docs = points.len() as i32;
+        let total_tokens: i32 = points.iter().filter_map(|point| point.payload.token_count).sum();
+        if (stored.total_docs, stored.total_tokens) == (total_docs, total_tokens) {
+            return Ok(false);
+        }
+        self.update_crate(crate_name, json!({ "total_docs": total_docs, "total_tokens": total_tokens }))
+            .await?;
+        Ok(true)
+    }
+
+    async fn upsert_documents(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        embeddings: &[(String, String, Array1<f32>, i32)],
+    ) -> Result<(), ServerError> {
+        let Some((_, _, first, _)) = embeddings.first() else {
+            return Ok(());
+        };
+        self.ensure_documents_collection(first.len()).await?;
+
+        for batch in embeddings.chunks(UPSERT_BATCH_POINTS) {
+            let points: Vec<Value> = batch
+                .iter()
+                .map(|(doc_path, content, embedding, token_count)| {
+                    let module_path = doc_loader::module_path(crate_name, doc_path);
+                    let payload = DocPayload {
+                        crate_id,
+                        crate_name: crate_name.to_string(),
+                        doc_path: doc_path.clone(),
+                        content: content.clone(),
+                        token_count: Some(*token_count),
+                        has_code_example: examples::is_code_example(content),
+                        module_ancestors: module_ancestors(module_path.as_deref()),
+                        module_path,
+                        content_hash: Some(dedupe::content_hash(content)),
+                        anchor: doc_loader::doc_anchor(doc_path).map(str::to_string),
+                        doc_kind: doc_loader::doc_kind(crate_name, doc_path).map(str::to_string),
+                    };
+                    json!({
+                        "id": doc_point_id(crate_name, doc_path),
+                        "vector": embedding.to_vec(),
+                        "payload": payload,
+                    })
+                })
+                .collect();
+            self.call::<Value>(
+                Method::PUT,
+                &format!("/collections/{}/points?wait=true", self.collection),
+                Some(json!({ "points": points })),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Filtered nearest-neighbour search, optionally re-ranking the candidates with an example boost
+    async fn search(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: Option<f32>,
+        filter: DocFilter<'_>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        let mut conditions = vec![("crate_name", json!(crate_name))];
+        if let Some(module_prefix) = filter.module_prefix {
+            conditions.push(("module_ancestors", json!(module_prefix)));
+        }
+        if let Some(kind) = filter.kind {
+            conditions.push(("doc_kind", json!(kind)));
+        }
+        let limit = limit.max(0) as usize;
+        let candidates = match example_boost {
+            Some(_) => limit.saturating_mul(EXAMPLE_CANDIDATE_FACTOR as usize),
+            None => limit,
+        };
+        let results: Option<Vec<ScoredPoint>> = self
+            .call(
+                Method::POST,
+                &format!("/collections/{}/points/search", self.collection),
+                Some(json!({
+                    "vector": query_embedding.to_vec(),
+                    "filter": match_all(&conditions),
+                    "limit": candidates,
+                    "with_payload": ["doc_path", "content", "token_count", "has_code_example"],
+                })),
+            )
+            .await?;
+
+        let mut results: Vec<(DocMatch, bool)> = results
+            .unwrap_or_default()
+            .into_iter()
+            .map(|point| {
+                let doc = DocMatch {
+                    doc_path: point.payload.doc_path,
+                    content: point.payload.content,
+                    similarity: score_to_similarity(self.distance, point.score),
+                    token_count: point.payload.token_count.map(|count| count.max(0) as usize),
+                };
+                (doc, point.payload.has_code_example)
+            })
+            .collect();
+        if let Some(boost) = example_boost {
+            let rank = |(doc, has_example): &(DocMatch, bool)| doc.similarity + if *has_example { boost } else { 0.0 };
+            results.sort_by(|a, b| rank(b).partial_cmp(&rank(a)).unwrap_or(std::cmp::Ordering::Equal));
+            results.truncate(limit);
+        }
+        Ok(results.into_iter().map(|(doc, _)| doc).collect())
+    }
+
+    /// Vector size of the documents collection when it holds any documents
+    async fn document_dimensions(&self) -> Result<Option<usize>, ServerError> {
+        Ok(self
+            .collection_info(&self.collection)
+            .await?
+            .filter(|info| info.points_count.unwrap_or(0) > 0)
+            .map(|info| info.config.params.vectors.size))
+    }
+}
+
+#[async_trait]
+impl VectorStore for QdrantStore {
+    async fn upsert_crate(&self, crate_name: &str, version: Option<&str>) -> Result<i32, ServerError> {
+        let now = chrono::Utc::now().naive_utc();
+        if let Some(stored) = self.get_crate(crate_name).await? {
+            let mut payload = json!({ "last_updated": now });
+            if let Some(version) = version {
+                payload["version"] = json!(version);
+            }
+            self.update_crate(crate_name, payload).await?;
+            return Ok(stored.id);
+        }
+
+        // Only informational here (documents are keyed by crate name), so derived from the name
+        let id = crate_point_id(crate_name)
+            .bytes()
+            .fold(0i32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as i32))
+            & i32::MAX;
+        let payload = CratePayload {
+            id,
+            name: crate_name.to_string(),
+            version: version.map(str::to_string),
+            last_updated: now,
+            status: CrateStatus::Ready.as_str().to_string(),
+            ..Default::default()
+        };
+        self.call::<Value>(
+            Method::PUT,
+            &format!("/collections/{}/points?wait=true", self.crates_collection()),
+            Some(json!({ "points": [{ "id": crate_point_id(crate_name), "vector": [1.0], "payload": payload }] })),
+        )
+        .await?;
+        Ok(id)
+    }
+
+    async fn set_crate_features(&self, crate_name: &str, features: &[String]) -> Result<(), ServerError> {
+        self.update_crate(crate_name, json!({ "features": join_features(features) })).await
+    }
+
+    async fn set_crate_embedding_model(&self, crate_name: &str, model: &str) -> Result<(), ServerError> {
+        self.update_crate(crate_name, json!({ "embedding_model": model })).await
+    }
+
+    async fn set_crate_status(&self, crate_name: &str, status: CrateStatus) -> Result<(), ServerError> {
+        self.update_crate(
+            crate_name,
+            json!({ "status": status.as_str(), "status_updated_at": chrono::Utc::now().naive_utc() }),
+        )
+        .await
+    }
+
+    async fn crate_status(&self, crate_name: &str) -> Result<Option<CrateStatus>, ServerError> {
+        Ok(self
+            .get_crate(crate_name)
+            .await?
+            .map(|stored| CrateStatus::from_db(&stored.status, stored.status_updated_at)))
+    }
+
+    async fn set_crate_dependencies(&self, crate_name: &str, dependencies: &[String]) -> Result<(), ServerError> {
+        let mut dependencies = dependencies.to_vec();
+        dependencies.sort();
+        dependencies.dedup();
+        self.update_crate(crate_name, json!({ "dependencies": dependencies })).await
+    }
+
+    async fn crate_dependencies(&self, crate_name: &str) -> Result<Vec<String>, ServerError> {
+        Ok(self.get_crate(crate_name).await?.map(|stored| stored.dependencies).unwrap_or_default())
+    }
+
+    async fn has_embeddings(&self, crate_name: &str) -> Result<bool, ServerError> {
+        Ok(self.count(match_all(&[("crate_name", json!(crate_name))])).await? > 0)
+    }
+
+    async fn filter_existing_crates(&self, crate_names: &[String]) -> Result<(Vec<String>, Vec<String>), ServerError> {
+        let found = try_join_all(crate_names.iter().map(|name| self.has_embeddings(name))).await?;
+        let present: HashSet<&String> = crate_names.iter().zip(found).filter(|(_, found)| *found).map(|(name, _)| name).collect();
+        Ok(crate_names.iter().cloned().partition(|name| present.contains(name)))
+    }
+
+    async fn insert_embedding(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        doc_path: &str,
+        content: &str,
+        embedding: &Array1<f32>,
+        token_count: i32,
+    ) -> Result<(), ServerError> {
+        let row = (doc_path.to_string(), content.to_string(), embedding.clone(), token_count);
+        self.upsert_documents(crate_id, crate_name, std::slice::from_ref(&row)).await
+    }
+
+    async fn insert_embeddings_batch(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        embeddings: &[(String, String, Array1<f32>, i32)],
+    ) -> Result<(), ServerError> {
+        self.upsert_documents(crate_id, crate_name, embeddings).await?;
+        self.update_crate_stats(crate_name).await?;
+        Ok(())
+    }
+
+    async fn search_similar_docs(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, None, DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_preferring_examples(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: f32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, Some(example_boost), DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_filtered(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        filter: DocFilter<'_>,
+        example_boost: Option<f32>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, example_boost, filter).await
+    }
+
+    async fn get_crate_documents(
+        &self,
+        crate_name: &str,
+    ) -> Result<Vec<(String, String, Array1<f32>, Option<usize>)>, ServerError> {
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(
+                &self.collection,
+                match_all(&[("crate_name", json!(crate_name))]),
+                &["doc_path", "content", "token_count"],
+                true,
+            )
+            .await?;
+        let mut documents: Vec<(String, String, Array1<f32>, Option<usize>)> = points
+            .into_iter()
+            .map(|point| {
+                let vector = Array1::from(point.vector.unwrap_or_default());
+                let token_count = point.payload.token_count.map(|count| count.max(0) as usize);
+                (point.payload.doc_path, point.payload.content, vector, token_count)
+            })
+            .collect();
+        documents.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(documents)
+    }
+
+    async fn get_doc_embeddings(
+        &self,
+        crate_name: &str,
+        doc_paths: &[String],
+    ) -> Result<Vec<(String, Array1<f32>)>, ServerError> {
+        if doc_paths.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<String> = doc_paths.iter().map(|doc_path| doc_point_id(crate_name, doc_path)).collect();
+        let points: Option<Vec<Point<DocPayload>>> = self
+            .call(
+                Method::POST,
+                &format!("/collections/{}/points", self.collection),
+                Some(json!({ "ids": ids, "with_payload": ["doc_path"], "with_vector": true })),
+            )
+            .await?;
+        Ok(points
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|point| Some((point.payload.doc_path, Array1::from(point.vector?))))
+            .collect())
+    }
+
+    async fn get_crate_doc_contents(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(&self.collection, match_all(&[("crate_name", json!(crate_name))]), &["doc_path", "content"], false)
+            .await?;
+        let mut documents: Vec<(String, String)> =
+            points.into_iter().map(|point| (point.payload.doc_path, point.payload.content)).collect();
+        documents.sort();
+        Ok(documents)
+    }
+
+    async fn get_content_hashes(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(
+                &self.collection,
+                match_all(&[("crate_name", json!(crate_name))]),
+                &["doc_path", "content_hash", "content"],
+                false,
+            )
+            .await?;
+        let mut hashes: Vec<(String, String)> = points
+            .into_iter()
+            .map(|point| {
+                let payload = point.payload;
+                let hash = payload.content_hash.unwrap_or_else(|| dedupe::content_hash(&payload.content));
+                (payload.doc_path, hash)
+            })
+            .collect();
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    async fn list_doc_paths(
+        &self,
+        crate_name: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ServerError> {
+        // Keyword payloads can't be range-filtered, so the page is cut from the sorted paths
+        Ok(self
+            .crate_doc_paths(crate_name)
+            .await?
+            .into_iter()
+            .filter(|path| after.is_none_or(|after| path.as_str() > after))
+            .take(limit)
+            .collect())
+    }
+
+    async fn get_doc_content(&self, crate_name: &str, doc_path: &str) -> Result<Option<String>, ServerError> {
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(
+                &self.collection,
+                match_all(&[("crate_name", json!(crate_name)), ("doc_path", json!(doc_path))]),
+                &["content"],
+                false,
+            )
+            .await?;
+        Ok(points.into_iter().next().map(|point| point.payload.content))
+    }
+
+    async fn get_document_by_path(
+        &self,
+        crate_name: &str,
+        doc_path: &str,
+    ) -> Result<Option<(String, String)>, ServerError> {
+        let needle = format!("/{}", doc_path);
+        let matching: Vec<String> = self
+            .crate_doc_paths(crate_name)
+            .await?
+            .into_iter()
+            .filter(|path| format!("/{}", path).contains(&needle))
+            .collect();
+        if matching.is_empty() {
+            return Ok(None);
+        }
+        let filter = json!({ "must": [
+            { "key": "crate_name", "match": { "value": crate_name } },
+            { "key": "doc_path", "match": { "any": matching } },
+        ] });
+        let points: Vec<Point<DocPayload>> = self.scroll(&self.collection, filter, &["doc_path", "content"], false).await?;
+        // Points have no insertion order; sections of a page come back in path order
+        let mut rows: Vec<(String, String)> =
+            points.into_iter().map(|point| (point.payload.doc_path, point.payload.content)).collect();
+        rows.sort();
+        Ok(assemble_page(rows, doc_path))
+    }
+
+    async fn find_doc_paths(&self, crate_name: &str, term: &str, limit: usize) -> Result<Vec<String>, ServerError> {
+        let term = term.to_lowercase();
+        let mut paths = self.crate_doc_paths(crate_name).await?;
+        paths.retain(|path| path.to_lowercase().contains(&term));
+        paths.dedup();
+        paths.truncate(limit);
+        Ok(paths)
+    }
+
+    async fn backfill_module_paths(&self) -> Result<usize, ServerError> {
+        // Module paths are written with every point; there are no older rows without them
+        Ok(0)
+    }
+
+    /// Points stored before `doc_kind` existed get it as a payload update, one request per kind
+    async fn backfill_doc_kinds(&self) -> Result<usize, ServerError> {
+        if self.collection_info(&self.collection).await?.is_none() {
+            return Ok(0);
+        }
+        // Collections created before the field existed have no index for kind filters
+        self.create_keyword_index(&self.collection, "doc_kind").await?;
+        let points: Vec<Point<DocPayload>> = self
+            .scroll(
+                &self.collection,
+                json!({ "must": [{ "is_empty": { "key": "doc_kind" } }] }),
+                &["crate_name", "doc_path"],
+                false,
+            )
+            .await?;
+
+        let mut by_kind: HashMap<&str, Vec<String>> = HashMap::new();
+        for point in &points {
+            if let Some(kind) = doc_loader::doc_kind(&point.payload.crate_name, &point.payload.doc_path) {
+                by_kind
+                    .entry(kind)
+                    .or_default()
+                    .push(doc_point_id(&point.payload.crate_name, &point.payload.doc_path));
+            }
+        }
+        let mut updated = 0;
+        for (kind, ids) in by_kind {
+            self.call::<Value>(
+                Method::POST,
+                &format!("/collections/{}/points/payload?wait=true", self.collection),
+                Some(json!({ "payload": { "doc_kind": kind }, "points": ids })),
+            )
+            .await?;
+            updated += ids.len();
+        }
+        Ok(updated)
+    }
+
+    async fn repair_crate_stats(&self) -> Result<usize, ServerError> {
+        let mut repaired = 0;
+        for stats in self.get_crate_stats().await? {
+            if self.update_crate_stats(&stats.name).await? {
+                repaired += 1;
+            }
+        }
+        Ok(repaired)
+    }
+
+    async fn delete_crate_embeddings(&self, crate_name: &str) -> Result<(), ServerError> {
+        self.call::<Value>(
+            Method::POST,
+            &format!("/collections/{}/points/delete?wait=true", self.collection),
+            Some(json!({ "filter": match_all(&[("crate_name", json!(crate_name))]) })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_doc_embeddings(&self, crate_name: &str, doc_paths: &[String]) -> Result<(), ServerError> {
+        if doc_paths.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<String> = doc_paths.iter().map(|doc_path| doc_point_id(crate_name, doc_path)).collect();
+        self.call::<Value>(
+            Method::POST,
+            &format!("/collections/{}/points/delete?wait=true", self.collection),
+            Some(json!({ "points": ids })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_crate_stats(&self) -> Result<Vec<CrateStats>, ServerError> {
+        let points: Vec<Point<CratePayload>> = self.scroll(&self.crates_collection(), json!({}), &[], false).await?;
+        let mut stats: Vec<CrateStats> = points.into_iter().map(|point| point.payload.into_stats()).collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(stats)
+    }
+
+    async fn global_stats(&self) -> Result<GlobalStats, ServerError> {
+        let stats = self.get_crate_stats().await?;
+        let total_docs = self.count(json!({})).await?;
+        let total_tokens: i64 = stats.iter().map(|stat| stat.total_tokens as i64).sum();
+        Ok(GlobalStats::new(
+            stats.len() as i64,
+            total_docs as i64,
+            total_tokens,
+            self.document_dimensions().await?.into_iter().collect(),
+            stats.iter().map(|stat| stat.last_updated).min(),
+            stats.iter().map(|stat| stat.last_updated).max(),
+        ))
+    }
+
+    async fn count_crate_documents(&self, crate_name: &str) -> Result<usize, ServerError> {
+        self.count(match_all(&[("crate_name", json!(crate_name))])).await
+    }
+
+    async fn stored_embedding_dimensions(&self) -> Result<Option<usize>, ServerError> {
+        self.document_dimensions().await
+    }
+
+    async fn crate_embedding_dimensions(&self, crate_name: &str) -> Result<Option<usize>, ServerError> {
+        // One collection holds every crate, so they all share its vector size
+        if !self.has_embeddings(crate_name).await? {
+            return Ok(None);
+        }
+        self.document_dimensions().await
+    }
+
+    async fn ping(&self) -> Result<(), ServerError> {
+        self.call::<Value>(Method::GET, "/collections", None).await?;
+        Ok(())
+    }
+
+    async fn validate_distance_metric(&self) -> Result<(), ServerError> {
+        let Some(info) = self.collection_info(&self.collection).await? else {
+            return Ok(());
+        };
+        let expected = qdrant_distance(self.distance);
+        let stored = info.config.params.vectors.distance;
+        if stored != expected {
+            return Err(ServerError::Config(format!(
+                "Qdrant collection '{}' ranks by {} distance, but the server is configured for {:?}. \
+                 Set MCPDOCS_DISTANCE (or --distance) to match, or recreate the collection.",
+                self.collection, stored, self.distance
+            )));
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Qdrant
+    }
+
+    fn distance_metric(&self) -> DistanceMetric {
+        self.distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::{Method as HttpMethod, Uri}, Json, Router};
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    /// Requests a fake Qdrant received, as `("METHOD /path?query", body)`
+    type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// Body of Qdrant's answer to a write with `wait=true`, as recorded from Qdrant 1.x
+    fn completed() -> Value {
+        json!({ "result": { "operation_id": 3, "status": "completed" }, "status": "ok", "time": 0.0021 })
+    }
+
+    /// A fake Qdrant answering each `"METHOD /path?query"` route with its recorded responses in
+    /// order (repeating the last one) and anything else with Qdrant's 404 body, and a store
+    /// on its `rust_docs` collection
+    async fn fake_qdrant(responses: Vec<(&str, StatusCode, Value)>) -> (QdrantStore, Requests) {
+        let mut routes: HashMap<String, VecDeque<(StatusCode, Value)>> = HashMap::new();
+        for (route, status, body) in responses {
+            routes.entry(route.to_string()).or_default().push_back((status, body));
+        }
+        let routes = Arc::new(Mutex::new(routes));
+        let requests: Requests = Arc::default();
+        let recorded = requests.clone();
+        let app = Router::new().fallback(move |method: HttpMethod, uri: Uri, body: String| {
+            let route = format!("{} {}", method, uri);
+            recorded.lock().unwrap().push((route.clone(), serde_json::from_str(&body).unwrap_or(Value::Null)));
+            let response = routes.lock().unwrap().get_mut(&route).and_then(|queue| {
+                if queue.len() > 1 { queue.pop_front() } else { queue.front().cloned() }
+            });
+            async move {
+                response.map_or_else(
+                    || (StatusCode::NOT_FOUND, Json(json!({ "status": { "error": "Not found: Collection `rust_docs` doesn't exist!" }, "time": 0.0001 }))),
+                    |(status, body)| (status, Json(body)),
+                )
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("qdrant://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let (base_url, collection) = parse_qdrant_url(&url).unwrap();
+        let store = QdrantStore {
+            client: reqwest::Client::new(),
+            base_url,
+            api_key: None,
+            collection,
+            distance: DistanceMetric::Cosine,
+            documents_ready: OnceCell::new(),
+        };
+        (store, requests)
+    }
+
+    /// Bodies of the requests sent to `route`, in order
+    fn bodies(requests: &Requests, route: &str) -> Vec<Value> {
+        requests.lock().unwrap().iter().filter(|(sent, _)| sent == route).map(|(_, body)| body.clone()).collect()
+    }
+
+    /// `GET /collections/{name}` for a collection of 1536-dimensional vectors, as recorded
+    fn collection_info(points_count: usize, distance: &str) -> Value {
+        json!({
+            "result": {
+                "status": "green",
+                "optimizer_status": "ok",
+                "indexed_vectors_count": 0,
+                "points_count": points_count,
+                "segments_count": 2,
+                "config": {
+                    "params": {
+                        "vectors": { "size": 1536, "distance": distance },
+                        "shard_number": 1,
+                        "replication_factor": 1,
+                        "write_consistency_factor": 1,
+                        "on_disk_payload": true
+                    },
+                    "hnsw_config": { "m": 16, "ef_construct": 100, "full_scan_threshold": 10000, "max_indexing_threads": 0, "on_disk": false },
+                    "wal_config": { "wal_capacity_mb": 32, "wal_segments_ahead": 0 },
+                    "quantization_config": null
+                },
+                "payload_schema": { "crate_name": { "data_type": "keyword", "points": points_count } }
+            },
+            "status": "ok",
+            "time": 0.000041
+        })
+    }
+
+    #[tokio::test]
+    async fn searches_one_crate_with_a_payload_filter() {
+        assert_eq!(
+            parse_qdrant_url("qdrant://localhost:6333").unwrap(),
+            ("http://localhost:6333".to_string(), DEFAULT_QDRANT_COLLECTION.to_string())
+        );
+        assert_eq!(
+            parse_qdrant_url("qdrant+https://db.example.com/docs/").unwrap(),
+            ("https://db.example.com".to_string(), "docs".to_string())
+        );
+        assert!(parse_qdrant_url("qdrant:///docs").is_err());
+        assert_eq!(doc_point_id("tokio", "tokio/fn.spawn.html"), doc_point_id("tokio", "tokio/fn.spawn.html"));
+        assert_ne!(doc_point_id("tokio", "tokio/fn.spawn.html"), doc_point_id("tokio@1.37.0", "tokio/fn.spawn.html"));
+        assert_eq!(module_ancestors(Some("tokio::sync::mpsc")), ["tokio", "tokio::sync", "tokio::sync::mpsc"]);
+
+        let (store, requests) = fake_qdrant(vec![(
+            "POST /collections/rust_docs/points/search",
+            StatusCode::OK,
+            json!({ "status": "ok", "time": 0.001, "result": [
+                { "id": doc_point_id("tokio", "a"), "version": 1, "score": 0.9,
+                  "payload": { "doc_path": "tokio/fn.spawn.html", "content": "Spawns a task.", "token_count": 4 } },
+                { "id": doc_point_id("tokio", "b"), "version": 1, "score": 0.85,
+                  "payload": { "doc_path": "tokio/task/index.html", "content": "```rust\nspawn();\n```", "has_code_example": true } },
+            ] }),
+        )])
+        .await;
+
+        let query = Array1::from(vec![1.0, 0.0]);
+        let results = store.search_similar_docs("tokio", &query, 2).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].doc_path, "tokio/fn.spawn.html");
+        assert_eq!(results[0].similarity, 0.9);
+        assert_eq!(results[0].token_count, Some(4));
+
+        // The example boost re-ranks the candidates, keeping unboosted similarities
+        let boosted = store
+            .search_similar_docs_filtered(
+                "tokio",
+                &query,
+                1,
+                DocFilter { module_prefix: Some("tokio::task"), kind: Some("mod") },
+                Some(0.1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(boosted.len(), 1);
+        assert_eq!(boosted[0].doc_path, "tokio/task/index.html");
+        assert_eq!(boosted[0].similarity, 0.85);
+
+        let requests = bodies(&requests, "POST /collections/rust_docs/points/search");
+        assert_eq!(requests[0]["filter"], json!({ "must": [{ "key": "crate_name", "match": { "value": "tokio" } }] }));
+        assert_eq!(requests[0]["limit"], 2);
+        assert_eq!(requests[1]["filter"]["must"][1], json!({ "key": "module_ancestors", "match": { "value": "tokio::task" } }));
+        assert_eq!(requests[1]["filter"]["must"][2], json!({ "key": "doc_kind", "match": { "value": "mod" } }));
+        assert_eq!(requests[1]["limit"], EXAMPLE_CANDIDATE_FACTOR);
+
+        // Euclid scores are distances
+        assert!((score_to_similarity(DistanceMetric::L2, 0.5) - 0.875).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn creates_the_collection_and_upserts_points_with_their_payload() {
+        let crate_record = json!({
+            "id": crate_point_id("tokio"),
+            "payload": { "id": 7, "name": "tokio", "version": "1.37.0", "last_updated": "2026-10-16T12:00:00.123456",
+                         "total_docs": 0, "total_tokens": 0, "features": null, "embedding_model": null,
+                         "status": "populating", "dependencies": [] },
+            "vector": null,
+            "shard_key": null,
+            "order_value": null
+        });
+        let (store, requests) = fake_qdrant(vec![
+            ("PUT /collections/rust_docs", StatusCode::OK, json!({ "result": true, "status": "ok", "time": 0.041 })),
+            ("PUT /collections/rust_docs/index?wait=true", StatusCode::OK, completed()),
+            ("PUT /collections/rust_docs/points?wait=true", StatusCode::OK, completed()),
+            ("POST /collections/rust_docs_crates/points", StatusCode::OK, json!({ "result": [crate_record], "status": "ok", "time": 0.0003 })),
+            (
+                "POST /collections/rust_docs/points/scroll",
+                StatusCode::OK,
+                json!({ "result": { "points": [
+                    { "id": doc_point_id("tokio", "tokio/latest/tokio/fn.spawn.html"), "payload": { "token_count": 4 } },
+                    { "id": doc_point_id("tokio", "tokio/latest/tokio/task/index.html"), "payload": { "token_count": 6 } },
+                ], "next_page_offset": null }, "status": "ok", "time": 0.0005 }),
+            ),
+            ("POST /collections/rust_docs_crates/points/payload?wait=true", StatusCode::OK, completed()),
+        ])
+        .await;
+
+        let rows = vec![
+            ("tokio/latest/tokio/fn.spawn.html".to_string(), "Spawns a task.".to_string(), Array1::from(vec![1.0, 0.0]), 4),
+            ("tokio/latest/tokio/task/index.html".to_string(), "```rust\nspawn();\n```".to_string(), Array1::from(vec![0.0, 1.0]), 6),
+        ];
+        store.insert_embeddings_batch(7, "tokio", &rows).await.unwrap();
+
+        // The missing collection (404) is created for the vectors' size, with keyword indexes
+        assert_eq!(bodies(&requests, "GET /collections/rust_docs").len(), 1);
+        assert_eq!(
+            bodies(&requests, "PUT /collections/rust_docs"),
+            [json!({ "vectors": { "size": 2, "distance": "Cosine" } })]
+        );
+        let indexed: Vec<Value> = bodies(&requests, "PUT /collections/rust_docs/index?wait=true")
+            .into_iter()
+            .map(|body| body["field_name"].clone())
+            .collect();
+        assert_eq!(indexed, [json!("crate_name"), json!("doc_path"), json!("module_ancestors"), json!("doc_kind")]);
+
+        let upserts = bodies(&requests, "PUT /collections/rust_docs/points?wait=true");
+        let points = upserts[0]["points"].as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0]["id"], json!(doc_point_id("tokio", "tokio/latest/tokio/fn.spawn.html")));
+        assert_eq!(points[0]["vector"], json!([1.0, 0.0]));
+        let payload = &points[0]["payload"];
+        assert_eq!(payload["crate_id"], 7);
+        assert_eq!(payload["crate_name"], "tokio");
+        assert_eq!(payload["token_count"], 4);
+        assert_eq!(payload["content_hash"], json!(dedupe::content_hash("Spawns a task.")));
+        assert_eq!(payload["doc_kind"], "fn");
+        assert_eq!(points[1]["payload"]["has_code_example"], examples::is_code_example("```rust\nspawn();\n```"));
+        assert_eq!(points[1]["payload"]["module_ancestors"], json!(["tokio", "tokio::task"]));
+        assert_eq!(points[1]["payload"]["doc_kind"], "mod");
+
+        // The crate record is recounted from a scroll of the crate's token counts
+        let scrolls = bodies(&requests, "POST /collections/rust_docs/points/scroll");
+        assert_eq!(scrolls[0]["with_payload"], json!(["token_count"]));
+        assert_eq!(scrolls[0]["with_vector"], false);
+        assert_eq!(
+            bodies(&requests, "POST /collections/rust_docs_crates/points/payload?wait=true"),
+            [json!({
+                "payload": { "total_docs": 2, "total_tokens": 10 },
+                "filter": { "must": [{ "key": "name", "match": { "value": "tokio" } }] },
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_crate_records_across_scroll_pages_and_counts() {
+        let page = |name: &str, next: Value| {
+            json!({ "result": { "points": [{
+                "id": crate_point_id(name),
+                "payload": { "id": 1, "name": name, "version": "1.0.0", "last_updated": "2026-10-16T12:00:00",
+                             "total_docs": 42, "total_tokens": 1200, "features": "full,macros",
+                             "embedding_model": "text-embedding-3-small", "status": "ready", "dependencies": ["bytes"] },
+                "vector": null
+            }], "next_page_offset": next }, "status": "ok", "time": 0.0004 })
+        };
+        let offset = crate_point_id("tokio");
+        let (mut store, requests) = fake_qdrant(vec![
+            ("POST /collections/rust_docs_crates/points/scroll", StatusCode::OK, page("tokio", json!(offset))),
+            ("POST /collections/rust_docs_crates/points/scroll", StatusCode::OK, page("serde", Value::Null)),
+            ("POST /collections/rust_docs/points/count", StatusCode::OK, json!({ "result": { "count": 42 }, "status": "ok", "time": 0.0002 })),
+            ("GET /collections/rust_docs", StatusCode::OK, collection_info(42, "Cosine")),
+        ])
+        .await;
+
+        let stats = store.get_crate_stats().await.unwrap();
+        let names: Vec<&str> = stats.iter().map(|stat| stat.name.as_str()).collect();
+        assert_eq!(names, ["serde", "tokio"]);
+        assert_eq!(stats[1].features, ["full", "macros"]);
+        assert_eq!(stats[1].status, CrateStatus::Ready);
+        assert_eq!(stats[1].total_docs, 42);
+        let scrolls = bodies(&requests, "POST /collections/rust_docs_crates/points/scroll");
+        assert_eq!(scrolls.len(), 2);
+        assert!(scrolls[0].get("offset").is_none());
+        assert_eq!(scrolls[1]["offset"], json!(offset));
+
+        assert_eq!(store.count_crate_documents("tokio").await.unwrap(), 42);
+        assert_eq!(bodies(&requests, "POST /collections/rust_docs/points/count")[0]["exact"], true);
+        assert_eq!(store.stored_embedding_dimensions().await.unwrap(), Some(1536));
+        store.validate_distance_metric().await.unwrap();
+
+        store.distance = DistanceMetric::L2;
+        assert!(matches!(store.validate_distance_metric().await, Err(ServerError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn treats_404s_as_missing_and_reports_other_errors() {
+        let (store, _) = fake_qdrant(vec![(
+            "POST /collections/rust_docs/points/count",
+            StatusCode::BAD_REQUEST,
+            json!({ "status": { "error": "Bad request: Index required but not found for \"crate_name\" of one of the following types: [keyword]" }, "time": 0.0001 }),
+        )])
+        .await;
+
+        // No crates collection yet: nothing stored rather than an error
+        assert_eq!(store.crate_status("tokio").await.unwrap(), None);
+        assert!(store.get_crate_stats().await.unwrap().is_empty());
+
+        let err = store.has_embeddings("tokio").await.unwrap_err();
+        assert!(matches!(&err, ServerError::Database(message) if message.contains("400") && message.contains("Index required")));
+    }
+}
@@ -0,0 +1,126 @@
+// Global cap on concurrently executing queries, so a burst of agent traffic queues up
+// instead of fanning out into unbounded embedding and LLM calls. Complements the
+// per-client `rate_limit`, which bounds how often each client may ask.
+
+use crate::error::ServerError;
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How long a query waits for a free slot before it is rejected as "server busy"
+pub const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lets at most `max_concurrent` queries run at once; the rest wait up to `queue_timeout`
+pub struct QueryLimiter {
+    max_concurrent: Option<usize>,
+    semaphore: Option<Arc<Semaphore>>,
+    queue_timeout: Duration,
+    in_flight: Arc<AtomicUsize>,
+    rejected: AtomicU64,
+}
+
+/// A running query's slot, released when dropped
+pub struct QueryPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for QueryPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl QueryLimiter {
+    /// `None` (or 0) disables the cap; in-flight queries are still counted
+    pub fn new(max_concurrent: Option<usize>, queue_timeout: Duration) -> Self {
+        let max_concurrent = max_concurrent.filter(|limit| *limit > 0);
+        Self {
+            max_concurrent,
+            semaphore: max_concurrent.map(|limit| Arc::new(Semaphore::new(limit))),
+            queue_timeout,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None, DEFAULT_QUEUE_TIMEOUT)
+    }
+
+    pub fn max_concurrent(&self) -> Option<usize> {
+        self.max_concurrent
+    }
+
+    /// Queries currently holding a slot
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Queries turned away because no slot freed up in time
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Wait for a free slot, or fail with a retryable `RateLimited` error once `queue_timeout` passes
+    pub async fn acquire(&self) -> Result<QueryPermit, ServerError> {
+        let permit = match &self.semaphore {
+            None => None,
+            Some(semaphore) => {
+                match tokio::time::timeout(self.queue_timeout, semaphore.clone().acquire_owned()).await {
+                    Ok(Ok(permit)) => Some(permit),
+                    // The semaphore is never closed, so only the timeout ends up here
+                    _ => {
+                        self.rejected.fetch_add(1, Ordering::Relaxed);
+                        return Err(ServerError::RateLimited(format!(
+                            "Server busy: {} queries already running; retry shortly",
+                            self.max_concurrent.unwrap_or_default()
+                        )));
+                    }
+                }
+            }
+        };
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Ok(QueryPermit {
+            _permit: permit,
+            in_flight: self.in_flight.clone(),
+        })
+    }
+}
+
+impl Default for QueryLimiter {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_queries_beyond_the_cap_until_a_slot_frees() {
+        let limiter = QueryLimiter::new(Some(1), Duration::from_millis(20));
+        let first = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.in_flight(), 1);
+
+        let err = limiter.acquire().await.err().unwrap();
+        assert!(err.to_string().contains("Server busy"));
+        assert!(err.is_retryable());
+        assert_eq!(limiter.rejected(), 1);
+
+        drop(first);
+        assert_eq!(limiter.in_flight(), 0);
+        let _second = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.in_flight(), 1);
+
+        let unlimited = QueryLimiter::default();
+        let _permits: Vec<_> = futures::future::join_all((0..50).map(|_| unlimited.acquire())).await;
+        assert_eq!(unlimited.in_flight(), 50);
+    }
+}
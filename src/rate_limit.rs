@@ -0,0 +1,155 @@
+// Token-bucket rate limiting for tool calls, so a runaway client can't run up
+// embedding and LLM costs. Buckets are keyed per client: the HTTP server uses the
+// SSE session id (or the caller's address for /query), stdio uses one global key.
+
+use crate::error::ServerError;
+use rmcp::Error as McpError;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Key used for the single client of the stdio server
+pub const STDIO_CLIENT: &str = "stdio";
+
+/// Buckets kept before refilled (idle) ones are dropped
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Allows `per_minute` calls per client, refilled continuously, with bursts up to `per_minute`
+pub struct RateLimiter {
+    per_minute: Option<u32>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// `None` (or 0) disables limiting
+    pub fn new(per_minute: Option<u32>) -> Self {
+        Self {
+            per_minute: per_minute.filter(|limit| *limit > 0),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    pub fn per_minute(&self) -> Option<u32> {
+        self.per_minute
+    }
+
+    /// Take one call from `client`'s bucket, or return how long until one is available
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        let Some(per_minute) = self.per_minute else {
+            return Ok(());
+        };
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_IDLE_BUCKETS && !buckets.contains_key(client) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * per_second < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+
+    /// Forget a client's bucket (e.g. when its session closes)
+    pub fn remove(&self, client: &str) {
+        self.buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(client);
+    }
+
+    /// `check` for a tool call, as an `McpError` carrying a `retry_after_secs` hint
+    pub fn check_tool_call(&self, client: &str) -> Result<(), McpError> {
+        self.check(client).map_err(|retry_after| {
+            let retry_after_secs = whole_seconds(retry_after);
+            let mut error = ServerError::RateLimited(format!(
+                "Rate limit of {} queries per minute exceeded; retry in {}s",
+                self.per_minute.unwrap_or_default(),
+                retry_after_secs
+            ))
+            .into_mcp_error("rate_limit");
+            if let Some(data) = error.data.as_mut().and_then(|data| data.as_object_mut()) {
+                data.insert("retry_after_secs".to_string(), retry_after_secs.into());
+            }
+            error
+        })
+    }
+}
+
+/// Round a retry delay up to whole seconds, as used by `Retry-After`
+pub fn whole_seconds(retry_after: Duration) -> u64 {
+    (retry_after.as_secs_f64().ceil() as u64).max(1)
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ErrorCode;
+
+    #[test]
+    fn unlimited_by_default() {
+        let limiter = RateLimiter::default();
+        for _ in 0..1000 {
+            assert!(limiter.check(STDIO_CLIENT).is_ok());
+        }
+    }
+
+    #[test]
+    fn limits_each_client_separately() {
+        let limiter = RateLimiter::new(Some(2));
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        let retry_after = limiter.check("a").unwrap_err();
+        assert!(retry_after > Duration::from_secs(20) && retry_after <= Duration::from_secs(30));
+
+        assert!(limiter.check("b").is_ok());
+
+        limiter.remove("a");
+        assert!(limiter.check("a").is_ok());
+    }
+
+    #[test]
+    fn exceeded_limit_returns_retry_hint() {
+        let limiter = RateLimiter::new(Some(1));
+        limiter.check_tool_call(STDIO_CLIENT).unwrap();
+
+        let err = limiter.check_tool_call(STDIO_CLIENT).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INTERNAL_ERROR);
+        let data = err.data.unwrap();
+        assert_eq!(data["kind"], "rate_limited");
+        assert_eq!(data["phase"], "rate_limit");
+        assert_eq!(data["retry_after_secs"], 60);
+    }
+}
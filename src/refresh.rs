@@ -0,0 +1,265 @@
+// Incremental re-population of a single crate: re-crawl docs.rs, compare content hashes
+// against what is stored, and only re-embed the pages that changed.
+
+use crate::{
+    database::VectorStore,
+    dedupe,
+    doc_loader::{self, CrawlLimit, Document, PageLimits},
+    embeddings::{chunk_documents, generate_embeddings},
+    error::ServerError,
+};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Outcome of [`refresh_crate`]
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshReport {
+    pub crate_name: String,
+    pub pages_scanned: usize,
+    pub pages_changed: usize,
+    /// Chunks embedded for the changed pages (after dedupe)
+    pub chunks_embedded: usize,
+    pub new_version: Option<String>,
+}
+
+/// Pages that need re-embedding, and stored rows they no longer produce
+#[derive(Debug, Default)]
+pub struct RefreshPlan {
+    pub changed: Vec<Document>,
+    /// How many of the changed pages had no stored rows at all
+    pub added: usize,
+    pub unchanged: usize,
+    /// Stored paths of changed pages that the new content no longer has (e.g. fewer chunks)
+    pub stale_paths: Vec<String>,
+    /// Stored paths of pages the crawl didn't find
+    pub missing_paths: Vec<String>,
+    pub missing_pages: usize,
+}
+
+impl RefreshPlan {
+    /// Changed pages that replace stored ones
+    pub fn updated(&self) -> usize {
+        self.changed.len() - self.added
+    }
+}
+
+/// Compare freshly loaded pages against the stored `(path, content_hash)` rows of a crate.
+/// A page is unchanged when it chunks into exactly the paths and content already stored.
+pub fn plan_refresh(documents: Vec<Document>, stored: &[(String, String)]) -> RefreshPlan {
+    let mut stored_by_page: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+    for (path, hash) in stored {
+        stored_by_page
+            .entry(doc_loader::strip_chunk_suffix(path))
+            .or_default()
+            .insert(path.as_str(), hash.as_str());
+    }
+
+    let mut chunks_by_page: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for (path, content) in chunk_documents(&documents) {
+        chunks_by_page
+            .entry(doc_loader::strip_chunk_suffix(&path).to_string())
+            .or_default()
+            .push((path, dedupe::content_hash(&content)));
+    }
+
+    let mut plan = RefreshPlan::default();
+    for document in documents {
+        let chunks = chunks_by_page.remove(&document.path).unwrap_or_default();
+        let Some(rows) = stored_by_page.remove(document.path.as_str()) else {
+            plan.added += 1;
+            plan.changed.push(document);
+            continue;
+        };
+        let unchanged = rows.len() == chunks.len()
+            && chunks.iter().all(|(path, hash)| rows.get(path.as_str()) == Some(&hash.as_str()));
+        if unchanged {
+            plan.unchanged += 1;
+            continue;
+        }
+
+        let new_paths: HashSet<&str> = chunks.iter().map(|(path, _)| path.as_str()).collect();
+        plan.stale_paths.extend(
+            rows.keys()
+                .filter(|path| !new_paths.contains(*path))
+                .map(|path| path.to_string()),
+        );
+        plan.changed.push(document);
+    }
+
+    plan.missing_pages = stored_by_page.len();
+    plan.missing_paths = stored_by_page
+        .into_values()
+        .flat_map(|rows| rows.into_keys().map(str::to_string))
+        .collect();
+    plan
+}
+
+/// How [`sync_crate`] treats the stored rows
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    /// Delete stored pages that aren't among the new documents
+    pub remove_missing: bool,
+    /// Skip content already stored (or repeated) under another path
+    pub dedupe: bool,
+}
+
+/// Page counts from [`sync_crate`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// Chunks embedded for the added and updated pages (after dedupe)
+    pub chunks_embedded: usize,
+    pub embedding_tokens: usize,
+}
+
+/// Make `documents` the stored docs of `crate_name`, embedding only new or changed pages.
+/// Requires the global embedding provider to be initialized.
+pub async fn sync_crate(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+    documents: Vec<Document>,
+    version: Option<&str>,
+    options: SyncOptions,
+) -> Result<SyncReport, ServerError> {
+    let stored = store.get_content_hashes(crate_name).await?;
+    let plan = plan_refresh(documents, &stored);
+    let mut report = SyncReport {
+        added: plan.added,
+        updated: plan.updated(),
+        removed: if options.remove_missing { plan.missing_pages } else { 0 },
+        unchanged: plan.unchanged,
+        ..SyncReport::default()
+    };
+
+    // Same dedupe as population: skip content already stored under another path
+    let existing_hashes = if options.dedupe {
+        dedupe::existing_content_hashes(store, crate_name).await?
+    } else {
+        HashMap::new()
+    };
+    let mut changed = plan.changed;
+    if options.dedupe {
+        changed = dedupe::dedupe_by_content(changed, |doc| (doc.path.as_str(), doc.content.as_str()), &existing_hashes).0;
+    }
+    let (mut embeddings, embedding_tokens) = generate_embeddings(&changed).await?;
+    if options.dedupe {
+        embeddings = dedupe::dedupe_by_content(
+            embeddings,
+            |(path, content, _)| (path.as_str(), content.as_str()),
+            &existing_hashes,
+        )
+        .0;
+    }
+
+    let mut removed_paths = plan.stale_paths;
+    if options.remove_missing {
+        removed_paths.extend(plan.missing_paths);
+    }
+    if !removed_paths.is_empty() {
+        store.delete_doc_embeddings(crate_name, &removed_paths).await?;
+    }
+
+    let crate_id = store.upsert_crate(crate_name, version).await?;
+    // An empty batch still refreshes the crate's doc and token totals after deletions
+    if !embeddings.is_empty() || !removed_paths.is_empty() {
+        let bpe = tiktoken_rs::cl100k_base_singleton();
+        let batch: Vec<_> = embeddings
+            .iter()
+            .map(|(path, content, embedding)| {
+                let token_count = bpe.encode_with_special_tokens(content).len() as i32;
+                (path.clone(), content.clone(), embedding.clone(), token_count)
+            })
+            .collect();
+        store.insert_embeddings_batch(crate_id, crate_name, &batch).await?;
+    }
+
+    report.chunks_embedded = embeddings.len();
+    report.embedding_tokens = embedding_tokens;
+    Ok(report)
+}
+
+/// Re-crawl `crate_name` on docs.rs and re-embed only the pages whose content changed.
+/// Pages missing from the new crawl are kept, since the crawl is capped by `max_pages`.
+/// Requires the global embedding provider to be initialized.
+pub async fn refresh_crate(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+    max_pages: Option<usize>,
+) -> Result<RefreshReport, ServerError> {
+    // Feature variants (e.g. `serde+derive`) and pinned releases (e.g. `tokio@1.38.0`)
+    // are re-crawled with their features and version
+    let (docs_crate, features) = doc_loader::split_crate_variant(crate_name);
+    let features = (!features.is_empty()).then_some(features);
+    let load_result = doc_loader::load_documents_from_docs_rs_with_limits(
+        &doc_loader::crawler_client()?,
+        docs_crate,
+        doc_loader::stored_crate_version(crate_name).unwrap_or("*"),
+        features.as_ref(),
+        CrawlLimit::new(max_pages, None),
+        &PageLimits::default(),
+        None,
+        None,
+        None,
+        false,
+    )
+    .await?;
+    let pages_scanned = load_result.documents.len();
+
+    let options = SyncOptions { remove_missing: false, dedupe: true };
+    let report = sync_crate(store, crate_name, load_result.documents, load_result.version.as_deref(), options).await?;
+
+    Ok(RefreshReport {
+        crate_name: crate_name.to_string(),
+        pages_scanned,
+        pages_changed: report.added + report.updated,
+        chunks_embedded: report.chunks_embedded,
+        new_version: load_result.version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(path: &str, content: &str) -> Document {
+        Document { path: path.to_string(), content: content.to_string(), anchor: None }
+    }
+
+    fn row(path: &str, content: &str) -> (String, String) {
+        (path.to_string(), dedupe::content_hash(content))
+    }
+
+    #[test]
+    fn only_changed_pages_are_planned() {
+        let stored = vec![
+            row("tokio/latest/tokio/fn.spawn.html", "Spawns a new task."),
+            row("tokio/latest/tokio/index.html", "A runtime for async Rust."),
+            row("tokio/latest/tokio/big.html [chunk 1/2]", "First half."),
+            row("tokio/latest/tokio/big.html [chunk 2/2]", "Second half."),
+            row("tokio/latest/tokio/fn.removed.html", "No longer documented."),
+        ];
+        let documents = vec![
+            doc("tokio/latest/tokio/fn.spawn.html", "Spawns a new task."),
+            doc("tokio/latest/tokio/index.html", "A runtime for writing reliable async Rust."),
+            doc("tokio/latest/tokio/big.html", "Now short enough for one chunk."),
+            doc("tokio/latest/tokio/fn.select.html", "Waits on multiple branches."),
+        ];
+
+        let plan = plan_refresh(documents, &stored);
+        let changed: Vec<&str> = plan.changed.iter().map(|doc| doc.path.as_str()).collect();
+        assert_eq!(
+            changed,
+            ["tokio/latest/tokio/index.html", "tokio/latest/tokio/big.html", "tokio/latest/tokio/fn.select.html"]
+        );
+        assert_eq!((plan.added, plan.updated(), plan.unchanged), (1, 2, 1));
+
+        let mut stale = plan.stale_paths;
+        stale.sort();
+        assert_eq!(stale, ["tokio/latest/tokio/big.html [chunk 1/2]", "tokio/latest/tokio/big.html [chunk 2/2]"]);
+        assert_eq!(plan.missing_pages, 1);
+        assert_eq!(plan.missing_paths, ["tokio/latest/tokio/fn.removed.html"]);
+    }
+}
@@ -0,0 +1,176 @@
+// Stored documents as MCP resources, so clients that support resource browsing can page
+// through a crate's docs directly. Each document is `doc://{crate}/{doc_path}`; listings
+// are paginated with the (encoded) URI of the last resource returned as the cursor.
+
+use crate::{
+    database::VectorStore,
+    pagination::{decode_cursor, encode_cursor, invalid_cursor},
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use rmcp::{
+    model::{AnnotateAble, ListResourcesResult, RawResource, ReadResourceResult, ResourceContents},
+    Error as McpError,
+};
+use serde_json::json;
+
+pub const DOC_URI_SCHEME: &str = "doc://";
+
+/// Resources returned per `resources/list` page
+pub const RESOURCE_PAGE_SIZE: usize = 100;
+
+/// Characters escaped in doc paths; `/` is kept so URIs mirror the docs.rs layout
+const PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// `doc://{crate}/{doc_path}`, with the path percent-encoded (chunked paths contain spaces)
+pub fn doc_uri(crate_name: &str, doc_path: &str) -> String {
+    format!("{}{}/{}", DOC_URI_SCHEME, crate_name, utf8_percent_encode(doc_path, PATH_ESCAPES))
+}
+
+/// Split a doc URI into its crate name and decoded doc path
+pub fn parse_doc_uri(uri: &str) -> Option<(&str, String)> {
+    let (crate_name, path) = uri.strip_prefix(DOC_URI_SCHEME)?.split_once('/')?;
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    (!crate_name.is_empty() && !path.is_empty()).then(|| (crate_name, path.into_owned()))
+}
+
+/// One page of the documents of `crates`, in crate order then path order
+pub async fn list_doc_resources(
+    store: &(dyn VectorStore + Send + Sync),
+    crates: &[String],
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<ListResourcesResult, McpError> {
+    let (first_crate, mut after) = match cursor {
+        None => (0, None),
+        Some(cursor) => {
+            let last_uri = decode_cursor(cursor)?;
+            parse_doc_uri(&last_uri)
+                .and_then(|(crate_name, path)| {
+                    let index = crates.iter().position(|c| c == crate_name)?;
+                    Some((index, Some(path)))
+                })
+                .ok_or_else(|| invalid_cursor(cursor))?
+        }
+    };
+
+    let mut resources = Vec::new();
+    let mut next_cursor = None;
+    for crate_name in &crates[first_crate..] {
+        let remaining = page_size - resources.len();
+        // One extra row tells whether another page follows
+        let mut paths = store
+            .list_doc_paths(crate_name, after.take().as_deref(), remaining + 1)
+            .await
+            .map_err(|e| e.into_mcp_error("resource"))?;
+        let more = paths.len() > remaining;
+        paths.truncate(remaining);
+
+        resources.extend(paths.into_iter().map(|path| {
+            let mut resource = RawResource::new(doc_uri(crate_name, &path), path);
+            resource.description = Some(format!("{} documentation", crate_name));
+            resource.mime_type = Some("text/plain".to_string());
+            resource.no_annotation()
+        }));
+        if more {
+            next_cursor = resources.last().map(|resource| encode_cursor(&resource.uri));
+            break;
+        }
+    }
+
+    Ok(ListResourcesResult { resources, next_cursor })
+}
+
+/// The stored content of a doc URI, if its crate is one of `crates`
+pub async fn read_doc_resource(
+    store: &(dyn VectorStore + Send + Sync),
+    crates: &[String],
+    uri: &str,
+) -> Result<ReadResourceResult, McpError> {
+    let not_found = || {
+        McpError::resource_not_found(format!("Resource URI not found: {}", uri), Some(json!({ "uri": uri })))
+    };
+    let (crate_name, doc_path) = parse_doc_uri(uri).ok_or_else(not_found)?;
+    if !crates.iter().any(|c| c == crate_name) {
+        return Err(not_found());
+    }
+
+    let content = store
+        .get_doc_content(crate_name, &doc_path)
+        .await
+        .map_err(|e| e.into_mcp_error("resource"))?
+        .ok_or_else(not_found)?;
+    Ok(ReadResourceResult {
+        contents: vec![ResourceContents::text(content, uri)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{test_doc, test_store_with};
+    use rmcp::model::ErrorCode;
+
+    #[test]
+    fn doc_uris_round_trip_chunked_paths() {
+        let uri = doc_uri("tokio", "tokio/latest/tokio/big.html [chunk 1/2]");
+        assert_eq!(uri, "doc://tokio/tokio/latest/tokio/big.html%20%5Bchunk%201/2%5D");
+        assert_eq!(
+            parse_doc_uri(&uri),
+            Some(("tokio", "tokio/latest/tokio/big.html [chunk 1/2]".to_string()))
+        );
+        assert_eq!(parse_doc_uri("crate://tokio"), None);
+    }
+
+    #[tokio::test]
+    async fn pages_through_every_crate_and_reads_documents() {
+        let pages = |crate_name: &str, count: usize| -> Vec<_> {
+            (0..count)
+                .map(|i| {
+                    let path = format!("{}/latest/{}/page{}.html", crate_name, crate_name, i);
+                    test_doc(&path, &format!("{} page {}", crate_name, i), &[1.0, 0.0])
+                })
+                .collect()
+        };
+        let store = test_store_with(&[("axum", pages("axum", 3)), ("tower", pages("tower", 2))]).await;
+        let crates = vec!["axum".to_string(), "tower".to_string()];
+
+        let mut uris = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = list_doc_resources(store.as_ref(), &crates, cursor.as_deref(), 2).await.unwrap();
+            assert!(page.resources.len() <= 2);
+            uris.extend(page.resources.into_iter().map(|resource| resource.raw.uri));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(uris.len(), 5);
+        assert_eq!(uris[0], "doc://axum/axum/latest/axum/page0.html");
+        assert_eq!(uris[4], "doc://tower/tower/latest/tower/page1.html");
+
+        let read = read_doc_resource(store.as_ref(), &crates, &uris[3]).await.unwrap();
+        assert!(matches!(&read.contents[0], ResourceContents::TextResourceContents { text, .. } if text == "tower page 0"));
+
+        let missing = read_doc_resource(store.as_ref(), &["axum".to_string()], &uris[3]).await.unwrap_err();
+        assert_eq!(missing.code, ErrorCode::RESOURCE_NOT_FOUND);
+        let cursor = encode_cursor("doc://serde/serde/latest/serde/index.html");
+        let bad_cursor = list_doc_resources(store.as_ref(), &crates, Some(&cursor), 2).await.unwrap_err();
+        assert_eq!(bad_cursor.code, ErrorCode::INVALID_PARAMS);
+    }
+}
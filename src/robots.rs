@@ -0,0 +1,192 @@
+// robots.txt rules for the crawler (RFC 9309). Rules are fetched once per host and
+// cached for the rest of the process, so every crate of a run shares one request.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+use tracing::{debug, info, warn};
+
+/// Bytes of a robots.txt that are parsed; RFC 9309 asks crawlers to read at least 500 KiB
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+
+static RULES: LazyLock<Mutex<HashMap<String, Arc<RobotsRules>>>> = LazyLock::new(Default::default);
+
+/// The Allow/Disallow rules of the robots.txt group that applies to this crawler
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsRules {
+    /// `(allowed, pattern)` in file order
+    rules: Vec<(bool, String)>,
+}
+
+impl RobotsRules {
+    /// Rules for `user_agent` from a robots.txt: the groups naming its product token
+    /// (e.g. `rustdocs-mcp-server`), or else the `*` groups
+    pub fn parse(robots_txt: &str, user_agent: &str) -> Self {
+        let product = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        // Groups name the crawler by its whole product token, compared case-insensitively
+        let names_crawler = |agent: &str| !agent.is_empty() && agent == product;
+
+        let mut specific = Vec::new();
+        let mut fallback = Vec::new();
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        let mut named = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    // A user-agent line after rules starts a new group
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    let agent = value.to_lowercase();
+                    named |= names_crawler(&agent);
+                    agents.push(agent);
+                }
+                field @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // An empty Disallow allows everything, so it adds no rule
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (field == "allow", value.to_string());
+                    if agents.iter().any(|agent| names_crawler(agent)) {
+                        specific.push(rule.clone());
+                    }
+                    if agents.iter().any(|agent| agent == "*") {
+                        fallback.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { rules: if named { specific } else { fallback } }
+    }
+
+    /// Whether `path` (with its query) may be fetched: the longest matching rule decides,
+    /// Allow winning a tie, and paths no rule matches are allowed
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, path))
+            .max_by_key(|(allowed, pattern)| (pattern.len(), *allowed))
+            .is_none_or(|(allowed, _)| *allowed)
+    }
+}
+
+/// Match a robots.txt path pattern, where `*` is any run of characters and a trailing `$`
+/// anchors the end of the path
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+/// Rules for the host of `url`, fetched on first use. A missing or unreadable robots.txt
+/// allows everything.
+pub async fn rules_for(client: &reqwest::Client, url: &reqwest::Url) -> Arc<RobotsRules> {
+    let origin = url.origin().ascii_serialization();
+    if let Some(rules) = RULES.lock().unwrap().get(&origin) {
+        return rules.clone();
+    }
+
+    let robots_url = format!("{}/robots.txt", origin);
+    let rules = match client.get(&robots_url).send().await {
+        Ok(response) if response.status().is_success() => match response.text().await {
+            Ok(text) => {
+                let mut end = text.len().min(MAX_ROBOTS_BYTES);
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let rules = RobotsRules::parse(&text[..end], &crate::doc_loader::user_agent());
+                info!("🤖 Following {} ({} rules apply)", robots_url, rules.rules.len());
+                rules
+            }
+            Err(e) => {
+                warn!("⚠️  Could not read {}, crawling without it: {}", robots_url, e);
+                RobotsRules::default()
+            }
+        },
+        Ok(response) => {
+            debug!("No robots.txt at {} (HTTP {})", robots_url, response.status());
+            RobotsRules::default()
+        }
+        Err(e) => {
+            warn!("⚠️  Could not fetch {}, crawling without it: {}", robots_url, e);
+            RobotsRules::default()
+        }
+    };
+
+    let rules = Arc::new(rules);
+    RULES.lock().unwrap().insert(origin, rules.clone());
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_the_most_specific_matching_rule() {
+        let robots = "\
+            # docs.rs style rules\n\
+            User-agent: *\n\
+            Disallow: /crate/*/source/\n\
+            Disallow: /*/src/\n\
+            Allow: /tokio/*/src/tokio/lib.rs.html$\n\
+            Disallow: /private\n\
+            \n\
+            User-agent: badbot\n\
+            Disallow: /\n";
+        let rules = RobotsRules::parse(robots, "rustdocs-mcp-server/1.0 (+https://example.com)");
+        assert!(rules.allows("/tokio/latest/tokio/sync/struct.Mutex.html"));
+        assert!(!rules.allows("/tokio/1.38.0/src/tokio/runtime/mod.rs.html"));
+        assert!(rules.allows("/tokio/1.38.0/src/tokio/lib.rs.html"));
+        assert!(!rules.allows("/tokio/1.38.0/src/tokio/lib.rs.html?x=1"));
+        assert!(!rules.allows("/crate/tokio/1.38.0/source/Cargo.toml"));
+        assert!(!rules.allows("/private/index.html"));
+
+        // A group naming the crawler replaces the `*` rules
+        let robots = "User-agent: *\nDisallow: /\n\nUser-agent: RustDocs-MCP-Server\nUser-agent: other\nAllow: /\nDisallow: /internal/\n";
+        let rules = RobotsRules::parse(robots, "rustdocs-mcp-server/1.0");
+        assert!(rules.allows("/mycrate/index.html"));
+        assert!(!rules.allows("/internal/index.html"));
+        // Only the whole product token names the crawler, not a part of it
+        let robots = "User-agent: *\nDisallow: /\n\nUser-agent: RustDocs-MCP\nAllow: /\n";
+        assert!(!RobotsRules::parse(robots, "rustdocs-mcp-server/1.0").allows("/mycrate/index.html"));
+
+        assert!(RobotsRules::parse("User-agent: *\nDisallow:\n", "rustdocs-mcp-server").allows("/"));
+        assert!(RobotsRules::default().allows("/anything"));
+    }
+}
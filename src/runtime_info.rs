@@ -0,0 +1,98 @@
+// Effective runtime configuration of a running server, reported by the `server_info` tool
+// and `GET /info`. API keys are never included and database passwords are masked.
+
+use crate::{
+    database::{self, Backend, DistanceMetric, VectorStore},
+    embeddings::EMBEDDING_CLIENT,
+    error::ServerError,
+    secrets,
+};
+use serde::Serialize;
+
+/// Configuration a server is actually running with, after env, flags and defaults are applied
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeInfo {
+    pub server: String,
+    pub version: String,
+    /// `null` until the embedding provider is initialized
+    pub embedding: Option<EmbeddingInfo>,
+    /// Chat model used to summarize answers; `null` for servers that return raw search results
+    pub llm_model: Option<String>,
+    pub database: DatabaseInfo,
+    pub crates: Vec<String>,
+    pub refresh_enabled: bool,
+    /// Queries per minute per client; `null` when unlimited
+    pub rate_limit_per_minute: Option<u32>,
+    /// Misconfigurations worth fixing, e.g. a model that doesn't match the stored vectors
+    pub warnings: Vec<String>,
+}
+
+/// The embedding provider queries are embedded with
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingInfo {
+    pub provider: String,
+    pub model: String,
+    pub dimensions: usize,
+}
+
+/// The vector store searches run against
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseInfo {
+    pub backend: Backend,
+    /// `MCPDOCS_DATABASE_URL` with the password masked
+    pub url: Option<String>,
+    pub distance_metric: DistanceMetric,
+    /// Dimension of the stored embeddings, if the backend knows it
+    pub stored_embedding_dimensions: Option<usize>,
+}
+
+impl RuntimeInfo {
+    /// Gather the settings shared by every transport from the store and the global embedding provider
+    pub async fn collect(
+        server: &str,
+        database: &(dyn VectorStore + Send + Sync),
+        crates: &[String],
+        refresh_enabled: bool,
+        rate_limit_per_minute: Option<u32>,
+    ) -> Result<Self, ServerError> {
+        let stored_embedding_dimensions = database.stored_embedding_dimensions().await?;
+        let embedding = EMBEDDING_CLIENT.get().map(|provider| EmbeddingInfo {
+            provider: provider.provider_name().to_string(),
+            model: provider.get_model_name().to_string(),
+            dimensions: provider.embedding_dimensions(),
+        });
+
+        let mut warnings = Vec::new();
+        if let (Some(embedding), Some(stored)) = (&embedding, stored_embedding_dimensions) {
+            if embedding.dimensions != stored {
+                warnings.push(format!(
+                    "Embedding model {} produces {}-dimensional vectors but the database stores {}-dimensional ones",
+                    embedding.model, embedding.dimensions, stored
+                ));
+            }
+        }
+
+        Ok(Self {
+            server: server.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            embedding,
+            llm_model: None,
+            database: DatabaseInfo {
+                backend: database.backend(),
+                url: database::database_url().ok().map(|url| secrets::mask_url_password(&url)),
+                distance_metric: database.distance_metric(),
+                stored_embedding_dimensions,
+            },
+            crates: crates.to_vec(),
+            refresh_enabled,
+            rate_limit_per_minute,
+            warnings,
+        })
+    }
+
+    /// Report the chat model answers are summarized with
+    pub fn with_llm_model(mut self, model: String) -> Self {
+        self.llm_model = Some(model);
+        self
+    }
+}
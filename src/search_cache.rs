@@ -0,0 +1,162 @@
+// In-process cache of vector search results, keyed by crate and a hash of the question.
+// A hit skips both the embedding request and the database query; it caches retrieval
+// only, so callers still build their own prompts from the cached documents.
+
+use crate::{database::DocMatch, dedupe::content_hash};
+use moka::future::Cache;
+use std::{env, time::Duration};
+
+/// Entries kept by default; `SEARCH_CACHE_CAPACITY=0` disables the cache
+pub const DEFAULT_SEARCH_CACHE_CAPACITY: u64 = 1000;
+/// How long results stay cached by default (`SEARCH_CACHE_TTL_SECS`)
+pub const DEFAULT_SEARCH_CACHE_TTL_SECS: u64 = 300;
+
+/// Documents as returned by `VectorStore` searches
+pub type SearchResults = Vec<DocMatch>;
+
+/// Identifies one search: same crate, question and search parameters give the same results
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchKey {
+    crate_name: String,
+    question_hash: String,
+    limit: i32,
+    prefer_examples: bool,
+    module_prefix: Option<String>,
+    kind: Option<String>,
+    /// Bits of the MMR lambda the results were diversified with (`f32` isn't `Hash`)
+    mmr_lambda: Option<u32>,
+}
+
+impl SearchKey {
+    pub fn new(crate_name: &str, question: &str, limit: i32, prefer_examples: bool) -> Self {
+        Self {
+            crate_name: crate_name.to_string(),
+            question_hash: content_hash(question),
+            limit,
+            prefer_examples,
+            module_prefix: None,
+            kind: None,
+            mmr_lambda: None,
+        }
+    }
+
+    /// The same search, limited to `module_prefix` (see `search_similar_docs_filtered`)
+    pub fn in_module(mut self, module_prefix: Option<&str>) -> Self {
+        self.module_prefix = module_prefix.map(str::to_string);
+        self
+    }
+
+    /// The same search, limited to documents of one `kind` (see `doc_loader::doc_kind`)
+    pub fn of_kind(mut self, kind: Option<&str>) -> Self {
+        self.kind = kind.map(str::to_string);
+        self
+    }
+
+    /// The same search, re-ranked with MMR at `mmr_lambda` (see `client::mmr_rerank`)
+    pub fn with_mmr(mut self, mmr_lambda: Option<f32>) -> Self {
+        self.mmr_lambda = mmr_lambda.map(f32::to_bits);
+        self
+    }
+}
+
+/// LRU-style cache with a TTL; a disabled cache never stores anything
+pub struct SearchCache {
+    cache: Option<Cache<SearchKey, SearchResults>>,
+}
+
+impl SearchCache {
+    /// A cache of up to `capacity` searches, each kept for `ttl`. A capacity of 0 disables it.
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        let cache = (capacity > 0).then(|| {
+            Cache::builder()
+                .max_capacity(capacity)
+                .time_to_live(ttl)
+                .build()
+        });
+        Self { cache }
+    }
+
+    pub fn disabled() -> Self {
+        Self { cache: None }
+    }
+
+    /// Configure from `SEARCH_CACHE_CAPACITY` and `SEARCH_CACHE_TTL_SECS`
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+        Self::new(
+            read("SEARCH_CACHE_CAPACITY", DEFAULT_SEARCH_CACHE_CAPACITY),
+            Duration::from_secs(read("SEARCH_CACHE_TTL_SECS", DEFAULT_SEARCH_CACHE_TTL_SECS)),
+        )
+    }
+
+    pub async fn get(&self, key: &SearchKey) -> Option<SearchResults> {
+        self.cache.as_ref()?.get(key).await
+    }
+
+    pub async fn insert(&self, key: SearchKey, results: SearchResults) {
+        if let Some(cache) = &self.cache {
+            cache.insert(key, results).await;
+        }
+    }
+
+    /// Drop every entry, e.g. after a crate's documents were re-embedded
+    pub fn clear(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_all();
+        }
+    }
+}
+
+impl Default for SearchCache {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> SearchResults {
+        vec![DocMatch {
+            doc_path: "tokio/latest/tokio/fn.spawn.html".to_string(),
+            content: "Spawns a new task.".to_string(),
+            similarity: 0.82,
+            token_count: Some(5),
+        }]
+    }
+
+    #[tokio::test]
+    async fn caches_by_crate_question_and_parameters() {
+        let cache = SearchCache::new(10, Duration::from_secs(60));
+        let key = SearchKey::new("tokio", "How do I spawn a task?", 3, false);
+        assert!(cache.get(&key).await.is_none());
+
+        cache.insert(key.clone(), results()).await;
+        assert_eq!(cache.get(&key).await, Some(results()));
+        assert!(cache.get(&SearchKey::new("axum", "How do I spawn a task?", 3, false)).await.is_none());
+        assert!(cache.get(&SearchKey::new("tokio", "How do I spawn a task?", 3, true)).await.is_none());
+        let in_sync = SearchKey::new("tokio", "How do I spawn a task?", 3, false).in_module(Some("tokio::sync"));
+        assert!(cache.get(&in_sync).await.is_none());
+        let traits = SearchKey::new("tokio", "How do I spawn a task?", 3, false).of_kind(Some("trait"));
+        assert!(cache.get(&traits).await.is_none());
+
+        cache.clear();
+        assert!(cache.get(&key).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn expires_entries_and_can_be_disabled() {
+        let cache = SearchCache::new(10, Duration::from_millis(50));
+        let key = SearchKey::new("tokio", "How do I spawn a task?", 3, false);
+        cache.insert(key.clone(), results()).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(cache.get(&key).await.is_none());
+
+        let disabled = SearchCache::new(0, Duration::from_secs(60));
+        disabled.insert(key.clone(), results()).await;
+        assert!(disabled.get(&key).await.is_none());
+    }
+}
@@ -0,0 +1,80 @@
+// Secrets from the environment, or from a mounted file named by `<VAR>_FILE`
+// (the convention used by most Docker images for Docker/Kubernetes secrets).
+
+use crate::error::ServerError;
+use std::{env, fs};
+
+/// Read `name` from the environment, falling back to the contents of the file at
+/// `<name>_FILE`. Blank values count as unset; file contents are trimmed.
+pub fn env_or_file(name: &str) -> Result<Option<String>, ServerError> {
+    if let Some(value) = env::var(name).ok().filter(|v| !v.trim().is_empty()) {
+        return Ok(Some(value));
+    }
+
+    let file_var = format!("{}_FILE", name);
+    let Some(path) = env::var(&file_var).ok().filter(|p| !p.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| ServerError::Config(format!("Failed to read {} from {}: {}", name, path, e)))?;
+    let value = contents.trim();
+    if value.is_empty() {
+        return Err(ServerError::Config(format!("{} file {} is empty", file_var, path)));
+    }
+    Ok(Some(value.to_string()))
+}
+
+/// Like [`env_or_file`], but missing is an error naming both variables
+pub fn require_env_or_file(name: &str) -> Result<String, ServerError> {
+    env_or_file(name)?.ok_or_else(|| ServerError::MissingEnvVar(format!("{} (or {}_FILE)", name, name)))
+}
+
+/// `url` with any password replaced by `****`, safe to log or report
+pub fn mask_url_password(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("****"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own variable names, since tests share the process environment
+    #[test]
+    fn reads_value_from_file() {
+        let path = env::temp_dir().join(format!("mcpdocs-secret-{}", std::process::id()));
+        fs::write(&path, "sk-from-file\n").unwrap();
+        env::set_var("MCPDOCS_TEST_SECRET_FILE", &path);
+
+        assert_eq!(env_or_file("MCPDOCS_TEST_SECRET").unwrap().as_deref(), Some("sk-from-file"));
+
+        env::set_var("MCPDOCS_TEST_SECRET", "sk-from-env");
+        assert_eq!(env_or_file("MCPDOCS_TEST_SECRET").unwrap().as_deref(), Some("sk-from-env"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_secret_names_both_variables() {
+        assert!(env_or_file("MCPDOCS_TEST_UNSET").unwrap().is_none());
+        let err = require_env_or_file("MCPDOCS_TEST_UNSET").unwrap_err();
+        assert!(err.to_string().contains("MCPDOCS_TEST_UNSET_FILE"));
+
+        env::set_var("MCPDOCS_TEST_UNREADABLE_FILE", "/nonexistent/mcpdocs-secret");
+        assert!(matches!(env_or_file("MCPDOCS_TEST_UNREADABLE"), Err(ServerError::Config(_))));
+    }
+
+    #[test]
+    fn masks_url_passwords() {
+        assert_eq!(
+            mask_url_password("postgres://docs:hunter2@db:5432/rust_docs"),
+            "postgres://docs:****@db:5432/rust_docs"
+        );
+        assert_eq!(mask_url_password("postgres://docs@db/rust_docs"), "postgres://docs@db/rust_docs");
+        assert_eq!(mask_url_password("sqlite://rust_docs.db"), "sqlite://rust_docs.db");
+    }
+}
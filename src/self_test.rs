@@ -0,0 +1,147 @@
+// Startup self-test: embed a trivial question per served crate and run a real search, so a
+// model/dimension mismatch, a broken provider key or an empty crate fails at boot instead of
+// on the first user query.
+
+use crate::{
+    database::{self, DocMatch, VectorStore},
+    embeddings::EmbeddingProvider,
+    error::ServerError,
+};
+use ndarray::Array1;
+
+/// Lowest best-match similarity accepted for the self-test question. Real embeddings of a
+/// question about a crate score well above this against its docs; near zero means the vectors
+/// don't share a space (wrong model, or documents embedded by a different provider).
+pub const MIN_SELF_TEST_SIMILARITY: f32 = 0.1;
+
+/// Documents fetched per crate during the self-test
+const SELF_TEST_RESULTS: i32 = 3;
+
+/// The question embedded for a crate
+pub fn self_test_question(crate_name: &str) -> String {
+    format!("What is the {} crate used for?", crate_name)
+}
+
+/// Self-test outcome for one crate
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrateCheck {
+    pub crate_name: String,
+    pub results: usize,
+    pub best_similarity: Option<f32>,
+    /// Why the crate failed, if it did
+    pub problem: Option<String>,
+}
+
+impl CrateCheck {
+    pub fn passed(&self) -> bool {
+        self.problem.is_none()
+    }
+
+    /// Judge a crate's search results
+    pub fn evaluate(crate_name: &str, results: &[DocMatch]) -> Self {
+        let best_similarity = results.iter().map(|result| result.similarity).reduce(f32::max);
+        let problem = match best_similarity {
+            None => Some("search returned no documents (is the crate populated?)".to_string()),
+            Some(similarity) if !similarity.is_finite() || similarity < MIN_SELF_TEST_SIMILARITY => Some(format!(
+                "best match similarity {:.3} is below {} (were the documents embedded with another model?)",
+                similarity, MIN_SELF_TEST_SIMILARITY
+            )),
+            Some(_) => None,
+        };
+        Self {
+            crate_name: crate_name.to_string(),
+            results: results.len(),
+            best_similarity,
+            problem,
+        }
+    }
+
+    fn failed(crate_name: &str, problem: String) -> Self {
+        Self {
+            crate_name: crate_name.to_string(),
+            results: 0,
+            best_similarity: None,
+            problem: Some(problem),
+        }
+    }
+}
+
+/// Check the embedding dimensions against the store, then embed one question per crate
+/// (in a single request) and search each crate with it. Provider and dimension errors
+/// fail the whole test; per-crate search problems are reported in the returned checks.
+pub async fn run_self_test(
+    store: &(dyn VectorStore + Send + Sync),
+    provider: &(dyn EmbeddingProvider + Send + Sync),
+    crate_names: &[String],
+) -> Result<Vec<CrateCheck>, ServerError> {
+    database::check_embedding_dimensions(store, provider).await?;
+
+    let questions: Vec<String> = crate_names.iter().map(|name| self_test_question(name)).collect();
+    let (embeddings, _) = provider.generate_embeddings(&questions).await?;
+    if embeddings.len() != crate_names.len() {
+        return Err(ServerError::Internal(format!(
+            "Embedding provider returned {} embeddings for {} questions",
+            embeddings.len(),
+            crate_names.len()
+        )));
+    }
+
+    let mut checks = Vec::with_capacity(crate_names.len());
+    for (crate_name, embedding) in crate_names.iter().zip(embeddings) {
+        let check = match store
+            .search_similar_docs(crate_name, &Array1::from(embedding), SELF_TEST_RESULTS)
+            .await
+        {
+            Ok(results) => CrateCheck::evaluate(crate_name, &results),
+            Err(e) => CrateCheck::failed(crate_name, format!("search failed: {}", e)),
+        };
+        checks.push(check);
+    }
+    Ok(checks)
+}
+
+/// `Ok` if every crate passed, otherwise an error listing each failed crate and why
+pub fn require_all_passed(checks: &[CrateCheck]) -> Result<(), ServerError> {
+    let failures: Vec<String> = checks
+        .iter()
+        .filter_map(|check| Some(format!("{}: {}", check.crate_name, check.problem.as_ref()?)))
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(ServerError::Internal(format!("Self-test failed for {}", failures.join("; "))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(similarity: f32) -> DocMatch {
+        DocMatch {
+            doc_path: "tokio/latest/tokio/index.html".to_string(),
+            content: "A runtime for async Rust.".to_string(),
+            similarity,
+            token_count: Some(6),
+        }
+    }
+
+    #[test]
+    fn passes_crates_with_a_reasonable_match() {
+        let check = CrateCheck::evaluate("tokio", &[row(0.21), row(0.48)]);
+        assert!(check.passed());
+        assert_eq!(check.results, 2);
+        assert_eq!(check.best_similarity, Some(0.48));
+    }
+
+    #[test]
+    fn fails_empty_crates_and_unrelated_vectors() {
+        let empty = CrateCheck::evaluate("tokio", &[]);
+        assert!(!empty.passed());
+        assert!(empty.problem.unwrap().contains("no documents"));
+
+        let mismatched = CrateCheck::evaluate("tokio", &[row(0.01), row(f32::NAN)]);
+        assert!(!mismatched.passed());
+        assert!(mismatched.problem.unwrap().contains("below"));
+    }
+}
@@ -1,16 +1,20 @@
+pub use crate::client::{collapse_chunks, AnswerUsage, Grounding};
 use crate::{
-    database::Database,
-    doc_loader::Document,
+    client::{self, max_context_tokens, truncate_context, SearchHit, CHUNK_OVERFETCH, MMR_CANDIDATE_FACTOR, RESULTS_PER_CRATE},
+    database::{CrateStatus, DocFilter, DocMatch, VectorStore},
+    doc_loader,
     embeddings::EMBEDDING_CLIENT,
     error::ServerError, // Keep ServerError for ::new()
-};
-use async_openai::{
-    config::OpenAIConfig,
-    types::{
-        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-        CreateChatCompletionRequestArgs,
-    },
-    Client as OpenAIClient,
+    examples,
+    rate_limit::{RateLimiter, STDIO_CLIENT},
+    pagination::{self, DEFAULT_PAGE_SIZE},
+    prompt_guard::{self, REFERENCE_DATA_INSTRUCTION},
+    refresh,
+    resources::{self, RESOURCE_PAGE_SIZE},
+    runtime_info::RuntimeInfo,
+    search_cache::{SearchCache, SearchKey},
+    vector_cache::VectorCache,
+    version_diff::{self, VersionComparison},
 };
 use ndarray::Array1;
 use rmcp::model::AnnotateAble; // Import trait for .no_annotation()
@@ -35,22 +39,24 @@ use rmcp::{
         PaginatedRequestParam,
         ProtocolVersion,
         RawResource,
-        /* Prompt, PromptArgument, PromptMessage, PromptMessageContent, PromptMessageRole, */ // Removed Prompt types
+        Prompt,
         ReadResourceRequestParam,
         ReadResourceResult,
+        ResourceTemplate,
         Resource,
-        ResourceContents,
         ServerCapabilities,
         ServerInfo,
         ServerNotification,
+        SetLevelRequestParam,
     },
     service::{RequestContext, RoleServer},
     tool,
 };
 use schemars::JsonSchema; // Import JsonSchema
-use serde::Deserialize; // Import Deserialize
+use serde::{Deserialize, Serialize}; // Import Deserialize
+use futures::future::join_all;
 use serde_json::json;
-use std::{/* borrow::Cow, */ env, sync::Arc}; // Removed borrow::Cow
+use std::{/* borrow::Cow, */ collections::{HashMap, HashSet}, env, sync::{atomic::{AtomicU8, Ordering}, Arc}, time::Instant}; // Removed borrow::Cow
 use tokio::sync::Mutex;
 
 // --- Argument Struct for the Tool ---
@@ -58,9 +64,556 @@ use tokio::sync::Mutex;
 #[derive(Debug, Deserialize, JsonSchema)]
 struct QueryRustDocsArgs {
     #[schemars(description = "The crate to search in (e.g., \"axum\", \"tokio\", \"serde\")")]
-    crate_name: String,
+    #[serde(default)]
+    crate_name: Option<String>,
+    #[schemars(description = "Several crates to search at once, as an alternative to crate_name (e.g., [\"axum\", \"tower\", \"hyper\"])")]
+    #[serde(default)]
+    crate_names: Option<Vec<String>>,
     #[schemars(description = "The specific question about the crate's API or usage.")]
     question: String,
+    #[schemars(description = "Rank documents containing code examples higher. Defaults to true for usage questions (\"how do I ...\").")]
+    #[serde(default)]
+    prefer_examples: Option<bool>,
+    #[schemars(description = "Only search items in this module and its submodules (e.g., \"tokio::sync\").")]
+    #[serde(default)]
+    module_prefix: Option<String>,
+    #[schemars(description = "Only search items of this kind: \"struct\", \"trait\", \"fn\", \"enum\", \"macro\", \"mod\" or \"type\".")]
+    #[serde(default)]
+    kind: Option<String>,
+    #[schemars(description = "\"text\" (default) for a formatted answer, or \"json\" for {answer, sources, usage}.")]
+    #[serde(default)]
+    output_format: OutputFormat,
+    #[schemars(description = "BCP-47 tag of the language to answer in (e.g., \"ja\", \"pt-BR\"). Defaults to English; the docs searched stay English.")]
+    #[serde(default)]
+    language: Option<String>,
+    #[schemars(description = "If the crate's own docs match poorly, also search its indexed dependencies (e.g., tower and hyper for axum). Single-crate queries only.")]
+    #[serde(default)]
+    follow_deps: bool,
+    #[schemars(description = "Diversify the retrieved documents (maximal marginal relevance), between 0 and 1: 1 ranks by relevance only, lower values (e.g. 0.7) skip documents that repeat ones already picked. Useful for questions with several parts.")]
+    #[serde(default)]
+    mmr_lambda: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct QueryWithHistoryArgs {
+    #[serde(flatten)]
+    query: QueryRustDocsArgs,
+    #[schemars(description = "Earlier messages of the conversation, oldest first, used to resolve references such as \"it\" in the question. Only the question is searched.")]
+    #[serde(default)]
+    history: Vec<HistoryMessage>,
+    #[schemars(description = "A short summary of the conversation so far, instead of or alongside history.")]
+    #[serde(default)]
+    summary: Option<String>,
+}
+
+/// An earlier message passed to `query_with_history`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct HistoryMessage {
+    pub role: HistoryRole,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryRole {
+    User,
+    Assistant,
+}
+
+/// How `query_rust_docs` returns its answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The answer followed by a `Sources:` list
+    #[default]
+    Text,
+    /// A [`QueryAnswer`] as JSON
+    Json,
+}
+
+/// `query_rust_docs` output in JSON mode
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryAnswer {
+    pub answer: String,
+    pub sources: Vec<AnswerSource>,
+    /// `null` when no documents were found
+    pub grounding: Option<Grounding>,
+    /// The best match is below the confidence floor, so `answer` is a best guess
+    pub low_confidence: bool,
+    pub usage: AnswerUsage,
+    /// Dependencies searched because the crate's own docs matched poorly (`follow_deps`)
+    /// that contributed sources
+    pub related_crates: Vec<String>,
+}
+
+/// A retrieved document the answer was generated from, best match first
+#[derive(Debug, Clone, Serialize)]
+pub struct AnswerSource {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub doc_path: String,
+    pub similarity: f32,
+    /// Page section the document holds (e.g. `method.route`), `null` for top-level docs
+    pub anchor: Option<String>,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct GetCrateExamplesArgs {
+    #[schemars(description = "The crate to search in (e.g., \"axum\", \"tokio\", \"serde\")")]
+    crate_name: String,
+    #[schemars(description = "What the example should show (e.g., \"spawning a task\", \"custom extractor\").")]
+    topic: String,
+    #[schemars(description = "Cut each example to about this many characters (at least 50), ending with its doc path for follow-up.")]
+    #[serde(default)]
+    snippet_chars: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CompareCratesArgs {
+    #[schemars(description = "The first crate to compare (e.g., \"hyper\")")]
+    crate_a: String,
+    #[schemars(description = "The second crate to compare (e.g., \"reqwest\")")]
+    crate_b: String,
+    #[schemars(description = "What to compare (e.g., \"What is the equivalent of hyper's Client::request?\")")]
+    question: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CompareVersionsArgs {
+    #[schemars(description = "The crate whose versions to compare (e.g., \"tokio\")")]
+    crate_name: String,
+    #[schemars(description = "The version upgrading from (e.g., \"1.37.0\"), as stored with populate_db --crate-version")]
+    from: String,
+    #[schemars(description = "The version upgrading to (e.g., \"1.38.0\", or \"latest\" for the crate's default entry)")]
+    to: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SearchByItemPathArgs {
+    #[schemars(description = "The crate to look in (e.g., \"tokio\")")]
+    crate_name: String,
+    #[schemars(description = "The item to return, as a Rust path (e.g., \"tokio::sync::Mutex\") or a rustdoc page (e.g., \"sync/struct.Mutex.html\")")]
+    item_path: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct LookupItemArgs {
+    #[schemars(description = "The crate to look in (e.g., \"axum\")")]
+    crate_name: String,
+    #[schemars(description = "The item to find, as a Rust path that may be inexact or misspelled (e.g., \"axum::Router::route\")")]
+    item_path: String,
+}
+
+/// A stored page or section suggested by `lookup_item`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemCandidate {
+    /// Item the page documents (e.g. `axum::Router::route`), or its path if unknown
+    pub item: String,
+    /// Stored path without chunk suffix
+    pub doc_path: String,
+    pub similarity: f32,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct RefreshCrateArgs {
+    #[schemars(description = "The crate to re-scrape from docs.rs (must already be in the database)")]
+    crate_name: String,
+}
+
+/// RFC 5424 order of MCP logging levels, least severe first. Until the client sends
+/// `logging/setLevel` every level is sent.
+pub fn log_severity(level: &LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// The sources of a JSON answer from crate-tagged search results
+pub fn answer_sources(results: &[(&str, DocMatch)]) -> Vec<AnswerSource> {
+    results
+        .iter()
+        .map(|(crate_name, result)| AnswerSource {
+            crate_name: crate_name.to_string(),
+            doc_path: result.doc_path.clone(),
+            similarity: result.similarity,
+            anchor: doc_loader::doc_anchor(&result.doc_path).map(str::to_string),
+            url: doc_loader::docs_rs_url(&result.doc_path),
+        })
+        .collect()
+}
+
+/// Reject an empty or whitespace-only tool argument before any network call
+pub fn require_non_empty<'a>(field: &str, value: &'a str) -> Result<&'a str, McpError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(McpError::invalid_params(
+            format!("{} must not be empty", field),
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": field })),
+        ));
+    }
+    Ok(value)
+}
+
+/// Validate `query_rust_docs` arguments so an empty question never reaches the embedding API.
+/// Returns the crate name and question trimmed, ready to use.
+pub fn validate_query_args<'a>(
+    crate_name: &'a str,
+    question: &'a str,
+) -> Result<(&'a str, &'a str), McpError> {
+    Ok((
+        require_non_empty("crate_name", crate_name)?,
+        require_non_empty("question", question)?,
+    ))
+}
+
+/// Crates a query should search: `crate_name` and/or `crate_names`, without duplicates
+pub fn resolve_target_crates(
+    crate_name: Option<&str>,
+    crate_names: Option<&[String]>,
+) -> Result<Vec<String>, McpError> {
+    let mut crates: Vec<String> = Vec::new();
+    for name in crate_name.into_iter().chain(crate_names.unwrap_or_default().iter().map(String::as_str)) {
+        let name = require_non_empty("crate_name", name)?;
+        if !crates.iter().any(|c| c == name) {
+            crates.push(name.to_string());
+        }
+    }
+
+    if crates.is_empty() {
+        return Err(McpError::invalid_params(
+            "Either crate_name or crate_names must be provided",
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "crate_name" })),
+        ));
+    }
+    Ok(crates)
+}
+
+/// Trim an optional `module_prefix` argument (`tokio::sync::` means `tokio::sync`)
+pub fn normalize_module_prefix(module_prefix: Option<&str>) -> Result<Option<&str>, McpError> {
+    module_prefix
+        .map(|prefix| require_non_empty("module_prefix", prefix.trim().trim_end_matches("::")))
+        .transpose()
+}
+
+/// Check an optional `kind` argument against [`doc_loader::DOC_KINDS`], ignoring case
+pub fn normalize_doc_kind(kind: Option<&str>) -> Result<Option<&'static str>, McpError> {
+    let Some(kind) = kind.map(|kind| require_non_empty("kind", kind)).transpose()? else {
+        return Ok(None);
+    };
+    match doc_loader::DOC_KINDS.into_iter().find(|known| known.eq_ignore_ascii_case(kind)) {
+        Some(known) => Ok(Some(known)),
+        None => Err(McpError::invalid_params(
+            format!("kind must be one of {}, not '{}'", doc_loader::DOC_KINDS.join(", "), kind),
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "kind" })),
+        )),
+    }
+}
+
+/// Trim an optional `language` argument and check it is a BCP-47 tag (e.g. `ja`, `pt-BR`)
+pub fn normalize_language(language: Option<&str>) -> Result<Option<&str>, McpError> {
+    let Some(language) = language.map(|language| require_non_empty("language", language)).transpose()? else {
+        return Ok(None);
+    };
+    if !client::is_language_tag(language) {
+        return Err(McpError::invalid_params(
+            format!("language must be a BCP-47 tag such as \"ja\" or \"pt-BR\", got \"{}\"", language),
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "language" })),
+        ));
+    }
+    Ok(Some(language))
+}
+
+/// Check an `mmr_lambda` argument, falling back to `MCPDOCS_MMR_LAMBDA` when it is absent
+pub fn resolve_mmr_lambda(mmr_lambda: Option<f32>) -> Result<Option<f32>, McpError> {
+    match mmr_lambda {
+        Some(lambda) if !(0.0..=1.0).contains(&lambda) => Err(McpError::invalid_params(
+            format!("mmr_lambda must be between 0 and 1, got {}", lambda),
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "mmr_lambda" })),
+        )),
+        Some(lambda) => Ok(Some(lambda)),
+        None => Ok(client::default_mmr_lambda()),
+    }
+}
+
+/// Re-rank `results` of a search in `crate_name` with MMR, using their stored vectors
+pub async fn diversify_results(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+    results: Vec<DocMatch>,
+    lambda: f32,
+    limit: usize,
+) -> Result<Vec<DocMatch>, ServerError> {
+    let paths: Vec<String> = results.iter().map(|result| result.doc_path.clone()).collect();
+    let vectors: HashMap<String, Array1<f32>> = store.get_doc_embeddings(crate_name, &paths).await?.into_iter().collect();
+    Ok(client::mmr_rerank(results, &vectors, lambda, limit))
+}
+
+/// Path fragments searched for an item path, most specific first (`route`, `Router`, `axum`
+/// for `axum::Router::route`; `Mutex`, `sync` for `sync/struct.Mutex.html`)
+pub fn item_lookup_terms(item_path: &str) -> Vec<String> {
+    let mut terms: Vec<String> = item_path
+        .split(['/', '#', ':'])
+        .map(|segment| segment.trim().trim_end_matches(".html"))
+        .map(|segment| segment.rsplit('.').next().unwrap_or(segment))
+        .filter(|segment| !segment.is_empty() && *segment != "index")
+        .map(str::to_string)
+        .collect();
+    terms.reverse();
+    terms.dedup();
+    terms
+}
+
+/// Trigram similarity of two item paths, ignoring case: shared over distinct 3-character
+/// windows (0.0 to 1.0), so `axum::Router::rout` is close to `axum::Router::route`
+pub fn item_path_similarity(a: &str, b: &str) -> f32 {
+    let trigrams = |text: &str| {
+        let chars: Vec<char> = format!("  {} ", text.to_lowercase()).chars().collect();
+        chars.windows(3).map(|window| window.iter().collect::<String>()).collect::<HashSet<_>>()
+    };
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Stored paths ranked by how close the item they document is to `item_path`, best first,
+/// with chunks of a page counted once
+pub fn rank_item_candidates(crate_name: &str, item_path: &str, doc_paths: Vec<String>, limit: usize) -> Vec<ItemCandidate> {
+    let mut seen = HashSet::new();
+    let mut candidates: Vec<ItemCandidate> = doc_paths
+        .iter()
+        .map(|doc_path| doc_loader::strip_chunk_suffix(doc_path))
+        .filter(|doc_path| seen.insert(doc_path.to_string()))
+        .map(|doc_path| {
+            let item = doc_loader::doc_item_path(crate_name, doc_path).unwrap_or_else(|| doc_path.to_string());
+            ItemCandidate {
+                similarity: item_path_similarity(item_path, &item),
+                item,
+                doc_path: doc_path.to_string(),
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.similarity
+            .partial_cmp(&a.similarity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.doc_path.cmp(&b.doc_path))
+    });
+    candidates.truncate(limit);
+    candidates
+}
+
+/// A stored page returned verbatim, headed by the item it documents and its docs.rs URL
+fn item_page_text(crate_name: &str, item_path: &str, doc_path: &str, content: &str) -> String {
+    let item = doc_loader::doc_item_path(crate_name, doc_path).unwrap_or_else(|| item_path.to_string());
+    format!(
+        "Item: {}\nPath: {}\nURL: {}\n\n{}",
+        item,
+        doc_path,
+        doc_loader::docs_rs_url(doc_path),
+        content
+    )
+}
+
+/// Most characters of conversation history `query_with_history` puts in the prompt
+pub const MAX_HISTORY_CHARS: usize = 4_000;
+
+/// The prompt text for a conversation's `summary` and earlier `messages`, keeping the most
+/// recent ones within [`MAX_HISTORY_CHARS`] (the summary counts as the oldest). Also returns
+/// whether anything was left out; `None` when there is no history.
+pub fn conversation_history(summary: Option<&str>, messages: &[HistoryMessage]) -> (Option<String>, bool) {
+    let entries: Vec<String> = summary
+        .map(str::trim)
+        .filter(|summary| !summary.is_empty())
+        .map(|summary| format!("Summary: {}", summary))
+        .into_iter()
+        .chain(messages.iter().filter(|message| !message.content.trim().is_empty()).map(|message| {
+            let speaker = match message.role {
+                HistoryRole::User => "User",
+                HistoryRole::Assistant => "Assistant",
+            };
+            format!("{}: {}", speaker, message.content.trim())
+        }))
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut used = 0;
+    let mut trimmed = false;
+    for entry in entries.iter().rev() {
+        let chars = entry.chars().count();
+        if used + chars > MAX_HISTORY_CHARS {
+            trimmed = true;
+            // The latest message is kept even when it alone is too long, by its end
+            if kept.is_empty() {
+                kept.push(format!("…{}", entry.chars().skip(chars - MAX_HISTORY_CHARS).collect::<String>()));
+            }
+            break;
+        }
+        used += chars + 1;
+        kept.push(entry.clone());
+    }
+    kept.reverse();
+    ((!kept.is_empty()).then(|| kept.join("\n")), trimmed)
+}
+
+/// Smallest `snippet_chars` accepted; shorter cuts leave nothing worth reading
+pub const MIN_SNIPPET_CHARS: usize = 50;
+
+/// Check an optional `snippet_chars` argument is at least [`MIN_SNIPPET_CHARS`]
+pub fn validate_snippet_chars(snippet_chars: Option<usize>) -> Result<Option<usize>, McpError> {
+    match snippet_chars {
+        Some(chars) if chars < MIN_SNIPPET_CHARS => Err(McpError::invalid_params(
+            format!("snippet_chars must be at least {}, got {}", MIN_SNIPPET_CHARS, chars),
+            Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "snippet_chars" })),
+        )),
+        _ => Ok(snippet_chars),
+    }
+}
+
+/// Cut a returned snippet to about `max_chars` characters, at the last sentence end (or else
+/// word break) in its second half, and point at `doc_path` for the full text. Shorter
+/// snippets are returned trimmed but otherwise unchanged.
+pub fn truncate_snippet(content: &str, max_chars: usize, doc_path: &str) -> String {
+    let content = content.trim();
+    let Some((limit, _)) = content.char_indices().nth(max_chars) else {
+        return content.to_string();
+    };
+    let head = &content[..limit];
+    let floor = head.len() / 2;
+    let sentence_end = head
+        .rmatch_indices(['.', '!', '?'])
+        .map(|(i, _)| i + 1)
+        .find(|end| *end >= floor && head[*end..].starts_with(char::is_whitespace));
+    let word_end = head.rfind(char::is_whitespace).filter(|end| *end >= floor);
+    let cut = sentence_end.or(word_end).unwrap_or(limit);
+    format!("{}… [truncated; full text: {}]", head[..cut].trim_end(), doc_path)
+}
+
+/// Fail with a `NotFound` error naming every crate that has no stored embeddings, and
+/// refuse crates that are still being populated (a retryable `NotReady`) or whose last
+/// population failed or was killed, rather than answering from part of their documents
+pub async fn require_crates_with_embeddings(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_names: &[String],
+) -> Result<(), McpError> {
+    let statuses = join_all(crate_names.iter().map(|name| store.crate_status(name))).await;
+    for (name, status) in crate_names.iter().zip(statuses) {
+        match status.map_err(|e| e.into_mcp_error("validation"))? {
+            Some(CrateStatus::Populating) => {
+                return Err(ServerError::NotReady(format!(
+                    "Crate '{}' is still being indexed, so its documentation is incomplete; try again in a few minutes",
+                    name
+                ))
+                .into_mcp_error("validation"));
+            }
+            Some(CrateStatus::Failed) => {
+                return Err(ServerError::NotFound(format!(
+                    "The last population of crate '{}' failed or was interrupted, so its documentation is incomplete; populate it again",
+                    name
+                ))
+                .into_mcp_error("validation"));
+            }
+            Some(CrateStatus::Ready) | None => {}
+        }
+    }
+
+    let (_, missing) = store
+        .filter_existing_crates(crate_names)
+        .await
+        .map_err(|e| e.into_mcp_error("validation"))?;
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let available: Vec<String> = store
+        .get_crate_stats()
+        .await
+        .map(|stats| stats.into_iter().map(|stat| stat.name).collect())
+        .unwrap_or_default();
+    Err(ServerError::NotFound(format!(
+        "No documentation stored for crate(s): {}. Available crates: {}",
+        missing.join(", "),
+        if available.is_empty() { "none".to_string() } else { available.join(", ") }
+    ))
+    .into_mcp_error("validation"))
+}
+
+/// Stored, ready crates among the recorded dependencies of `crate_name`, at most [`MAX_FOLLOWED_DEPS`].
+/// A dependency matches a stored crate of the same name, or else a stored version or
+/// feature variant of it (`tower@0.4.13`).
+pub async fn indexed_dependencies(
+    store: &(dyn VectorStore + Send + Sync),
+    crate_name: &str,
+) -> Result<Vec<String>, ServerError> {
+    let dependencies = store.crate_dependencies(crate_name).await?;
+    if dependencies.is_empty() {
+        return Ok(Vec::new());
+    }
+    let stored: Vec<String> = store
+        .get_crate_stats()
+        .await?
+        .into_iter()
+        .filter(|stat| stat.total_docs > 0 && stat.status == CrateStatus::Ready)
+        .map(|stat| stat.name)
+        .collect();
+    Ok(dependencies
+        .iter()
+        .filter_map(|dependency| {
+            stored
+                .iter()
+                .find(|name| *name == dependency)
+                .or_else(|| stored.iter().find(|name| doc_loader::split_crate_variant(name).0 == dependency))
+                .cloned()
+        })
+        .take(MAX_FOLLOWED_DEPS)
+        .collect())
+}
+
+/// Documents kept after merging a multi-crate search by similarity
+const MAX_MERGED_RESULTS: usize = 5;
+/// Dependencies searched at most when `follow_deps` widens a weak search
+const MAX_FOLLOWED_DEPS: usize = 3;
+/// Similarity bonus for example-bearing documents when examples are preferred
+const EXAMPLE_BOOST: f32 = 0.05;
+
+// --- Example retrieval limits ---
+
+/// Candidate documents pulled from the vector search before filtering for code
+const EXAMPLE_CANDIDATES: i32 = 20;
+/// Maximum number of examples returned by get_crate_examples
+const MAX_EXAMPLES: usize = 5;
+/// Maximum combined size (in characters) of the returned examples
+const MAX_EXAMPLES_CHARS: usize = 12_000;
+
+// --- Item lookup limits ---
+
+/// Most specific parts of an item path searched when lookup_item has no exact page
+const LOOKUP_TERMS: usize = 3;
+/// Stored paths fetched per searched part before ranking
+const LOOKUP_SCAN_LIMIT: usize = 200;
+/// Close matches returned by lookup_item
+const MAX_LOOKUP_CANDIDATES: usize = 10;
+
+/// Documents retrieved from each side of a compare_crates query
+const COMPARE_RESULTS_PER_CRATE: i32 = 4;
+/// Changed pages whose old and new text are given to the LLM for an upgrade note
+const COMPARE_VERSIONS_CHANGED_PAGES: usize = 8;
+/// Added or removed pages listed by name in the upgrade note prompt
+const COMPARE_VERSIONS_LISTED_PAGES: usize = 100;
+
+/// Whole milliseconds since `started`, for the phase timings logged with each answer
+pub fn elapsed_ms(started: Instant) -> u64 {
+    u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)
 }
 
 // --- Main Server Struct ---
@@ -69,12 +622,18 @@ struct QueryRustDocsArgs {
 #[derive(Clone)] // Add Clone for tool macro requirements
 pub struct RustDocsServer {
     crate_name: Arc<String>, // Use Arc for cheap cloning
-    documents: Arc<Vec<Document>>,
+    crate_names: Arc<Vec<String>>, // Crates whose documents are listed as resources
     embeddings: Arc<Vec<(String, Array1<f32>)>>,
-    database: Arc<Database>, // Add database connection
+    database: Arc<dyn VectorStore + Send + Sync>, // Add database connection
     peer: Arc<Mutex<Option<Peer<RoleServer>>>>, // Uses tokio::sync::Mutex
     startup_message: Arc<Mutex<Option<String>>>, // Keep the message itself
     startup_message_sent: Arc<Mutex<bool>>,     // Flag to track if sent (using tokio::sync::Mutex)
+    rate_limiter: Arc<RateLimiter>,             // Single bucket: stdio serves one client
+    allow_refresh: bool,                        // Gate for the refresh_crate tool
+    no_llm: bool,                               // Answer with the retrieved documents, never calling the chat model
+    search_cache: Arc<SearchCache>,             // Retrieval results, skips embedding + DB on a hit
+    vector_cache: VectorCache,                  // Hot crates' vectors, searched in memory (MAX_CACHED_VECTORS)
+    min_log_level: Arc<AtomicU8>,               // Severity set via logging/setLevel; lower levels aren't sent
                                                 // tool_name and info are handled by ServerHandler/macros now
 }
 
@@ -82,25 +641,63 @@ impl RustDocsServer {
     // Updated constructor
     pub fn new(
         crate_name: String,
-        documents: Vec<Document>,
         embeddings: Vec<(String, Array1<f32>)>,
-        database: Database,
+        database: Arc<dyn VectorStore + Send + Sync>,
         startup_message: String,
     ) -> Result<Self, ServerError> {
         // Keep ServerError for potential future init errors
         Ok(Self {
+            crate_names: Arc::new(vec![crate_name.clone()]),
             crate_name: Arc::new(crate_name),
-            documents: Arc::new(documents),
             embeddings: Arc::new(embeddings),
-            database: Arc::new(database),
+            database,
             peer: Arc::new(Mutex::new(None)), // Uses tokio::sync::Mutex
             startup_message: Arc::new(Mutex::new(Some(startup_message))), // Initialize message
             startup_message_sent: Arc::new(Mutex::new(false)), // Initialize flag to false
+            rate_limiter: Arc::new(RateLimiter::unlimited()),
+            allow_refresh: false,
+            no_llm: false,
+            search_cache: Arc::new(SearchCache::from_env()),
+            vector_cache: VectorCache::from_env(),
+            min_log_level: Arc::new(AtomicU8::new(log_severity(&LoggingLevel::Debug))),
         })
     }
 
+    /// Enable the `refresh_crate` tool, which re-scrapes docs.rs and writes to the database
+    pub fn with_refresh(mut self, allow_refresh: bool) -> Self {
+        self.allow_refresh = allow_refresh;
+        self
+    }
+
+    /// Answer `query_rust_docs` with the top retrieved documents verbatim instead of an LLM
+    /// summary; tools that need the chat model fail or leave out their LLM part
+    pub fn with_no_llm(mut self, no_llm: bool) -> Self {
+        self.no_llm = no_llm;
+        self
+    }
+
+    /// Crates served when there are several (`crate_name` is then only a display name)
+    pub fn with_crate_names(mut self, crate_names: Vec<String>) -> Self {
+        self.crate_names = Arc::new(crate_names);
+        self
+    }
+
+    /// Limit tool calls to `per_minute` (`None` keeps the default of unlimited)
+    pub fn with_rate_limit(mut self, per_minute: Option<u32>) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(per_minute));
+        self
+    }
+
+    /// Whether a message at `level` reaches the client, given the level it asked for
+    pub fn log_enabled(&self, level: &LoggingLevel) -> bool {
+        log_severity(level) >= self.min_log_level.load(Ordering::Relaxed)
+    }
+
     // Helper function to send log messages via MCP notification (remains mostly the same)
     pub fn send_log(&self, level: LoggingLevel, message: String) {
+        if !self.log_enabled(&level) {
+            return;
+        }
         let peer_arc = Arc::clone(&self.peer);
         tokio::spawn(async move {
             let mut peer_guard = peer_arc.lock().await;
@@ -117,76 +714,132 @@ impl RustDocsServer {
                 let server_notification =
                     ServerNotification::LoggingMessageNotification(log_notification);
                 if let Err(e) = peer.send_notification(server_notification).await {
-                    eprintln!("Failed to send MCP log notification: {}", e);
+                    tracing::warn!("Failed to send MCP log notification: {}", e);
                 }
             } else {
-                eprintln!("Log task ran but MCP peer was not connected.");
+                tracing::debug!("Log task ran but MCP peer was not connected.");
             }
         });
     }
 
-    // Helper for creating simple text resources (like in counter example)
-    fn _create_resource_text(&self, uri: &str, name: &str) -> Resource {
-        RawResource::new(uri, name.to_string()).no_annotation()
-    }
-    
-    // Parse crate name from question
-    fn parse_crate_name_from_question(&self, question: &str) -> Option<String> {
-        // Common patterns for crate names in questions
-        let patterns = [
-            // "How do I use axum?" -> "axum"
-            r"(?i)\buse\s+(\w+)\b",
-            // "What is tokio?" -> "tokio"
-            r"(?i)\bwhat\s+is\s+(\w+)\b",
-            // "How does serde work?" -> "serde"
-            r"(?i)\bhow\s+does\s+(\w+)\s+work\b",
-            // "axum router" -> "axum"
-            r"^(\w+)\s+",
-            // "in axum" -> "axum"
-            r"(?i)\bin\s+(\w+)\b",
-            // "with tokio" -> "tokio"
-            r"(?i)\bwith\s+(\w+)\b",
-            // "using serde" -> "serde"
-            r"(?i)\busing\s+(\w+)\b",
-            // Direct crate name at beginning
-            r"^(\w+)(?:\s|$)",
-        ];
-        
-        // Common Rust crate names to look for
-        let known_crates = [
-            "axum", "tokio", "serde", "reqwest", "clap", "anyhow", "thiserror",
-            "tracing", "futures", "async-trait", "sqlx", "diesel", "rocket",
-            "actix", "actix-web", "warp", "hyper", "tonic", "prost", "bytes",
-            "rand", "chrono", "regex", "uuid", "base64", "hex", "sha2", "aes",
-            "rsa", "ed25519", "x25519", "chacha20", "poly1305", "argon2",
-        ];
-        
-        let question_lower = question.to_lowercase();
-        
-        // First check for exact known crate names
-        for crate_name in &known_crates {
-            if question_lower.contains(crate_name) {
-                return Some(crate_name.to_string());
+    /// The page stored for an item path (any item kind, or a module) or a rustdoc page path,
+    /// as its stored path and content
+    async fn find_item_page(&self, crate_name: &str, item_path: &str) -> Result<Option<(String, String)>, McpError> {
+        let candidates = if item_path.ends_with(".html") {
+            vec![doc_loader::relative_doc_path(crate_name, item_path)]
+        } else {
+            doc_loader::item_doc_paths(item_path)
+        };
+        for candidate in &candidates {
+            let found = self.database
+                .get_document_by_path(crate_name, candidate)
+                .await
+                .map_err(|e| e.into_mcp_error("search"))?;
+            if found.is_some() {
+                return Ok(found);
             }
         }
-        
-        // Then try patterns
-        for pattern in &patterns {
-            if let Ok(re) = regex::Regex::new(pattern) {
-                if let Some(captures) = re.captures(question) {
-                    if let Some(matched) = captures.get(1) {
-                        let potential_crate = matched.as_str().to_lowercase();
-                        // Validate that it looks like a crate name
-                        if potential_crate.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') 
-                            && potential_crate.len() > 1 {
-                            return Some(potential_crate);
-                        }
-                    }
-                }
+        Ok(None)
+    }
+
+    /// Warn the client about retrieved documents that read like prompt injection.
+    /// They are still used, fenced and flagged, since the phrases can be legitimate.
+    fn warn_on_injections<'a>(&self, documents: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        for (path, content) in documents {
+            let phrases = prompt_guard::injection_phrases(content);
+            if !phrases.is_empty() {
+                self.send_log(
+                    LoggingLevel::Warning,
+                    format!("Document {} contains instruction-like text ({}); flagged for the LLM", path, phrases.join(", ")),
+                );
             }
         }
-        
-        None
+    }
+
+    /// Embed a query with the global provider, bounded by `LLM_TIMEOUT_SECS`
+    async fn embed_query(&self, text: &str) -> Result<Array1<f32>, McpError> {
+        self.embed_query_counted(text).await.map(|(embedding, _)| embedding)
+    }
+
+    /// [`Self::embed_query`], also returning the tokens the provider counted
+    async fn embed_query_counted(&self, text: &str) -> Result<(Array1<f32>, usize), McpError> {
+        let embedding_provider = EMBEDDING_CLIENT.get().ok_or_else(|| {
+            ServerError::Internal("Embedding provider not initialized".to_string())
+                .into_mcp_error("embedding")
+        })?;
+        client::embed_text(embedding_provider.as_ref(), text)
+            .await
+            .map_err(|e| e.into_mcp_error("embedding"))
+    }
+
+    /// Search `name` for `vector`: filtered, boosting code examples, or through the vector
+    /// cache, then diversified with MMR when `mmr_lambda` is set. Chunks of one page are
+    /// collapsed, leaving at most [`RESULTS_PER_CRATE`] documents.
+    async fn search_crate(
+        &self,
+        name: &str,
+        vector: &Array1<f32>,
+        filter: DocFilter<'_>,
+        prefer_examples: bool,
+        mmr_lambda: Option<f32>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        let limit = RESULTS_PER_CRATE * CHUNK_OVERFETCH;
+        // MMR picks from a wider candidate set
+        let candidates = if mmr_lambda.is_some() { limit * MMR_CANDIDATE_FACTOR } else { limit };
+        let results = if !filter.is_empty() {
+            self.database
+                .search_similar_docs_filtered(name, vector, candidates, filter, prefer_examples.then_some(EXAMPLE_BOOST))
+                .await?
+        } else if prefer_examples {
+            self.database
+                .search_similar_docs_preferring_examples(name, vector, candidates, EXAMPLE_BOOST)
+                .await?
+        } else {
+            self.vector_cache.search_similar_docs(&self.database, name, vector, candidates).await?
+        };
+        let results = match mmr_lambda {
+            Some(lambda) => diversify_results(self.database.as_ref(), name, results, lambda, limit as usize).await?,
+            None => results,
+        };
+        Ok(collapse_chunks(results, RESULTS_PER_CRATE as usize))
+    }
+
+    /// Ask the LLM to answer from retrieved context, bounded by `LLM_TIMEOUT_SECS`
+    async fn complete_chat(
+        &self,
+        llm_model: &str,
+        system_prompt: String,
+        user_prompt: String,
+    ) -> Result<String, McpError> {
+        self.complete_chat_counted(llm_model, system_prompt, user_prompt)
+            .await
+            .map(|(answer, _)| answer)
+    }
+
+    /// [`Self::complete_chat`], also returning the prompt and completion tokens used
+    async fn complete_chat_counted(
+        &self,
+        llm_model: &str,
+        system_prompt: String,
+        user_prompt: String,
+    ) -> Result<(String, AnswerUsage), McpError> {
+        if self.no_llm {
+            return Err(ServerError::Config("The chat model is disabled on this server (--no-llm)".to_string())
+                .into_mcp_error("generation"));
+        }
+        client::complete_chat(llm_model, system_prompt, user_prompt)
+            .await
+            .map_err(|e| self.log_generation_error(e))
+    }
+
+    fn log_generation_error(&self, error: ServerError) -> McpError {
+        self.send_log(LoggingLevel::Error, format!("LLM chat request failed: {}", error));
+        error.into_mcp_error("generation")
+    }
+
+    // Helper for creating simple text resources (like in counter example)
+    fn _create_resource_text(&self, uri: &str, name: &str) -> Resource {
+        RawResource::new(uri, name.to_string()).no_annotation()
     }
 }
 
@@ -205,6 +858,43 @@ impl RustDocsServer {
         #[tool(aggr)] // Aggregate arguments into the struct
         args: QueryRustDocsArgs,
     ) -> Result<CallToolResult, McpError> {
+        self.answer_query(args, None).await
+    }
+
+    #[tool(
+        description = "Ask a follow-up question about a Rust crate in a conversation. Takes the query_rust_docs arguments plus the earlier messages (or a summary of them), which the LLM uses to resolve references like \"it\"; only the new question is searched."
+    )]
+    async fn query_with_history(
+        &self,
+        #[tool(aggr)]
+        args: QueryWithHistoryArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let (history, trimmed) = conversation_history(args.summary.as_deref(), &args.history);
+        if trimmed {
+            self.send_log(
+                LoggingLevel::Info,
+                format!("Conversation history exceeds {} characters; using only the latest turns", MAX_HISTORY_CHARS),
+            );
+        }
+        self.answer_query(args.query, history).await
+    }
+
+    /// `query_rust_docs`, with earlier turns of a conversation added to the LLM prompt.
+    /// Logs one event with the time spent embedding, searching and generating.
+    #[tracing::instrument(name = "query_rust_docs", skip_all, fields(crate_name = tracing::field::Empty))]
+    async fn answer_query(&self, args: QueryRustDocsArgs, history: Option<String>) -> Result<CallToolResult, McpError> {
+        let target_crates = resolve_target_crates(args.crate_name.as_deref(), args.crate_names.as_deref())?;
+        tracing::Span::current().record("crate_name", target_crates.join(", "));
+        let question = require_non_empty("question", &args.question)?;
+        let module_prefix = normalize_module_prefix(args.module_prefix.as_deref())?;
+        let kind = normalize_doc_kind(args.kind.as_deref())?;
+        let filter = DocFilter { module_prefix, kind };
+        let language = normalize_language(args.language.as_deref())?;
+        let mmr_lambda = resolve_mmr_lambda(args.mmr_lambda)?;
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+        require_crates_with_embeddings(self.database.as_ref(), &target_crates).await?;
+        let multi_crate = target_crates.len() > 1;
+
         // --- Send Startup Message (if not already sent) ---
         let mut sent_guard = self.startup_message_sent.lock().await;
         if !*sent_guard {
@@ -222,11 +912,9 @@ impl RustDocsServer {
             drop(sent_guard);
         }
 
-        let crate_name = &args.crate_name;
-        let question = &args.question;
-        
-        // Use the explicitly provided crate name
-        let target_crate = crate_name;
+
+        // Label used in logs and prompts ("axum" or "axum, tower, hyper")
+        let target_crate = target_crates.join(", ");
 
         // Log received query via MCP
         self.send_log(
@@ -237,168 +925,267 @@ impl RustDocsServer {
             ),
         );
 
-        // --- Embedding Generation for Question ---
-        let embedding_provider = EMBEDDING_CLIENT
-            .get()
-            .ok_or_else(|| McpError::internal_error("Embedding provider not initialized", None))?;
-
-        // Generate embedding for the question using the configured provider
-        let (embeddings, _tokens) = embedding_provider
-            .generate_embeddings(&[question.to_string()])
-            .await
-            .map_err(|e| McpError::internal_error(format!("Embedding API error: {}", e), None))?;
+        // Usage questions are best answered by documents that contain code
+        let prefer_examples = args
+            .prefer_examples
+            .unwrap_or_else(|| examples::is_usage_question(question));
 
-        let question_embedding = embeddings.into_iter().next().ok_or_else(|| {
-            McpError::internal_error("Failed to get embedding for question", None)
-        })?;
+        // Recently searched crates are served from the cache, without embedding the question
+        let cache_keys: Vec<SearchKey> = target_crates
+            .iter()
+            .map(|name| {
+                SearchKey::new(name, question, RESULTS_PER_CRATE, prefer_examples)
+                    .in_module(module_prefix)
+                    .of_kind(kind)
+                    .with_mmr(mmr_lambda)
+            })
+            .collect();
+        let cached = join_all(cache_keys.iter().map(|key| self.search_cache.get(key))).await;
 
-        let question_vector = Array1::from(question_embedding);
+        // --- Embedding Generation for Question ---
+        let embedding_started = Instant::now();
+        let mut usage = AnswerUsage::default();
+        let question_vector = if cached.iter().all(Option::is_some) {
+            None
+        } else {
+            let (vector, tokens) = self.embed_query_counted(question).await?;
+            usage.embedding_tokens = tokens;
+            Some(vector)
+        };
+        let embedding_ms = elapsed_ms(embedding_started);
 
         // --- Search for similar documents using database ---
         self.send_log(
             LoggingLevel::Info,
             format!("Performing vector search in database for crate '{}'", target_crate),
         );
-        
-        let search_results = self.database
-            .search_similar_docs(target_crate, &question_vector, 3)
-            .await
-            .map_err(|e| {
+
+        // The question is embedded once; every crate is searched concurrently
+        let search_started = Instant::now();
+        // Embedding done late, for dependency searches when every crate was cached
+        let mut late_embedding_ms = 0;
+        let searches = target_crates.iter().zip(cache_keys).zip(cached).map(|((name, key), cached)| {
+            let question_vector = question_vector.as_ref();
+            async move {
+                if let Some(results) = cached {
+                    return Ok(results);
+                }
+                // Only `None` when every crate was cached
+                let Some(question_vector) = question_vector else {
+                    return Ok(Vec::new());
+                };
+                let results = self.search_crate(name, question_vector, filter, prefer_examples, mmr_lambda).await?;
+                self.search_cache.insert(key, results.clone()).await;
+                Ok::<_, ServerError>(results)
+            }
+        });
+        let mut tagged_results = Vec::new();
+        for (name, result) in target_crates.iter().zip(join_all(searches).await) {
+            let results = result.map_err(|e| {
                 self.send_log(
                     LoggingLevel::Error,
-                    format!("Database search failed: {}", e),
+                    format!("Database search failed for crate '{}': {}", name, e),
                 );
-                McpError::internal_error(format!("Database search error: {}", e), None)
+                e.into_mcp_error("search")
             })?;
-        
-        // --- Generate Response using LLM ---
-        let response_text = if !search_results.is_empty() {
-            let (best_path, best_content, best_score) = &search_results[0];
-            
+            tagged_results.extend(results.into_iter().map(|result| (name.as_str(), result)));
+        }
+
+        // A crate whose own docs match poorly may be answered by its dependencies (axum -> tower, hyper)
+        let best_similarity = tagged_results.iter().map(|(_, result)| result.similarity).reduce(f32::max);
+        let dependency_crates = if args.follow_deps
+            && !multi_crate
+            && best_similarity.is_none_or(|best| best < client::min_confidence())
+        {
+            indexed_dependencies(self.database.as_ref(), &target_crates[0])
+                .await
+                .map_err(|e| e.into_mcp_error("search"))?
+        } else {
+            Vec::new()
+        };
+        if !dependency_crates.is_empty() {
             self.send_log(
                 LoggingLevel::Info,
                 format!(
-                    "Found {} relevant documents via vector DB. Best match: {} (similarity: {:.3})",
-                    search_results.len(), best_path, best_score
+                    "Weak matches in '{}'; also searching its dependencies: {}",
+                    target_crate,
+                    dependency_crates.join(", ")
                 ),
             );
-            
-            // Combine top results for better context
-            let combined_context = if search_results.len() > 1 {
-                search_results
-                    .iter()
-                    .enumerate()
-                    .map(|(i, (path, content, score))| {
-                        format!(
-                            "--- Document {} (similarity: {:.3}) ---\nPath: {}\n\n{}",
-                            i + 1, score, path, content
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n\n")
-            } else {
-                best_content.clone()
+            let question_vector = match question_vector {
+                Some(vector) => vector,
+                None => {
+                    let started = Instant::now();
+                    let (vector, tokens) = self.embed_query_counted(question).await?;
+                    usage.embedding_tokens += tokens;
+                    late_embedding_ms = elapsed_ms(started);
+                    vector
+                }
             };
-            
-            // Check if this is an in-memory fallback or actual DB result
-            let source = if self.embeddings.is_empty() {
-                "vector database"
-            } else {
-                "vector database (with in-memory cache)"
+            // Module paths are per crate, but a kind applies to dependencies too
+            let filter = DocFilter { module_prefix: None, kind };
+            let searches = dependency_crates
+                .iter()
+                .map(|name| self.search_crate(name, &question_vector, filter, prefer_examples, mmr_lambda));
+            for (name, result) in dependency_crates.iter().zip(join_all(searches).await) {
+                let results = result.map_err(|e| e.into_mcp_error("search"))?;
+                tagged_results.extend(results.into_iter().map(|result| (name.as_str(), result)));
+            }
+        }
+
+        // Merge by similarity across crates, applying the same example boost as the store.
+        // A single crate's results already come back ranked.
+        if multi_crate || !dependency_crates.is_empty() {
+            let rank = |(_, result): &(&str, DocMatch)| {
+                if prefer_examples && examples::is_code_example(&result.content) {
+                    result.similarity + EXAMPLE_BOOST
+                } else {
+                    result.similarity
+                }
             };
-            
+            tagged_results.sort_by(|a, b| rank(b).partial_cmp(&rank(a)).unwrap_or(std::cmp::Ordering::Equal));
+            tagged_results.truncate(MAX_MERGED_RESULTS);
+        }
+        // Dependencies that made it into the context, suggested alongside the answer
+        let related_crates: Vec<String> = dependency_crates
+            .iter()
+            .filter(|name| tagged_results.iter().any(|(crate_name, _)| crate_name == name))
+            .cloned()
+            .collect();
+        let crate_label = if related_crates.is_empty() {
+            target_crate.clone()
+        } else {
+            format!("{}, {}", target_crate, related_crates.join(", "))
+        };
+        let search_ms = elapsed_ms(search_started).saturating_sub(late_embedding_ms);
+        let embedding_ms = embedding_ms + late_embedding_ms;
+        let hits: Vec<SearchHit> = tagged_results
+            .iter()
+            .map(|(name, result)| SearchHit::new(name, result.clone()))
+            .collect();
+
+        if let Some(best) = hits.first() {
             self.send_log(
                 LoggingLevel::Info,
-                format!("Using {} results from {} for LLM context", search_results.len(), source),
+                format!(
+                    "Found {} relevant documents via vector DB. Best match: {} (similarity: {:.3})",
+                    hits.len(), best.doc_path, best.similarity
+                ),
             );
+            self.warn_on_injections(hits.iter().map(|hit| (hit.doc_path.as_str(), hit.content.as_str())));
 
-            {
-                    // Get OpenAI client for LLM chat completion (separate from embedding provider)
-                    let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-                        let config = OpenAIConfig::new().with_api_base(api_base);
-                        OpenAIClient::with_config(config)
-                    } else {
-                        OpenAIClient::new()
-                    };
-
-                    let system_prompt = format!(
-                        "You are an expert technical assistant for the Rust crate '{}'. \
-                         Answer the user's question based *only* on the provided context. \
-                         If the context does not contain the answer, say so. \
-                         Do not make up information. Be clear, concise, and comprehensive providing example usage code when possible.",
-                        target_crate
-                    );
-                    let user_prompt = format!(
-                        "Context:\n---\n{}\n---\n\nQuestion: {}",
-                        combined_context, question
-                    );
-
-                    let llm_model: String = env::var("LLM_MODEL")
-                        .unwrap_or_else(|_| "gpt-4o-mini-2024-07-18".to_string());
-                    let chat_request = CreateChatCompletionRequestArgs::default()
-                        .model(llm_model)
-                        .messages(vec![
-                            ChatCompletionRequestSystemMessageArgs::default()
-                                .content(system_prompt)
-                                .build()
-                                .map_err(|e| {
-                                    McpError::internal_error(
-                                        format!("Failed to build system message: {}", e),
-                                        None,
-                                    )
-                                })?
-                                .into(),
-                            ChatCompletionRequestUserMessageArgs::default()
-                                .content(user_prompt)
-                                .build()
-                                .map_err(|e| {
-                                    McpError::internal_error(
-                                        format!("Failed to build user message: {}", e),
-                                        None,
-                                    )
-                                })?
-                                .into(),
-                        ])
-                        .build()
-                        .map_err(|e| {
-                            McpError::internal_error(
-                                format!("Failed to build chat request: {}", e),
-                                None,
-                            )
-                        })?;
-
-                    let chat_response = openai_client.chat().create(chat_request).await.map_err(|e| {
-                        McpError::internal_error(format!("OpenAI chat API error: {}", e), None)
-                    })?;
-
-                    self.send_log(
-                        LoggingLevel::Info,
-                        "Generating response using LLM based on vector DB results".to_string(),
-                    );
-                    
-                    chat_response
-                        .choices
-                        .first()
-                        .and_then(|choice| choice.message.content.clone())
-                        .unwrap_or_else(|| "Error: No response from LLM.".to_string())
+            // Check if this is an in-memory fallback or actual DB result
+            let source = if self.embeddings.is_empty() && !hits.iter().any(|hit| self.vector_cache.contains(&hit.crate_name)) {
+                "vector database"
+            } else {
+                "vector database (with in-memory cache)"
+            };
+            if self.no_llm {
+                self.send_log(
+                    LoggingLevel::Info,
+                    format!("Returning {} results from {} verbatim (--no-llm)", hits.len(), source),
+                );
+            } else {
+                self.send_log(
+                    LoggingLevel::Info,
+                    format!("Using {} results from {} for LLM context", hits.len(), source),
+                );
+                self.send_log(
+                    LoggingLevel::Info,
+                    "Generating response using LLM based on vector DB results".to_string(),
+                );
             }
         } else {
             self.send_log(
                 LoggingLevel::Warning,
                 format!("No relevant documents found in vector DB for crate '{}'", target_crate),
             );
-            "No relevant documentation found in the vector database for this query.".to_string()
-        };
+        }
 
-        // --- Format and Return Result ---
-        let final_response = if !search_results.is_empty() {
-            format!(
-                "From {} docs (via vector database search): {}",
-                target_crate, response_text
-            )
+        // --- Generate Response using LLM ---
+        let generation_started = Instant::now();
+        let llm_model = client::llm_model();
+        let answer = if self.no_llm {
+            if language.is_some() || history.is_some() {
+                self.send_log(
+                    LoggingLevel::Warning,
+                    "language and conversation history are ignored: documents are returned verbatim (--no-llm)".to_string(),
+                );
+            }
+            client::documents_answer(hits)
         } else {
-            format!(
-                "From {} docs: {}",
+            client::answer_from_hits(&llm_model, &crate_label, question, hits, language, history.as_deref())
+                .await
+                .map_err(|e| self.log_generation_error(e))?
+        };
+        if answer.context_truncated {
+            self.send_log(
+                LoggingLevel::Warning,
+                format!(
+                    "Retrieved context exceeded {} tokens for model '{}'; truncated lower-ranked documents",
+                    max_context_tokens(&llm_model), llm_model
+                ),
+            );
+        }
+        if let Some(grounding) = answer.grounding.as_ref().filter(|grounding| grounding.low_confidence) {
+            self.send_log(
+                LoggingLevel::Warning,
+                format!(
+                    "Best match similarity {:.3} is below {:.2}; answer flagged as low-confidence",
+                    grounding.best_similarity, grounding.floor
+                ),
+            );
+        }
+        let generation_ms = elapsed_ms(generation_started);
+        tracing::info!(
+            embedding_ms,
+            search_ms,
+            generation_ms,
+            documents = tagged_results.len(),
+            "✅ Answered query in {} ms",
+            embedding_ms + search_ms + generation_ms
+        );
+        usage.prompt_tokens = answer.usage.prompt_tokens;
+        usage.completion_tokens = answer.usage.completion_tokens;
+        let low_confidence = answer.low_confidence;
+        // JSON answers carry the flag instead of the caveat
+        let response_text = if args.output_format == OutputFormat::Json { answer.text.clone() } else { answer.text_with_caveat() };
+        let grounding = answer.grounding;
+
+        // --- Format and Return Result ---
+        let final_response = if args.output_format == OutputFormat::Json {
+            let answer = QueryAnswer {
+                answer: response_text,
+                sources: answer_sources(&tagged_results),
+                grounding,
+                low_confidence,
+                usage,
+                related_crates,
+            };
+            serde_json::to_string_pretty(&answer).map_err(|e| ServerError::Json(e).into_mcp_error("generation"))?
+        } else if !tagged_results.is_empty() {
+            let sources = doc_loader::source_urls(tagged_results.iter().map(|(_, result)| result.doc_path.as_str()));
+            let related = if related_crates.is_empty() {
+                String::new()
+            } else {
+                let names: Vec<String> = related_crates.iter().map(|name| format!("`{}`", name)).collect();
+                format!("\n\nYou may also want to check {}.", names.join(", "))
+            };
+            format!(
+                "From {} docs (via vector database search): {}{}\n\n{}\n\nSources:\n{}",
+                crate_label,
+                response_text,
+                related,
+                grounding.as_ref().map(Grounding::summary).unwrap_or_default(),
+                sources
+                    .iter()
+                    .map(|url| format!("- {}", url))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        } else {
+            format!(
+                "From {} docs: {}",
                 target_crate, response_text
             )
         };
@@ -410,6 +1197,474 @@ impl RustDocsServer {
         
         Ok(CallToolResult::success(vec![Content::text(final_response)]))
     }
+
+    #[tool(
+        description = "Return code examples from a Rust crate's documentation relevant to a topic, verbatim and without LLM rewriting."
+    )]
+    async fn get_crate_examples(
+        &self,
+        #[tool(aggr)]
+        args: GetCrateExamplesArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        let topic = require_non_empty("topic", &args.topic)?;
+        let snippet_chars = validate_snippet_chars(args.snippet_chars)?;
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+
+        self.send_log(
+            LoggingLevel::Info,
+            format!("Searching examples in crate '{}' for: {}", crate_name, topic),
+        );
+
+        let topic_vector = self.embed_query(topic).await?;
+
+        // Results come back ordered by similarity, so filtering keeps the ranking
+        let candidates = self.database
+            .search_similar_docs(crate_name, &topic_vector, EXAMPLE_CANDIDATES)
+            .await
+            .map_err(|e| e.into_mcp_error("search"))?;
+
+        let mut sections = Vec::new();
+        let mut remaining_chars = MAX_EXAMPLES_CHARS;
+        for DocMatch { doc_path: path, content, similarity: score, .. } in candidates
+            .iter()
+            .filter(|candidate| examples::is_code_example(&candidate.content))
+            .take(MAX_EXAMPLES)
+        {
+            if remaining_chars == 0 {
+                break;
+            }
+
+            let content = match snippet_chars {
+                Some(max_chars) => truncate_snippet(content, max_chars, path),
+                None => content.clone(),
+            };
+            let example = if content.chars().count() > remaining_chars {
+                let truncated: String = content.chars().take(remaining_chars).collect();
+                format!("{}\n... (truncated)", truncated)
+            } else {
+                content.clone()
+            };
+            remaining_chars = remaining_chars.saturating_sub(content.chars().count());
+
+            sections.push(format!(
+                "--- Example {} (similarity: {:.3}) ---\nPath: {}\n\n{}",
+                sections.len() + 1, score, path, example
+            ));
+        }
+
+        let response = if sections.is_empty() {
+            self.send_log(
+                LoggingLevel::Warning,
+                format!("No code examples found in crate '{}' for: {}", crate_name, topic),
+            );
+            format!("No code examples found in {} docs for '{}'.", crate_name, topic)
+        } else {
+            self.send_log(
+                LoggingLevel::Info,
+                format!("Returning {} examples from {} candidates", sections.len(), candidates.len()),
+            );
+            format!(
+                "Examples from {} docs for '{}':\n\n{}",
+                crate_name, topic, sections.join("\n\n")
+            )
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Compare two Rust crates for a question (e.g. \"what is the reqwest equivalent of hyper's X?\") using side-by-side documentation from both."
+    )]
+    async fn compare_crates(
+        &self,
+        #[tool(aggr)]
+        args: CompareCratesArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_a = require_non_empty("crate_a", &args.crate_a)?;
+        let crate_b = require_non_empty("crate_b", &args.crate_b)?;
+        let question = require_non_empty("question", &args.question)?;
+        if crate_a == crate_b {
+            return Err(McpError::invalid_params(
+                "crate_a and crate_b must be different crates",
+                Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "crate_b" })),
+            ));
+        }
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+
+        self.send_log(
+            LoggingLevel::Info,
+            format!("Comparing crates '{}' and '{}' for: {}", crate_a, crate_b, question),
+        );
+
+        // Embed once and search each crate independently, so neither side crowds out the other
+        let question_vector = self.embed_query(question).await?;
+        let limit = COMPARE_RESULTS_PER_CRATE * CHUNK_OVERFETCH;
+        let (results_a, results_b) = futures::join!(
+            self.database.search_similar_docs(crate_a, &question_vector, limit),
+            self.database.search_similar_docs(crate_b, &question_vector, limit),
+        );
+        let results_a = collapse_chunks(results_a.map_err(|e| e.into_mcp_error("search"))?, COMPARE_RESULTS_PER_CRATE as usize);
+        let results_b = collapse_chunks(results_b.map_err(|e| e.into_mcp_error("search"))?, COMPARE_RESULTS_PER_CRATE as usize);
+
+        if results_a.is_empty() && results_b.is_empty() {
+            self.send_log(
+                LoggingLevel::Warning,
+                format!("No relevant documents found for '{}' or '{}'", crate_a, crate_b),
+            );
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "No relevant documentation found in the vector database for {} or {}.",
+                crate_a, crate_b
+            ))]));
+        }
+
+        // Each crate gets half of the context budget
+        let llm_model = client::llm_model();
+        let crate_budget = max_context_tokens(&llm_model) / 2;
+        let crate_context = |crate_name: &str, results: &[DocMatch]| {
+            if results.is_empty() {
+                return "(no relevant documentation found)".to_string();
+            }
+            let sections: Vec<(String, Option<usize>)> = results
+                .iter()
+                .enumerate()
+                .map(|(i, result)| {
+                    let attributes = [
+                        ("crate", crate_name.to_string()),
+                        ("index", (i + 1).to_string()),
+                        ("path", result.doc_path.clone()),
+                        ("similarity", format!("{:.3}", result.similarity)),
+                    ];
+                    (prompt_guard::fence_document(&attributes, &result.content), result.token_count)
+                })
+                .collect();
+            self.warn_on_injections(results.iter().map(|result| (result.doc_path.as_str(), result.content.as_str())));
+            let (context, truncated) = truncate_context(&sections, crate_budget);
+            let context = prompt_guard::close_open_blocks(&context);
+            if truncated {
+                self.send_log(
+                    LoggingLevel::Warning,
+                    format!("Context for crate '{}' exceeded {} tokens; truncated", crate_name, crate_budget),
+                );
+            }
+            context
+        };
+
+        let system_prompt = format!(
+            "You are an expert technical assistant for the Rust crates '{a}' and '{b}'. \
+             Answer the user's question by comparing the two crates based *only* on the provided context, \
+             which is labelled by crate. Point out the equivalent types, functions or patterns in each crate \
+             and where they differ. If the context for one crate does not cover the question, say so. \
+             Do not make up information. Show example code for both crates when possible. {guard}",
+            a = crate_a,
+            b = crate_b,
+            guard = REFERENCE_DATA_INSTRUCTION
+        );
+        let user_prompt = format!(
+            "=== Context from crate '{}' ===\n{}\n\n=== Context from crate '{}' ===\n{}\n\nQuestion: {}",
+            crate_a,
+            crate_context(crate_a, &results_a),
+            crate_b,
+            crate_context(crate_b, &results_b),
+            question
+        );
+
+        self.send_log(
+            LoggingLevel::Info,
+            format!(
+                "Generating comparison from {} '{}' and {} '{}' documents",
+                results_a.len(), crate_a, results_b.len(), crate_b
+            ),
+        );
+        let answer = self.complete_chat(&llm_model, system_prompt, user_prompt).await?;
+
+        let sources = doc_loader::source_urls(
+            results_a.iter().chain(results_b.iter()).map(|result| result.doc_path.as_str()),
+        );
+        let response = format!(
+            "Comparing {} and {} docs (via vector database search): {}\n\nSources:\n{}",
+            crate_a,
+            crate_b,
+            answer,
+            sources
+                .iter()
+                .map(|url| format!("- {}", url))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Compare the stored documentation of two versions of a crate. Returns the added, removed and changed pages (by content hash) and an LLM-written upgrade note."
+    )]
+    async fn compare_versions(
+        &self,
+        #[tool(aggr)]
+        args: CompareVersionsArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        let from = require_non_empty("from", &args.from)?;
+        let to = require_non_empty("to", &args.to)?;
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+
+        let stats = self.database.get_crate_stats().await.map_err(|e| e.into_mcp_error("validation"))?;
+        let resolve = |version: &str| {
+            version_diff::resolve_version(&stats, crate_name, version).ok_or_else(|| {
+                let stored = version_diff::stored_versions(&stats, crate_name);
+                ServerError::NotFound(format!(
+                    "No documentation stored for {} {}. Stored versions: {}",
+                    crate_name,
+                    version,
+                    if stored.is_empty() { "none".to_string() } else { stored.join(", ") }
+                ))
+                .into_mcp_error("validation")
+            })
+        };
+        let from_name = resolve(from)?;
+        let to_name = resolve(to)?;
+        if from_name == to_name {
+            return Err(McpError::invalid_params(
+                format!("from and to both refer to the stored docs of {}", from_name),
+                Some(json!({ "kind": "invalid_argument", "phase": "validation", "field": "to" })),
+            ));
+        }
+
+        self.send_log(
+            LoggingLevel::Info,
+            format!("Comparing {} {} with {}", crate_name, from, to),
+        );
+        let (from_rows, to_rows) = futures::try_join!(
+            self.database.get_content_hashes(&from_name),
+            self.database.get_content_hashes(&to_name),
+        )
+        .map_err(|e| e.into_mcp_error("search"))?;
+        let diff = version_diff::diff_versions(crate_name, &from_rows, &to_rows);
+
+        // Hashes already found the changes; only changed pages are loaded, for the LLM
+        // Without the chat model the diff itself is the answer
+        let upgrade_note = if diff.is_empty() || self.no_llm {
+            None
+        } else {
+            let mut sections = Vec::new();
+            for key in diff.changed.iter().take(COMPARE_VERSIONS_CHANGED_PAGES) {
+                let old_paths = version_diff::chunk_paths(crate_name, &from_rows, key);
+                let new_paths = version_diff::chunk_paths(crate_name, &to_rows, key);
+                let (old, new) = futures::try_join!(
+                    version_diff::page_content(self.database.as_ref(), &from_name, &old_paths),
+                    version_diff::page_content(self.database.as_ref(), &to_name, &new_paths),
+                )
+                .map_err(|e| e.into_mcp_error("search"))?;
+                self.warn_on_injections([(key.as_str(), old.as_str()), (key.as_str(), new.as_str())]);
+                // Pages are stitched from their chunks, so there is no stored count to reuse
+                let section = format!(
+                    "{}\n{}",
+                    prompt_guard::fence_document(&[("page", key.clone()), ("version", from.to_string())], &old),
+                    prompt_guard::fence_document(&[("page", key.clone()), ("version", to.to_string())], &new)
+                );
+                sections.push((section, None));
+            }
+
+            let llm_model = client::llm_model();
+            let (context, truncated) = truncate_context(&sections, max_context_tokens(&llm_model));
+            let context = prompt_guard::close_open_blocks(&context);
+            if truncated {
+                self.send_log(
+                    LoggingLevel::Warning,
+                    format!("Changed pages of '{}' exceeded the context budget; truncated", crate_name),
+                );
+            }
+            let listed = |pages: &[String]| {
+                if pages.is_empty() {
+                    return "(none)".to_string();
+                }
+                let mut list: Vec<String> = pages
+                    .iter()
+                    .take(COMPARE_VERSIONS_LISTED_PAGES)
+                    .map(|page| format!("- {}", page))
+                    .collect();
+                if pages.len() > COMPARE_VERSIONS_LISTED_PAGES {
+                    list.push(format!("- ... and {} more", pages.len() - COMPARE_VERSIONS_LISTED_PAGES));
+                }
+                list.join("\n")
+            };
+
+            let system_prompt = format!(
+                "You are an expert technical assistant for the Rust crate '{}'. \
+                 Write a concise upgrade note for moving from version {} to {} based *only* on the \
+                 provided lists of added and removed documentation pages and the old and new text of \
+                 changed pages. Call out breaking changes first, then new APIs, then other changes. \
+                 Do not make up information. {}",
+                crate_name, from, to, REFERENCE_DATA_INSTRUCTION
+            );
+            let user_prompt = format!(
+                "Pages added in {}:\n{}\n\nPages removed since {}:\n{}\n\n{} pages changed; the first {} are shown:\n\n{}",
+                to,
+                listed(&diff.added),
+                from,
+                listed(&diff.removed),
+                diff.changed.len(),
+                sections.len(),
+                context
+            );
+            Some(self.complete_chat(&llm_model, system_prompt, user_prompt).await?)
+        };
+
+        let comparison = VersionComparison {
+            crate_name: crate_name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            diff,
+            upgrade_note,
+        };
+        let response = serde_json::to_string_pretty(&comparison)
+            .map_err(|e| ServerError::Json(e).into_mcp_error("generation"))?;
+
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Return the stored documentation page of one item verbatim, by Rust path (e.g. \"tokio::sync::Mutex\") or rustdoc page (e.g. \"sync/struct.Mutex.html\"). No embedding or LLM call."
+    )]
+    async fn search_by_item_path(
+        &self,
+        #[tool(aggr)]
+        args: SearchByItemPathArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        let item_path = require_non_empty("item_path", &args.item_path)?;
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+        self.send_log(
+            LoggingLevel::Info,
+            format!("Looking up '{}' in crate '{}'", item_path, crate_name),
+        );
+
+        let Some((doc_path, content)) = self.find_item_page(crate_name, item_path).await? else {
+            return Err(ServerError::NotFound(format!(
+                "No documentation page stored for {} in crate {}; lookup_item suggests close matches",
+                item_path, crate_name
+            ))
+            .into_mcp_error("search"));
+        };
+        Ok(CallToolResult::success(vec![Content::text(item_page_text(crate_name, item_path, &doc_path, &content))]))
+    }
+
+    #[tool(
+        description = "Find an item's documentation by Rust path (e.g. \"axum::Router::route\"). Returns the page verbatim on an exact match, otherwise the closest stored items ranked by path similarity. No embedding or LLM call."
+    )]
+    async fn lookup_item(
+        &self,
+        #[tool(aggr)]
+        args: LookupItemArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        let item_path = require_non_empty("item_path", &args.item_path)?;
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+
+        if let Some((doc_path, content)) = self.find_item_page(crate_name, item_path).await? {
+            return Ok(CallToolResult::success(vec![Content::text(item_page_text(crate_name, item_path, &doc_path, &content))]));
+        }
+
+        // No exact page: gather paths containing the most specific parts of the item path
+        let mut doc_paths = Vec::new();
+        for term in item_lookup_terms(item_path).iter().take(LOOKUP_TERMS) {
+            doc_paths.extend(
+                self.database
+                    .find_doc_paths(crate_name, term, LOOKUP_SCAN_LIMIT)
+                    .await
+                    .map_err(|e| e.into_mcp_error("search"))?,
+            );
+        }
+        let candidates = rank_item_candidates(crate_name, item_path, doc_paths, MAX_LOOKUP_CANDIDATES);
+        if candidates.is_empty() {
+            return Err(ServerError::NotFound(format!(
+                "No documentation stored for {} or similar items in crate {}",
+                item_path, crate_name
+            ))
+            .into_mcp_error("search"));
+        }
+        self.send_log(
+            LoggingLevel::Info,
+            format!("No exact page for '{}'; suggesting {} close matches", item_path, candidates.len()),
+        );
+
+        let list: Vec<String> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                format!(
+                    "{}. {} (similarity: {:.2})\n   Path: {}\n   URL: {}",
+                    i + 1,
+                    candidate.item,
+                    candidate.similarity,
+                    candidate.doc_path,
+                    doc_loader::docs_rs_url(&candidate.doc_path)
+                )
+            })
+            .collect();
+        let response = format!(
+            "No exact page for {} in crate {}. Closest stored items:\n\n{}",
+            item_path,
+            crate_name,
+            list.join("\n")
+        );
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Re-scrape one crate from docs.rs and re-embed only the pages that changed. Returns {pages_scanned, pages_changed, new_version}. Only available when the server runs with --allow-refresh."
+    )]
+    async fn refresh_crate(
+        &self,
+        #[tool(aggr)]
+        args: RefreshCrateArgs,
+    ) -> Result<CallToolResult, McpError> {
+        let crate_name = require_non_empty("crate_name", &args.crate_name)?;
+        if !self.allow_refresh {
+            return Err(ServerError::Config(
+                "refresh_crate is disabled; start the server with --allow-refresh".to_string(),
+            )
+            .into_mcp_error("validation"));
+        }
+        self.rate_limiter.check_tool_call(STDIO_CLIENT)?;
+        // Only crates that were populated before, so an agent can't grow the database
+        require_crates_with_embeddings(self.database.as_ref(), &[crate_name.to_string()]).await?;
+
+        self.send_log(LoggingLevel::Info, format!("Refreshing crate '{}' from docs.rs", crate_name));
+        let report = refresh::refresh_crate(self.database.as_ref(), crate_name, None)
+            .await
+            .map_err(|e| e.into_mcp_error("refresh"))?;
+        self.search_cache.clear();
+        self.vector_cache.invalidate(crate_name).await;
+        let response = serde_json::to_string_pretty(&report)
+            .map_err(|e| ServerError::Json(e).into_mcp_error("refresh"))?;
+
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
+
+    #[tool(
+        description = "Report the server's effective runtime configuration as JSON: version, embedding provider/model/dimensions, LLM model, database backend and distance metric, and served crates. Secrets are masked."
+    )]
+    async fn server_info(&self) -> Result<CallToolResult, McpError> {
+        let mut info = RuntimeInfo::collect(
+            "rust-docs-mcp-server",
+            self.database.as_ref(),
+            &self.crate_names,
+            self.allow_refresh,
+            self.rate_limiter.per_minute(),
+        )
+        .await
+        .map_err(|e| e.into_mcp_error("server_info"))?;
+        // No chat model is called with --no-llm
+        if !self.no_llm {
+            info = info.with_llm_model(client::llm_model());
+        }
+        let response = serde_json::to_string_pretty(&info)
+            .map_err(|e| ServerError::Json(e).into_mcp_error("server_info"))?;
+
+        Ok(CallToolResult::success(vec![Content::text(response)]))
+    }
 }
 
 // --- ServerHandler Implementation ---
@@ -419,9 +1674,9 @@ impl ServerHandler for RustDocsServer {
     fn get_info(&self) -> ServerInfo {
         // Define capabilities using the builder
         let capabilities = ServerCapabilities::builder()
-            .enable_tools() // Enable tools capability
             .enable_logging() // Enable logging capability
-            // Add other capabilities like resources, prompts if needed later
+            .enable_resources() // Stored documents as doc:// resources
+            .enable_tools() // Enable tools capability
             .build();
 
         ServerInfo {
@@ -435,27 +1690,42 @@ impl ServerHandler for RustDocsServer {
             instructions: Some(format!(
                 "This server provides tools to query documentation for the '{}' crate. \
                  Use the 'query_rust_docs' tool with a specific question to get information \
-                 about its API, usage, and examples, derived from its official documentation.",
+                 about its API, usage, and examples, derived from its official documentation. \
+                 Pass 'crate_names' instead of 'crate_name' to search several crates at once. \
+                 For follow-up questions in a conversation, 'query_with_history' also takes the earlier messages. \
+                 Use 'get_crate_examples' with a topic to get code samples verbatim, and \
+                 'compare_crates' to find the equivalent of one crate's API in another. \
+                 'compare_versions' diffs the stored docs of two versions of a crate for an upgrade note. \
+                 'search_by_item_path' returns one item's page verbatim given its path (e.g. tokio::sync::Mutex), \
+                 and 'lookup_item' also suggests the closest items when the path is inexact. \
+                 'refresh_crate' re-scrapes a crate whose docs are out of date, if enabled. \
+                 'server_info' reports the models and database the server runs with. \
+                 Every stored page can also be browsed as a doc://{{crate}}/{{path}} resource.",
                 self.crate_name
             )),
         }
     }
 
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.min_log_level.store(log_severity(&request.level), Ordering::Relaxed);
+        Ok(())
+    }
+
     // --- Placeholder Implementations for other ServerHandler methods ---
-    // Implement these properly if resource/prompt features are added later.
+    // Implement these properly if prompt features are added later.
 
     async fn list_resources(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListResourcesResult, McpError> {
-        // Example: Return the crate name as a resource
-        Ok(ListResourcesResult {
-            resources: vec![
-                self._create_resource_text(&format!("crate://{}", self.crate_name), "crate_name"),
-            ],
-            next_cursor: None,
-        })
+        let cursor = pagination::request_cursor(request);
+        resources::list_doc_resources(self.database.as_ref(), &self.crate_names, cursor.as_deref(), RESOURCE_PAGE_SIZE)
+            .await
     }
 
     async fn read_resource(
@@ -463,31 +1733,18 @@ impl ServerHandler for RustDocsServer {
         request: ReadResourceRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ReadResourceResult, McpError> {
-        let expected_uri = format!("crate://{}", self.crate_name);
-        if request.uri == expected_uri {
-            Ok(ReadResourceResult {
-                contents: vec![ResourceContents::text(
-                    self.crate_name.as_str(), // Explicitly get &str from Arc<String>
-                    &request.uri,
-                )],
-            })
-        } else {
-            Err(McpError::resource_not_found(
-                format!("Resource URI not found: {}", request.uri),
-                Some(json!({ "uri": request.uri })),
-            ))
-        }
+        resources::read_doc_resource(self.database.as_ref(), &self.crate_names, &request.uri).await
     }
 
     async fn list_prompts(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListPromptsResult, McpError> {
-        Ok(ListPromptsResult {
-            next_cursor: None,
-            prompts: Vec::new(), // No prompts defined yet
-        })
+        let cursor = pagination::request_cursor(request);
+        let prompts: Vec<Prompt> = Vec::new(); // No prompts defined yet
+        let (prompts, next_cursor) = pagination::paginate(prompts, cursor.as_deref(), DEFAULT_PAGE_SIZE)?;
+        Ok(ListPromptsResult { prompts, next_cursor })
     }
 
     async fn get_prompt(
@@ -504,12 +1761,373 @@ impl ServerHandler for RustDocsServer {
 
     async fn list_resource_templates(
         &self,
-        _request: PaginatedRequestParam,
+        request: PaginatedRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListResourceTemplatesResult, McpError> {
-        Ok(ListResourceTemplatesResult {
-            next_cursor: None,
-            resource_templates: Vec::new(), // No templates defined yet
+        let cursor = pagination::request_cursor(request);
+        let resource_templates: Vec<ResourceTemplate> = Vec::new(); // No templates defined yet
+        let (resource_templates, next_cursor) =
+            pagination::paginate(resource_templates, cursor.as_deref(), DEFAULT_PAGE_SIZE)?;
+        Ok(ListResourceTemplatesResult { resource_templates, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{test_doc, test_store_with};
+    use rmcp::model::ErrorCode;
+
+    #[tokio::test]
+    async fn compare_crates_rejects_comparing_a_crate_with_itself() {
+        let store = test_store_with(&[]).await;
+        let server = RustDocsServer::new("hyper".to_string(), Vec::new(), store, String::new()).unwrap();
+
+        let err = server
+            .compare_crates(CompareCratesArgs {
+                crate_a: "hyper".to_string(),
+                crate_b: " hyper ".to_string(),
+                question: "How do I send a request?".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn compare_versions_lists_stored_versions_when_one_is_missing() {
+        let store = test_store_with(&[]).await;
+        store.upsert_crate("tokio", Some("1.38.0")).await.unwrap();
+        store.upsert_crate("tokio@1.37.0", Some("1.37.0")).await.unwrap();
+        let server = RustDocsServer::new("tokio".to_string(), Vec::new(), store, String::new()).unwrap();
+        let args = |from: &str, to: &str| CompareVersionsArgs {
+            crate_name: "tokio".to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+
+        let err = server.compare_versions(args("1.36.0", "1.38.0")).await.unwrap_err();
+        assert!(err.message.contains("Stored versions: 1.38.0, 1.37.0"), "{}", err.message);
+
+        let err = server.compare_versions(args("latest", "1.38.0")).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+
+        // Neither version has any pages, so there is nothing to summarize
+        let result = server.compare_versions(args("1.37.0", "latest")).await.unwrap();
+        let text = serde_json::to_value(&result.content[0]).unwrap()["text"].as_str().unwrap().to_string();
+        let comparison: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(comparison["unchanged"], 0);
+        assert!(comparison["upgrade_note"].is_null());
+    }
+
+    #[test]
+    fn keeps_the_latest_conversation_turns_within_the_cap() {
+        let args: QueryWithHistoryArgs = serde_json::from_value(json!({
+            "crate_name": "tokio",
+            "question": "And how do I cancel it?",
+            "summary": "Asked how to spawn a task.",
+            "history": [
+                { "role": "user", "content": "How do I spawn a task?" },
+                { "role": "assistant", "content": "Use tokio::spawn, which returns a JoinHandle." }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(args.query.question, "And how do I cancel it?");
+        let (history, trimmed) = conversation_history(args.summary.as_deref(), &args.history);
+        assert_eq!(
+            history.as_deref(),
+            Some("Summary: Asked how to spawn a task.\nUser: How do I spawn a task?\nAssistant: Use tokio::spawn, which returns a JoinHandle.")
+        );
+        assert!(!trimmed);
+
+        let message = |role, content: String| HistoryMessage { role, content };
+        let long = vec![
+            message(HistoryRole::User, "old ".repeat(600)),
+            message(HistoryRole::Assistant, "new ".repeat(600)),
+        ];
+        let (history, trimmed) = conversation_history(Some("ignored"), &long);
+        let history = history.unwrap();
+        assert!(trimmed);
+        assert!(history.starts_with("Assistant: new") && !history.contains("old"));
+
+        let (history, _) = conversation_history(None, &[message(HistoryRole::User, "x".repeat(MAX_HISTORY_CHARS * 2))]);
+        assert_eq!(history.unwrap().chars().count(), MAX_HISTORY_CHARS + 1);
+        assert_eq!(conversation_history(Some("  "), &[]), (None, false));
+    }
+
+    #[test]
+    fn truncates_snippets_at_sentence_or_word_boundaries() {
+        let path = "tokio/latest/tokio/fn.spawn.html";
+        let text = "Spawns a new asynchronous task. The spawned task may run on the current thread or another one.";
+        assert_eq!(truncate_snippet(&format!("  {}\n", text), 500, path), text);
+
+        assert_eq!(
+            truncate_snippet(text, 60, path),
+            format!("Spawns a new asynchronous task.… [truncated; full text: {}]", path)
+        );
+        // No sentence end in the second half: cut at the last word break
+        let cut = truncate_snippet(text, 80, path);
+        assert!(cut.starts_with("Spawns a new asynchronous task. The spawned task may run on the current thread…"), "{}", cut);
+        assert!(cut.ends_with(&format!("full text: {}]", path)));
+
+        assert_eq!(validate_snippet_chars(None).unwrap(), None);
+        assert_eq!(validate_snippet_chars(Some(200)).unwrap(), Some(200));
+        let err = validate_snippet_chars(Some(10)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("snippet_chars"));
+    }
+
+    #[tokio::test]
+    async fn follows_recorded_dependencies_that_are_indexed() {
+        let seeds: Vec<_> = ["axum", "tower@0.4.13", "hyper", "serde"]
+            .map(|name| (name, vec![test_doc(&format!("{}/index.html", name), "docs", &[1.0, 0.0])]))
+            .into();
+        let store = test_store_with(&seeds).await;
+        assert!(indexed_dependencies(store.as_ref(), "axum").await.unwrap().is_empty());
+
+        let dependencies = ["bytes", "hyper", "serde", "tower"].map(str::to_string);
+        store.set_crate_dependencies("axum", &dependencies).await.unwrap();
+        assert_eq!(store.crate_dependencies("axum").await.unwrap(), dependencies);
+        // Not indexed (bytes) is skipped; a stored version stands in for the plain name
+        assert_eq!(
+            indexed_dependencies(store.as_ref(), "axum").await.unwrap(),
+            ["hyper", "serde", "tower@0.4.13"]
+        );
+
+        store.set_crate_dependencies("axum", &["hyper".to_string()]).await.unwrap();
+        assert_eq!(indexed_dependencies(store.as_ref(), "axum").await.unwrap(), ["hyper"]);
+    }
+
+    #[tokio::test]
+    async fn mmr_skips_near_duplicates_of_picked_documents() {
+        let store = test_store_with(&[(
+            "tokio",
+            vec![
+                test_doc("tokio/sync/struct.Mutex.html [chunk 1/2]", "docs", &[1.0, 0.0, 0.0]),
+                test_doc("tokio/sync/struct.Mutex.html [chunk 2/2]", "docs", &[0.98, 0.2, 0.0]),
+                test_doc("tokio/sync/struct.RwLock.html", "docs", &[0.6, 0.0, 0.8]),
+            ],
+        )])
+        .await;
+        let query = Array1::from(vec![1.0, 0.1, 0.1]);
+        let results = store.search_similar_docs("tokio", &query, 3).await.unwrap();
+        let paths = |results: &[DocMatch]| results.iter().map(|result| result.doc_path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&results)[2], "tokio/sync/struct.RwLock.html");
+
+        let diverse = diversify_results(store.as_ref(), "tokio", results.clone(), 0.5, 2).await.unwrap();
+        assert_eq!(paths(&diverse), ["tokio/sync/struct.Mutex.html [chunk 1/2]", "tokio/sync/struct.RwLock.html"]);
+        // Similarities stay those of the search
+        assert_eq!(diverse[1].similarity, results[2].similarity);
+
+        let relevant = diversify_results(store.as_ref(), "tokio", results.clone(), 1.0, 2).await.unwrap();
+        assert_eq!(paths(&relevant), paths(&results[..2]));
+
+        assert!(resolve_mmr_lambda(Some(1.5)).is_err());
+        assert_eq!(resolve_mmr_lambda(Some(0.7)).unwrap(), Some(0.7));
+    }
+
+    #[tokio::test]
+    async fn refuses_crates_until_population_finishes() {
+        let store = test_store_with(&[]).await;
+        let crate_id = store.upsert_crate("tokio", None).await.unwrap();
+        let tokio = ["tokio".to_string()];
+        assert_eq!(store.crate_status("tokio").await.unwrap(), Some(CrateStatus::Ready));
+        assert_eq!(store.crate_status("axum").await.unwrap(), None);
+
+        crate::database::populate_with_status(store.as_ref(), "tokio", async {
+            let doc = ("tokio/index.html".to_string(), "docs".to_string(), Array1::from(vec![1.0, 0.0]), 1);
+            store.insert_embeddings_batch(crate_id, "tokio", &[doc]).await?;
+            // The first batch is stored, but the crate isn't served yet
+            let err = require_crates_with_embeddings(store.as_ref(), &tokio).await.unwrap_err();
+            assert!(err.message.contains("still being indexed"));
+            assert_eq!(err.data.unwrap()["retryable"], true);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        require_crates_with_embeddings(store.as_ref(), &tokio).await.unwrap();
+
+        let failed: Result<(), ServerError> = crate::database::populate_with_status(store.as_ref(), "tokio", async {
+            Err(ServerError::Network("docs.rs went away".to_string()))
         })
+        .await;
+        assert!(failed.is_err());
+        assert_eq!(store.get_crate_stats().await.unwrap()[0].status, CrateStatus::Failed);
+        let err = require_crates_with_embeddings(store.as_ref(), &tokio).await.unwrap_err();
+        assert!(err.message.contains("last population of crate 'tokio' failed"));
+    }
+
+    #[tokio::test]
+    async fn server_info_reports_effective_config() {
+        let store = crate::database::test_store_with_metric(crate::database::DistanceMetric::L2, &[]).await;
+        let server = RustDocsServer::new("tokio".to_string(), Vec::new(), store, String::new())
+            .unwrap()
+            .with_rate_limit(Some(30));
+
+        let result = server.server_info().await.unwrap();
+        let text = serde_json::to_value(&result.content[0]).unwrap()["text"].as_str().unwrap().to_string();
+        let info: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(info["database"]["backend"], "sqlite");
+        assert_eq!(info["database"]["distance_metric"], "l2");
+        assert_eq!(info["crates"], json!(["tokio"]));
+        assert_eq!(info["rate_limit_per_minute"], 30);
+        assert_eq!(info["refresh_enabled"], false);
+        assert!(info["llm_model"].is_string());
+    }
+
+    #[tokio::test]
+    async fn search_by_item_path_returns_the_page_for_either_path_form() {
+        let row = |path: &str, content: &str| test_doc(path, content, &[1.0, 0.0]);
+        let store = test_store_with(&[(
+            "tokio",
+            vec![
+                row("tokio/latest/tokio/sync/struct.Mutex.html [chunk 2/2]", "second half"),
+                row("tokio/latest/tokio/sync/struct.Mutex.html [chunk 1/2]", "first half"),
+                row("tokio/latest/tokio/sync/struct.MutexGuard.html", "guard"),
+                row("tokio/latest/tokio/sync/index.html", "synchronization primitives"),
+            ],
+        )])
+        .await;
+        let server = RustDocsServer::new("tokio".to_string(), Vec::new(), store, String::new()).unwrap();
+        let text = |result: CallToolResult| {
+            serde_json::to_value(&result.content[0]).unwrap()["text"].as_str().unwrap().to_string()
+        };
+        let args = |item_path: &str| SearchByItemPathArgs {
+            crate_name: "tokio".to_string(),
+            item_path: item_path.to_string(),
+        };
+
+        for item_path in ["tokio::sync::Mutex", "sync/struct.Mutex.html"] {
+            let response = text(server.search_by_item_path(args(item_path)).await.unwrap());
+            assert!(response.starts_with("Item: tokio::sync::Mutex\nPath: tokio/latest/tokio/sync/struct.Mutex.html\n"));
+            assert!(response.ends_with("first half\nsecond half"), "{}", response);
+        }
+        let module = text(server.search_by_item_path(args("tokio::sync")).await.unwrap());
+        assert!(module.ends_with("synchronization primitives"));
+
+        let err = server.search_by_item_path(args("tokio::sync::Semaphore")).await.unwrap_err();
+        assert!(err.message.contains("tokio::sync::Semaphore"));
+    }
+
+    #[tokio::test]
+    async fn lookup_item_ranks_close_matches_when_no_page_matches() {
+        let row = |path: &str, content: &str| test_doc(path, content, &[1.0, 0.0]);
+        let store = test_store_with(&[(
+            "axum",
+            vec![
+                row("axum/latest/axum/struct.Router.html", "Router docs"),
+                row("axum/latest/axum/struct.Router.html#method.route [chunk 1/2]", "Add a route"),
+                row("axum/latest/axum/struct.Router.html#method.route [chunk 2/2]", "more"),
+                row("axum/latest/axum/struct.Router.html#method.route_service", "Add a service route"),
+                row("axum/latest/axum/routing/fn.get.html", "GET handler"),
+            ],
+        )])
+        .await;
+        let server = RustDocsServer::new("axum".to_string(), Vec::new(), store, String::new()).unwrap();
+        let text = |result: CallToolResult| {
+            serde_json::to_value(&result.content[0]).unwrap()["text"].as_str().unwrap().to_string()
+        };
+        let args = |item_path: &str| LookupItemArgs {
+            crate_name: "axum".to_string(),
+            item_path: item_path.to_string(),
+        };
+
+        let exact = text(server.lookup_item(args("axum::Router")).await.unwrap());
+        assert!(exact.starts_with("Item: axum::Router\n") && exact.contains("\n\nRouter docs\n"), "{}", exact);
+
+        for item_path in ["axum::Router::route", "axum::Routr::rout"] {
+            let response = text(server.lookup_item(args(item_path)).await.unwrap());
+            assert!(response.contains("Closest stored items:\n\n1. axum::Router::route (similarity: "), "{}", response);
+            assert_eq!(response.matches("Path: axum/latest/axum/struct.Router.html#method.route\n").count(), 1, "{}", response);
+        }
+
+        assert_eq!(item_lookup_terms("sync/struct.Mutex.html"), ["Mutex", "sync"]);
+        assert!(server.lookup_item(args("serde::Deserialize")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn suppresses_logs_below_the_client_level() {
+        let store = test_store_with(&[]).await;
+        let server = RustDocsServer::new("tokio".to_string(), Vec::new(), store, String::new()).unwrap();
+        assert!(server.log_enabled(&LoggingLevel::Debug));
+
+        server.min_log_level.store(log_severity(&LoggingLevel::Warning), Ordering::Relaxed);
+        assert!(!server.log_enabled(&LoggingLevel::Info));
+        assert!(server.log_enabled(&LoggingLevel::Warning));
+        assert!(server.log_enabled(&LoggingLevel::Emergency));
+    }
+
+    #[test]
+    fn json_answers_list_sources_with_crate_and_url() {
+        let results = vec![(
+            "tokio",
+            DocMatch {
+                doc_path: "tokio/latest/tokio/sync/struct.Mutex.html [chunk 1/2]".to_string(),
+                content: "content".to_string(),
+                similarity: 0.5,
+                token_count: Some(1),
+            },
+        )];
+        let answer = QueryAnswer {
+            answer: "Use a Mutex.".to_string(),
+            sources: answer_sources(&results),
+            grounding: None,
+            low_confidence: false,
+            usage: AnswerUsage { embedding_tokens: 7, prompt_tokens: 120, completion_tokens: 30 },
+            related_crates: Vec::new(),
+        };
+        let value = serde_json::to_value(&answer).unwrap();
+        assert_eq!(value["sources"][0]["crate"], "tokio");
+        assert_eq!(value["sources"][0]["url"], "https://docs.rs/tokio/latest/tokio/sync/struct.Mutex.html");
+        assert_eq!(value["usage"]["embedding_tokens"], 7);
+
+        let format: OutputFormat = serde_json::from_value(json!("json")).unwrap();
+        assert_eq!(format, OutputFormat::Json);
+        assert_eq!(OutputFormat::default(), OutputFormat::Text);
+    }
+
+    #[test]
+    fn resolves_single_and_multiple_target_crates() {
+        assert_eq!(resolve_target_crates(Some(" axum "), None).unwrap(), vec!["axum"]);
+
+        let names = vec!["axum".to_string(), "tower".to_string(), "axum".to_string()];
+        assert_eq!(
+            resolve_target_crates(Some("hyper"), Some(&names)).unwrap(),
+            vec!["hyper", "axum", "tower"]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_blank_target_crates() {
+        let err = resolve_target_crates(None, None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+
+        let names = vec!["axum".to_string(), " ".to_string()];
+        let err = resolve_target_crates(None, Some(&names)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn accepts_non_empty_query_args() {
+        assert_eq!(
+            validate_query_args(" tokio ", "  How do I spawn a task?\n").unwrap(),
+            ("tokio", "How do I spawn a task?")
+        );
+    }
+
+    #[test]
+    fn rejects_empty_or_whitespace_question() {
+        for question in ["", "   ", "\n\t"] {
+            let err = validate_query_args("tokio", question).unwrap_err();
+            assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+            assert!(err.message.contains("question"));
+        }
+    }
+
+    #[test]
+    fn rejects_empty_crate_name() {
+        let err = validate_query_args(" ", "How do I spawn a task?").unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("crate_name"));
     }
 }
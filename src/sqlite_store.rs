@@ -0,0 +1,907 @@
+use crate::{
+    database::{assemble_page, join_features, split_features, Backend, CrateStats, CrateStatus, DistanceMetric, DocFilter, DocMatch, GlobalStats, VectorStore, EXAMPLE_CANDIDATE_FACTOR},
+    dedupe,
+    doc_loader,
+    error::ServerError,
+    examples,
+};
+use async_trait::async_trait;
+use ndarray::Array1;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Row, SqlitePool,
+};
+use std::{collections::HashSet, str::FromStr, sync::Once};
+
+const SCHEMA: &str = include_str!("../sql/sqlite_schema.sql");
+
+static REGISTER_SQLITE_VEC: Once = Once::new();
+
+/// Register sqlite-vec as an auto extension so every new connection gets `vec_*` functions
+fn register_sqlite_vec() {
+    REGISTER_SQLITE_VEC.call_once(|| unsafe {
+        #[allow(clippy::missing_transmute_annotations)]
+        libsqlite3_sys::sqlite3_auto_extension(Some(std::mem::transmute(
+            sqlite_vec::sqlite3_vec_init as *const (),
+        )));
+    });
+}
+
+/// Encode an embedding as the little-endian float32 blob sqlite-vec expects
+fn embedding_to_blob(embedding: &Array1<f32>) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn blob_to_embedding(blob: &[u8]) -> Array1<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Add `column` to `table` with the given type and constraints if an older database lacks it,
+/// returning whether it was added
+async fn ensure_column(pool: &SqlitePool, table: &str, column: &str, ddl: &str) -> Result<bool, ServerError> {
+    let exists: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to inspect SQLite schema: {}", e)))?;
+    if exists {
+        return Ok(false);
+    }
+    sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, ddl))
+        .execute(pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to add {} column: {}", column, e)))?;
+    Ok(true)
+}
+
+/// File-based vector store backed by SQLite and sqlite-vec
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+    distance: DistanceMetric,
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) the SQLite database at `database_url` and apply the schema
+    pub async fn connect(database_url: &str) -> Result<Self, ServerError> {
+        register_sqlite_vec();
+
+        let options = SqliteConnectOptions::from_str(database_url)
+            .map_err(|e| ServerError::Config(format!("Invalid SQLite URL: {}", e)))?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        // An in-memory database only lives as long as its connection, so keep exactly one
+        let pool_options = if database_url.contains(":memory:") {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(5)
+        };
+
+        let pool = pool_options
+            .connect_with(options)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to connect to database: {}", e)))?;
+
+        sqlx::raw_sql(SCHEMA)
+            .execute(&pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to apply SQLite schema: {}", e)))?;
+
+        // Databases created before the column existed; documents default to "no example"
+        // until their crate is populated again
+        ensure_column(&pool, "doc_embeddings", "has_code_example", "BOOLEAN NOT NULL DEFAULT 0").await?;
+        // Module paths only depend on doc_path, so older databases are backfilled right away
+        let added_module_column = ensure_column(&pool, "doc_embeddings", "module_path", "TEXT").await?;
+        ensure_column(&pool, "crates", "features", "TEXT").await?;
+        ensure_column(&pool, "crates", "embedding_model", "TEXT").await?;
+        // Crates stored before the status was recorded were complete
+        ensure_column(&pool, "crates", "status", "TEXT NOT NULL DEFAULT 'ready'").await?;
+        ensure_column(&pool, "crates", "status_updated_at", "TIMESTAMP").await?;
+        ensure_column(&pool, "doc_embeddings", "anchor", "TEXT").await?;
+        // Rows without a content hash are hashed on read (VectorStore::get_content_hashes)
+        ensure_column(&pool, "doc_embeddings", "content_hash", "TEXT").await?;
+        // Kinds also only depend on doc_path
+        let added_kind_column = ensure_column(&pool, "doc_embeddings", "doc_kind", "TEXT").await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_doc_embeddings_module_path ON doc_embeddings(crate_name, module_path)")
+            .execute(&pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to index module_path: {}", e)))?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_doc_embeddings_doc_kind ON doc_embeddings(crate_name, doc_kind)")
+            .execute(&pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to index doc_kind: {}", e)))?;
+
+        let store = Self { pool, distance: DistanceMetric::default() };
+        if added_module_column {
+            store.backfill_module_paths().await?;
+        }
+        if added_kind_column {
+            store.backfill_doc_kinds().await?;
+        }
+        Ok(store)
+    }
+
+    /// Rank search results with `metric`. sqlite-vec has no inner product distance.
+    pub fn with_distance_metric(mut self, metric: DistanceMetric) -> Result<Self, ServerError> {
+        if metric == DistanceMetric::InnerProduct {
+            return Err(ServerError::Config(
+                "The SQLite backend supports only cosine and l2 distance".to_string(),
+            ));
+        }
+        self.distance = metric;
+        Ok(self)
+    }
+
+    /// Nearest-neighbour search, optionally re-ranking the candidates with an example boost
+    async fn search(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: Option<f32>,
+        filter: DocFilter<'_>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        // sqlite-vec scans the crate's rows; fine for the local/CI sized databases this backend targets
+        let (distance, similarity) = match self.distance {
+            DistanceMetric::L2 => (
+                "vec_distance_l2(embedding, ?1)",
+                "1 - vec_distance_l2(embedding, ?1) * vec_distance_l2(embedding, ?1) / 2",
+            ),
+            _ => ("vec_distance_cosine(embedding, ?1)", "1 - vec_distance_cosine(embedding, ?1)"),
+        };
+        let nearest = format!(
+            r#"
+            SELECT
+                doc_path,
+                content,
+                token_count,
+                has_code_example,
+                {} as similarity
+            FROM doc_embeddings
+            WHERE crate_name = ?2
+              AND (?4 IS NULL OR module_path = ?4 OR substr(module_path, 1, length(?4) + 2) = ?4 || '::')
+              AND (?5 IS NULL OR doc_kind = ?5)
+            ORDER BY {}
+            LIMIT ?3
+            "#,
+            similarity, distance
+        );
+        let (sql, candidates) = match example_boost {
+            None => (nearest, limit),
+            Some(boost) => (
+                format!(
+                    r#"
+                    SELECT doc_path, content, token_count, similarity
+                    FROM ({}) candidates
+                    ORDER BY similarity + CASE WHEN has_code_example THEN {} ELSE 0 END DESC
+                    LIMIT {}
+                    "#,
+                    nearest, boost as f64, limit
+                ),
+                limit.saturating_mul(EXAMPLE_CANDIDATE_FACTOR),
+            ),
+        };
+
+        let results = sqlx::query(&sql)
+        .bind(embedding_to_blob(query_embedding))
+        .bind(crate_name)
+        .bind(candidates)
+        .bind(filter.module_prefix)
+        .bind(filter.kind)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to search documents: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let token_count: Option<i32> = row.get("token_count");
+                let similarity: f64 = row.get("similarity");
+                DocMatch {
+                    doc_path: row.get("doc_path"),
+                    content: row.get("content"),
+                    similarity: similarity as f32,
+                    token_count: token_count.map(|count| count.max(0) as usize),
+                }
+            })
+            .collect())
+    }
+
+    /// Update crate statistics
+    async fn update_crate_stats(&self, crate_id: i32) -> Result<(), ServerError> {
+        sqlx::query(
+            r#"
+            UPDATE crates
+            SET total_docs = (
+                SELECT COUNT(*) FROM doc_embeddings WHERE crate_id = ?1
+            ),
+            total_tokens = (
+                SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings WHERE crate_id = ?1
+            )
+            WHERE id = ?1
+            "#
+        )
+        .bind(crate_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to update crate stats: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VectorStore for SqliteStore {
+    async fn upsert_crate(&self, crate_name: &str, version: Option<&str>) -> Result<i32, ServerError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO crates (name, version)
+            VALUES (?1, ?2)
+            ON CONFLICT (name)
+            DO UPDATE SET
+                version = COALESCE(?2, crates.version),
+                last_updated = CURRENT_TIMESTAMP
+            RETURNING id
+            "#
+        )
+        .bind(crate_name)
+        .bind(version)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to upsert crate: {}", e)))?;
+
+        let id: i32 = result.get("id");
+        Ok(id)
+    }
+
+    async fn set_crate_features(&self, crate_name: &str, features: &[String]) -> Result<(), ServerError> {
+        sqlx::query("UPDATE crates SET features = ?2 WHERE name = ?1")
+            .bind(crate_name)
+            .bind(join_features(features))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to set crate features: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn set_crate_embedding_model(&self, crate_name: &str, model: &str) -> Result<(), ServerError> {
+        sqlx::query("UPDATE crates SET embedding_model = ?2 WHERE name = ?1")
+            .bind(crate_name)
+            .bind(model)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to set crate embedding model: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn set_crate_status(&self, crate_name: &str, status: CrateStatus) -> Result<(), ServerError> {
+        sqlx::query("UPDATE crates SET status = ?2, status_updated_at = ?3 WHERE name = ?1")
+            .bind(crate_name)
+            .bind(status.as_str())
+            .bind(chrono::Utc::now().naive_utc())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to set crate status: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn crate_status(&self, crate_name: &str) -> Result<Option<CrateStatus>, ServerError> {
+        let status: Option<(String, Option<chrono::NaiveDateTime>)> =
+            sqlx::query_as("SELECT status, status_updated_at FROM crates WHERE name = ?1")
+                .bind(crate_name)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| ServerError::Database(format!("Failed to get crate status: {}", e)))?;
+
+        Ok(status.map(|(status, updated_at)| CrateStatus::from_db(&status, updated_at)))
+    }
+
+    async fn set_crate_dependencies(&self, crate_name: &str, dependencies: &[String]) -> Result<(), ServerError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+        sqlx::query("DELETE FROM crate_deps WHERE crate_name = ?1")
+            .bind(crate_name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to clear crate dependencies: {}", e)))?;
+        for dependency in dependencies {
+            sqlx::query("INSERT OR IGNORE INTO crate_deps (crate_name, dependency) VALUES (?1, ?2)")
+                .bind(crate_name)
+                .bind(dependency)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| ServerError::Database(format!("Failed to insert crate dependency: {}", e)))?;
+        }
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn crate_dependencies(&self, crate_name: &str) -> Result<Vec<String>, ServerError> {
+        let results = sqlx::query("SELECT dependency FROM crate_deps WHERE crate_name = ?1 ORDER BY dependency")
+            .bind(crate_name)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to get crate dependencies: {}", e)))?;
+
+        Ok(results.into_iter().map(|row| row.get("dependency")).collect())
+    }
+
+    async fn has_embeddings(&self, crate_name: &str) -> Result<bool, ServerError> {
+        let result = sqlx::query(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM doc_embeddings WHERE crate_name = ?1
+            ) as found
+            "#
+        )
+        .bind(crate_name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to check embeddings: {}", e)))?;
+
+        let exists: bool = result.get("found");
+        Ok(exists)
+    }
+
+    async fn insert_embedding(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        doc_path: &str,
+        content: &str,
+        embedding: &Array1<f32>,
+        token_count: i32,
+    ) -> Result<(), ServerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count, has_code_example, module_path, content_hash, anchor, doc_kind)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            ON CONFLICT (crate_name, doc_path)
+            DO UPDATE SET
+                content = ?4,
+                embedding = ?5,
+                token_count = ?6,
+                has_code_example = ?7,
+                module_path = ?8,
+                content_hash = ?9,
+                anchor = ?10,
+                doc_kind = ?11,
+                created_at = CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(crate_id)
+        .bind(crate_name)
+        .bind(doc_path)
+        .bind(content)
+        .bind(embedding_to_blob(embedding))
+        .bind(token_count)
+        .bind(examples::is_code_example(content))
+        .bind(doc_loader::module_path(crate_name, doc_path))
+        .bind(dedupe::content_hash(content))
+        .bind(doc_loader::doc_anchor(doc_path))
+        .bind(doc_loader::doc_kind(crate_name, doc_path))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to insert embedding: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn insert_embeddings_batch(
+        &self,
+        crate_id: i32,
+        crate_name: &str,
+        embeddings: &[(String, String, Array1<f32>, i32)],
+    ) -> Result<(), ServerError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+
+        for (doc_path, content, embedding, token_count) in embeddings {
+            sqlx::query(
+                r#"
+                INSERT INTO doc_embeddings (crate_id, crate_name, doc_path, content, embedding, token_count, has_code_example, module_path, content_hash, anchor, doc_kind)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                ON CONFLICT (crate_name, doc_path)
+                DO UPDATE SET
+                    content = ?4,
+                    embedding = ?5,
+                    token_count = ?6,
+                    has_code_example = ?7,
+                    module_path = ?8,
+                    content_hash = ?9,
+                    anchor = ?10,
+                    doc_kind = ?11,
+                    created_at = CURRENT_TIMESTAMP
+                "#
+            )
+            .bind(crate_id)
+            .bind(crate_name)
+            .bind(doc_path)
+            .bind(content)
+            .bind(embedding_to_blob(embedding))
+            .bind(*token_count)
+            .bind(examples::is_code_example(content))
+            .bind(doc_loader::module_path(crate_name, doc_path))
+            .bind(dedupe::content_hash(content))
+            .bind(doc_loader::doc_anchor(doc_path))
+            .bind(doc_loader::doc_kind(crate_name, doc_path))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to insert embedding: {}", e)))?;
+        }
+
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        self.update_crate_stats(crate_id).await?;
+
+        Ok(())
+    }
+
+    async fn search_similar_docs(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, None, DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_preferring_examples(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        example_boost: f32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, Some(example_boost), DocFilter::default()).await
+    }
+
+    async fn search_similar_docs_filtered(
+        &self,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+        filter: DocFilter<'_>,
+        example_boost: Option<f32>,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        self.search(crate_name, query_embedding, limit, example_boost, filter).await
+    }
+
+    async fn get_crate_documents(
+        &self,
+        crate_name: &str,
+    ) -> Result<Vec<(String, String, Array1<f32>, Option<usize>)>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, content, embedding, token_count
+            FROM doc_embeddings
+            WHERE crate_name = ?1
+            ORDER BY doc_path
+            "#
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get crate documents: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let doc_path: String = row.get("doc_path");
+                let content: String = row.get("content");
+                let blob: Vec<u8> = row.get("embedding");
+                let token_count: Option<i32> = row.get("token_count");
+                (doc_path, content, blob_to_embedding(&blob), token_count.map(|count| count.max(0) as usize))
+            })
+            .collect())
+    }
+
+    async fn get_doc_embeddings(
+        &self,
+        crate_name: &str,
+        doc_paths: &[String],
+    ) -> Result<Vec<(String, Array1<f32>)>, ServerError> {
+        let paths = serde_json::to_string(doc_paths)
+            .map_err(|e| ServerError::Internal(format!("Failed to encode document paths: {}", e)))?;
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, embedding FROM doc_embeddings
+            WHERE crate_name = ?1 AND doc_path IN (SELECT value FROM json_each(?2))
+            "#
+        )
+        .bind(crate_name)
+        .bind(paths)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get document embeddings: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let blob: Vec<u8> = row.get("embedding");
+                (row.get("doc_path"), blob_to_embedding(&blob))
+            })
+            .collect())
+    }
+
+    async fn get_crate_doc_contents(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, content
+            FROM doc_embeddings
+            WHERE crate_name = ?1
+            ORDER BY doc_path
+            "#
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get crate documents: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| (row.get("doc_path"), row.get("content")))
+            .collect())
+    }
+
+    async fn get_content_hashes(&self, crate_name: &str) -> Result<Vec<(String, String)>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, content_hash, CASE WHEN content_hash IS NULL THEN content END AS content
+            FROM doc_embeddings
+            WHERE crate_name = ?1
+            ORDER BY doc_path
+            "#
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get content hashes: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let hash: Option<String> = row.get("content_hash");
+                let hash = hash.unwrap_or_else(|| dedupe::content_hash(row.get("content")));
+                (row.get("doc_path"), hash)
+            })
+            .collect())
+    }
+
+    async fn list_doc_paths(
+        &self,
+        crate_name: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<String>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path
+            FROM doc_embeddings
+            WHERE crate_name = ?1 AND (?2 IS NULL OR doc_path > ?2)
+            ORDER BY doc_path
+            LIMIT ?3
+            "#
+        )
+        .bind(crate_name)
+        .bind(after)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to list document paths: {}", e)))?;
+
+        Ok(results.into_iter().map(|row| row.get("doc_path")).collect())
+    }
+
+    async fn get_doc_content(&self, crate_name: &str, doc_path: &str) -> Result<Option<String>, ServerError> {
+        let row = sqlx::query("SELECT content FROM doc_embeddings WHERE crate_name = ?1 AND doc_path = ?2")
+            .bind(crate_name)
+            .bind(doc_path)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to get document content: {}", e)))?;
+
+        Ok(row.map(|row| row.get("content")))
+    }
+
+    async fn get_document_by_path(
+        &self,
+        crate_name: &str,
+        doc_path: &str,
+    ) -> Result<Option<(String, String)>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT doc_path, content
+            FROM doc_embeddings
+            WHERE crate_name = ?1 AND instr('/' || doc_path, '/' || ?2) > 0
+            ORDER BY id
+            "#
+        )
+        .bind(crate_name)
+        .bind(doc_path)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get document by path: {}", e)))?;
+
+        let rows = results.into_iter().map(|row| (row.get("doc_path"), row.get("content"))).collect();
+        Ok(assemble_page(rows, doc_path))
+    }
+
+    async fn find_doc_paths(&self, crate_name: &str, term: &str, limit: usize) -> Result<Vec<String>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT DISTINCT doc_path
+            FROM doc_embeddings
+            WHERE crate_name = ?1 AND instr(lower(doc_path), lower(?2)) > 0
+            ORDER BY doc_path
+            LIMIT ?3
+            "#
+        )
+        .bind(crate_name)
+        .bind(term)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to find document paths: {}", e)))?;
+
+        Ok(results.into_iter().map(|row| row.get("doc_path")).collect())
+    }
+
+    async fn backfill_module_paths(&self) -> Result<usize, ServerError> {
+        let rows = sqlx::query("SELECT id, crate_name, doc_path FROM doc_embeddings WHERE module_path IS NULL")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to read document paths: {}", e)))?;
+
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+        let mut updated = 0;
+        for row in rows {
+            let crate_name: String = row.get("crate_name");
+            let doc_path: String = row.get("doc_path");
+            let Some(module) = doc_loader::module_path(&crate_name, &doc_path) else {
+                continue;
+            };
+            sqlx::query("UPDATE doc_embeddings SET module_path = ?1 WHERE id = ?2")
+                .bind(module)
+                .bind(row.get::<i64, _>("id"))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| ServerError::Database(format!("Failed to backfill module paths: {}", e)))?;
+            updated += 1;
+        }
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(updated)
+    }
+
+    async fn backfill_doc_kinds(&self) -> Result<usize, ServerError> {
+        let rows = sqlx::query("SELECT id, crate_name, doc_path FROM doc_embeddings WHERE doc_kind IS NULL")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to read document paths: {}", e)))?;
+
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+        let mut updated = 0;
+        for row in rows {
+            let crate_name: String = row.get("crate_name");
+            let doc_path: String = row.get("doc_path");
+            let Some(kind) = doc_loader::doc_kind(&crate_name, &doc_path) else {
+                continue;
+            };
+            sqlx::query("UPDATE doc_embeddings SET doc_kind = ?1 WHERE id = ?2")
+                .bind(kind)
+                .bind(row.get::<i64, _>("id"))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| ServerError::Database(format!("Failed to backfill doc kinds: {}", e)))?;
+            updated += 1;
+        }
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(updated)
+    }
+
+    async fn repair_crate_stats(&self) -> Result<usize, ServerError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE crates
+            SET total_docs = (SELECT COUNT(*) FROM doc_embeddings WHERE crate_id = crates.id),
+                total_tokens = (SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings WHERE crate_id = crates.id)
+            WHERE total_docs IS NOT (SELECT COUNT(*) FROM doc_embeddings WHERE crate_id = crates.id)
+               OR total_tokens IS NOT (SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings WHERE crate_id = crates.id)
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to repair crate stats: {}", e)))?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn filter_existing_crates(&self, crate_names: &[String]) -> Result<(Vec<String>, Vec<String>), ServerError> {
+        // SQLite has no array binds, so the names go in as one JSON array
+        let names = serde_json::to_string(crate_names)
+            .map_err(|e| ServerError::Internal(format!("Failed to encode crate names: {}", e)))?;
+        let results = sqlx::query(
+            r#"
+            SELECT DISTINCT crate_name FROM doc_embeddings
+            WHERE crate_name IN (SELECT value FROM json_each(?1))
+            "#
+        )
+        .bind(names)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to check embeddings: {}", e)))?;
+
+        let present: HashSet<String> = results.into_iter().map(|row| row.get("crate_name")).collect();
+        Ok(crate_names.iter().cloned().partition(|name| present.contains(name)))
+    }
+
+    async fn delete_crate_embeddings(&self, crate_name: &str) -> Result<(), ServerError> {
+        sqlx::query("DELETE FROM doc_embeddings WHERE crate_name = ?1")
+            .bind(crate_name)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to delete embeddings: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn delete_doc_embeddings(&self, crate_name: &str, doc_paths: &[String]) -> Result<(), ServerError> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| ServerError::Database(format!("Failed to begin transaction: {}", e)))?;
+        for doc_path in doc_paths {
+            sqlx::query("DELETE FROM doc_embeddings WHERE crate_name = ?1 AND doc_path = ?2")
+                .bind(crate_name)
+                .bind(doc_path)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| ServerError::Database(format!("Failed to delete embeddings: {}", e)))?;
+        }
+        tx.commit().await
+            .map_err(|e| ServerError::Database(format!("Failed to commit transaction: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn get_crate_stats(&self) -> Result<Vec<CrateStats>, ServerError> {
+        let results = sqlx::query(
+            r#"
+            SELECT
+                name,
+                version,
+                last_updated,
+                total_docs,
+                total_tokens,
+                features,
+                embedding_model,
+                status,
+                status_updated_at
+            FROM crates
+            ORDER BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get crate stats: {}", e)))?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let total_docs: Option<i32> = row.get("total_docs");
+                let total_tokens: Option<i32> = row.get("total_tokens");
+
+                CrateStats {
+                    name: row.get("name"),
+                    version: row.get("version"),
+                    last_updated: row.get("last_updated"),
+                    total_docs: total_docs.unwrap_or(0),
+                    total_tokens: total_tokens.unwrap_or(0),
+                    features: split_features(row.get("features")),
+                    embedding_model: row.get("embedding_model"),
+                    status: CrateStatus::from_db(row.get("status"), row.get("status_updated_at")),
+                }
+            })
+            .collect())
+    }
+
+    async fn global_stats(&self) -> Result<GlobalStats, ServerError> {
+        let totals = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM crates) AS total_crates,
+                (SELECT COUNT(*) FROM doc_embeddings) AS total_docs,
+                (SELECT COALESCE(SUM(token_count), 0) FROM doc_embeddings) AS total_tokens,
+                (SELECT MIN(last_updated) FROM crates) AS oldest_update,
+                (SELECT MAX(last_updated) FROM crates) AS newest_update
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get global stats: {}", e)))?;
+        let dimensions = sqlx::query(
+            "SELECT DISTINCT length(embedding) / 4 AS dims FROM doc_embeddings WHERE embedding IS NOT NULL ORDER BY dims"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServerError::Database(format!("Failed to get embedding dimensions: {}", e)))?;
+
+        Ok(GlobalStats::new(
+            totals.get("total_crates"),
+            totals.get("total_docs"),
+            totals.get("total_tokens"),
+            dimensions.iter().map(|row| row.get::<i64, _>("dims") as usize).collect(),
+            totals.get("oldest_update"),
+            totals.get("newest_update"),
+        ))
+    }
+
+    async fn count_crate_documents(&self, crate_name: &str) -> Result<usize, ServerError> {
+        let result = sqlx::query("SELECT COUNT(*) as count FROM doc_embeddings WHERE crate_name = ?1")
+            .bind(crate_name)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to count crate documents: {}", e)))?;
+
+        let count: i64 = result.get("count");
+        Ok(count as usize)
+    }
+
+    async fn stored_embedding_dimensions(&self) -> Result<Option<usize>, ServerError> {
+        // Embeddings are float32 blobs without a declared size; infer it from any stored row
+        let result = sqlx::query("SELECT length(embedding) / 4 as dims FROM doc_embeddings LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to read embedding dimensions: {}", e)))?;
+
+        Ok(result.map(|row| row.get::<i64, _>("dims") as usize))
+    }
+
+    async fn crate_embedding_dimensions(&self, crate_name: &str) -> Result<Option<usize>, ServerError> {
+        let result = sqlx::query("SELECT length(embedding) / 4 as dims FROM doc_embeddings WHERE crate_name = ?1 LIMIT 1")
+            .bind(crate_name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to read crate embedding dimensions: {}", e)))?;
+
+        Ok(result.map(|row| row.get::<i64, _>("dims") as usize))
+    }
+
+    async fn ping(&self) -> Result<(), ServerError> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| ServerError::Database(format!("Failed to ping database: {}", e)))?;
+        Ok(())
+    }
+
+    async fn validate_distance_metric(&self) -> Result<(), ServerError> {
+        // No vector index: every search is a scan regardless of the metric
+        Ok(())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Sqlite
+    }
+
+    fn distance_metric(&self) -> DistanceMetric {
+        self.distance
+    }
+}
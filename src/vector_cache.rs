@@ -0,0 +1,221 @@
+// In-memory copies of the most-queried crates' vectors. A crate is loaded in the
+// background on its first search (via `get_crate_documents`) and later searches rank
+// it in memory; until then, and for crates that don't fit, searches go to the store,
+// which stays the source of truth.
+
+use crate::{
+    database::{DistanceMetric, DocMatch, VectorStore},
+    embeddings::cosine_similarity,
+    error::ServerError,
+};
+use moka::future::Cache;
+use ndarray::Array1;
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Cached crates are reloaded after this long, so documents re-populated by another
+/// process show up without a restart
+const VECTOR_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// One document as returned by `get_crate_documents`: path, content, embedding, token count
+type CachedDoc = (String, String, Array1<f32>, Option<usize>);
+
+/// A crate's cached documents
+type CrateVectors = Arc<Vec<CachedDoc>>;
+
+/// LRU of per-crate vectors, sized by the total number of vectors it holds.
+/// A disabled cache sends every search to the store.
+#[derive(Clone)]
+pub struct VectorCache {
+    cache: Option<Cache<String, CrateVectors>>,
+    max_vectors: u64,
+    /// Crates being loaded, so concurrent misses start one load
+    loading: Arc<Mutex<HashSet<String>>>,
+    /// Crates with more vectors than the whole cache holds; always searched in the store
+    oversized: Arc<Mutex<HashSet<String>>>,
+}
+
+impl VectorCache {
+    /// A cache of up to `max_vectors` vectors across all crates. 0 disables it.
+    pub fn new(max_vectors: u64) -> Self {
+        let cache = (max_vectors > 0).then(|| {
+            Cache::builder()
+                .max_capacity(max_vectors)
+                .weigher(|_: &String, docs: &CrateVectors| u32::try_from(docs.len()).unwrap_or(u32::MAX))
+                .time_to_live(VECTOR_CACHE_TTL)
+                .build()
+        });
+        Self {
+            cache,
+            max_vectors,
+            loading: Arc::default(),
+            oversized: Arc::default(),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0)
+    }
+
+    /// Configure from `MAX_CACHED_VECTORS`; unset or 0 keeps the cache off
+    pub fn from_env() -> Self {
+        Self::new(env::var("MAX_CACHED_VECTORS").ok().and_then(|v| v.parse().ok()).unwrap_or(0))
+    }
+
+    /// Whether searches of `crate_name` are currently answered from memory
+    pub fn contains(&self, crate_name: &str) -> bool {
+        self.cache.as_ref().is_some_and(|cache| cache.contains_key(crate_name))
+    }
+
+    /// Like [`VectorStore::search_similar_docs`], ranked in memory when the crate is cached.
+    /// A miss is answered by the store and starts loading the crate for later searches.
+    pub async fn search_similar_docs(
+        &self,
+        store: &Arc<dyn VectorStore + Send + Sync>,
+        crate_name: &str,
+        query_embedding: &Array1<f32>,
+        limit: i32,
+    ) -> Result<Vec<DocMatch>, ServerError> {
+        let Some(cache) = &self.cache else {
+            return store.search_similar_docs(crate_name, query_embedding, limit).await;
+        };
+        if let Some(docs) = cache.get(crate_name).await {
+            return Ok(rank(&docs, query_embedding, store.distance_metric(), limit));
+        }
+        self.start_loading(store, crate_name);
+        store.search_similar_docs(crate_name, query_embedding, limit).await
+    }
+
+    /// Drop a crate, e.g. after its documents were re-embedded
+    pub async fn invalidate(&self, crate_name: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(crate_name).await;
+        }
+        self.oversized.lock().unwrap().remove(crate_name);
+    }
+
+    fn start_loading(&self, store: &Arc<dyn VectorStore + Send + Sync>, crate_name: &str) {
+        if self.oversized.lock().unwrap().contains(crate_name)
+            || !self.loading.lock().unwrap().insert(crate_name.to_string())
+        {
+            return;
+        }
+        let this = self.clone();
+        let store = store.clone();
+        let crate_name = crate_name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = this.load(store.as_ref(), &crate_name).await {
+                warn!("⚠️  Could not cache vectors of {}: {}", crate_name, e);
+            }
+            this.loading.lock().unwrap().remove(&crate_name);
+        });
+    }
+
+    async fn load(&self, store: &(dyn VectorStore + Send + Sync), crate_name: &str) -> Result<(), ServerError> {
+        let Some(cache) = &self.cache else {
+            return Ok(());
+        };
+        // Counting first avoids pulling a crate that could never fit
+        let count = store.count_crate_documents(crate_name).await?;
+        if count as u64 > self.max_vectors {
+            info!(
+                "📦 {} has {} vectors, more than MAX_CACHED_VECTORS ({}); searching it in the database",
+                crate_name, count, self.max_vectors
+            );
+            self.oversized.lock().unwrap().insert(crate_name.to_string());
+            return Ok(());
+        }
+        let docs = store.get_crate_documents(crate_name).await?;
+        info!("🧠 Cached {} vectors of {} in memory", docs.len(), crate_name);
+        cache.insert(crate_name.to_string(), Arc::new(docs)).await;
+        Ok(())
+    }
+}
+
+impl Default for VectorCache {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+/// The `limit` closest documents, scored the way the store's SQL scores them
+fn rank(docs: &[CachedDoc], query: &Array1<f32>, metric: DistanceMetric, limit: i32) -> Vec<DocMatch> {
+    let mut scored: Vec<(f32, &CachedDoc)> = docs
+        .iter()
+        .map(|doc| (similarity(metric, query, &doc.2), doc))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(limit.max(0) as usize)
+        .map(|(similarity, (doc_path, content, _, token_count))| DocMatch {
+            doc_path: doc_path.clone(),
+            content: content.clone(),
+            similarity,
+            token_count: *token_count,
+        })
+        .collect()
+}
+
+/// Similarity as `DistanceMetric::similarity_sql` computes it
+fn similarity(metric: DistanceMetric, query: &Array1<f32>, doc: &Array1<f32>) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => cosine_similarity(query.view(), doc.view()),
+        DistanceMetric::L2 => {
+            let distance_squared: f32 = (query - doc).mapv(|d| d * d).sum();
+            1.0 - distance_squared / 2.0
+        }
+        DistanceMetric::InnerProduct => query.dot(doc),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answers_from_memory_once_a_crate_is_loaded() {
+        let docs = [
+            ("tokio/fn.spawn.html", [1.0, 0.0]),
+            ("tokio/sync/struct.Mutex.html", [0.0, 1.0]),
+            ("tokio/time/fn.sleep.html", [0.7, 0.7]),
+        ]
+        .map(|(path, vector)| crate::database::test_doc(path, &format!("docs of {}", path), &vector));
+        let store = crate::database::test_store_with(&[("tokio", docs.into())]).await;
+
+        let cache = VectorCache::new(10);
+        let query = Array1::from(vec![1.0, 0.1]);
+        let from_store = cache.search_similar_docs(&store, "tokio", &query, 2).await.unwrap();
+        // The miss loads the crate in the background
+        for _ in 0..100 {
+            if cache.contains("tokio") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(cache.contains("tokio"));
+
+        let from_memory = cache.search_similar_docs(&store, "tokio", &query, 2).await.unwrap();
+        let paths = |hits: &[DocMatch]| hits.iter().map(|hit| hit.doc_path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&from_memory), paths(&from_store));
+        assert!((from_memory[0].similarity - from_store[0].similarity).abs() < 1e-4);
+        assert_eq!(from_memory[0].token_count, Some(1));
+
+        // Crates larger than the whole cache stay in the store
+        let small = VectorCache::new(2);
+        small.search_similar_docs(&store, "tokio", &query, 2).await.unwrap();
+        for _ in 0..100 {
+            if small.oversized.lock().unwrap().contains("tokio") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!small.contains("tokio"));
+        assert!(small.oversized.lock().unwrap().contains("tokio"));
+    }
+}
@@ -0,0 +1,175 @@
+// How much the binaries log. Logs go through `tracing` to stderr only, so stdout carries
+// nothing but results (and, for the stdio server, MCP frames). The default is a concise
+// summary; per-page, per-chunk and per-row progress only appears at `Verbose`, and `Quiet`
+// keeps warnings and errors. `--log-level` or `RUST_LOG` take a full `tracing` filter.
+// `--log-format json` writes one JSON object per line for log aggregators instead.
+
+use crate::error::ServerError;
+use clap::ValueEnum;
+use std::env;
+use tracing_subscriber::EnvFilter;
+
+/// `tracing` target prefix of the library's modules
+const LIBRARY_TARGET: &str = env!("CARGO_CRATE_NAME");
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Verbosity {
+    /// Warnings and errors only
+    Quiet,
+    /// Start/finish lines and summaries
+    #[default]
+    Normal,
+    /// Every page crawled, chunk embedded and row loaded
+    Verbose,
+}
+
+impl Verbosity {
+    /// `tracing` level for this crate's logs
+    pub fn level(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "warn",
+            Verbosity::Normal => "info",
+            Verbosity::Verbose => "debug",
+        }
+    }
+
+    /// Filter directives logging the library and the binary `bin` at this level.
+    /// Dependencies (sqlx, reqwest, rmcp, ...) only log warnings.
+    pub fn directives(self, bin: &str) -> String {
+        let level = self.level();
+        // Targets match by prefix, so the library's directive also covers rustdocs_mcp_server*
+        if bin.starts_with(LIBRARY_TARGET) {
+            format!("warn,{}={}", LIBRARY_TARGET, level)
+        } else {
+            format!("warn,{}={},{}={}", LIBRARY_TARGET, level, bin, level)
+        }
+    }
+}
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Compact human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line with the timestamp, level, fields and enclosing spans
+    Json,
+}
+
+/// `-v`/`-q`/`--log-level`/`--log-format` flags shared by the binaries (`#[command(flatten)]`)
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct VerbosityArgs {
+    /// Print detailed progress: every page crawled, chunk embedded and row loaded
+    #[arg(short, long, conflicts_with_all = ["quiet", "log_level"])]
+    pub verbose: bool,
+
+    /// Only print warnings, errors and final results
+    #[arg(short, long, conflicts_with = "log_level")]
+    pub quiet: bool,
+
+    /// Log filter in `RUST_LOG` syntax (e.g. "debug" or "info,sqlx=debug"); overrides -v/-q
+    #[arg(long, value_name = "FILTER")]
+    pub log_level: Option<String>,
+
+    /// Write logs as text or JSON lines (for Loki, ELK, ...)
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, env = "LOG_FORMAT")]
+    pub log_format: LogFormat,
+}
+
+impl VerbosityArgs {
+    /// The flags if given, else `MCPDOCS_VERBOSITY` (quiet, normal or verbose), else normal
+    pub fn resolve(&self) -> Result<Verbosity, ServerError> {
+        if self.verbose {
+            return Ok(Verbosity::Verbose);
+        }
+        if self.quiet {
+            return Ok(Verbosity::Quiet);
+        }
+        match env::var("MCPDOCS_VERBOSITY").ok().filter(|v| !v.trim().is_empty()) {
+            Some(value) => Verbosity::from_str(value.trim(), true).map_err(|_| {
+                ServerError::Config(format!(
+                    "Invalid MCPDOCS_VERBOSITY: {}. Use 'quiet', 'normal' or 'verbose'",
+                    value
+                ))
+            }),
+            None => Ok(Verbosity::default()),
+        }
+    }
+
+    /// Filter directives for the binary `bin`: `--log-level`, then `-v`/`-q`, then
+    /// `RUST_LOG`, then `MCPDOCS_VERBOSITY`
+    pub fn directives(&self, bin: &str) -> Result<String, ServerError> {
+        if let Some(log_level) = &self.log_level {
+            return Ok(log_level.clone());
+        }
+        if !self.verbose && !self.quiet {
+            if let Some(rust_log) = env::var("RUST_LOG").ok().filter(|v| !v.trim().is_empty()) {
+                return Ok(rust_log);
+            }
+        }
+        Ok(self.resolve()?.directives(bin))
+    }
+
+    /// Send logs to stderr, filtered by [`Self::directives`]. `bin` is the calling binary's
+    /// crate name (`env!("CARGO_CRATE_NAME")`).
+    pub fn apply(&self, bin: &str) -> Result<(), ServerError> {
+        let directives = self.directives(bin)?;
+        let filter = EnvFilter::try_new(&directives)
+            .map_err(|e| ServerError::Config(format!("Invalid log filter '{}': {}", directives, e)))?;
+        init_logging(filter, self.log_format);
+        Ok(())
+    }
+}
+
+/// Install a `tracing` subscriber writing to stderr (above any progress bars): compact
+/// text, or JSON lines with the current span and its parents. Does nothing if one is
+/// already installed.
+pub fn init_logging(filter: EnvFilter, format: LogFormat) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| crate::progress::LogWriter);
+    let _ = match format {
+        LogFormat::Text => builder.with_target(false).without_time().try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).try_init(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        verbosity: VerbosityArgs,
+    }
+
+    #[test]
+    fn flags_select_the_level() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).map(|cli| cli.verbosity);
+        assert_eq!(parse(&["bin", "-v"]).unwrap().resolve().unwrap(), Verbosity::Verbose);
+        assert_eq!(parse(&["bin", "--quiet"]).unwrap().resolve().unwrap(), Verbosity::Quiet);
+        assert!(parse(&["bin", "-v", "-q"]).is_err());
+        assert!(parse(&["bin", "-q", "--log-level", "debug"]).is_err());
+
+        assert_eq!(
+            parse(&["bin", "-q"]).unwrap().directives("populate_db").unwrap(),
+            "warn,rustdocs_mcp_server=warn,populate_db=warn"
+        );
+        assert_eq!(
+            parse(&["bin", "--log-level", "info,sqlx=debug"]).unwrap().directives("populate_db").unwrap(),
+            "info,sqlx=debug"
+        );
+        assert_eq!(Verbosity::Verbose.directives("rustdocs_mcp_server"), "warn,rustdocs_mcp_server=debug");
+        assert!(parse(&["bin", "--log-level", "sqlx=nonsense"]).unwrap().apply("populate_db").is_err());
+    }
+
+    #[test]
+    fn log_format_defaults_to_text() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).map(|cli| cli.verbosity.log_format);
+        assert_eq!(parse(&["bin"]).unwrap(), LogFormat::Text);
+        assert_eq!(parse(&["bin", "--log-format", "json"]).unwrap(), LogFormat::Json);
+        assert!(parse(&["bin", "--log-format", "xml"]).is_err());
+    }
+}
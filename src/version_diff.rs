@@ -0,0 +1,183 @@
+// Differences between two stored releases of a crate (`populate_db --crate-version`),
+// found from content hashes so unchanged pages are never loaded.
+
+use crate::{
+    database::{CrateStats, VectorStore},
+    doc_loader,
+    error::ServerError,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Pages added, removed and changed between two versions, keyed by their path inside
+/// the docs (e.g. `tokio/sync/struct.Mutex.html`) so the version segment doesn't differ
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VersionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// What `compare_versions` returns: the page diff, and an LLM-written upgrade note
+/// when anything changed
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionComparison {
+    pub crate_name: String,
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub diff: VersionDiff,
+    pub upgrade_note: Option<String>,
+}
+
+impl VersionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A stored path without its chunk suffix and docs.rs `{crate}/{version}/` prefix
+pub fn page_key(crate_name: &str, doc_path: &str) -> String {
+    let path = doc_loader::strip_chunk_suffix(doc_path);
+    match path.strip_prefix(&format!("{}/", crate_name)) {
+        Some(rest) => rest.split_once('/').map_or(rest, |(_, page)| page).to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Content hashes of each page's chunks, in path order, by page key
+fn page_hashes(crate_name: &str, rows: &[(String, String)]) -> BTreeMap<String, Vec<(String, String)>> {
+    let mut pages: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for (path, hash) in rows {
+        pages.entry(page_key(crate_name, path)).or_default().push((path.clone(), hash.clone()));
+    }
+    pages.values_mut().for_each(|chunks| chunks.sort());
+    pages
+}
+
+/// Compare the `(path, content_hash)` rows of two versions. A page changed when its
+/// chunks' content differs; pages come out sorted by key.
+pub fn diff_versions(crate_name: &str, from: &[(String, String)], to: &[(String, String)]) -> VersionDiff {
+    let from = page_hashes(crate_name, from);
+    let to = page_hashes(crate_name, to);
+    let hashes = |chunks: &[(String, String)]| chunks.iter().map(|(_, hash)| hash.clone()).collect::<Vec<_>>();
+
+    let mut diff = VersionDiff::default();
+    for (key, chunks) in &to {
+        match from.get(key) {
+            None => diff.added.push(key.clone()),
+            Some(old) if hashes(old) != hashes(chunks) => diff.changed.push(key.clone()),
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    diff.removed = from.keys().filter(|key| !to.contains_key(*key)).cloned().collect();
+    diff
+}
+
+/// Stored paths of the chunks of the page `key`, in path order
+pub fn chunk_paths(crate_name: &str, rows: &[(String, String)], key: &str) -> Vec<String> {
+    let mut paths: Vec<String> = rows
+        .iter()
+        .map(|(path, _)| path)
+        .filter(|path| page_key(crate_name, path) == key)
+        .cloned()
+        .collect();
+    paths.sort_by(|a, b| doc_loader::chunk_order(a).cmp(&doc_loader::chunk_order(b)));
+    paths
+}
+
+/// The stored crate name holding `version` of `crate_name`: a pinned `crate@version`
+/// entry, or the plain crate when that is the version it was last populated with
+pub fn resolve_version(stats: &[CrateStats], crate_name: &str, version: &str) -> Option<String> {
+    let pinned = doc_loader::crate_version_name(crate_name, version);
+    if stats.iter().any(|stat| stat.name == pinned) {
+        return Some(pinned);
+    }
+    stats
+        .iter()
+        .find(|stat| stat.name == crate_name && (version == "latest" || stat.version.as_deref() == Some(version)))
+        .map(|stat| stat.name.clone())
+}
+
+/// Versions of `crate_name` available to [`resolve_version`]
+pub fn stored_versions(stats: &[CrateStats], crate_name: &str) -> Vec<String> {
+    stats
+        .iter()
+        .filter_map(|stat| match doc_loader::stored_crate_version(&stat.name) {
+            Some(version) if stat.name == doc_loader::crate_version_name(crate_name, version) => Some(version.to_string()),
+            None if stat.name == crate_name => stat.version.clone(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The stored text of a page, its chunks joined in order
+pub async fn page_content(
+    store: &(dyn VectorStore + Send + Sync),
+    stored_name: &str,
+    chunk_paths: &[String],
+) -> Result<String, ServerError> {
+    let mut chunks = Vec::with_capacity(chunk_paths.len());
+    for path in chunk_paths {
+        if let Some(content) = store.get_doc_content(stored_name, path).await? {
+            chunks.push(content);
+        }
+    }
+    Ok(chunks.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(path: &str, hash: &str) -> (String, String) {
+        (path.to_string(), hash.to_string())
+    }
+
+    #[test]
+    fn diffs_pages_across_versioned_paths() {
+        let from = vec![
+            row("tokio/1.37.0/tokio/fn.spawn.html", "a"),
+            row("tokio/1.37.0/tokio/sync/struct.Mutex.html [chunk 1/2]", "b"),
+            row("tokio/1.37.0/tokio/sync/struct.Mutex.html [chunk 2/2]", "c"),
+            row("tokio/1.37.0/tokio/fn.old.html", "d"),
+        ];
+        let to = vec![
+            row("tokio/1.38.0/tokio/fn.spawn.html", "a"),
+            row("tokio/1.38.0/tokio/sync/struct.Mutex.html", "b"),
+            row("tokio/1.38.0/tokio/fn.new.html", "e"),
+        ];
+
+        let diff = diff_versions("tokio", &from, &to);
+        assert_eq!(diff.added, ["tokio/fn.new.html"]);
+        assert_eq!(diff.removed, ["tokio/fn.old.html"]);
+        assert_eq!(diff.changed, ["tokio/sync/struct.Mutex.html"]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            chunk_paths("tokio", &from, "tokio/sync/struct.Mutex.html"),
+            ["tokio/1.37.0/tokio/sync/struct.Mutex.html [chunk 1/2]", "tokio/1.37.0/tokio/sync/struct.Mutex.html [chunk 2/2]"]
+        );
+    }
+
+    #[test]
+    fn resolves_pinned_and_latest_versions() {
+        let stat = |name: &str, version: Option<&str>| CrateStats {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            last_updated: chrono::NaiveDateTime::default(),
+            total_docs: 1,
+            total_tokens: 1,
+            features: Vec::new(),
+            embedding_model: None,
+            status: Default::default(),
+        };
+        let stats = vec![stat("tokio", Some("1.38.0")), stat("tokio@1.37.0", Some("1.37.0")), stat("axum", None)];
+
+        assert_eq!(resolve_version(&stats, "tokio", "1.37.0").as_deref(), Some("tokio@1.37.0"));
+        assert_eq!(resolve_version(&stats, "tokio", "1.38.0").as_deref(), Some("tokio"));
+        assert_eq!(resolve_version(&stats, "tokio", "latest").as_deref(), Some("tokio"));
+        assert_eq!(resolve_version(&stats, "tokio", "1.36.0"), None);
+        assert_eq!(stored_versions(&stats, "tokio"), ["1.38.0", "1.37.0"]);
+    }
+}
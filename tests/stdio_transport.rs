@@ -0,0 +1,76 @@
+// The stdio server's stdout is its MCP transport: anything else printed there corrupts the
+// client's stream. This runs the real binary over a seeded SQLite file and checks that every
+// line it writes to stdout is a JSON-RPC message.
+
+use ndarray::Array1;
+use rustdocs_mcp_server::database::{self, DistanceMetric};
+use std::{process::Stdio, time::Duration};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+#[tokio::test]
+async fn stdio_server_writes_only_json_rpc_to_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_url = format!("sqlite://{}", dir.path().join("docs.db").display());
+    {
+        let store = database::connect_url(&database_url, DistanceMetric::Cosine).await.unwrap();
+        let crate_id = store.upsert_crate("tokio", None).await.unwrap();
+        let doc = (
+            "tokio/latest/tokio/sync/struct.Mutex.html".to_string(),
+            "mutex".to_string(),
+            Array1::from(vec![1.0, 0.0, 0.0]),
+            1,
+        );
+        store.insert_embeddings_batch(crate_id, "tokio", &[doc]).await.unwrap();
+    }
+
+    // A clean environment in an empty directory, so no .env or proxy-config.json is picked up;
+    // the API key is never used because the tool call below doesn't embed anything. Debug
+    // logging makes the server say as much as it can, all of which must go to stderr.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rustdocs_mcp_server"))
+        .arg("tokio")
+        .current_dir(dir.path())
+        .env_clear()
+        .env("MCPDOCS_DATABASE_URL", &database_url)
+        .env("OPENAI_API_KEY", "unused")
+        .env("EMBEDDING_DIMENSIONS", "3")
+        .env("RUST_LOG", "debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take();
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{},"clientInfo":{"name":"test","version":"0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"search_by_item_path","arguments":{"crate_name":"tokio","item_path":"tokio::sync::Mutex"}}}"#,
+    ];
+    for request in requests {
+        stdin.as_mut().unwrap().write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+    }
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut answered = false;
+    let read_all = async {
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let message: serde_json::Value =
+                serde_json::from_str(&line).unwrap_or_else(|e| panic!("non-JSON-RPC stdout line {:?}: {}", line, e));
+            assert_eq!(message["jsonrpc"], "2.0", "{}", line);
+            if message["id"] == 2 {
+                assert!(message["result"]["content"][0]["text"].as_str().unwrap().ends_with("mutex"), "{}", line);
+                answered = true;
+                // Closing stdin shuts the server down, so everything it wrote after the
+                // response is checked too
+                drop(stdin.take());
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), read_all)
+        .await
+        .expect("server didn't answer and exit in time");
+    assert!(answered, "server exited without answering the tool call");
+    child.wait().await.unwrap();
+}
@@ -1,4 +1,4 @@
-use rustdocs_mcp_server::{database::Database, embeddings::*};
+use rustdocs_mcp_server::{database, embeddings::*};
 use std::env;
 use ndarray::Array1;
 
@@ -9,20 +9,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Connect to database
     println!("Connecting to database...");
-    let db = Database::new().await?;
+    let db = database::connect().await?;
     
     // Initialize embedding provider
     println!("Initializing embedding provider...");
-    let openai_client = if let Ok(api_base) = env::var("OPENAI_API_BASE") {
-        let config = async_openai::config::OpenAIConfig::new().with_api_base(api_base);
-        async_openai::Client::with_config(config)
-    } else {
-        async_openai::Client::new()
-    };
+    let openai_client = build_openai_client()?;
     
     let embedding_config = EmbeddingConfig::OpenAI {
         client: openai_client,
-        model: "text-embedding-ada-002".to_string(),
+        model: env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "text-embedding-3-large".to_string()),
     };
     
     let provider = initialize_embedding_provider(embedding_config);
@@ -41,10 +36,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let results = db.search_similar_docs("axum", &query_embedding, 5).await?;
     
     println!("\nFound {} results:", results.len());
-    for (i, (path, content, similarity)) in results.iter().enumerate() {
-        println!("\n--- Result {} (similarity: {:.3}) ---", i + 1, similarity);
-        println!("Path: {}", path);
-        println!("Content preview: {}...", &content[..content.len().min(200)]);
+    for (i, result) in results.iter().enumerate() {
+        println!("\n--- Result {} (similarity: {:.3}) ---", i + 1, result.similarity);
+        println!("Path: {}", result.doc_path);
+        println!("Content preview: {}...", &result.content[..result.content.len().min(200)]);
     }
     
     Ok(())